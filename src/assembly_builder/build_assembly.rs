@@ -1,74 +1,95 @@
 //! Convert a single Koopa IR component into assembly code.
 
+use std::collections::HashSet;
 use std::vec;
 
 use crate::assembly_builder::{REG_RA, REG_X31};
 
 use super::{
-    MyBBValueTable, ARG_SIZE, REGISTER_FOR_ARGS, REGISTER_FOR_TEMP, REGISTER_NAMES, REG_A0, REG_SP,
+    global_label, register_name, MyBBValueTable, Target, FLOAT_REGISTER_FOR_TEMP,
+    REGISTER_FOR_ARGS, REGISTER_FOR_TEMP, REGISTER_NAMES, REG_A0, REG_FA0, REG_SP,
+};
+use crate::diagnostics::{CompileError, ErrorCode};
+use crate::ir_builder::ProgramInfo;
+use koopa::ir::{
+    entities::ValueData, BasicBlock, BinaryOp, FunctionData, Program, TypeKind, Value, ValueKind,
 };
-use koopa::ir::{entities::ValueData, FunctionData, Program, Type};
 
 pub trait AssemblyBuildable {
-    fn build(&self, program: &Program) -> Result<Vec<String>, String>;
+    fn build(
+        &self,
+        program: &Program,
+        program_info: &ProgramInfo,
+        target: Target,
+    ) -> Result<Vec<String>, CompileError>;
 }
 
 impl AssemblyBuildable for Program {
-    fn build(&self, _: &Program) -> Result<Vec<String>, String> {
+    fn build(
+        &self,
+        _: &Program,
+        program_info: &ProgramInfo,
+        target: Target,
+    ) -> Result<Vec<String>, CompileError> {
         let mut program_codes = vec![];
 
         // Assembly code of global variables
-        program_codes.push(format!("  .data"));
+        program_codes.push("  .data".to_string());
         for &global in self.inst_layout() {
-            program_codes.extend(self.borrow_value(global).build(self)?);
+            program_codes.extend(global.build(self, program_info, target)?);
         }
 
         // Assembly code of functions
-        program_codes.push(format!("  .text"));
+        program_codes.push("  .text".to_string());
         for &func in self.func_layout() {
-            if self.func(func).layout().bbs().len() > 0 {
-                program_codes.extend(self.func(func).build(self)?);
+            if !self.func(func).layout().bbs().is_empty() {
+                program_codes.extend(self.func(func).build(self, program_info, target)?);
             }
         }
         Ok(program_codes)
     }
 }
 
+/// Converts a binary operation into RISC-V instructions.
+/// In RV64, an int operation uses the word instruction (like addw), which keeps the int
+/// sign-extended in the register. Comparisons and bitwise operations do not need it.
 fn binary_op_to_assembly(
-    binary: &koopa::ir::values::Binary,
+    binary_op: BinaryOp,
+    is_word: bool,
     reg_ans: usize,
     reg1: usize,
     reg2: usize,
 ) -> String {
-    match binary.op() {
+    let w = if is_word { "w" } else { "" };
+    match binary_op {
         koopa::ir::BinaryOp::Add => {
             format!(
-                "  add\t{}, {}, {}",
-                REGISTER_NAMES[reg_ans], REGISTER_NAMES[reg1], REGISTER_NAMES[reg2],
+                "  add{}\t{}, {}, {}",
+                w, REGISTER_NAMES[reg_ans], REGISTER_NAMES[reg1], REGISTER_NAMES[reg2],
             )
         }
         koopa::ir::BinaryOp::Sub => {
             format!(
-                "  sub\t{}, {}, {}",
-                REGISTER_NAMES[reg_ans], REGISTER_NAMES[reg1], REGISTER_NAMES[reg2],
+                "  sub{}\t{}, {}, {}",
+                w, REGISTER_NAMES[reg_ans], REGISTER_NAMES[reg1], REGISTER_NAMES[reg2],
             )
         }
         koopa::ir::BinaryOp::Mul => {
             format!(
-                "  mul\t{}, {}, {}",
-                REGISTER_NAMES[reg_ans], REGISTER_NAMES[reg1], REGISTER_NAMES[reg2],
+                "  mul{}\t{}, {}, {}",
+                w, REGISTER_NAMES[reg_ans], REGISTER_NAMES[reg1], REGISTER_NAMES[reg2],
             )
         }
         koopa::ir::BinaryOp::Div => {
             format!(
-                "  div\t{}, {}, {}",
-                REGISTER_NAMES[reg_ans], REGISTER_NAMES[reg1], REGISTER_NAMES[reg2],
+                "  div{}\t{}, {}, {}",
+                w, REGISTER_NAMES[reg_ans], REGISTER_NAMES[reg1], REGISTER_NAMES[reg2],
             )
         }
         koopa::ir::BinaryOp::Mod => {
             format!(
-                "  rem\t{}, {}, {}",
-                REGISTER_NAMES[reg_ans], REGISTER_NAMES[reg1], REGISTER_NAMES[reg2],
+                "  rem{}\t{}, {}, {}",
+                w, REGISTER_NAMES[reg_ans], REGISTER_NAMES[reg1], REGISTER_NAMES[reg2],
             )
         }
         koopa::ir::BinaryOp::Eq => {
@@ -143,26 +164,400 @@ fn binary_op_to_assembly(
         }
         koopa::ir::BinaryOp::Shl => {
             format!(
-                "  sll\t{}, {}, {}",
-                REGISTER_NAMES[reg_ans], REGISTER_NAMES[reg1], REGISTER_NAMES[reg2],
+                "  sll{}\t{}, {}, {}",
+                w, REGISTER_NAMES[reg_ans], REGISTER_NAMES[reg1], REGISTER_NAMES[reg2],
             )
         }
         koopa::ir::BinaryOp::Shr => {
             format!(
-                "  srl\t{}, {}, {}",
-                REGISTER_NAMES[reg_ans], REGISTER_NAMES[reg1], REGISTER_NAMES[reg2],
+                "  srl{}\t{}, {}, {}",
+                w, REGISTER_NAMES[reg_ans], REGISTER_NAMES[reg1], REGISTER_NAMES[reg2],
             )
         }
         koopa::ir::BinaryOp::Sar => {
             format!(
-                "  sra\t{}, {}, {}",
-                REGISTER_NAMES[reg_ans], REGISTER_NAMES[reg1], REGISTER_NAMES[reg2],
+                "  sra{}\t{}, {}, {}",
+                w, REGISTER_NAMES[reg_ans], REGISTER_NAMES[reg1], REGISTER_NAMES[reg2],
             )
         }
     }
 }
 
-fn init_global_aggregate(aggr: &koopa::ir::values::Aggregate, program: &Program) -> Vec<String> {
+/// Converts a call of an intrinsic (see ir_builder) into RISC-V instructions.
+/// Float args and float results are in float registers.
+/// A long long is a single register in RV64, so its operations are plain RV64 instructions.
+fn intrinsic_to_assembly(
+    name: &str,
+    reg_ans: usize,
+    arg_regs: &[usize],
+    target: Target,
+) -> Result<String, CompileError> {
+    // Only RV64 gets here with long longs. See uses_long_long.
+    if name.contains("long_long") {
+        return long_long_intrinsic_to_assembly(name, reg_ans, arg_regs);
+    }
+    let ans = register_name(reg_ans);
+    let args: Vec<&str> = arg_regs.iter().map(|&reg| register_name(reg)).collect();
+    let code = match name {
+        "__float_add" | "__float_sub" | "__float_mul" | "__float_div" => {
+            format!("  f{}.s\t{}, {}, {}", &name[8..], ans, args[0], args[1])
+        }
+        "__float_eq" => format!("  feq.s\t{}, {}, {}", ans, args[0], args[1]),
+        "__float_ne" => format!(
+            "  feq.s\t{}, {}, {}\n  seqz\t{}, {}",
+            ans, args[0], args[1], ans, ans
+        ),
+        "__float_lt" => format!("  flt.s\t{}, {}, {}", ans, args[0], args[1]),
+        "__float_gt" => format!("  flt.s\t{}, {}, {}", ans, args[1], args[0]),
+        "__float_le" => format!("  fle.s\t{}, {}, {}", ans, args[0], args[1]),
+        "__float_ge" => format!("  fle.s\t{}, {}, {}", ans, args[1], args[0]),
+        "__int_to_float" => format!("  fcvt.s.w\t{}, {}", ans, args[0]),
+        // Converting a float into an int rounds towards zero, as C does.
+        "__float_to_int" => format!("  fcvt.w.s\t{}, {}, rtz", ans, args[0]),
+        // The conversion is done when the double is passed. See call_arg_locations.
+        "__float_to_double" => format!("  fmv.s\t{}, {}", ans, args[0]),
+        // A pointer is already its address. In RV64, the int keeps the low 32 bits.
+        "__ptr_to_int" => match target {
+            Target::RV32 => format!("  mv\t{}, {}", ans, args[0]),
+            Target::RV64 => format!("  sext.w\t{}, {}", ans, args[0]),
+        },
+        "__ptr_cast" => format!("  mv\t{}, {}", ans, args[0]),
+        _ => {
+            return Err(CompileError::without_span(
+                ErrorCode::UnsupportedInstruction,
+                format!("Unknown intrinsic function '{}'! ", name),
+            ))
+        }
+    };
+    Ok(code)
+}
+
+fn long_long_intrinsic_to_assembly(
+    name: &str,
+    reg_ans: usize,
+    arg_regs: &[usize],
+) -> Result<String, CompileError> {
+    let ans = register_name(reg_ans);
+    let args: Vec<&str> = arg_regs.iter().map(|&reg| register_name(reg)).collect();
+    let binary_op = match name {
+        "__long_long_add" => BinaryOp::Add,
+        "__long_long_sub" => BinaryOp::Sub,
+        "__long_long_mul" => BinaryOp::Mul,
+        "__long_long_div" => BinaryOp::Div,
+        "__long_long_mod" => BinaryOp::Mod,
+        "__long_long_and" => BinaryOp::And,
+        "__long_long_or" => BinaryOp::Or,
+        "__long_long_xor" => BinaryOp::Xor,
+        "__long_long_shl" => BinaryOp::Shl,
+        "__long_long_shr" => BinaryOp::Shr,
+        "__long_long_sar" => BinaryOp::Sar,
+        "__long_long_eq" => BinaryOp::Eq,
+        "__long_long_ne" => BinaryOp::NotEq,
+        "__long_long_lt" => BinaryOp::Lt,
+        "__long_long_gt" => BinaryOp::Gt,
+        "__long_long_le" => BinaryOp::Le,
+        "__long_long_ge" => BinaryOp::Ge,
+        // Ints are sign-extended in RV64 registers.
+        "__int_to_long_long" | "__long_long_to_int" => {
+            return Ok(format!("  sext.w\t{}, {}", ans, args[0]))
+        }
+        // Converting a float into a long long rounds towards zero, as C does.
+        "__float_to_long_long" => return Ok(format!("  fcvt.l.s\t{}, {}, rtz", ans, args[0])),
+        "__long_long_to_float" => return Ok(format!("  fcvt.s.l\t{}, {}", ans, args[0])),
+        _ => {
+            return Err(CompileError::without_span(
+                ErrorCode::UnsupportedInstruction,
+                format!("Unknown intrinsic function '{}'! ", name),
+            ))
+        }
+    };
+    Ok(binary_op_to_assembly(
+        binary_op,
+        false,
+        reg_ans,
+        arg_regs[0],
+        arg_regs[1],
+    ))
+}
+
+/// Whether an instruction computes or uses a long long, which is an array of 2 words in Koopa IR.
+/// Only RV64 supports them. Other arrays are only reached through pointers.
+fn uses_long_long(fd: &FunctionData, value_data: &ValueData) -> bool {
+    let is_long_long =
+        |value_data: &ValueData| matches!(value_data.ty().kind(), TypeKind::Array(..));
+    if let ValueKind::Alloc(_) = value_data.kind() {
+        return false;
+    }
+    is_long_long(value_data)
+        || value_data
+            .kind()
+            .value_uses()
+            .any(|used_value| !used_value.is_global() && is_long_long(fd.dfg().value(used_value)))
+}
+
+/// Converts the bits of a float in `low` into the bits of a double. Only integer instructions
+/// and RV32F are used, so RV32D is not needed. `label` is the unique prefix of its labels.
+/// In RV32, the low word is put in `low`, and the high word in `high`.
+/// In RV64, the double is put in `low`, and `high` is changed too. x31 is always changed.
+/// The exponent is rebiased, and the mantissa is shifted left by 29 bits.
+/// A subnormal float is made normal first, by multiplying by 2^24 in single precision.
+fn float_to_double_assembly(label: &str, low: usize, high: usize, target: Target) -> String {
+    let (low, high, x31) = (
+        REGISTER_NAMES[low],
+        REGISTER_NAMES[high],
+        REGISTER_NAMES[REG_X31],
+    );
+    // The bits of the register above the 31 bits of the float without its sign.
+    let upper_bits = target.ptr_size() * 8 - 31;
+    let mut codes = vec![
+        // high = the float without its sign.
+        format!("  slli\t{}, {}, {}", high, low, upper_bits),
+        format!("  srli\t{}, {}, {}", high, high, upper_bits),
+        // x31 = the difference of the biased exponents, in the high word.
+        format!("  li\t{}, 0x7f800000", x31),
+        format!("  bgeu\t{}, {}, .{}_inf_nan", high, x31, label),
+        format!("  li\t{}, 0x800000", x31),
+        format!("  bgeu\t{}, {}, .{}_normal", high, x31, label),
+        format!("  li\t{}, 0", x31),
+        format!("  beqz\t{}, .{}_end", high, label),
+        format!("  fmv.w.x\tft0, {}", high),
+        format!("  li\t{}, 0x4b800000", x31),
+        format!("  fmv.w.x\tft1, {}", x31),
+        "  fmul.s\tft0, ft0, ft1".to_string(),
+        format!("  fmv.x.w\t{}, ft0", high),
+        format!("  li\t{}, {}", x31, (1023 - 127 - 24) << 20),
+        format!("  j\t.{}_end", label),
+        format!(".{}_inf_nan:", label),
+        format!("  li\t{}, {}", x31, (2047 - 255) << 20),
+        format!("  j\t.{}_end", label),
+        format!(".{}_normal:", label),
+        format!("  li\t{}, {}", x31, (1023 - 127) << 20),
+        format!(".{}_end:", label),
+        // Add the sign to x31.
+        format!("  srli\t{}, {}, 31", low, low),
+        format!("  andi\t{}, {}, 1", low, low),
+        format!("  slli\t{}, {}, 31", low, low),
+        format!("  or\t{}, {}, {}", x31, x31, low),
+        // The low word has the low 3 bits of the mantissa, and the high word has the rest.
+        format!("  slli\t{}, {}, 29", low, high),
+        format!("  srli\t{}, {}, 3", high, high),
+        format!("  add\t{}, {}, {}", high, high, x31),
+    ];
+    if target == Target::RV64 {
+        codes.extend([
+            format!("  slli\t{}, {}, 32", high, high),
+            format!("  slli\t{}, {}, 32", low, low),
+            format!("  srli\t{}, {}, 32", low, low),
+            format!("  or\t{}, {}, {}", low, low, high),
+        ]);
+    }
+    codes.join("\n")
+}
+
+/// Where an arg of a call is passed.
+enum ArgLocation {
+    Register(usize),
+    FloatRegister(usize), // The index of fa0-fa7
+    Stack(usize),         // Offset from sp
+    /// A variadic double in an aligned pair of registers. The first one has the low bits.
+    RegisterPair(usize, usize),
+    DoubleInRegister(usize), // A variadic double in a RV64 register
+    DoubleOnStack(usize),    // Offset from sp, aligned to 8 bytes
+}
+
+/// How an arg is passed. Koopa IR can not tell floats from ints, so ProgramInfo tells it.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ArgKind {
+    Int,
+    Float,
+    Double, // A variadic float, which is the result of __float_to_double.
+}
+
+/// Finds the locations of the args of a call, and the stack size needed by the args.
+fn call_arg_locations(
+    call: &koopa::ir::values::Call,
+    program_info: &ProgramInfo,
+    fd: &FunctionData,
+    target: Target,
+) -> (Vec<ArgLocation>, usize) {
+    let float_params = match program_info.float_signatures.get(&call.callee()) {
+        Some((float_params, _)) => float_params.as_slice(),
+        None => &[],
+    };
+    let kinds: Vec<ArgKind> = call
+        .args()
+        .iter()
+        .enumerate()
+        .map(|(i, &arg)| match fd.dfg().value(arg).kind() {
+            ValueKind::Call(arg_call)
+                if program_info.intrinsics.get(&arg_call.callee())
+                    == Some(&"__float_to_double") =>
+            {
+                ArgKind::Double
+            }
+            _ if float_params.get(i) == Some(&true) => ArgKind::Float,
+            _ => ArgKind::Int,
+        })
+        .collect();
+    arg_locations(&kinds, target)
+}
+
+/// Finds the locations of args of the given kinds, and the stack size needed by the args,
+/// following the RISC-V ilp32f (or lp64f for RV64) calling convention.
+/// An arg in the stack takes the size of a register.
+/// Floats are passed in fa0-fa7. When they are used up, floats are passed like ints.
+/// Variadic floats are passed as doubles: in an aligned pair of registers,
+/// or in the stack if there is no pair left. After that, the rest args are in the stack.
+/// In RV64, a double fits in a register, so it is passed like other args.
+fn arg_locations(kinds: &[ArgKind], target: Target) -> (Vec<ArgLocation>, usize) {
+    let arg_size = target.ptr_size();
+    let mut locations = vec![];
+    let mut next_reg = 0;
+    let mut next_float_reg = 0;
+    let mut stack_size = 0;
+    for &kind in kinds {
+        let is_double = kind == ArgKind::Double;
+        if kind == ArgKind::Float && next_float_reg < REGISTER_FOR_ARGS.len() {
+            locations.push(ArgLocation::FloatRegister(next_float_reg));
+            next_float_reg += 1;
+        } else if !is_double && next_reg < REGISTER_FOR_ARGS.len() {
+            locations.push(ArgLocation::Register(REGISTER_FOR_ARGS[next_reg]));
+            next_reg += 1;
+        } else if !is_double {
+            locations.push(ArgLocation::Stack(stack_size));
+            stack_size += arg_size;
+        } else if target == Target::RV64 && next_reg < REGISTER_FOR_ARGS.len() {
+            locations.push(ArgLocation::DoubleInRegister(REGISTER_FOR_ARGS[next_reg]));
+            next_reg += 1;
+        } else if target == Target::RV64 {
+            locations.push(ArgLocation::DoubleOnStack(stack_size));
+            stack_size += arg_size;
+        } else if next_reg.next_multiple_of(2) < REGISTER_FOR_ARGS.len() {
+            next_reg = next_reg.next_multiple_of(2);
+            locations.push(ArgLocation::RegisterPair(
+                REGISTER_FOR_ARGS[next_reg],
+                REGISTER_FOR_ARGS[next_reg + 1],
+            ));
+            next_reg += 2;
+        } else {
+            next_reg = REGISTER_FOR_ARGS.len();
+            stack_size = stack_size.next_multiple_of(8);
+            locations.push(ArgLocation::DoubleOnStack(stack_size));
+            stack_size += 8;
+        }
+    }
+    (locations, stack_size)
+}
+
+/// The bytes of a local value in the stack frame. An alloc takes the size of what it allocates.
+/// In RV64, every value is aligned to 8 bytes, so that pointers and long longs are aligned.
+fn local_value_size(value_data: &ValueData, target: Target) -> usize {
+    let size = match (value_data.kind(), value_data.ty().kind()) {
+        (ValueKind::Alloc(_), TypeKind::Pointer(base_type)) => base_type.size(),
+        _ => value_data.ty().size(),
+    };
+    match target {
+        Target::RV32 => size,
+        Target::RV64 => size.next_multiple_of(8),
+    }
+}
+
+/// Whether the program takes the address of a variable, like "getptr @x, 0".
+/// Then a pointer may change the variable, so it should not stay in a register.
+fn takes_variable_address(program: &Program) -> bool {
+    program.funcs().values().any(|fd| {
+        fd.dfg()
+            .values()
+            .values()
+            .any(|value_data| match value_data.kind() {
+                ValueKind::GetPtr(getptr) => {
+                    getptr.src().is_global()
+                        || matches!(fd.dfg().value(getptr.src()).kind(), ValueKind::Alloc(_))
+                }
+                _ => false,
+            })
+    })
+}
+
+/// Finds the values holding floats in a function, which are kept in float registers.
+/// Koopa IR can not tell floats from ints, so they are found from the float params and
+/// the calls with float signatures. A variable is a float if a float is stored into it,
+/// and then a value loaded from it is a float too. A value loaded as a float arg is a float.
+/// Other values holding floats, like -x (which flips the sign bit), are kept in integer registers.
+fn find_float_values(
+    fd: &FunctionData,
+    program_info: &ProgramInfo,
+    float_params: &[bool],
+) -> HashSet<Value> {
+    let mut float_values: HashSet<Value> = fd
+        .params()
+        .iter()
+        .zip(float_params)
+        .filter(|(_, &is_float)| is_float)
+        .map(|(&param, _)| param)
+        .collect();
+    let is_load = |value: Value| {
+        !value.is_global() && matches!(fd.dfg().value(value).kind(), ValueKind::Load(_))
+    };
+    for (&value, value_data) in fd.dfg().values() {
+        let ValueKind::Call(call) = value_data.kind() else {
+            continue;
+        };
+        if let Some((float_params, returns_float)) =
+            program_info.float_signatures.get(&call.callee())
+        {
+            if *returns_float {
+                float_values.insert(value);
+            }
+            for (&arg, _) in call
+                .args()
+                .iter()
+                .zip(float_params)
+                .filter(|(_, &is_float)| is_float)
+            {
+                if is_load(arg) {
+                    float_values.insert(arg);
+                }
+            }
+        }
+    }
+    let is_variable = |value: Value| {
+        value.is_global() || matches!(fd.dfg().value(value).kind(), ValueKind::Alloc(_))
+    };
+    loop {
+        let old_len = float_values.len();
+        for (&value, value_data) in fd.dfg().values() {
+            match value_data.kind() {
+                ValueKind::Store(store) if is_variable(store.dest()) => {
+                    if float_values.contains(&store.value()) {
+                        float_values.insert(store.dest());
+                    }
+                    // A value from memory can be loaded into a float register.
+                    if float_values.contains(&store.dest()) && is_load(store.value()) {
+                        float_values.insert(store.value());
+                    }
+                }
+                ValueKind::Load(load)
+                    if is_variable(load.src())
+                        && (float_values.contains(&load.src())
+                            || float_values.contains(&value)) =>
+                {
+                    float_values.insert(load.src());
+                    float_values.insert(value);
+                }
+                _ => {}
+            }
+        }
+        if float_values.len() == old_len {
+            return float_values;
+        }
+    }
+}
+
+fn init_global_aggregate(
+    aggr: &koopa::ir::values::Aggregate,
+    program: &Program,
+) -> Result<Vec<String>, CompileError> {
     let mut codes = vec![];
     for child in aggr.elems() {
         match program.borrow_value(*child).kind() {
@@ -170,22 +565,171 @@ fn init_global_aggregate(aggr: &koopa::ir::values::Aggregate, program: &Program)
                 codes.push(format!("  .word {}", int.value()));
             }
             koopa::ir::ValueKind::Aggregate(a) => {
-                codes.extend(init_global_aggregate(a, program));
+                codes.extend(init_global_aggregate(a, program)?);
+            }
+            value_kind => {
+                return Err(CompileError::without_span(
+                    ErrorCode::UnsupportedInitializer,
+                    format!("Wrong Aggregate struct: {:?}! ", value_kind),
+                ))
             }
-            _ => panic!("Wrong Aggregate struct. "),
         }
     }
-    codes
+    Ok(codes)
+}
+
+/// Returns the name of a basic block, without the leading '%'.
+fn bb_name(fd: &FunctionData, bb: BasicBlock) -> Result<&str, CompileError> {
+    match fd
+        .dfg()
+        .bbs()
+        .get(&bb)
+        .and_then(|bb_data| bb_data.name().as_ref())
+    {
+        Some(name) => Ok(&name[1..]),
+        None => Err(CompileError::without_span(
+            ErrorCode::BackendInternal,
+            format!(
+                "Can't find the name of BasicBlock {:?} in {}! ",
+                bb,
+                fd.name()
+            ),
+        )),
+    }
+}
+
+/// A dense switch statement. The frontend lowers it into a chain of test blocks.
+/// Every test block loads the switch value, compares it with a case label, and branches.
+/// Instead of running the tests one by one, the backend looks up the target in a jump table.
+struct JumpTable {
+    loaded_value: Value,      // The switch value loaded in the first test block
+    tests: Vec<BasicBlock>,   // Test blocks except the first one, which are not needed any more
+    min_label: i32,           // The label of targets[0]
+    targets: Vec<BasicBlock>, // Target of every value from the min label to the max label
+    default: BasicBlock,      // Target of other values
 }
 
-impl AssemblyBuildable for ValueData {
+/// Returns the jump table, if the block is the first test block of a dense switch.
+/// If the test blocks do not look like what the frontend generates, returns None.
+fn find_jump_table(
+    fd: &FunctionData,
+    program_info: &ProgramInfo,
+    first_test: BasicBlock,
+) -> Option<JumpTable> {
+    let tests = program_info.dense_switches.get(&first_test)?;
+    let mut cases = vec![];
+    let mut switch_value = None;
+    let mut default = first_test;
+    for (i, &curr_test) in std::iter::once(&first_test).chain(tests).enumerate() {
+        // Every test block is [load, eq, br].
+        let insts: Vec<Value> = fd
+            .layout()
+            .bbs()
+            .node(&curr_test)?
+            .insts()
+            .keys()
+            .copied()
+            .collect();
+        let [load, eq, br] = insts[..] else {
+            return None;
+        };
+        let (ValueKind::Load(load_data), ValueKind::Binary(binary), ValueKind::Branch(branch)) = (
+            fd.dfg().value(load).kind(),
+            fd.dfg().value(eq).kind(),
+            fd.dfg().value(br).kind(),
+        ) else {
+            return None;
+        };
+        let ValueKind::Integer(label) = fd.dfg().value(binary.rhs()).kind() else {
+            return None;
+        };
+        if binary.op() != BinaryOp::Eq
+            || binary.lhs() != load
+            || branch.cond() != eq
+            || *switch_value.get_or_insert(load_data.src()) != load_data.src()
+        {
+            return None;
+        }
+        cases.push((label.value(), branch.true_bb()));
+
+        // Every test goes to the next one, if the value is not its label.
+        // The last false target is default.
+        default = branch.false_bb();
+        if tests.get(i).is_some_and(|&next_test| next_test != default) {
+            return None;
+        }
+    }
+
+    let min_label = cases.iter().map(|&(label, _)| label).min()?;
+    let max_label = cases.iter().map(|&(label, _)| label).max()?;
+    let len = (max_label as i64 - min_label as i64 + 1) as usize;
+    if len > 2 * cases.len() {
+        return None;
+    }
+    let mut targets = vec![None; len];
+    for (label, target) in cases {
+        // The first test of a label wins, like the chain of tests does.
+        targets[(label as i64 - min_label as i64) as usize].get_or_insert(target);
+    }
+    let first_load = fd
+        .layout()
+        .bbs()
+        .node(&first_test)?
+        .insts()
+        .keys()
+        .next()
+        .copied()?;
+    Some(JumpTable {
+        loaded_value: first_load,
+        tests: tests.clone(),
+        min_label,
+        targets: targets
+            .into_iter()
+            .map(|target| target.unwrap_or(default))
+            .collect(),
+        default,
+    })
+}
+
+impl AssemblyBuildable for Value {
     /// Used to handle global variable declarations.
-    /// The ValueData's kind should be GlobalAlloc. Or it will panic.
-    fn build(&self, program: &Program) -> Result<Vec<String>, String> {
-        if let koopa::ir::ValueKind::GlobalAlloc(global) = self.kind() {
+    /// The Value's kind should be GlobalAlloc. Or it will return an error.
+    fn build(
+        &self,
+        program: &Program,
+        program_info: &ProgramInfo,
+        target: Target,
+    ) -> Result<Vec<String>, CompileError> {
+        let value_data = program.borrow_value(*self);
+        if let koopa::ir::ValueKind::GlobalAlloc(global) = value_data.kind() {
             let mut codes = vec![];
-            codes.push(format!("{}:", &self.name().clone().unwrap()[1..]));
             let init_value_data = program.borrow_value(global.init());
+            // An extern variable that is never defined here is defined in another file.
+            if let koopa::ir::ValueKind::Undef(_) = init_value_data.kind() {
+                return Ok(codes);
+            }
+            let name = global_label(program, program_info, *self);
+            // String literals are read-only.
+            if let (true, koopa::ir::ValueKind::Aggregate(aggr)) = (
+                program_info.string_constants.contains(self),
+                init_value_data.kind(),
+            ) {
+                codes.push("  .section .rodata".to_string());
+                codes.push("  .p2align 2".to_string());
+                codes.push(format!("{}:", name));
+                codes.extend(init_global_aggregate(aggr, program)?);
+                codes.push("  .data\n".to_string());
+                return Ok(codes);
+            }
+            // Other files can not see statics.
+            if !program_info.static_values.contains(self) {
+                codes.push(format!("  .global {}", name));
+            }
+            // Pointers and long longs in RV64 are aligned to 8 bytes.
+            if target == Target::RV64 {
+                codes.push("  .p2align 3".to_string());
+            }
+            codes.push(format!("{}:", name));
             match init_value_data.kind() {
                 koopa::ir::ValueKind::Integer(int) => {
                     codes.push(format!("  .word {}\n", int.value()));
@@ -194,44 +738,65 @@ impl AssemblyBuildable for ValueData {
                     codes.push(format!("  .zero {}\n", init_value_data.ty().size()));
                 }
                 koopa::ir::ValueKind::Aggregate(aggr) => {
-                    codes.extend(init_global_aggregate(aggr, program));
-                    codes.push(format!("\n"));
+                    codes.extend(init_global_aggregate(aggr, program)?);
+                    codes.push("\n".to_string());
                     // TODO: init them one by one.
                 }
-                value_kind => panic!(
-                    "Global variable {} has wrong kind of initialization: {:?}! ",
-                    &self.name().clone().unwrap()[1..],
-                    value_kind
-                ),
+                value_kind => {
+                    return Err(CompileError::without_span(
+                        ErrorCode::UnsupportedInitializer,
+                        format!(
+                            "Global variable {} has wrong kind of initialization: {:?}! ",
+                            name, value_kind
+                        ),
+                    ))
+                }
             }
             Ok(codes)
         } else {
-            panic!("Not a global alloc instruction. ")
+            Err(CompileError::without_span(
+                ErrorCode::BackendInternal,
+                format!("Not a global alloc instruction: {:?}! ", value_data.kind()),
+            ))
         }
     }
 }
 
 impl AssemblyBuildable for FunctionData {
-    fn build(&self, program: &Program) -> Result<Vec<String>, String> {
+    fn build(
+        &self,
+        program: &Program,
+        program_info: &ProgramInfo,
+        target: Target,
+    ) -> Result<Vec<String>, CompileError> {
+        // FunctionData does not know its handle, so it is found in the program.
+        let func = program
+            .func_layout()
+            .iter()
+            .find(|&&func| std::ptr::eq(program.func(func), self))
+            .copied();
         let mut prologue_codes = vec![];
-        prologue_codes.push(format!("  .global {}", &self.name()[1..]));
+        let is_static = func.is_some_and(|func| program_info.static_functions.contains(&func));
+        if !is_static {
+            prologue_codes.push(format!("  .global {}", &self.name()[1..]));
+        }
         prologue_codes.push(format!("{}:", &self.name()[1..]));
 
         // Clear register usages when entering the function.
-        let mut my_table = MyBBValueTable::new(program, self);
+        let mut my_table = MyBBValueTable::new(program, program_info, self, target);
 
         // In my compiler, every defined local variable (like "@y = alloc i32")
         // and temp values has its place in memory.
         // The registers work like a LRU cache.
 
         // Calculate the stack frame size.
-        let reg_ra_size = 4; // Bytes for storing register ra's value.
+        let reg_ra_size = target.ptr_size(); // Bytes for storing register ra's value.
         let mut local_var_size = 0; // Bytes for storing local variables.
         for &value in self.dfg().values().keys() {
             if value.is_global() || my_table.is_temp_value(value) {
                 continue;
             }
-            local_var_size += self.dfg().value(value).ty().size();
+            local_var_size += local_value_size(self.dfg().value(value), target);
         }
         let mut max_call_arg_size = 0; // Bytes for storing all call args.
         let mut max_temp_var_size = 0; // Bytes for storing temp values.
@@ -243,34 +808,71 @@ impl AssemblyBuildable for FunctionData {
                 }
                 let value_data = self.dfg().value(value);
                 if let koopa::ir::ValueKind::Call(call) = value_data.kind() {
-                    let mut arg_size = 0;
-                    if call.args().len() > REGISTER_FOR_ARGS.len() {
-                        for &_arg in &call.args()[REGISTER_FOR_ARGS.len()..] {
-                            arg_size += ARG_SIZE;
-                        }
-                    }
+                    let (_, arg_size) = call_arg_locations(call, program_info, self, target);
                     max_call_arg_size = std::cmp::max(arg_size, max_call_arg_size);
                 }
-                temp_var_size += value_data.ty().size();
+                temp_var_size += local_value_size(value_data, target);
             }
             max_temp_var_size = std::cmp::max(max_temp_var_size, temp_var_size);
         }
 
-        let stack_frame_size =
-            (reg_ra_size + max_temp_var_size + local_var_size + max_call_arg_size + 15) / 16 * 16;
+        // Float params come in fa0-fa7. They are moved into the stack, after temp values.
+        let (float_params, returns_float) =
+            match func.and_then(|func| program_info.float_signatures.get(&func)) {
+                Some((float_params, returns_float)) => (float_params.as_slice(), *returns_float),
+                None => (&[][..], false),
+            };
+        let param_kinds: Vec<ArgKind> = (0..self.params().len())
+            .map(|i| match float_params.get(i) {
+                Some(true) => ArgKind::Float,
+                _ => ArgKind::Int,
+            })
+            .collect();
+        let (param_locations, _) = arg_locations(&param_kinds, target);
+        my_table.float_values = find_float_values(self, program_info, float_params);
+        let float_param_size = param_locations
+            .iter()
+            .filter(|location| matches!(location, ArgLocation::FloatRegister(_)))
+            .count()
+            * target.ptr_size();
+
+        let stack_frame_size = (reg_ra_size
+            + float_param_size
+            + max_temp_var_size
+            + local_var_size
+            + max_call_arg_size)
+            .div_ceil(16)
+            * 16;
 
         // Change the stack pointer.
         prologue_codes.extend(my_table.add_with_offset(REG_SP, -(stack_frame_size as isize)));
 
         // Push every arg's location into the value table.
-        for i in 0..std::cmp::min(REGISTER_FOR_ARGS.len(), self.params().len()) {
-            my_table.register_user[REGISTER_FOR_ARGS[i]] = Some(self.params()[i]);
-        }
-        for i in REGISTER_FOR_ARGS.len()..self.params().len() {
-            my_table.local_value_location.insert(
-                self.params()[i],
-                (i - REGISTER_FOR_ARGS.len()) * ARG_SIZE + stack_frame_size,
-            );
+        let mut float_param_offset = max_call_arg_size + local_var_size + max_temp_var_size;
+        for (&param, location) in self.params().iter().zip(&param_locations) {
+            match *location {
+                ArgLocation::Register(reg) => my_table.register_user[reg] = Some(param),
+                ArgLocation::FloatRegister(index) => {
+                    prologue_codes.extend(my_table.store_with_offset(
+                        REG_FA0 + index,
+                        float_param_offset as isize,
+                        4,
+                    ));
+                    my_table
+                        .local_value_location
+                        .insert(param, float_param_offset);
+                    float_param_offset += target.ptr_size();
+                }
+                ArgLocation::Stack(offset) => {
+                    my_table
+                        .local_value_location
+                        .insert(param, offset + stack_frame_size);
+                }
+                // Params are never variadic.
+                ArgLocation::RegisterPair(..)
+                | ArgLocation::DoubleInRegister(_)
+                | ArgLocation::DoubleOnStack(_) => {}
+            }
         }
 
         // Push every local variable into the value table.
@@ -280,7 +882,7 @@ impl AssemblyBuildable for FunctionData {
                 continue;
             }
             my_table.local_value_location.insert(value, curr_offset);
-            curr_offset += self.dfg().value(value).ty().size();
+            curr_offset += local_value_size(self.dfg().value(value), target);
         }
         assert_eq!(
             curr_offset,
@@ -292,15 +894,41 @@ impl AssemblyBuildable for FunctionData {
         // (Currently no callee-saved registers need to be saved. )
 
         // Save registar ra. (Return address)
-        prologue_codes
-            .extend(my_table.store_with_offset(REG_RA, (stack_frame_size - reg_ra_size) as isize));
+        prologue_codes.extend(my_table.store_with_offset(
+            REG_RA,
+            (stack_frame_size - reg_ra_size) as isize,
+            reg_ra_size,
+        ));
 
         let mut body_codes = vec![];
         body_codes.push(format!("\n.{}_body:", &self.name()[1..]));
 
         // dbg!(&self.name(), &my_table);
 
+        // Dense switches use jump tables. Their test blocks except the first one are skipped.
+        let mut jump_tables = std::collections::HashMap::new();
+        for &block in self.layout().bbs().keys() {
+            if let Some(jump_table) = find_jump_table(self, program_info, block) {
+                jump_tables.insert(block, jump_table);
+            }
+        }
+        let skipped_blocks: Vec<BasicBlock> = jump_tables
+            .values()
+            .flat_map(|jump_table| jump_table.tests.clone())
+            .collect();
+        let mut rodata_codes = vec![];
+        let mut double_cnt = 0; // Number of floats converted into doubles, for unique labels.
+
+        // If a pointer may point to a variable, the variables in registers are stored back
+        // before memory is accessed through a pointer.
+        let may_alias = takes_variable_address(program);
+
         for (&block, node) in self.layout().bbs() {
+            if skipped_blocks.contains(&block) {
+                continue;
+            }
+            let jump_table = jump_tables.get(&block);
+
             // Insert every temp values into the value table.
             let mut curr_offset = max_call_arg_size + local_var_size;
             for &value in node.insts().keys() {
@@ -308,45 +936,66 @@ impl AssemblyBuildable for FunctionData {
                     continue;
                 }
                 my_table.local_value_location.insert(value, curr_offset);
-                curr_offset += self.dfg().value(value).ty().size();
+                curr_offset += local_value_size(self.dfg().value(value), target);
             }
             assert!(
                 curr_offset <= max_call_arg_size + local_var_size + max_temp_var_size,
                 "Inconsistent offset!"
             );
+            // Count the uses of every value in this block.
+            my_table.temp_value_uses.clear();
+            for &value in node.insts().keys() {
+                for used_value in self.dfg().value(value).kind().value_uses() {
+                    *my_table.temp_value_uses.entry(used_value).or_insert(0) += 1;
+                }
+            }
 
             // At the beginning of the BasicBlock, declare its name.
-            let possible_bb_name = self
-                .dfg()
-                .bbs()
-                .get(&block)
-                .expect("Can't find BasicBlock. Should not happen. ")
-                .name()
-                .clone();
-            if let Some(bb_name) = possible_bb_name {
-                body_codes.push(format!("\n.{}:", &bb_name[1..]));
+            // The entry block has no name. It uses the "_body" label above.
+            if self.layout().entry_bb() != Some(block) {
+                body_codes.push(format!("\n.{}:", bb_name(self, block)?));
             }
 
             // Generate instructions.
             for &value in node.insts().keys() {
                 let value_data = self.dfg().value(value); // A value in Koopa IR is an instruction.
                 body_codes.push(format!("# {:?}", value_data.kind()));
+                if target == Target::RV32 && uses_long_long(self, value_data) {
+                    return Err(CompileError::without_span(
+                        ErrorCode::UnsupportedTarget,
+                        "long long is only supported by RV64! Use -march=rv64. ".to_string(),
+                    )
+                    .with_note(format!("in function {}", &self.name()[1..])));
+                }
                 // dbg!(value_data);
+                // In the first test block of a dense switch, the test is replaced by the jump table.
+                if let Some(jump_table) = jump_table {
+                    match value_data.kind() {
+                        koopa::ir::ValueKind::Binary(_) => continue,
+                        koopa::ir::ValueKind::Branch(_) => {
+                            let (codes, table_codes) =
+                                jump_table_to_assembly(self, &mut my_table, block, jump_table)?;
+                            body_codes.extend(codes);
+                            rodata_codes.extend(table_codes);
+                            continue;
+                        }
+                        _ => {}
+                    }
+                }
                 match value_data.kind() {
                     // Do different things based on instruction kind.
 
                     // Return instruction
                     koopa::ir::ValueKind::Return(return_inst) => {
                         // Does it have a return value?
-                        match return_inst.value() {
-                            Some(return_value) => {
-                                let (reg, codes) =
-                                    my_table.want_to_visit_value(return_value, true, Some(REG_A0));
-                                assert_eq!(reg, REG_A0, "WTF??! I asked to load into reg a0!!!");
-                                body_codes.extend(codes);
-                                my_table.remove_temp_value(return_value);
-                            }
-                            None => {}
+                        if let Some(return_value) = return_inst.value() {
+                            // A float is returned in fa0.
+                            let reg_ret = if returns_float { REG_FA0 } else { REG_A0 };
+                            let (reg, codes) =
+                                my_table.want_to_visit_value(return_value, true, Some(reg_ret));
+                            assert_eq!(reg, reg_ret, "WTF??! I asked to load into reg a0!!!");
+                            body_codes.extend(codes);
+                            my_table.remove_temp_value(return_value);
                         }
                         // At the end of the basic block, store all global and local variables into memory.
                         body_codes.extend(my_table.store_global_variables());
@@ -365,7 +1014,13 @@ impl AssemblyBuildable for FunctionData {
                         my_table.remove_temp_value(binary.rhs());
                         let (reg_ans, codes) = my_table.want_to_visit_value(value, false, None);
                         body_codes.extend(codes);
-                        body_codes.push(binary_op_to_assembly(binary, reg_ans, reg1, reg2));
+                        body_codes.push(binary_op_to_assembly(
+                            binary.op(),
+                            target == Target::RV64,
+                            reg_ans,
+                            reg1,
+                            reg2,
+                        ));
                     }
 
                     // Alloc operation
@@ -375,15 +1030,26 @@ impl AssemblyBuildable for FunctionData {
                     koopa::ir::ValueKind::Store(store) => {
                         if my_table.is_temp_value(store.dest()) {
                             // Store to a definite location
+                            if may_alias {
+                                body_codes.extend(my_table.store_global_variables());
+                                body_codes.extend(my_table.store_local_variables());
+                            }
+                            let is_float = my_table.is_float_value(store.value());
                             let (reg_v, codes_v) =
-                                my_table.want_to_visit_value(store.value(), true, None);
+                                my_table.want_to_visit_as(store.value(), true, is_float);
                             let (reg_d, codes_d) =
                                 my_table.want_to_visit_value(store.dest(), true, None);
                             body_codes.extend(codes_v);
                             body_codes.extend(codes_d);
+                            let (_, store_op) = MyBBValueTable::memory_ops(
+                                reg_v,
+                                my_table.register_size(store.value()),
+                            );
                             body_codes.push(format!(
-                                "  sw\t{}, 0({})",
-                                REGISTER_NAMES[reg_v], REGISTER_NAMES[reg_d]
+                                "  {}\t{}, 0({})",
+                                store_op,
+                                register_name(reg_v),
+                                REGISTER_NAMES[reg_d]
                             ));
                         } else {
                             // Store to a local variable
@@ -397,14 +1063,24 @@ impl AssemblyBuildable for FunctionData {
                     koopa::ir::ValueKind::Load(load) => {
                         if my_table.is_temp_value(load.src()) {
                             // Store to a definite location
+                            if may_alias {
+                                body_codes.extend(my_table.store_global_variables());
+                                body_codes.extend(my_table.store_local_variables());
+                            }
                             let (reg_s, codes_s) =
                                 my_table.want_to_visit_value(load.src(), true, None);
-                            let (reg_v, codes_v) = my_table.want_to_visit_value(value, true, None);
+                            let is_float = my_table.is_float_value(value);
+                            let (reg_v, codes_v) =
+                                my_table.want_to_visit_as(value, false, is_float);
                             body_codes.extend(codes_s);
                             body_codes.extend(codes_v);
+                            let (load_op, _) =
+                                MyBBValueTable::memory_ops(reg_v, my_table.register_size(value));
                             body_codes.push(format!(
-                                "  lw\t{}, 0({})",
-                                REGISTER_NAMES[reg_v], REGISTER_NAMES[reg_s]
+                                "  {}\t{}, 0({})",
+                                load_op,
+                                register_name(reg_v),
+                                REGISTER_NAMES[reg_s]
                             ));
                         } else {
                             body_codes.extend(my_table.assign_v1_to_v2(load.src(), value));
@@ -416,18 +1092,7 @@ impl AssemblyBuildable for FunctionData {
                         // At the end of the basic block, store all global and local variables into memory.
                         body_codes.extend(my_table.store_global_variables());
                         body_codes.extend(my_table.store_local_variables());
-                        body_codes.push(format!(
-                            "  j\t.{}",
-                            &self
-                                .dfg()
-                                .bbs()
-                                .get(&jump.target())
-                                .expect("Can't find jump target BasicBlock. Should not happen. ")
-                                .name()
-                                .clone()
-                                .expect("Jump target BasicBlock has no name. Should not happen. ")
-                                [1..]
-                        ));
+                        body_codes.push(format!("  j\t.{}", bb_name(self, jump.target())?));
                     }
 
                     // Branch operation
@@ -442,71 +1107,151 @@ impl AssemblyBuildable for FunctionData {
                         body_codes.push(format!(
                             "  bnez\t{}, .{}",
                             REGISTER_NAMES[cond_reg],
-                            &self
-                                .dfg()
-                                .bbs()
-                                .get(&branch.true_bb())
-                                .expect("Can't find branch BasicBlock 1. Should not happen. ")
-                                .name()
-                                .clone()
-                                .expect("Branch BasicBlock 1 has no name. Should not happen. ")
-                                [1..]
-                        ));
-                        body_codes.push(format!(
-                            "  j\t.{}",
-                            &self
-                                .dfg()
-                                .bbs()
-                                .get(&branch.false_bb())
-                                .expect("Can't find branch BasicBlock 2. Should not happen. ")
-                                .name()
-                                .clone()
-                                .expect("Branch BasicBlock 2 has no name. Should not happen. ")
-                                [1..]
+                            bb_name(self, branch.true_bb())?
                         ));
+                        body_codes.push(format!("  j\t.{}", bb_name(self, branch.false_bb())?));
                     }
 
                     koopa::ir::ValueKind::Call(call) => {
-                        // Push args into registers for args.
-                        for i in 0..std::cmp::min(REGISTER_FOR_ARGS.len(), call.args().len()) {
-                            let (reg, codes) = my_table.want_to_visit_value(
-                                call.args()[i],
-                                true,
-                                Some(REGISTER_FOR_ARGS[i]),
-                            );
-                            assert_eq!(
-                                reg, REGISTER_FOR_ARGS[i],
-                                "WTF??! I asked to load into this reg!!!"
-                            );
+                        let callee_name = &program.func(call.callee()).name()[1..];
+                        // Intrinsics are not real calls.
+                        if let Some(&intrinsic_name) = program_info.intrinsics.get(&call.callee()) {
+                            let (float_params, returns_float) =
+                                match program_info.float_signatures.get(&call.callee()) {
+                                    Some((float_params, returns_float)) => {
+                                        (float_params.as_slice(), *returns_float)
+                                    }
+                                    None => (&[][..], false),
+                                };
+                            let mut arg_regs = vec![];
+                            for (i, &arg) in call.args().iter().enumerate() {
+                                let is_float = float_params.get(i) == Some(&true);
+                                let (reg, codes) = my_table.want_to_visit_as(arg, true, is_float);
+                                body_codes.extend(codes);
+                                arg_regs.push(reg);
+                            }
+                            for &arg in call.args() {
+                                my_table.remove_temp_value(arg);
+                            }
+                            let (reg_ans, codes) =
+                                my_table.want_to_visit_as(value, false, returns_float);
                             body_codes.extend(codes);
-                            my_table.remove_temp_value(call.args()[i]);
+                            body_codes.push(intrinsic_to_assembly(
+                                intrinsic_name,
+                                reg_ans,
+                                &arg_regs,
+                                target,
+                            )?);
+                            continue;
                         }
-                        for i in REGISTER_FOR_ARGS.len()..call.args().len() {
-                            let (reg, codes) =
-                                my_table.want_to_visit_value(call.args()[i], true, None);
-                            body_codes.extend(codes);
-                            let offset = (i - REGISTER_FOR_ARGS.len()) * ARG_SIZE;
-                            body_codes.extend(my_table.store_with_offset(reg, offset as isize));
-                            my_table.remove_temp_value(call.args()[i]);
+
+                        // Push args into registers for args, or into the stack.
+                        let (arg_locations, _) =
+                            call_arg_locations(call, program_info, self, target);
+                        for (&arg, location) in call.args().iter().zip(&arg_locations) {
+                            match *location {
+                                // A double is converted after all args are loaded.
+                                ArgLocation::Register(arg_reg)
+                                | ArgLocation::RegisterPair(arg_reg, _)
+                                | ArgLocation::DoubleInRegister(arg_reg) => {
+                                    let (reg, codes) =
+                                        my_table.want_to_visit_value(arg, true, Some(arg_reg));
+                                    assert_eq!(
+                                        reg, arg_reg,
+                                        "WTF??! I asked to load into this reg!!!"
+                                    );
+                                    body_codes.extend(codes);
+                                }
+                                ArgLocation::FloatRegister(index) => {
+                                    let (_, codes) = my_table.want_to_visit_value(
+                                        arg,
+                                        true,
+                                        Some(REG_FA0 + index),
+                                    );
+                                    body_codes.extend(codes);
+                                }
+                                ArgLocation::Stack(offset) => {
+                                    let (reg, codes) =
+                                        my_table.want_to_visit_value(arg, true, None);
+                                    body_codes.extend(codes);
+                                    body_codes.extend(my_table.store_with_offset(
+                                        reg,
+                                        offset as isize,
+                                        target.ptr_size(),
+                                    ));
+                                }
+                                // The float is converted after the registers are saved.
+                                ArgLocation::DoubleOnStack(offset) => {
+                                    let (reg, codes) =
+                                        my_table.want_to_visit_value(arg, true, None);
+                                    body_codes.extend(codes);
+                                    body_codes.push(format!(
+                                        "  sw\t{}, {}(sp)",
+                                        REGISTER_NAMES[reg], offset
+                                    ));
+                                }
+                            }
+                            my_table.remove_temp_value(arg);
                         }
 
                         // Save caller-saved registers.
-                        for reg in REGISTER_FOR_TEMP {
+                        for reg in REGISTER_FOR_TEMP.into_iter().chain(FLOAT_REGISTER_FOR_TEMP) {
                             body_codes.extend(my_table.save_register(reg));
                         }
 
                         // Store back all the global variables in registers.
                         body_codes.extend(my_table.store_global_variables());
 
+                        // Convert the floats into doubles. The temp registers are free now.
+                        let [temp0, temp1, ..] = REGISTER_FOR_TEMP;
+                        for location in &arg_locations {
+                            let label = format!("{}_double_{}", &self.name()[1..], double_cnt);
+                            match *location {
+                                ArgLocation::RegisterPair(low, high) => body_codes
+                                    .push(float_to_double_assembly(&label, low, high, target)),
+                                ArgLocation::DoubleInRegister(reg) => body_codes
+                                    .push(float_to_double_assembly(&label, reg, temp0, target)),
+                                ArgLocation::DoubleOnStack(offset) => {
+                                    body_codes.push(format!(
+                                        "  lw\t{}, {}(sp)",
+                                        REGISTER_NAMES[temp0], offset
+                                    ));
+                                    body_codes.push(float_to_double_assembly(
+                                        &label, temp0, temp1, target,
+                                    ));
+                                    body_codes.push(match target {
+                                        Target::RV32 => format!(
+                                            "  sw\t{}, {}(sp)\n  sw\t{}, {}(sp)",
+                                            REGISTER_NAMES[temp0],
+                                            offset,
+                                            REGISTER_NAMES[temp1],
+                                            offset + 4
+                                        ),
+                                        Target::RV64 => format!(
+                                            "  sd\t{}, {}(sp)",
+                                            REGISTER_NAMES[temp0], offset
+                                        ),
+                                    });
+                                }
+                                _ => continue,
+                            }
+                            double_cnt += 1;
+                        }
+
                         // Call the function.
-                        body_codes.push(format!(
-                            "  call\t{}",
-                            &program.func(call.callee()).name()[1..]
-                        ));
+                        body_codes.push(format!("  call\t{}", callee_name));
 
-                        // Now the returned value is in register a0.
-                        let (reg, codes) = my_table.want_to_visit_value(value, false, None);
-                        body_codes.push(format!("  mv\t{}, a0", REGISTER_NAMES[reg]));
+                        // Now the returned value is in register a0, or fa0 for a float.
+                        // A void function returns nothing, and its result has no place in the stack.
+                        if value_data.ty().is_unit() {
+                            continue;
+                        }
+                        let returns_float = my_table.is_float_value(value);
+                        let (reg, codes) = my_table.want_to_visit_as(value, false, returns_float);
+                        body_codes.push(match returns_float {
+                            true => format!("  fmv.s\t{}, fa0", register_name(reg)),
+                            false => format!("  mv\t{}, a0", REGISTER_NAMES[reg]),
+                        });
                         body_codes.extend(codes);
                     }
 
@@ -515,15 +1260,20 @@ impl AssemblyBuildable for FunctionData {
                         let (reg_o, codes_o) =
                             my_table.want_to_visit_value(getptr.index(), true, None);
                         body_codes.extend(codes_o);
-                        // Starting address
-                        let (reg_addr, codes_addr) =
-                            my_table.want_to_visit_value(getptr.src(), true, None);
+                        // Starting address. It is the address of a variable for "getptr @x, 0".
+                        let (reg_addr, codes_addr) = my_table.get_absolute_location(getptr.src());
                         body_codes.extend(codes_addr);
-                        // Target address = Offset * ptr_size + Starting address
+                        // Target address = Offset * elem_size + Starting address
+                        let TypeKind::Pointer(elem_type) = value_data.ty().kind() else {
+                            return Err(CompileError::without_span(
+                                ErrorCode::BackendInternal,
+                                "getptr does not return a pointer! ".to_string(),
+                            ));
+                        };
                         body_codes.push(format!(
                             "  li\t{}, {}\n  mul\t{}, {}, {}\n  add\t{}, {}, {}",
                             REGISTER_NAMES[REG_X31],
-                            Type::get_pointer(Type::get_i32()).size(),
+                            elem_type.size(),
                             REGISTER_NAMES[REG_X31],
                             REGISTER_NAMES[reg_o],
                             REGISTER_NAMES[REG_X31],
@@ -546,11 +1296,17 @@ impl AssemblyBuildable for FunctionData {
                         let (reg_addr, codes_addr) =
                             my_table.get_absolute_location(getelemptr.src());
                         body_codes.extend(codes_addr);
-                        // Target address = Offset * ptr_size + Starting address
+                        // Target address = Offset * elem_size + Starting address
+                        let TypeKind::Pointer(elem_type) = value_data.ty().kind() else {
+                            return Err(CompileError::without_span(
+                                ErrorCode::BackendInternal,
+                                "getelemptr does not return a pointer! ".to_string(),
+                            ));
+                        };
                         body_codes.push(format!(
                             "  li\t{}, {}\n  mul\t{}, {}, {}\n  add\t{}, {}, {}",
                             REGISTER_NAMES[REG_X31],
-                            Type::get_pointer(Type::get_i32()).size(),
+                            elem_type.size(),
                             REGISTER_NAMES[REG_X31],
                             REGISTER_NAMES[reg_o],
                             REGISTER_NAMES[REG_X31],
@@ -566,7 +1322,11 @@ impl AssemblyBuildable for FunctionData {
 
                     // Other instructions (TODO: Not implemented)
                     value_kind => {
-                        panic!("Unknown Koopa IR instruction value {:?}", value_kind)
+                        return Err(CompileError::without_span(
+                            ErrorCode::UnsupportedInstruction,
+                            format!("Unknown Koopa IR instruction value {:?}! ", value_kind),
+                        )
+                        .with_note(format!("in function {}", &self.name()[1..])))
                     }
                 }
             }
@@ -577,8 +1337,11 @@ impl AssemblyBuildable for FunctionData {
         epilogue_codes.push(format!("\n.{}_ret:", &self.name()[1..]));
 
         // Restore registar ra. (Return address)
-        epilogue_codes
-            .extend(my_table.load_with_offset(REG_RA, (stack_frame_size - reg_ra_size) as isize));
+        epilogue_codes.extend(my_table.load_with_offset(
+            REG_RA,
+            (stack_frame_size - reg_ra_size) as isize,
+            reg_ra_size,
+        ));
 
         // Restore callee-saved registers.
         // (Currently no callee-saved registers need to be restored. )
@@ -588,12 +1351,74 @@ impl AssemblyBuildable for FunctionData {
         epilogue_codes.extend(my_table.add_with_offset(REG_SP, stack_frame_size as isize));
 
         // Return
-        epilogue_codes.push(format!("  ret\n"));
+        epilogue_codes.push("  ret\n".to_string());
 
         let mut all_codes = vec![];
         all_codes.extend(prologue_codes);
         all_codes.extend(body_codes);
         all_codes.extend(epilogue_codes);
+        if !rodata_codes.is_empty() {
+            all_codes.push("  .section .rodata".to_string());
+            all_codes.extend(rodata_codes);
+            all_codes.push("  .text".to_string());
+        }
         Ok(all_codes)
     }
 }
+
+/// Jumps to the target in the jump table, and returns (codes, codes of the table in .rodata).
+fn jump_table_to_assembly(
+    fd: &FunctionData,
+    my_table: &mut MyBBValueTable,
+    block: BasicBlock,
+    jump_table: &JumpTable,
+) -> Result<(Vec<String>, Vec<String>), CompileError> {
+    let mut codes = vec![];
+    let (reg, codes_to_visit) = my_table.want_to_visit_value(jump_table.loaded_value, true, None);
+    codes.extend(codes_to_visit);
+    my_table.remove_temp_value(jump_table.loaded_value);
+    // At the end of the basic block, store all global and local variables into memory.
+    codes.extend(my_table.store_global_variables());
+    codes.extend(my_table.store_local_variables());
+
+    // index = value - min_label. If index >= len (unsigned), go to default.
+    codes.extend(my_table.add_with_offset(reg, jump_table.min_label.wrapping_neg() as isize));
+    codes.push(format!(
+        "  li\t{}, {}\n  bgeu\t{}, {}, .{}",
+        REGISTER_NAMES[REG_X31],
+        jump_table.targets.len(),
+        REGISTER_NAMES[reg],
+        REGISTER_NAMES[REG_X31],
+        bb_name(fd, jump_table.default)?
+    ));
+    // Load the target address from the table, and jump. An address takes a register.
+    let table_label = format!("{}_labels", bb_name(fd, block)?);
+    let (shift, load_op, directive) = match my_table.target {
+        Target::RV32 => (2, "lw", ".word"),
+        Target::RV64 => (3, "ld", ".dword"),
+    };
+    codes.push(format!(
+        "  slli\t{}, {}, {}\n  la\t{}, .{}\n  add\t{}, {}, {}\n  {}\t{}, 0({})\n  jr\t{}",
+        REGISTER_NAMES[reg],
+        REGISTER_NAMES[reg],
+        shift,
+        REGISTER_NAMES[REG_X31],
+        table_label,
+        REGISTER_NAMES[REG_X31],
+        REGISTER_NAMES[REG_X31],
+        REGISTER_NAMES[reg],
+        load_op,
+        REGISTER_NAMES[REG_X31],
+        REGISTER_NAMES[REG_X31],
+        REGISTER_NAMES[REG_X31],
+    ));
+
+    let mut table_codes = vec![
+        format!("  .p2align {}", shift),
+        format!(".{}:", table_label),
+    ];
+    for &target in &jump_table.targets {
+        table_codes.push(format!("  {} .{}", directive, bb_name(fd, target)?));
+    }
+    Ok((codes, table_codes))
+}
@@ -2,18 +2,45 @@
 //! It converts the Koopa IR into assembly code.
 
 mod build_assembly;
-use std::collections::HashMap;
-use std::io::Write;
+use std::collections::{HashMap, HashSet};
 
+use crate::diagnostics::CompileError;
+use crate::ir_builder::ProgramInfo;
 use build_assembly::AssemblyBuildable;
-use koopa::ir::{FunctionData, Program, Value};
+use koopa::ir::{FunctionData, Program, TypeKind, Value};
 
-pub fn generate_assembly(program: &Program, output_file: &mut std::fs::File) -> Result<(), String> {
-    let codes = program.build(program)?;
-    for code in codes {
-        writeln!(output_file, "{}", code).expect("Write error. ");
+/// The RISC-V target of the assembly code, selected by -march.
+/// RV64 supports long long, which is a single register there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    RV32,
+    RV64,
+}
+
+impl Target {
+    pub fn from_march(march: &str) -> Option<Target> {
+        match march {
+            "rv32" => Some(Target::RV32),
+            "rv64" => Some(Target::RV64),
+            _ => None,
+        }
+    }
+
+    /// The bytes of a pointer, which is also the bytes of a register.
+    pub fn ptr_size(self) -> usize {
+        match self {
+            Target::RV32 => 4,
+            Target::RV64 => 8,
+        }
     }
-    Ok(())
+}
+
+pub fn generate_assembly(
+    program: &Program,
+    program_info: &ProgramInfo,
+    target: Target,
+) -> Result<Vec<String>, CompileError> {
+    program.build(program, program_info, target)
 }
 
 const REGISTER_NAMES: [&str; 32] = [
@@ -28,7 +55,20 @@ const REGISTER_FOR_TEMP: [usize; 6] = [5, 6, 7, 28, 29, 30];
 
 const REGISTER_FOR_ARGS: [usize; 8] = [10, 11, 12, 13, 14, 15, 16, 17];
 
-const ARG_SIZE: usize = 4;
+const FLOAT_REGISTER_NAMES: [&str; 32] = [
+    "ft0", "ft1", "ft2", "ft3", "ft4", "ft5", "ft6", "ft7", "fs0", "fs1", "fa0", "fa1", "fa2",
+    "fa3", "fa4", "fa5", "fa6", "fa7", "fs2", "fs3", "fs4", "fs5", "fs6", "fs7", "fs8", "fs9",
+    "fs10", "fs11", "ft8", "ft9", "ft10", "ft11",
+];
+
+/// Float registers are numbered after the integer registers.
+/// FLOAT_REG_BASE + i is FLOAT_REGISTER_NAMES[i].
+const FLOAT_REG_BASE: usize = 32;
+
+/// ft0 and ft1 are not here. They are for converting floats into doubles.
+const FLOAT_REGISTER_FOR_TEMP: [usize; 10] = [34, 35, 36, 37, 38, 39, 60, 61, 62, 63];
+
+const REG_FA0: usize = 42;
 
 const REG_A0: usize = 10;
 const REG_SP: usize = 2;
@@ -39,49 +79,115 @@ const REG_X31: usize = 31;
 const MAX_SHORT_INT: isize = 2047;
 const MIN_SHORT_INT: isize = -2048;
 
+fn is_float_register(reg: usize) -> bool {
+    reg >= FLOAT_REG_BASE
+}
+
+/// The name of an integer register or a float register.
+fn register_name(reg: usize) -> &'static str {
+    match reg.checked_sub(FLOAT_REG_BASE) {
+        Some(float_reg) => FLOAT_REGISTER_NAMES[float_reg],
+        None => REGISTER_NAMES[reg],
+    }
+}
+
+/// Every integer register and float register.
+fn all_registers() -> impl Iterator<Item = usize> {
+    (0..REGISTER_NAMES.len()).chain(FLOAT_REG_BASE..FLOAT_REG_BASE + FLOAT_REGISTER_NAMES.len())
+}
+
+/// The label of a global value in assembly.
+/// String literals and static local variables are named by the IR builder,
+/// so they get local labels (".L..."), which can not be the same as any name in C.
+fn global_label(program: &Program, program_info: &ProgramInfo, value: Value) -> String {
+    let name = &program.borrow_value(value).name().clone().unwrap()[1..];
+    match program_info.string_constants.contains(&value)
+        || program_info.static_locals.contains(&value)
+    {
+        true => format!(".L{}", name),
+        false => name.to_string(),
+    }
+}
+
 pub struct MyBBValueTable<'a> {
     program: &'a Program,
+    program_info: &'a ProgramInfo,
     fd: &'a FunctionData,
+    target: Target,
     curr_time: i32,
     register_user: [Option<Value>; 32],
     register_used_time: [i32; 32], // LRU registers
+    float_register_user: [Option<Value>; 32],
+    float_register_used_time: [i32; 32],
+    float_values: HashSet<Value>, // Values holding floats, which are loaded into float registers
     local_value_location: HashMap<Value, usize>,
+    temp_value_uses: HashMap<Value, usize>, // Uses of temp values in the current basic block
 }
 
 impl MyBBValueTable<'_> {
-    fn new<'a>(program: &'a Program, fd: &'a FunctionData) -> MyBBValueTable<'a> {
+    fn new<'a>(
+        program: &'a Program,
+        program_info: &'a ProgramInfo,
+        fd: &'a FunctionData,
+        target: Target,
+    ) -> MyBBValueTable<'a> {
         MyBBValueTable {
             program,
+            program_info,
             fd,
+            target,
             curr_time: 0,
             register_user: [None; 32],
             register_used_time: [0; 32],
+            float_register_user: [None; 32],
+            float_register_used_time: [0; 32],
+            float_values: HashSet::new(),
             local_value_location: HashMap::new(),
+            temp_value_uses: HashMap::new(),
         }
     }
 
+    fn __user(&self, reg: usize) -> Option<Value> {
+        match reg.checked_sub(FLOAT_REG_BASE) {
+            Some(float_reg) => self.float_register_user[float_reg],
+            None => self.register_user[reg],
+        }
+    }
+    fn __used_time(&self, reg: usize) -> i32 {
+        match reg.checked_sub(FLOAT_REG_BASE) {
+            Some(float_reg) => self.float_register_used_time[float_reg],
+            None => self.register_used_time[reg],
+        }
+    }
+    fn __set_user(&mut self, reg: usize, user: Option<Value>, time: i32) {
+        match reg.checked_sub(FLOAT_REG_BASE) {
+            Some(float_reg) => {
+                self.float_register_user[float_reg] = user;
+                self.float_register_used_time[float_reg] = time;
+            }
+            None => {
+                self.register_user[reg] = user;
+                self.register_used_time[reg] = time;
+            }
+        }
+    }
     fn __update_user(&mut self, reg: usize, user: Value) {
         self.curr_time += 1;
-        self.register_user[reg] = Some(user);
-        self.register_used_time[reg] = self.curr_time;
+        self.__set_user(reg, Some(user), self.curr_time);
     }
     fn __free_user(&mut self, reg: usize) {
-        self.register_user[reg] = None;
-        self.register_used_time[reg] = 0;
+        self.__set_user(reg, None, 0);
     }
 
-    fn __is_value_in_register(&self, value: Value) -> Option<usize> {
-        for i in 0..REGISTER_NAMES.len() {
-            match self.register_user[i] {
-                Some(v) => {
-                    if v == value {
-                        return Some(i);
-                    }
-                }
-                None => {}
-            }
-        }
-        None
+    /// Finds the value in the integer registers, or in the float registers.
+    fn __is_value_in_register(&self, value: Value, is_float: bool) -> Option<usize> {
+        all_registers()
+            .filter(|&reg| is_float_register(reg) == is_float)
+            .find(|&reg| self.__user(reg) == Some(value))
+    }
+
+    fn is_float_value(&self, value: Value) -> bool {
+        self.float_values.contains(&value)
     }
 
     fn is_temp_value(&self, value: Value) -> bool {
@@ -95,6 +201,35 @@ impl MyBBValueTable<'_> {
         }
     }
 
+    /// The bytes of a value in a register: 8 for pointers and long longs in RV64, otherwise 4.
+    /// A variable is in a register as its value, so its size is the size of what it allocates.
+    fn register_size(&self, value: Value) -> usize {
+        let value_data = match value.is_global() {
+            true => self.program.borrow_value(value).clone(),
+            false => self.fd.dfg().value(value).clone(),
+        };
+        let size = match (value_data.kind(), value_data.ty().kind()) {
+            (
+                koopa::ir::ValueKind::Alloc(_) | koopa::ir::ValueKind::GlobalAlloc(_),
+                TypeKind::Pointer(base_type),
+            ) => base_type.size(),
+            _ => value_data.ty().size(),
+        };
+        match (self.target, size) {
+            (Target::RV64, 8) => 8,
+            _ => 4,
+        }
+    }
+
+    /// The load and store instructions for a value of the given bytes in the register.
+    fn memory_ops(reg: usize, size: usize) -> (&'static str, &'static str) {
+        match (is_float_register(reg), size) {
+            (true, _) => ("flw", "fsw"),
+            (false, 8) => ("ld", "sd"),
+            (false, _) => ("lw", "sw"),
+        }
+    }
+
     fn remove_temp_value(&mut self, value: Value) {
         // assert!(
         //     self.is_temp_value(value),
@@ -103,41 +238,66 @@ impl MyBBValueTable<'_> {
         if !self.is_temp_value(value) {
             return;
         }
-        if let Some(reg) = self.__is_value_in_register(value) {
-            self.__free_user(reg);
+        // A temp value may be used several times, e.g. the old value of a++.
+        // Only remove it after the last use.
+        if let Some(uses) = self.temp_value_uses.get_mut(&value) {
+            *uses = uses.saturating_sub(1);
+            if *uses > 0 {
+                return;
+            }
+        }
+        // It may be in an integer register and a float register.
+        for reg in all_registers() {
+            if self.__user(reg) == Some(value) {
+                self.__free_user(reg);
+            }
         }
         self.local_value_location.remove(&value);
     }
 
-    fn store_with_offset(&mut self, reg: usize, offset: isize) -> Vec<String> {
+    fn store_with_offset(&mut self, reg: usize, offset: isize, size: usize) -> Vec<String> {
         let mut codes = vec![];
-        if MIN_SHORT_INT <= offset && offset <= MAX_SHORT_INT {
-            codes.push(format!("  sw\t{}, {}(sp)", REGISTER_NAMES[reg], offset));
+        let (_, store_op) = Self::memory_ops(reg, size);
+        if (MIN_SHORT_INT..=MAX_SHORT_INT).contains(&offset) {
+            codes.push(format!(
+                "  {}\t{}, {}(sp)",
+                store_op,
+                register_name(reg),
+                offset
+            ));
         } else {
             codes.push(format!(
-                "  li\t{}, {}\n  add\t{}, {}, sp\n  sw\t{}, 0({})",
+                "  li\t{}, {}\n  add\t{}, {}, sp\n  {}\t{}, 0({})",
                 REGISTER_NAMES[REG_X31],
                 offset,
                 REGISTER_NAMES[REG_X31],
                 REGISTER_NAMES[REG_X31],
-                REGISTER_NAMES[reg],
+                store_op,
+                register_name(reg),
                 REGISTER_NAMES[REG_X31]
             ));
         }
         codes
     }
-    fn load_with_offset(&mut self, reg: usize, offset: isize) -> Vec<String> {
+    fn load_with_offset(&mut self, reg: usize, offset: isize, size: usize) -> Vec<String> {
         let mut codes = vec![];
-        if MIN_SHORT_INT <= offset && offset <= MAX_SHORT_INT {
-            codes.push(format!("  lw\t{}, {}(sp)", REGISTER_NAMES[reg], offset));
+        let (load_op, _) = Self::memory_ops(reg, size);
+        if (MIN_SHORT_INT..=MAX_SHORT_INT).contains(&offset) {
+            codes.push(format!(
+                "  {}\t{}, {}(sp)",
+                load_op,
+                register_name(reg),
+                offset
+            ));
         } else {
             codes.push(format!(
-                "  li\t{}, {}\n  add\t{}, {}, sp\n  lw\t{}, 0({})",
+                "  li\t{}, {}\n  add\t{}, {}, sp\n  {}\t{}, 0({})",
                 REGISTER_NAMES[REG_X31],
                 offset,
                 REGISTER_NAMES[REG_X31],
                 REGISTER_NAMES[REG_X31],
-                REGISTER_NAMES[reg],
+                load_op,
+                register_name(reg),
                 REGISTER_NAMES[REG_X31]
             ));
         }
@@ -145,7 +305,7 @@ impl MyBBValueTable<'_> {
     }
     fn add_with_offset(&mut self, reg: usize, offset: isize) -> Vec<String> {
         let mut codes = vec![];
-        if MIN_SHORT_INT <= offset && offset <= MAX_SHORT_INT {
+        if (MIN_SHORT_INT..=MAX_SHORT_INT).contains(&offset) {
             codes.push(format!(
                 "  addi\t{}, {}, {}",
                 REGISTER_NAMES[reg], REGISTER_NAMES[reg], offset
@@ -162,25 +322,35 @@ impl MyBBValueTable<'_> {
         }
         codes
     }
-    fn load_global(&mut self, reg: usize, symbol_name: String) -> Vec<String> {
+    fn load_global(&mut self, reg: usize, symbol_name: String, size: usize) -> Vec<String> {
+        let (load_op, _) = Self::memory_ops(reg, size);
         vec![format!(
-            "  la\t{}, {}\n  lw\t{}, 0({})",
-            REGISTER_NAMES[REG_X31], symbol_name, REGISTER_NAMES[reg], REGISTER_NAMES[REG_X31]
+            "  la\t{}, {}\n  {}\t{}, 0({})",
+            REGISTER_NAMES[REG_X31],
+            symbol_name,
+            load_op,
+            register_name(reg),
+            REGISTER_NAMES[REG_X31]
         )]
     }
-    fn store_global(&mut self, reg: usize, symbol_name: String) -> Vec<String> {
+    fn store_global(&mut self, reg: usize, symbol_name: String, size: usize) -> Vec<String> {
+        let (_, store_op) = Self::memory_ops(reg, size);
         vec![format!(
-            "  la\t{}, {}\n  sw\t{}, 0({})",
-            REGISTER_NAMES[REG_X31], symbol_name, REGISTER_NAMES[reg], REGISTER_NAMES[REG_X31]
+            "  la\t{}, {}\n  {}\t{}, 0({})",
+            REGISTER_NAMES[REG_X31],
+            symbol_name,
+            store_op,
+            register_name(reg),
+            REGISTER_NAMES[REG_X31]
         )]
     }
 
     fn store_global_variables(&mut self) -> Vec<String> {
         let mut codes = vec![format!("# Save global variables.")];
-        for i in 0..REGISTER_NAMES.len() {
-            if let Some(value) = self.register_user[i] {
+        for reg in all_registers() {
+            if let Some(value) = self.__user(reg) {
                 if value.is_global() {
-                    codes.extend(self.save_register(i));
+                    codes.extend(self.save_register(reg));
                 }
             }
         }
@@ -189,12 +359,12 @@ impl MyBBValueTable<'_> {
 
     fn store_local_variables(&mut self) -> Vec<String> {
         let mut codes = vec![format!("# Save local variables.")];
-        for i in 0..REGISTER_NAMES.len() {
-            if let Some(value) = self.register_user[i] {
+        for reg in all_registers() {
+            if let Some(value) = self.__user(reg) {
                 if value.is_global() || self.is_temp_value(value) {
                     continue;
                 }
-                codes.extend(self.save_register(i));
+                codes.extend(self.save_register(reg));
             }
         }
         codes
@@ -202,21 +372,20 @@ impl MyBBValueTable<'_> {
 
     /// Kick the value in a register and store it to memory.
     fn save_register(&mut self, reg: usize) -> Vec<String> {
-        let kicked_value = match self.register_user[reg] {
+        let kicked_value = match self.__user(reg) {
             Some(value) => value,
             None => return vec![],
         };
-        let value_data = match kicked_value.is_global() {
-            true => self.program.borrow_value(kicked_value).clone(),
-            false => self.fd.dfg().value(kicked_value).clone(),
-        };
         let mut codes = vec![];
+        let size = self.register_size(kicked_value);
         // Store the value into memory.
         // Global value
         if kicked_value.is_global() {
-            codes.extend(
-                self.store_global(reg, value_data.name().clone().unwrap()[1..].to_string()),
-            );
+            codes.extend(self.store_global(
+                reg,
+                global_label(self.program, self.program_info, kicked_value),
+                size,
+            ));
         }
         // Local or temp value
         else {
@@ -228,6 +397,7 @@ impl MyBBValueTable<'_> {
                 codes.extend(self.store_with_offset(
                     reg,
                     *self.local_value_location.get(&kicked_value).unwrap() as isize,
+                    size,
                 ));
             }
         }
@@ -236,17 +406,26 @@ impl MyBBValueTable<'_> {
         codes
     }
 
-    /// Finds a usable register.
+    /// Finds a usable integer register.
     /// If all registers are being used, then kicks one.
     fn get_tmp_reg(&mut self) -> (usize, Vec<String>) {
+        self.__get_tmp_reg(&REGISTER_FOR_TEMP)
+    }
+
+    /// Finds a usable float register.
+    fn get_tmp_float_reg(&mut self) -> (usize, Vec<String>) {
+        self.__get_tmp_reg(&FLOAT_REGISTER_FOR_TEMP)
+    }
+
+    fn __get_tmp_reg(&mut self, registers: &[usize]) -> (usize, Vec<String>) {
         self.curr_time += 1;
-        let mut now_min = std::i32::MAX;
+        let mut now_min = i32::MAX;
         let mut possible_choice: Option<usize> = None;
-        for i in REGISTER_FOR_TEMP {
-            match self.register_user[i] {
+        for &i in registers {
+            match self.__user(i) {
                 Some(_) => {
-                    if self.register_used_time[i] < now_min {
-                        now_min = self.register_used_time[i];
+                    if self.__used_time(i) < now_min {
+                        now_min = self.__used_time(i);
                         possible_choice = Some(i);
                     }
                 }
@@ -258,78 +437,141 @@ impl MyBBValueTable<'_> {
     }
 
     /// Want to visit a value. Make it appear in a register.
+    /// It is an integer register, unless the certain register is a float register.
     fn want_to_visit_value(
         &mut self,
         value: Value,
         do_load: bool,
         use_certain_reg: Option<usize>,
+    ) -> (usize, Vec<String>) {
+        let is_float = use_certain_reg.is_some_and(is_float_register);
+        self.__want_to_visit_value(value, do_load, use_certain_reg, is_float)
+    }
+
+    /// Make a value appear in a float register, or in an integer register.
+    fn want_to_visit_as(
+        &mut self,
+        value: Value,
+        do_load: bool,
+        is_float: bool,
+    ) -> (usize, Vec<String>) {
+        self.__want_to_visit_value(value, do_load, None, is_float)
+    }
+
+    fn __want_to_visit_value(
+        &mut self,
+        value: Value,
+        do_load: bool,
+        use_certain_reg: Option<usize>,
+        is_float: bool,
     ) -> (usize, Vec<String>) {
         self.curr_time += 1;
         let value_data = match value.is_global() {
             true => self.program.borrow_value(value).clone(),
             false => self.fd.dfg().value(value).clone(),
         };
-        if let koopa::ir::ValueKind::Integer(int) = value_data.kind() {
+        // A local zeroinit is the null pointer.
+        // A local aggregate is a long long constant in RV64: its low word and its high word.
+        let possible_int = match value_data.kind() {
+            koopa::ir::ValueKind::Integer(int) => Some(int.value() as i64),
+            koopa::ir::ValueKind::ZeroInit(_) => Some(0),
+            koopa::ir::ValueKind::Aggregate(aggr) => {
+                let word = |i: usize| match self.fd.dfg().value(aggr.elems()[i]).kind() {
+                    koopa::ir::ValueKind::Integer(int) => int.value(),
+                    _ => unreachable!("A long long constant is made of integers"),
+                };
+                Some(((word(1) as i64) << 32) | (word(0) as u32 as i64))
+            }
+            _ => None,
+        };
+        if let Some(int) = possible_int {
             // Allocate a new register for the Integer.
             // I don't want to use assembly codes like addi because I am lazy.
             let (dst_reg, mut codes) = match use_certain_reg {
                 Some(reg) => (reg, vec![]),
+                None if is_float => self.get_tmp_float_reg(),
                 None => self.get_tmp_reg(),
             };
-            codes.push(format!(
-                "  li\t{}, {}",
-                REGISTER_NAMES[dst_reg],
-                int.value()
-            ));
+            match is_float {
+                false => codes.push(format!("  li\t{}, {}", REGISTER_NAMES[dst_reg], int)),
+                // A float constant is its bits.
+                true => codes.push(format!(
+                    "  li\t{}, {}\n  fmv.w.x\t{}, {}",
+                    REGISTER_NAMES[REG_X31],
+                    int,
+                    register_name(dst_reg),
+                    REGISTER_NAMES[REG_X31]
+                )),
+            }
             self.__update_user(dst_reg, value);
             return (dst_reg, codes);
         }
+        // The value will be changed, so the other registers holding it are out of date.
+        if !do_load {
+            let written_reg =
+                use_certain_reg.or_else(|| self.__is_value_in_register(value, is_float));
+            for reg in all_registers() {
+                if Some(reg) != written_reg && self.__user(reg) == Some(value) {
+                    self.__free_user(reg);
+                }
+            }
+        }
         // Value already in a register
-        if let Some(src_reg) = self.__is_value_in_register(value) {
+        if let Some(src_reg) = self.__is_value_in_register(value, is_float) {
             match use_certain_reg {
-                Some(reg_dst) => {
-                    if src_reg != reg_dst {
-                        self.__update_user(reg_dst, value);
-                        // self.__free_user(src_reg); Don't do that! May be used several times.
-                        return (
-                            reg_dst,
-                            vec![format!(
-                                "  mv\t{}, {}",
-                                REGISTER_NAMES[reg_dst], REGISTER_NAMES[src_reg]
-                            )],
-                        );
-                    } else {
-                        return (src_reg, vec![]);
-                    }
+                Some(reg_dst) if src_reg != reg_dst => {
+                    self.__update_user(reg_dst, value);
+                    // self.__free_user(src_reg); Don't do that! May be used several times.
+                    let move_op = if is_float { "fmv.s" } else { "mv" };
+                    return (
+                        reg_dst,
+                        vec![format!(
+                            "  {}\t{}, {}",
+                            move_op,
+                            register_name(reg_dst),
+                            register_name(src_reg)
+                        )],
+                    );
                 }
-                None => return (src_reg, vec![]),
+                _ => return (src_reg, vec![]),
             };
         }
-        // Value not in registers
         let (reg, mut codes) = match use_certain_reg {
             Some(reg_dst) => (reg_dst, vec![]),
+            None if is_float => self.get_tmp_float_reg(),
             None => self.get_tmp_reg(),
         };
+        // Value in a register of the other kind. The bits are moved.
+        if let Some(src_reg) = self.__is_value_in_register(value, !is_float) {
+            let move_op = if is_float { "fmv.w.x" } else { "fmv.x.w" };
+            codes.push(format!(
+                "  {}\t{}, {}",
+                move_op,
+                register_name(reg),
+                register_name(src_reg)
+            ));
+            self.__update_user(reg, value);
+            return (reg, codes);
+        }
+        // Value not in registers
         if do_load {
+            let size = self.register_size(value);
             match value.is_global() {
                 true => {
-                    codes.extend(
-                        self.load_global(reg, value_data.name().clone().unwrap()[1..].to_string()),
-                    );
+                    codes.extend(self.load_global(
+                        reg,
+                        global_label(self.program, self.program_info, value),
+                        size,
+                    ));
                 }
                 false => {
-                    let offset = self
-                        .local_value_location
-                        .get(&value)
-                        .expect(
-                            format!(
-                                "Cannot find local or temp value {:?} in table! Impossible.",
-                                value_data
-                            )
-                            .as_str(),
+                    let offset = self.local_value_location.get(&value).unwrap_or_else(|| {
+                        panic!(
+                            "Cannot find local or temp value {:?} in table! Impossible.",
+                            value_data
                         )
-                        .clone();
-                    codes.extend(self.load_with_offset(reg, offset as isize));
+                    });
+                    codes.extend(self.load_with_offset(reg, *offset as isize, size));
                 }
             }
         }
@@ -337,15 +579,20 @@ impl MyBBValueTable<'_> {
         (reg, codes)
     }
 
+    /// A float variable is assigned in float registers.
     fn assign_v1_to_v2(&mut self, v1: Value, v2: Value) -> Vec<String> {
         let mut codes = vec![];
-        let (reg1, codes1) = self.want_to_visit_value(v1, true, None);
-        let (reg2, codes2) = self.want_to_visit_value(v2, false, None);
+        let is_float = self.is_float_value(v2);
+        let (reg1, codes1) = self.want_to_visit_as(v1, true, is_float);
+        let (reg2, codes2) = self.want_to_visit_as(v2, false, is_float);
         codes.extend(codes1);
         codes.extend(codes2);
+        let move_op = if is_float { "fmv.s" } else { "mv" };
         codes.push(format!(
-            "  mv\t{}, {}",
-            REGISTER_NAMES[reg2], REGISTER_NAMES[reg1]
+            "  {}\t{}, {}",
+            move_op,
+            register_name(reg2),
+            register_name(reg1)
         ));
         codes
     }
@@ -359,7 +606,7 @@ impl MyBBValueTable<'_> {
             codes.push(format!(
                 "  la\t{}, {}",
                 REGISTER_NAMES[reg],
-                &self.program.borrow_value(value).name().clone().unwrap()[1..]
+                global_label(self.program, self.program_info, value)
             ));
         } else {
             if self.is_temp_value(value) {
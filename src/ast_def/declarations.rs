@@ -1,35 +1,78 @@
 //! Definitions of the Abstract Syntax Tree (AST).
 //! This is the part of declarations.
 
+use std::cell::RefCell;
+use std::collections::HashMap;
+
 use super::expressions::*;
 use super::statements::*;
 use super::symbols::*;
+use crate::diagnostics::{CompileError, ErrorCode};
 
+// No error points at a whole function yet, so its span is unused.
+#[allow(dead_code)]
 #[derive(Debug)]
 pub enum FuncDef {
-    Default(BType, IDENT, Vec<FuncFParam>, Block),
+    Default(BType, IDENT, Vec<FuncFParam>, Block, Span),
+}
+
+/// A prototype, which declares a function defined later or in another file.
+#[allow(dead_code)]
+#[derive(Debug)]
+pub enum FuncDecl {
+    Default(BType, IDENT, Vec<FuncFParam>, bool, Span), // The bool is whether it ends with "...".
 }
 
 #[derive(Debug)]
 pub enum FuncFParam {
-    Default(BType, IDENT, Option<Vec<Exp>>),
+    Default(BType, usize, IDENT, Option<Vec<Exp>>, Span), // The usize is the number of "*"s.
 }
 
 #[derive(Debug)]
 pub enum Block {
-    Default(Vec<BlockItem>), // May have 0 or more items.
+    Default(Vec<BlockItem>, Span), // May have 0 or more items.
 }
 
 #[derive(Debug)]
 pub enum BlockItem {
     Decl(Decl),
+    StaticDecl(VarDecl), // A local variable stored like a global one, initialized only once.
+    TypeDef(TypeDef),    // Only the parser uses it.
     Stmt(Stmt),
 }
 
+impl BlockItem {
+    pub fn span(&self) -> Span {
+        match self {
+            BlockItem::Decl(Decl::ConstDecl(ConstDecl::Default(_, _, span)))
+            | BlockItem::Decl(Decl::VarDecl(VarDecl::Default(_, _, span)))
+            | BlockItem::StaticDecl(VarDecl::Default(_, _, span))
+            | BlockItem::TypeDef(TypeDef::Default(_, span)) => *span,
+            BlockItem::Stmt(stmt) => stmt.basic_stmt().span(),
+        }
+    }
+
+    pub fn has_label(&self) -> bool {
+        match self {
+            BlockItem::Decl(_) | BlockItem::StaticDecl(_) | BlockItem::TypeDef(_) => false,
+            BlockItem::Stmt(stmt) => stmt.basic_stmt().has_label(),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum InitVal {
     Exp(Exp),
-    Aggregate(Vec<Box<InitVal>>),
+    Aggregate(Vec<Box<InitVal>>, Span),
+}
+
+impl InitVal {
+    pub fn span(&self) -> Span {
+        match self {
+            InitVal::Exp(exp) => exp.span(),
+            InitVal::Aggregate(_, span) => *span,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -40,20 +83,215 @@ pub enum Decl {
 
 #[derive(Debug)]
 pub enum ConstDecl {
-    Default(BType, Vec<ConstDef>),
+    Default(BType, Vec<ConstDef>, Span),
 }
 
 #[derive(Debug)]
 pub enum ConstDef {
-    Default(IDENT, Vec<Exp>, InitVal),
+    Default(IDENT, Vec<Exp>, InitVal, Span),
 }
 
 #[derive(Debug)]
 pub enum VarDecl {
-    Default(BType, Vec<VarDef>),
+    Default(BType, Vec<VarDef>, Span),
+}
+
+#[allow(dead_code)] // Errors point at the tag or the members, not at the whole struct.
+#[derive(Debug)]
+pub enum StructDef {
+    Default(IDENT, Vec<VarDecl>, Span), // Members are declared like variables, without initvals.
 }
 
 #[derive(Debug)]
 pub enum VarDef {
-    Default(IDENT, Vec<Exp>, Option<InitVal>),
+    Default(usize, IDENT, Vec<Exp>, Option<InitVal>, Span), // The usize is the number of "*"s.
+}
+
+/// Enumerators are constants of type int. The tag is not checked, like the one of "enum" BType.
+#[allow(dead_code)]
+#[derive(Debug)]
+pub enum EnumDef {
+    Default(Option<IDENT>, Vec<Enumerator>, Span),
+}
+
+#[allow(dead_code)]
+#[derive(Debug)]
+pub enum Enumerator {
+    Default(IDENT, Option<Exp>, Span), // Without a value, it is 1 more than the one before, or 0.
+}
+
+/// Only the name is kept. The parser replaces the name with its TypeSpec where it is used.
+#[allow(dead_code)]
+#[derive(Debug)]
+pub enum TypeDef {
+    Default(IDENT, Span),
+}
+
+/// The type before the declarators of a declaration.
+/// It is a BType, or a name declared by typedef, which may add "*"s and dimensions to it.
+#[derive(Debug, Clone)]
+pub struct TypeSpec {
+    pub btype: BType,
+    pub ptr_depth: usize,
+    pub shape: Vec<Exp>,
+}
+
+/// The typedefs seen so far, in scopes like the symbols. The lexer reads the names to tell
+/// type names from identifiers, and opens a scope at each "{" and closes it at its "}",
+/// because the parser only runs its actions after the token after them has been read.
+/// A variable or a parameter hides the typedefs of the same name in outer scopes.
+#[derive(Debug)]
+pub struct TypeAliases {
+    scopes: RefCell<Vec<HashMap<String, Option<TypeSpec>>>>, // None for a hidden name
+    params: RefCell<Vec<String>>, // Parameters that hide typedefs in the next function body
+}
+
+impl Default for TypeAliases {
+    fn default() -> Self {
+        TypeAliases {
+            scopes: RefCell::new(vec![HashMap::new()]),
+            params: RefCell::new(vec![]),
+        }
+    }
+}
+
+impl TypeAliases {
+    /// The parameters before a block are in the scope of the block.
+    pub fn open_scope(&self) {
+        let params = self.params.take().into_iter().map(|name| (name, None));
+        self.scopes.borrow_mut().push(params.collect());
+    }
+
+    /// The file scope is never closed, even if there are too many "}"s.
+    pub fn close_scope(&self) {
+        let mut scopes = self.scopes.borrow_mut();
+        if scopes.len() > 1 {
+            scopes.pop();
+        }
+    }
+
+    /// The parameters of a prototype have no block, so they are forgotten at its ";".
+    pub fn forget_params(&self) {
+        self.params.borrow_mut().clear();
+    }
+
+    /// Whether the name is a type name in the current scope.
+    pub fn is_type_name(&self, name: &str) -> bool {
+        let scopes = self.scopes.borrow();
+        let found = scopes.iter().rev().find_map(|scope| scope.get(name));
+        matches!(found, Some(Some(_)))
+    }
+
+    /// The type of a type name in the current scope.
+    pub fn get(&self, name: &str) -> Option<TypeSpec> {
+        let scopes = self.scopes.borrow();
+        let found = scopes.iter().rev().find_map(|scope| scope.get(name));
+        found.cloned().flatten()
+    }
+
+    /// Declares a type name, which may hide a variable or a type of an outer scope.
+    pub fn declare_type(&self, name: &str, type_spec: TypeSpec) {
+        let mut scopes = self.scopes.borrow_mut();
+        let scope = scopes.last_mut().unwrap();
+        scope.insert(name.to_string(), Some(type_spec));
+    }
+
+    /// Declares a variable that hides the type name of an outer scope.
+    /// A type name of the same scope can not be declared again as a variable.
+    pub fn hide_type(&self, ident: &IDENT) -> Result<(), CompileError> {
+        let mut scopes = self.scopes.borrow_mut();
+        let scope = scopes.last_mut().unwrap();
+        if let Some(Some(_)) = scope.get(&ident.content) {
+            return Err(CompileError::new(
+                ErrorCode::Redefinition,
+                format!("'{}' has been declared as a type! ", ident.content),
+                ident.span,
+            ));
+        }
+        scope.insert(ident.content.clone(), None);
+        Ok(())
+    }
+
+    /// Declares a parameter that hides the type name in the body of its function.
+    pub fn hide_type_in_body(&self, name: &str) {
+        self.params.borrow_mut().push(name.to_string());
+    }
+}
+
+impl TypeSpec {
+    pub fn new(btype: BType) -> TypeSpec {
+        TypeSpec {
+            btype,
+            ptr_depth: 0,
+            shape: vec![],
+        }
+    }
+
+    /// The number of "*"s and the shape of a declarator like "*p" or "a[2]" of this type.
+    /// "T a[2]" is an array of 2 Ts, so the dimensions of T come after the ones of a.
+    pub fn declare(
+        &self,
+        ptr_depth: usize,
+        shape: Vec<Exp>,
+        span: Span,
+    ) -> Result<(usize, Vec<Exp>), CompileError> {
+        if self.shape.is_empty() {
+            Ok((self.ptr_depth + ptr_depth, shape))
+        } else if ptr_depth == 0 {
+            Ok((
+                self.ptr_depth,
+                shape.into_iter().chain(self.shape.clone()).collect(),
+            ))
+        } else {
+            Err(CompileError::new(
+                ErrorCode::UnsupportedTypedef,
+                "Pointers to arrays are not supported! ".to_string(),
+                span,
+            ))
+        }
+    }
+
+    /// Like declare, but constants can not be pointers.
+    pub fn declare_const(&self, shape: Vec<Exp>, span: Span) -> Result<Vec<Exp>, CompileError> {
+        match self.declare(0, shape, span)? {
+            (0, shape) => Ok(shape),
+            _ => Err(CompileError::new(
+                ErrorCode::UnsupportedTypedef,
+                "Constant pointers are not supported! ".to_string(),
+                span,
+            )),
+        }
+    }
+
+    /// Like declare, but `shape` is None for a parameter without "[" "]".
+    /// An array parameter is a pointer to its first element, so its first dimension is dropped.
+    pub fn declare_param(
+        &self,
+        ptr_depth: usize,
+        shape: Option<Vec<Exp>>,
+        span: Span,
+    ) -> Result<(usize, Option<Vec<Exp>>), CompileError> {
+        match shape {
+            Some(shape) => {
+                let (ptr_depth, shape) = self.declare(ptr_depth, shape, span)?;
+                Ok((ptr_depth, Some(shape)))
+            }
+            None => match self.declare(ptr_depth, vec![], span)? {
+                (ptr_depth, shape) if shape.is_empty() => Ok((ptr_depth, None)),
+                (ptr_depth, shape) => Ok((ptr_depth, Some(shape[1..].to_vec()))),
+            },
+        }
+    }
+
+    /// The return type of a function, which can only be a BType.
+    pub fn return_type(self) -> Result<BType, CompileError> {
+        match self.ptr_depth == 0 && self.shape.is_empty() {
+            true => Ok(self.btype),
+            false => Err(CompileError::new(
+                ErrorCode::UnsupportedTypedef,
+                "Functions can not return pointers or arrays! ".to_string(),
+                self.btype.span,
+            )),
+        }
+    }
 }
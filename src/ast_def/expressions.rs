@@ -2,77 +2,186 @@
 //! This is the part of expressions.
 
 use super::symbols::*;
+use crate::diagnostics::{CompileError, ErrorCode};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Exp {
+    CondExp(CondExp, Span),
+}
+
+impl Exp {
+    pub fn span(&self) -> Span {
+        match self {
+            Exp::CondExp(_, span) => *span,
+        }
+    }
+
+    /// Takes a parenthesized expression as a LVal, like "(*p)" in "(*p)++" and "(*p).a".
+    /// Only a dereference or a LVal, in any number of parentheses, is a LVal.
+    pub fn into_lval(self) -> Result<LVal, CompileError> {
+        let span = self.span();
+        if let Exp::CondExp(
+            CondExp::LOrExp(LOrExp::LAndExp(LAndExp::BitOrExp(BitOrExp::BitXorExp(
+                BitXorExp::BitAndExp(BitAndExp::EqExp(EqExp::RelExp(RelExp::ShiftExp(
+                    ShiftExp::AddExp(AddExp::MulExp(MulExp::UnaryExp(unary_exp))),
+                )))),
+            )))),
+            _,
+        ) = self
+        {
+            match unary_exp {
+                UnaryExp::DerefExp(exp, span) => return Ok(LVal::Deref(exp, span)),
+                UnaryExp::PrimaryExp(PrimaryExp::LVal(lval)) => return Ok(lval),
+                UnaryExp::PrimaryExp(PrimaryExp::BracedExp(exp)) => return exp.into_lval(),
+                _ => {}
+            }
+        }
+        Err(CompileError::new(
+            ErrorCode::NotAssignable,
+            "Only a dereference or a LVal in parentheses is a LVal! ".to_string(),
+            span,
+        ))
+    }
+
+    /// Returns the bytes of a string literal, if the expression is only a string literal.
+    pub fn string_literal(&self) -> Option<&[u8]> {
+        match self {
+            Exp::CondExp(
+                CondExp::LOrExp(LOrExp::LAndExp(LAndExp::BitOrExp(BitOrExp::BitXorExp(
+                    BitXorExp::BitAndExp(BitAndExp::EqExp(EqExp::RelExp(RelExp::ShiftExp(
+                        ShiftExp::AddExp(AddExp::MulExp(MulExp::UnaryExp(UnaryExp::PrimaryExp(
+                            PrimaryExp::StringConst(bytes, _),
+                        )))),
+                    )))),
+                )))),
+                _,
+            ) => Some(bytes),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum CondExp {
     LOrExp(LOrExp),
+    TernaryExp(LOrExp, Box<Exp>, Box<Exp>, Span), // cond ? exp1 : exp2
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum LOrExp {
     LAndExp(LAndExp),
-    BinaryLOrExp(Box<LOrExp>, LAndExp),
+    BinaryLOrExp(Box<LOrExp>, LAndExp, Span),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum LAndExp {
+    BitOrExp(BitOrExp),
+    BinaryLAndExp(Box<LAndExp>, BitOrExp, Span),
+}
+
+#[derive(Debug, Clone)]
+pub enum BitOrExp {
+    BitXorExp(BitXorExp),
+    BinaryBitOrExp(Box<BitOrExp>, BitXorExp, Span),
+}
+
+#[derive(Debug, Clone)]
+pub enum BitXorExp {
+    BitAndExp(BitAndExp),
+    BinaryBitXorExp(Box<BitXorExp>, BitAndExp, Span),
+}
+
+#[derive(Debug, Clone)]
+pub enum BitAndExp {
     EqExp(EqExp),
-    BinaryLAndExp(Box<LAndExp>, EqExp),
+    BinaryBitAndExp(Box<BitAndExp>, EqExp, Span),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum EqExp {
     RelExp(RelExp),
-    BinaryEqExp(Box<EqExp>, RelExp),
-    BinaryUneqExp(Box<EqExp>, RelExp),
+    BinaryEqExp(Box<EqExp>, RelExp, Span),
+    BinaryUneqExp(Box<EqExp>, RelExp, Span),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum RelExp {
+    ShiftExp(ShiftExp),
+    BinaryLtExp(Box<RelExp>, ShiftExp, Span),
+    BinaryGtExp(Box<RelExp>, ShiftExp, Span),
+    BinaryLeExp(Box<RelExp>, ShiftExp, Span),
+    BinaryGeExp(Box<RelExp>, ShiftExp, Span),
+}
+
+#[derive(Debug, Clone)]
+pub enum ShiftExp {
     AddExp(AddExp),
-    BinaryLtExp(Box<RelExp>, AddExp),
-    BinaryGtExp(Box<RelExp>, AddExp),
-    BinaryLeExp(Box<RelExp>, AddExp),
-    BinaryGeExp(Box<RelExp>, AddExp),
+    BinaryShlExp(Box<ShiftExp>, AddExp, Span),
+    BinaryShrExp(Box<ShiftExp>, AddExp, Span), // Arithmetic shift, because int is signed.
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum AddExp {
     MulExp(MulExp),
-    BinaryAddExp(Box<AddExp>, MulExp),
-    BinarySubExp(Box<AddExp>, MulExp),
+    BinaryAddExp(Box<AddExp>, MulExp, Span),
+    BinarySubExp(Box<AddExp>, MulExp, Span),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum MulExp {
     UnaryExp(UnaryExp),
-    BinaryMulExp(Box<MulExp>, UnaryExp),
-    BinaryDivExp(Box<MulExp>, UnaryExp),
-    BinaryModExp(Box<MulExp>, UnaryExp),
+    BinaryMulExp(Box<MulExp>, UnaryExp, Span),
+    BinaryDivExp(Box<MulExp>, UnaryExp, Span),
+    BinaryModExp(Box<MulExp>, UnaryExp, Span),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum UnaryExp {
     PrimaryExp(PrimaryExp),
-    PlusUnaryExp(Box<UnaryExp>),
-    MinusUnaryExp(Box<UnaryExp>),
-    NotUnaryExp(Box<UnaryExp>),
-    FuncCall(IDENT, Vec<Exp>),
+    PlusUnaryExp(Box<UnaryExp>, Span),
+    MinusUnaryExp(Box<UnaryExp>, Span),
+    NotUnaryExp(Box<UnaryExp>, Span),
+    BitNotUnaryExp(Box<UnaryExp>, Span),
+    AddrOfExp(LVal, Span),         // &a
+    DerefExp(Box<UnaryExp>, Span), // *p
+    FuncCall(IDENT, Vec<Exp>, Span),
+    PreIncExp(LVal, Span),  // ++a
+    PreDecExp(LVal, Span),  // --a
+    PostIncExp(LVal, Span), // a++
+    PostDecExp(LVal, Span), // a--
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum PrimaryExp {
     BracedExp(Box<Exp>),
     LVal(LVal),
     Number(Number),
+    StringConst(Vec<u8>, Span), // The bytes of the string, without the ending '\0'.
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum LVal {
-    Default(IDENT, Vec<Exp>),
+    Default(IDENT, Vec<Exp>, Span),
+    Member(Box<LVal>, IDENT, Vec<Exp>, Span),    // s.a[i]
+    PtrMember(Box<LVal>, IDENT, Vec<Exp>, Span), // p->a[i]
+    Deref(Box<UnaryExp>, Span), // *p. A LVal for assignments, "++"/"--", or in parentheses like "(*p).a".
+}
+
+impl LVal {
+    pub fn span(&self) -> Span {
+        match self {
+            LVal::Default(_, _, span)
+            | LVal::Member(_, _, _, span)
+            | LVal::PtrMember(_, _, _, span)
+            | LVal::Deref(_, span) => *span,
+        }
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Number {
-    INTCONST(i32),
+    INTCONST(i64, Span), // A long long if it does not fit in an int.
+    LONGLONGCONST(i64, Span),
+    FLOATCONST(f32, Span),
+    CHARCONST(i32, Span),
 }
@@ -4,27 +4,52 @@
 //! Currently, AST is defined as follows:
 //!
 //! CompUnit ::= {Unit};
-//! Unit     ::= Decl | FuncDef;
+//! Unit     ::= Decl | FuncDef | FuncDecl | StructDef | EnumDef | TypeDef | "extern" VarDecl
+//!            | "static" (Decl | FuncDef | FuncDecl); // Not visible to other files
+//!
+//! On a syntax error, the parser skips to the next ";" (statements and local declarations)
+//! or "}" (blocks and global units) and goes on, so that all syntax errors are reported.
 //!
 //! ======================================================
 //! Declarations
 //! ======================================================
 //! FuncDef       ::= FuncType IDENT "(" [FuncFParams] ")" Block;
+//!                 // Functions can not return pointers, so "int *f()" is a syntax error.
+//!                 // A pointer parameter like "int **out" can pass a pointer back instead.
+//! FuncDecl      ::= ["extern"] FuncType FuncDeclarator {"," FuncDeclarator} ";";
+//!                 // Prototypes of the same return type
+//! FuncDeclarator ::= IDENT "(" [FuncFParams] ")"
+//!                  | IDENT "(" FuncFParams "," "..." ")"; // Only for library functions like putf
 //! FuncFParams   ::= FuncFParam {"," FuncFParam};
-//! FuncFParam    ::= BType IDENT ["[" "]" {"[" Exp "]"}]; // Exp should be const
+//! FuncFParam    ::= TypeSpec {"*"} IDENT ["[" "]" {"[" Exp "]"}]; // Exp should be const
 //!
 //! Block         ::= "{" {BlockItem} "}";
-//! BlockItem     ::= Decl | Stmt;
+//! BlockItem     ::= Decl | Stmt | TypeDef
+//!                 | "static" VarDecl    // Stored like a global variable, initialized once
+//!                 | "static" ConstDecl; // The same as a ConstDecl
 //!
 //! Decl          ::= ConstDecl | VarDecl;
 //!
-//! ConstDecl     ::= "const" BType ConstDef {"," ConstDef} ";";
+//! ConstDecl     ::= "const" TypeSpec ConstDef {"," ConstDef} ";";
 //! ConstDef      ::= IDENT {"[" Exp "]"} "=" InitVal;
 //!                 // Exps and InitVal should be const. Checked at semantic level.
 //!
-//! VarDecl       ::= BType VarDef {"," VarDef} ";";
-//! VarDef        ::= IDENT {"[" Exp "]"}
-//!                 | IDENT {"[" Exp "]"} "=" InitVal;
+//! StructDef     ::= "struct" Tag "{" VarDecl {VarDecl} "}" ";";
+//!                 // Members are declared like variables, but they have no initvals.
+//!                 // A struct is not a value. Only "=" and the initval of a local variable
+//!                 // can copy a whole struct, from a struct LVal of the same type.
+//!
+//! EnumDef       ::= "enum" [Tag] "{" Enumerator {"," Enumerator} [","] "}" ";";
+//! Enumerator    ::= IDENT ["=" Exp]; // An int constant. Without Exp, the one before + 1, or 0.
+//!
+//! TypeDef       ::= "typedef" TypeSpec {"*"} IDENT {"[" Exp "]"} ";";
+//!                 // The IDENT becomes a TYPENAME in its scope, which the parser replaces with its type.
+//!                 // A variable or a parameter of the same name hides it in an inner scope.
+//!
+//! VarDecl       ::= TypeSpec VarDef {"," VarDef} ";";
+//! VarDef        ::= {"*"} IDENT {"[" Exp "]"}
+//!                 | {"*"} IDENT {"[" Exp "]"} "=" InitVal;
+//!                 // Each "*" makes a pointer to the type before it.
 //!                 // If this VarDef is global, InitVal should be const.
 //!                 // Exps in indexes should be const. Checked at semantic level.
 //!                 // This check is done in semantic analysis, not in syntax analysis.
@@ -37,16 +62,30 @@
 //!
 //! UnmatchedStmt ::= "if" "(" Exp ")" MatchedStmt ["else" UnmatchedStmt]
 //!                 | "if" "(" Exp ")" UnmatchedStmt
-//!                 | "while" "(" Exp ")" UnmatchedStmt;
+//!                 | "while" "(" Exp ")" UnmatchedStmt
+//!                 | "for" "(" ForInit [Exp] ";" [ForStep] ")" UnmatchedStmt
+//!                 | IDENT ":" UnmatchedStmt;
 //!
-//! MatchedStmt ::= LVal "=" Exp ";"
+//! MatchedStmt ::= AssignLVal "=" Exp ";"
+//!               | AssignLVal AssignOp Exp ";"
 //!               | [Exp] ";"
 //!               | Block
 //!               | "if" "(" Exp ")" MatchedStmt "else" MatchedStmt
 //!               | "while" "(" Exp ")" MatchedStmt
+//!               | "for" "(" ForInit [Exp] ";" [ForStep] ")" MatchedStmt
+//!               | "do" Stmt "while" "(" Exp ")" ";"
+//!               | "switch" "(" Exp ")" "{" {SwitchCase} "}"
 //!               | "break" ";"
 //!               | "continue" ";"
-//!               | "return" [Exp] ";";
+//!               | "return" [Exp] ";"
+//!               | "goto" IDENT ";"
+//!               | IDENT ":" MatchedStmt; // A label, which the gotos in the function jump to
+//!
+//! ForInit ::= Decl | AssignLVal "=" Exp ";" | AssignLVal AssignOp Exp ";" | [Exp] ";";
+//! ForStep ::= AssignLVal "=" Exp | AssignLVal AssignOp Exp | Exp;
+//! AssignOp ::= "+=" | "-=" | "*=" | "/=" | "%=" | "&=" | "|=" | "^=" | "<<=" | ">>=";
+//! SwitchCase ::= "case" Exp ":" {BlockItem}  // Exp should be const
+//!              | "default" ":" {BlockItem};
 //!
 //! ======================================================
 //! Expressions
@@ -55,38 +94,68 @@
 //!                 | "{" "}"
 //!                 | "{" InitVal {"," InitVal} "}";
 //!                 // The check of "whether an exp is const" is done in semantic analysis.
+//!                 // A char array may be initialized by a string literal, with room for its '\0'.
 //!
-//! Exp         ::= LOrExp;
+//! Exp         ::= CondExp;
+//!
+//! CondExp     ::= LOrExp | LOrExp "?" Exp ":" CondExp;
 //!
 //! LOrExp      ::= LAndExp | LOrExp "||" LAndExp;
-//! LAndExp     ::= EqExp | LAndExp "&&" EqExp;
+//! LAndExp     ::= BitOrExp | LAndExp "&&" BitOrExp;
+//! BitOrExp    ::= BitXorExp | BitOrExp "|" BitXorExp;
+//! BitXorExp   ::= BitAndExp | BitXorExp "^" BitAndExp;
+//! BitAndExp   ::= EqExp | BitAndExp "&" EqExp;
 //! EqExp       ::= RelExp | EqExp ("==" | "!=") RelExp;
-//! RelExp      ::= AddExp | RelExp ("<" | ">" | "<=" | ">=") AddExp;
+//! RelExp      ::= ShiftExp | RelExp ("<" | ">" | "<=" | ">=") ShiftExp;
+//! ShiftExp    ::= AddExp | ShiftExp ("<<" | ">>") AddExp;
 //!
 //! AddExp      ::= MulExp | AddExp ("+" | "-") MulExp;
 //! MulExp      ::= UnaryExp | MulExp ("*" | "/" | "%") UnaryExp;
 //!
 //! UnaryExp    ::= PrimaryExp
 //!               | UnaryOp UnaryExp
-//!               | IDENT "(" [FuncRParams] ")";
-//! UnaryOp     ::= "+" | "-" | "!";
-//! PrimaryExp  ::= "(" Exp ")" | LVal | Number;
-//!
-//! LVal        ::= IDENT {"[" Exp "]"};
-//! Number      ::= INTCONST;
+//!               | IDENT "(" [FuncRParams] ")"
+//!               | "&" LVal
+//!               | ("++" | "--") AssignLVal
+//!               | (LVal | "(" AssignLVal ")") ("++" | "--");
+//! UnaryOp     ::= "+" | "-" | "!" | "~" | "*";
+//! PrimaryExp  ::= "(" Exp ")" | LVal | Number | STRINGCONST;
+//!
+//! LVal        ::= IDENT {"[" Exp "]"}
+//!               | (LVal | "(" AssignLVal ")") ("." | "->") IDENT {"[" Exp "]"};
+//! AssignLVal  ::= LVal | "*" UnaryExp | "(" AssignLVal ")";
+//! Number      ::= INTCONST | LONGLONGCONST | FLOATCONST | CHARCONST;
+//!               // LONGLONGCONST has the suffix "ll" or "LL".
+//!               // An INTCONST that does not fit in an int is a long long too.
+//!               // On RV32, one that fits in 32 bits is an int that wraps around, like 0xFFFFFFFF.
 //!
 //! ======================================================
 //! Symbols
 //! ======================================================
 //! // FuncType  ::= "void" | "int"; (Currently removed to aviod lalrpop conflict. )
-//! BType     ::= "int";
+//! BType     ::= "int" | "long" "long" | "float" | "char" | "void" | "struct" Tag
+//!             | "enum" Tag; // An int. Floats are stored as the bits of i32 in Koopa IR.
+//! TypeSpec  ::= BType | TYPENAME; // A return type can not be a TYPENAME of a pointer or an array.
+//! Tag       ::= IDENT | TYPENAME; // Tags have their own namespace.
 //!
+//! The lexer tells a TYPENAME (declared by typedef) from an IDENT, so that "T * p;" is a declaration.
+//!
+//! Every AST node records its source span, so that errors can point at the source code.
+
+// Node names follow the grammar in sysy.lalrpop.
+#![allow(
+    clippy::enum_variant_names,
+    clippy::upper_case_acronyms,
+    clippy::large_enum_variant,
+    clippy::vec_box
+)]
 
 pub mod declarations;
 pub mod expressions;
 pub mod statements;
 pub mod symbols;
 use declarations::*;
+use symbols::Span;
 
 #[derive(Debug)]
 pub enum CompUnit {
@@ -97,4 +166,17 @@ pub enum CompUnit {
 pub enum Unit {
     Decl(Decl),
     FuncDef(FuncDef),
+    FuncDecl(Vec<FuncDecl>),
+    StructDef(StructDef),
+    EnumDef(EnumDef),
+    // Typedefs are expanded by the parser, so the passes skip them.
+    #[allow(dead_code)]
+    TypeDef(TypeDef),
+    ExternVarDecl(VarDecl), // Variables defined later or in another file. They have no initvals.
+    StaticDecl(Decl),       // Variables and constants that other files can not see.
+    StaticFuncDef(FuncDef), // Functions that other files can not see.
+    StaticFuncDecl(Vec<FuncDecl>), // Prototypes of static functions.
+    // A syntax error, skipped by error recovery. The parser has reported it.
+    #[allow(dead_code)]
+    Error(Span),
 }
@@ -3,6 +3,8 @@
 
 use super::declarations::*;
 use super::expressions::*;
+use super::symbols::*;
+use koopa::ir::BinaryOp;
 
 #[derive(Debug)]
 pub enum Stmt {
@@ -10,6 +12,22 @@ pub enum Stmt {
     MatchedStmt(MatchedStmt),
 }
 
+impl Stmt {
+    pub fn into_basic_stmt(self) -> BasicStmt {
+        match self {
+            Stmt::UnmatchedStmt(stmt) => stmt.default,
+            Stmt::MatchedStmt(stmt) => stmt.default,
+        }
+    }
+
+    pub fn basic_stmt(&self) -> &BasicStmt {
+        match self {
+            Stmt::UnmatchedStmt(stmt) => &stmt.default,
+            Stmt::MatchedStmt(stmt) => &stmt.default,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct UnmatchedStmt {
     pub default: BasicStmt,
@@ -22,12 +40,86 @@ pub struct MatchedStmt {
 
 #[derive(Debug)]
 pub enum BasicStmt {
-    AssignStmt(LVal, Exp),
-    Exp(Option<Exp>),
+    AssignStmt(LVal, Exp, Span),
+    CompoundAssignStmt(LVal, BinaryOp, Exp, Span), // a += b, where the BinaryOp is Add
+    Exp(Option<Exp>, Span),
     Block(Block),
-    IfStmt(Exp, Box<BasicStmt>, Box<Option<BasicStmt>>),
-    WhileStmt(Exp, Box<BasicStmt>),
-    BreakStmt,
-    ContinueStmt,
-    ReturnStmt(Option<Exp>),
+    IfStmt(Exp, Box<BasicStmt>, Box<Option<BasicStmt>>, Span),
+    WhileStmt(Exp, Box<BasicStmt>, Span),
+    DoWhileStmt(Box<BasicStmt>, Exp, Span),
+    SwitchStmt(Exp, Vec<SwitchCase>, Span),
+    ForStmt(
+        Box<ForInit>,
+        Option<Exp>,
+        Box<Option<BasicStmt>>,
+        Box<BasicStmt>,
+        Span,
+    ),
+    BreakStmt(Span),
+    ContinueStmt(Span),
+    ReturnStmt(Option<Exp>, Span),
+    GotoStmt(IDENT, Span),
+    LabeledStmt(IDENT, Box<BasicStmt>, Span), // label: stmt
+    Error(Span),                              // A syntax error, skipped by error recovery.
+}
+
+impl BasicStmt {
+    pub fn span(&self) -> Span {
+        match self {
+            BasicStmt::Block(Block::Default(_, span))
+            | BasicStmt::AssignStmt(_, _, span)
+            | BasicStmt::CompoundAssignStmt(_, _, _, span)
+            | BasicStmt::Exp(_, span)
+            | BasicStmt::IfStmt(_, _, _, span)
+            | BasicStmt::WhileStmt(_, _, span)
+            | BasicStmt::DoWhileStmt(_, _, span)
+            | BasicStmt::SwitchStmt(_, _, span)
+            | BasicStmt::ForStmt(_, _, _, _, span)
+            | BasicStmt::BreakStmt(span)
+            | BasicStmt::ContinueStmt(span)
+            | BasicStmt::ReturnStmt(_, span)
+            | BasicStmt::GotoStmt(_, span)
+            | BasicStmt::LabeledStmt(_, _, span)
+            | BasicStmt::Error(span) => *span,
+        }
+    }
+
+    /// Whether a goto can jump into the statement, i.e. it has a label.
+    pub fn has_label(&self) -> bool {
+        match self {
+            BasicStmt::LabeledStmt(_, _, _) => true,
+            BasicStmt::Block(Block::Default(items, _)) => items.iter().any(BlockItem::has_label),
+            BasicStmt::IfStmt(_, stmt1, possible_stmt2, _) => {
+                stmt1.has_label()
+                    || possible_stmt2
+                        .as_ref()
+                        .as_ref()
+                        .is_some_and(Self::has_label)
+            }
+            BasicStmt::WhileStmt(_, body, _)
+            | BasicStmt::DoWhileStmt(body, _, _)
+            | BasicStmt::ForStmt(_, _, _, body, _) => body.has_label(),
+            BasicStmt::SwitchStmt(_, cases, _) => cases.iter().any(|case| {
+                let (SwitchCase::Case(_, items, _) | SwitchCase::DefaultCase(items, _)) = case;
+                items.iter().any(BlockItem::has_label)
+            }),
+            _ => false,
+        }
+    }
+}
+
+/// The init part of a for statement. The declared variables are only visible in the for statement.
+#[derive(Debug)]
+pub enum ForInit {
+    Decl(Decl),
+    Stmt(BasicStmt), // AssignStmt or Exp
+}
+
+/// A case of a switch statement. The case label should be const. Checked at semantic level.
+/// Errors point at the case label, so the span of a default case is unused.
+#[allow(dead_code)]
+#[derive(Debug)]
+pub enum SwitchCase {
+    Case(Exp, Vec<BlockItem>, Span),
+    DefaultCase(Vec<BlockItem>, Span),
 }
@@ -1,10 +1,138 @@
 use std::fmt::Debug;
 
-use koopa::ir::TypeKind;
+use koopa::ir::{Type, TypeKind};
 
-#[derive(Debug)]
+/// A range of byte offsets `[start, end)` in the source file.
+/// Produced by lalrpop's `@L` and `@R` locations.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Span {
+        Span { start, end }
+    }
+}
+
+/// Parses the digits of an integer literal in a radix.
+/// The lexer only accepts valid digits. A literal too large for 64 bits wraps around.
+pub fn parse_integer(digits: &str, radix: u32) -> u64 {
+    digits.chars().fold(0, |value: u64, digit| {
+        value
+            .wrapping_mul(radix as u64)
+            .wrapping_add(digit.to_digit(radix).unwrap() as u64)
+    })
+}
+
+/// Parses an integer literal without its suffix.
+/// It is hexadecimal with the prefix "0x", octal with the prefix "0", and decimal otherwise.
+pub fn parse_int_literal(literal: &str) -> u64 {
+    match literal.get(..2) {
+        Some("0x" | "0X") => parse_integer(&literal[2..], 16),
+        _ if literal.starts_with('0') => parse_integer(literal, 8),
+        _ => parse_integer(literal, 10),
+    }
+}
+
+/// Parses a float literal. Like C, a value too large for a float is inf,
+/// and a value too small is 0. Returns None if Rust can not parse a decimal literal.
+pub fn parse_float_literal(literal: &str) -> Option<f32> {
+    match literal.get(..2) {
+        Some("0x" | "0X") => Some(parse_hex_float(literal)),
+        _ => literal.parse::<f32>().ok(),
+    }
+}
+
+/// Parses a hexadecimal float literal like "0x1.8p1", which Rust can not parse.
+/// The value is mantissa * 2^exponent, where the mantissa is in hexadecimal.
+fn parse_hex_float(literal: &str) -> f32 {
+    let (mantissa, exponent) = literal[2..].split_once(['p', 'P']).unwrap();
+    let (int_part, frac_part) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    // Digits beyond the precision of a f64 are dropped, and make the exponent larger instead.
+    let mut value = 0f64;
+    let mut dropped_digits = 0;
+    for digit in int_part.chars().chain(frac_part.chars()) {
+        if value < (1u64 << 60) as f64 {
+            value = value * 16.0 + digit.to_digit(16).unwrap() as f64;
+        } else {
+            dropped_digits += 1;
+        }
+    }
+    if value == 0.0 {
+        return 0.0;
+    }
+    // Any exponent beyond 2^16 makes inf or 0, so a larger one is cut down to it.
+    let (sign, digits) = match exponent.strip_prefix('-') {
+        Some(digits) => (-1, digits),
+        None => (1, exponent.trim_start_matches('+')),
+    };
+    let exponent = digits.chars().fold(0i32, |exponent, digit| {
+        (exponent * 10 + digit.to_digit(10).unwrap() as i32).min(1 << 16)
+    });
+    let exponent = sign * exponent + 4 * (dropped_digits - frac_part.len() as i32);
+    // The exponent is applied in two halves, so that 2^exponent itself does not overflow
+    // or underflow when a long mantissa makes up for it.
+    let half = exponent / 2;
+    (value * 2f64.powi(half) * 2f64.powi(exponent - half)) as f32
+}
+
+/// Converts the escape sequences in a string or char literal (without quotes) into bytes.
+/// The lexer only accepts valid escape sequences, so this never fails.
+pub fn unescape(literal: &str) -> Vec<u8> {
+    let mut bytes = vec![];
+    let mut chars = literal.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buf = [0; 4];
+            bytes.extend(c.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+        let escaped = chars.next().unwrap();
+        let byte = match escaped {
+            'n' => b'\n',
+            't' => b'\t',
+            'r' => b'\r',
+            'a' => 0x07,
+            'b' => 0x08,
+            'f' => 0x0c,
+            'v' => 0x0b,
+            // \xhh: hexadecimal digits.
+            'x' => {
+                let mut value = 0u32;
+                while let Some(digit) = chars.peek().and_then(|c| c.to_digit(16)) {
+                    value = value.wrapping_mul(16).wrapping_add(digit);
+                    chars.next();
+                }
+                value as u8
+            }
+            // \ooo: at most 3 octal digits. \0 is one of them.
+            '0'..='7' => {
+                let mut value = escaped.to_digit(8).unwrap();
+                for _ in 0..2 {
+                    match chars.peek().and_then(|c| c.to_digit(8)) {
+                        Some(digit) => {
+                            value = value * 8 + digit;
+                            chars.next();
+                        }
+                        None => break,
+                    }
+                }
+                value as u8
+            }
+            // \\, \', \" and \?
+            _ => escaped as u8,
+        };
+        bytes.push(byte);
+    }
+    bytes
+}
+
+#[derive(Debug, Clone)]
 pub struct IDENT {
     pub content: String,
+    pub span: Span,
 }
 
 // pub struct FuncType {
@@ -17,12 +145,87 @@ pub struct IDENT {
 //     }
 // }
 
+/// Basic types in SysY.
+/// Koopa IR has no i8, so a char is stored in an i32, and a char array is an array of i32.
+/// Only string literals are stored as bytes.
+/// A struct is named by its tag, and its layout is in the struct table of the IR builder.
+/// Koopa IR has no i64 either, so a long long is an array of 2 i32s, the low word first,
+/// which is laid out like a 64-bit integer in memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BTypeKind {
+    Int,
+    LongLong,
+    Float,
+    Char,
+    Void,
+    Struct(&'static str),
+}
+
+impl BTypeKind {
+    /// Koopa IR has no float type, so a float is stored as the bits of an i32.
+    pub fn to_type_kind(self) -> TypeKind {
+        match self {
+            BTypeKind::Int | BTypeKind::Float | BTypeKind::Char => TypeKind::Int32,
+            BTypeKind::LongLong => TypeKind::Array(Type::get_i32(), 2),
+            BTypeKind::Void => TypeKind::Unit,
+            BTypeKind::Struct(_) => unreachable!("The type of a struct is given by get_array_type"),
+        }
+    }
+}
+
+impl std::fmt::Display for BTypeKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BTypeKind::Int => write!(f, "int"),
+            BTypeKind::LongLong => write!(f, "long long"),
+            BTypeKind::Float => write!(f, "float"),
+            BTypeKind::Char => write!(f, "char"),
+            BTypeKind::Void => write!(f, "void"),
+            BTypeKind::Struct(name) => write!(f, "struct {}", name),
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct BType {
-    pub content: TypeKind,
+    pub content: BTypeKind,
+    pub span: Span,
 }
 
 impl Debug for BType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.content)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn float_literals() {
+        assert_eq!(parse_float_literal("1.5"), Some(1.5));
+        assert_eq!(parse_float_literal(".5e1"), Some(5.0));
+        assert_eq!(parse_float_literal("0x1.8p1"), Some(3.0));
+        assert_eq!(parse_float_literal("0X.8P-1"), Some(0.25));
+        assert_eq!(parse_float_literal("0x1p+2"), Some(4.0));
+    }
+
+    #[test]
+    fn huge_exponents_saturate() {
+        assert_eq!(parse_float_literal("1e99999999999"), Some(f32::INFINITY));
+        assert_eq!(parse_float_literal("1e-99999999999"), Some(0.0));
+        assert_eq!(parse_float_literal("0x1p99999999999"), Some(f32::INFINITY));
+        assert_eq!(parse_float_literal("0x1p-99999999999"), Some(0.0));
+        assert_eq!(parse_float_literal("0x0p99999999999"), Some(0.0));
+        assert_eq!(parse_float_literal("0x1p128"), Some(f32::INFINITY));
+        assert_eq!(parse_float_literal("0x1p-149"), Some(f32::from_bits(1)));
+    }
+
+    #[test]
+    fn long_mantissas_make_up_for_exponents() {
+        // 0x10...0 with 300 zeros is 2^1200, which is more than a f64 can hold.
+        let literal = format!("0x1{}p-1200", "0".repeat(300));
+        assert_eq!(parse_float_literal(&literal), Some(1.0));
+    }
+}
@@ -0,0 +1,256 @@
+//! Diagnostics shared by all phases of my compiler.
+//! Errors and warnings carry a source span, and are rendered as `file:line:col`
+//! plus the offending source line with a caret under the span.
+
+mod source_file;
+
+pub use source_file::{LineOrigin, SourceFile};
+
+use crate::ast_def::symbols::Span;
+
+/// Which phase of the compiler rejected the program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    Parse,
+    Semantic,
+    Type,
+    UnsupportedIR,
+    BackendInternal,
+}
+
+/// Every error has a stable code. The hundreds digit tells the error kind:
+/// 1xx parse (including preprocessing), 2xx semantic, 3xx type, 4xx unsupported IR, 5xx backend internal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    InvalidToken = 101,
+    UnexpectedToken = 102,
+    UnexpectedEof = 103,
+    ExtraToken = 104,
+    InvalidDirective = 105,
+    IncludeNotFound = 106,
+    UnterminatedConditional = 107,
+    InvalidMacroCall = 108,
+    ErrorDirective = 109,
+    UnsupportedTypedef = 110,
+
+    UndeclaredSymbol = 201,
+    Redefinition = 202,
+    NonConstantExpression = 203,
+    MisplacedBreakOrContinue = 204,
+    NotAssignable = 205,
+    DuplicateCaseLabel = 206,
+    DivisionByZero = 207,
+    JumpIntoScope = 208,
+    StaticMain = 209,
+    IndexOutOfBounds = 210,
+
+    MismatchedTypes = 301,
+    WrongArgumentCount = 302,
+    NotAnArray = 303,
+    InvalidInitializer = 304,
+    VoidVariable = 305,
+    InvalidArraySize = 306,
+    NotAStruct = 307,
+
+    UnsupportedInstruction = 401,
+    UnsupportedInitializer = 402,
+    UnsupportedTarget = 403,
+    UnsupportedVariadic = 404,
+
+    BackendInternal = 501,
+}
+
+impl ErrorCode {
+    pub fn kind(self) -> ErrorKind {
+        match self as u32 / 100 {
+            1 => ErrorKind::Parse,
+            2 => ErrorKind::Semantic,
+            3 => ErrorKind::Type,
+            4 => ErrorKind::UnsupportedIR,
+            _ => ErrorKind::BackendInternal,
+        }
+    }
+}
+
+impl std::fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "E{:04}", *self as u32)
+    }
+}
+
+/// An error found while compiling, and where it happened.
+/// Errors from the backend usually have no span, because Koopa IR does not remember the source.
+#[derive(Debug)]
+pub struct CompileError {
+    pub code: ErrorCode,
+    pub message: String,
+    pub span: Option<Span>,
+    pub notes: Vec<String>,
+}
+
+impl CompileError {
+    pub fn new(code: ErrorCode, message: String, span: Span) -> CompileError {
+        CompileError {
+            code,
+            message,
+            span: Some(span),
+            notes: vec![],
+        }
+    }
+
+    pub fn without_span(code: ErrorCode, message: String) -> CompileError {
+        CompileError {
+            code,
+            message,
+            span: None,
+            notes: vec![],
+        }
+    }
+
+    pub fn with_note(mut self, note: String) -> CompileError {
+        self.notes.push(note);
+        self
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        self.code.kind()
+    }
+}
+
+/// A kind of warnings, enabled by its flag like -Wshadow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WarningFlag {
+    Shadow,          // A declaration hides a symbol of an outer scope.
+    UnusedVariable,  // A local variable or constant is never used.
+    UnusedParameter, // A parameter is never used.
+    Uninitialized,   // A local variable is read before it is assigned on some path.
+    ReturnType,      // A non-void function can reach its end without returning a value.
+    UnreachableCode, // A statement after return, break or continue can never run.
+}
+
+impl WarningFlag {
+    pub const ALL: [WarningFlag; 6] = [
+        WarningFlag::Shadow,
+        WarningFlag::UnusedVariable,
+        WarningFlag::UnusedParameter,
+        WarningFlag::Uninitialized,
+        WarningFlag::ReturnType,
+        WarningFlag::UnreachableCode,
+    ];
+
+    /// The name in the flag, e.g. "shadow" for -Wshadow.
+    pub fn name(self) -> &'static str {
+        match self {
+            WarningFlag::Shadow => "shadow",
+            WarningFlag::UnusedVariable => "unused-variable",
+            WarningFlag::UnusedParameter => "unused-parameter",
+            WarningFlag::Uninitialized => "uninitialized",
+            WarningFlag::ReturnType => "return-type",
+            WarningFlag::UnreachableCode => "unreachable-code",
+        }
+    }
+
+    /// Whether -Wall enables it. Like gcc, -Wall does not enable -Wshadow.
+    pub fn is_in_wall(self) -> bool {
+        self != WarningFlag::Shadow
+    }
+
+    pub fn from_name(name: &str) -> Option<WarningFlag> {
+        WarningFlag::ALL
+            .into_iter()
+            .find(|flag| flag.name() == name)
+    }
+}
+
+/// A warning does not stop the compilation. It is only shown if its flag is enabled.
+#[derive(Debug)]
+pub struct Warning {
+    pub flag: WarningFlag,
+    pub message: String,
+    pub span: Span,
+}
+
+impl Warning {
+    pub fn new(flag: WarningFlag, message: String, span: Span) -> Warning {
+        Warning {
+            flag,
+            message,
+            span,
+        }
+    }
+}
+
+/// Makes a terminal name of lalrpop readable.
+/// Quoted terminals keep their text, and the other terminals are named by what they match.
+fn readable_terminal(terminal: &str) -> String {
+    match terminal {
+        "Identifier" => "identifier".to_string(),
+        "TypeName" => "type name".to_string(),
+        "IntLiteral" | "LongLongLiteral" | "FloatLiteral" => "number".to_string(),
+        "CharLiteral" => "character".to_string(),
+        "StringLiteral" => "string".to_string(),
+        _ => format!("'{}'", terminal.trim_matches('"')),
+    }
+}
+
+fn readable_expected(expected: &[String]) -> String {
+    let mut terminals: Vec<String> = vec![];
+    for terminal in expected.iter().map(|t| readable_terminal(t)) {
+        if !terminals.contains(&terminal) {
+            terminals.push(terminal);
+        }
+    }
+    match terminals.len() {
+        1 => terminals[0].clone(),
+        _ => format!("one of {}", terminals.join(", ")),
+    }
+}
+
+/// Converts an error reported by the lalrpop parser.
+/// Errors from the lexer and the grammar actions are already CompileErrors.
+pub fn from_parse_error<T: std::fmt::Display>(
+    error: lalrpop_util::ParseError<usize, T, CompileError>,
+) -> CompileError {
+    use lalrpop_util::ParseError;
+    match error {
+        ParseError::InvalidToken { location } => CompileError::new(
+            ErrorCode::InvalidToken,
+            "Invalid token! ".to_string(),
+            Span::new(location, location + 1),
+        ),
+        ParseError::UnrecognizedEof { location, expected } => CompileError::new(
+            ErrorCode::UnexpectedEof,
+            match expected.is_empty() {
+                true => "Unexpected end of file! ".to_string(),
+                false => format!(
+                    "Expected {}, but found the end of file! ",
+                    readable_expected(&expected)
+                ),
+            },
+            Span::new(location, location),
+        ),
+        ParseError::UnrecognizedToken {
+            token: (l, token, r),
+            expected,
+        } => CompileError::new(
+            ErrorCode::UnexpectedToken,
+            match expected.is_empty() {
+                true => format!("Unexpected token '{}'! ", token),
+                false => format!(
+                    "Expected {}, but found '{}'! ",
+                    readable_expected(&expected),
+                    token
+                ),
+            },
+            Span::new(l, r),
+        ),
+        ParseError::ExtraToken {
+            token: (l, token, r),
+        } => CompileError::new(
+            ErrorCode::ExtraToken,
+            format!("Expected the end of file, but found '{}'! ", token),
+            Span::new(l, r),
+        ),
+        ParseError::User { error } => error,
+    }
+}
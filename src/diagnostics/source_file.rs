@@ -0,0 +1,185 @@
+//! Map byte offsets back to lines and columns, and render error messages.
+//! The parser reads the preprocessed text, so every line of it remembers where it comes from.
+
+use super::{CompileError, Warning};
+use crate::ast_def::symbols::Span;
+
+/// A text, and the byte offset of the first char of every line.
+struct Lines {
+    text: String,
+    line_starts: Vec<usize>,
+}
+
+impl Lines {
+    fn new(text: String) -> Lines {
+        let mut line_starts = vec![0];
+        for (offset, c) in text.char_indices() {
+            if c == '\n' {
+                line_starts.push(offset + 1);
+            }
+        }
+        Lines { text, line_starts }
+    }
+
+    /// Returns the 0-based line index containing the byte offset.
+    fn line_index(&self, offset: usize) -> usize {
+        match self.line_starts.binary_search(&offset) {
+            Ok(idx) => idx,
+            Err(idx) => idx - 1,
+        }
+    }
+
+    /// Returns the content of a line, without the line break.
+    fn line_content(&self, line_idx: usize) -> &str {
+        let start = self.line_starts[line_idx];
+        let end = match self.line_starts.get(line_idx + 1) {
+            Some(&next_start) => next_start,
+            None => self.text.len(),
+        };
+        self.text[start..end].trim_end_matches(['\n', '\r'])
+    }
+}
+
+/// Where a line of the preprocessed text comes from.
+#[derive(Debug, Clone, Copy)]
+pub struct LineOrigin {
+    pub file: usize, // Index of the file in SourceFile
+    pub line: usize, // 1-based line number in that file
+    // Whether the line has the same columns as in that file, i.e. no macro was expanded.
+    pub verbatim: bool,
+}
+
+pub struct SourceFile {
+    files: Vec<(String, Lines)>, // Name and original text of every file read
+    text: Lines,                 // The preprocessed text
+    line_origins: Vec<LineOrigin>,
+}
+
+impl SourceFile {
+    /// `files` are the names and texts of all files read by the preprocessor,
+    /// and `line_origins` has an entry for every line of the preprocessed text.
+    pub fn new(
+        files: Vec<(String, String)>,
+        text: String,
+        mut line_origins: Vec<LineOrigin>,
+    ) -> SourceFile {
+        let text = Lines::new(text);
+        // The text ends with a line break, so its last line is empty.
+        // It is the line after the last line of the file.
+        while line_origins.len() < text.line_starts.len() {
+            let last = line_origins.last().copied().unwrap_or(LineOrigin {
+                file: 0,
+                line: 0,
+                verbatim: false,
+            });
+            line_origins.push(LineOrigin {
+                line: last.line + 1,
+                verbatim: false,
+                ..last
+            });
+        }
+        SourceFile {
+            files: files
+                .into_iter()
+                .map(|(name, text)| (name, Lines::new(text)))
+                .collect(),
+            text,
+            line_origins,
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text.text
+    }
+
+    /// Returns the 1-based (line, column) of a byte offset in the preprocessed text.
+    /// Columns count chars, not bytes.
+    pub fn location(&self, offset: usize) -> (usize, usize) {
+        let offset = std::cmp::min(offset, self.text.text.len());
+        let line_idx = self.text.line_index(offset);
+        let col = self.text.text[self.text.line_starts[line_idx]..offset]
+            .chars()
+            .count();
+        (line_idx + 1, col + 1)
+    }
+
+    /// Example:
+    /// ```text
+    /// error[E0203]: The shape of array must be constant!
+    ///  --> hello.c:3:11
+    ///   |
+    /// 3 |     int a[n];
+    ///   |           ^
+    ///   = note: ...
+    /// ```
+    /// The location is in the file that the line comes from.
+    /// If a macro was expanded in the line, the expanded line is shown instead.
+    pub fn render(&self, error: &CompileError) -> String {
+        let header = format!("error[{}]: {}", error.code, error.message.trim_end());
+        self.render_diagnostic(header, error.span, &error.notes)
+    }
+
+    /// Like errors, but the header names the flag that enables the warning:
+    /// ```text
+    /// warning: The declaration of 'a' shadows a global variable! [-Wshadow]
+    /// ```
+    pub fn render_warning(&self, warning: &Warning) -> String {
+        let header = format!(
+            "warning: {} [-W{}]",
+            warning.message.trim_end(),
+            warning.flag.name()
+        );
+        self.render_diagnostic(header, Some(warning.span), &[])
+    }
+
+    fn render_diagnostic(&self, header: String, span: Option<Span>, notes: &[String]) -> String {
+        let mut text = header;
+        let mut padding = String::new();
+        if let Some(span) = span {
+            let (line, col) = self.location(span.start);
+            let expanded = self.text.line_content(line - 1);
+            let origin = self.line_origins[line - 1];
+            let (name, lines) = &self.files[origin.file];
+            // Comments are replaced by spaces, so the original line is shown if it has the same columns.
+            let content = match origin.verbatim {
+                true => lines.line_content(origin.line - 1),
+                false => expanded,
+            };
+            let line_number = origin.line.to_string();
+            padding = " ".repeat(line_number.len());
+
+            // Keep tabs in the prefix so that the caret lines up with the source.
+            let prefix: String = content
+                .chars()
+                .take(col - 1)
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+            // Underline the span, but never past the end of this line.
+            let line_end = self.text.line_starts[line - 1] + expanded.len();
+            let underline_start = std::cmp::min(span.start, line_end);
+            let underline_end = span.end.clamp(underline_start, line_end);
+            let underline_len = self.text.text[underline_start..underline_end]
+                .chars()
+                .count()
+                .max(1);
+
+            text += &format!(
+                "\n{}--> {}:{}:{}\n{} |\n{} | {}\n{} | {}{}",
+                padding,
+                name,
+                line_number,
+                col,
+                padding,
+                line_number,
+                content,
+                padding,
+                prefix,
+                "^".repeat(underline_len),
+            );
+        }
+        for note in notes {
+            text += &format!("\n{} = note: {}", padding, note);
+        }
+        text
+    }
+}
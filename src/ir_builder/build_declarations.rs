@@ -1,51 +1,80 @@
 //! Build a single component into Koopa IR.
 
 use crate::ast_def::declarations::*;
-use koopa::ir::{builder_traits::*, FunctionData, Program, Type, Value};
+use crate::ast_def::symbols::{BType, BTypeKind, IDENT};
+use crate::diagnostics::{CompileError, ErrorCode};
+use koopa::ir::{builder_traits::*, FunctionData, Program, Type, TypeKind, Value, ValueKind};
 
 use super::{
-    build_expressions::{IRExpBuildResult, IRExpBuildable},
-    build_shape, create_new_local_value, get_array_type, get_valuedata, insert_local_instructions,
-    IRBuildResult, IRBuildable, MyIRGeneratorInfo, SymbolTableEntry,
+    build_expressions::{build_struct_addr, copy_struct, IRExpBuildResult, IRExpBuildable},
+    build_shape, check_not_void, create_new_block, create_new_local_value, declare_pointer_cast,
+    get_array_type, get_const_bits, get_const_long_long, get_pointer_value, get_typed_value,
+    get_valuedata, insert_basic_blocks, insert_local_instructions, new_long_long, ConstArray,
+    FunctionEntry, IRBuildResult, IRBuildable, MyIRGeneratorInfo, StructEntry, StructMember,
+    SymbolKind, SymbolTableEntry,
 };
+use std::rc::Rc;
 
 impl IRBuildable for FuncDef {
     fn build(
         &self,
         program: &mut Program,
         my_ir_generator_info: &mut MyIRGeneratorInfo,
-    ) -> Result<IRBuildResult, String> {
-        let FuncDef::Default(return_type, func_id, params, block) = self;
+    ) -> Result<IRBuildResult, CompileError> {
+        let FuncDef::Default(return_type, func_id, params, block, _) = self;
         // Tell Koopa IR its return type and params.
-        let return_type = Type::get(return_type.content.clone());
-        let mut koopa_ir_params = Vec::<(Option<String>, Type)>::new();
-        for FuncFParam::Default(btype, ident, possible_shape_exps) in params {
-            let param_type = match possible_shape_exps {
-                Some(shape_exps) => {
-                    let shape = build_shape(shape_exps, program, my_ir_generator_info)?;
-                    Type::get_pointer(Type::get(get_array_type(btype, &shape)))
-                }
-                None => Type::get(btype.content.clone()),
-            };
-            koopa_ir_params.push((Some(format!("%{}_param", &ident.content)), param_type));
-        }
-        let func = program.new_func(FunctionData::with_param_names(
-            format!("@{}", func_id.content),
-            koopa_ir_params,
+        let (function_data, return_btype, param_btypes) = build_signature(
             return_type,
-        ));
+            func_id,
+            params,
+            true,
+            program,
+            my_ir_generator_info,
+        )?;
 
         // Insert the function name into symbol table.
-        // Check whether there are two global symbols with the same name.
-        if my_ir_generator_info.check_duplicate_global_symbol(&func_id.content) {
-            return Err(format!(
-                "Invalid function name: {}. There was another global symbol with the same name! ",
-                func_id.content
-            ));
-        }
-        my_ir_generator_info
-            .function_table
-            .insert(func_id.content.clone(), func);
+        // A function declared by a prototype keeps its handle, because it may have been called.
+        let func = match my_ir_generator_info.function_table.get(&func_id.content) {
+            Some(entry) if entry.is_prototype => {
+                let func = entry.func;
+                if !has_signature(
+                    entry,
+                    &function_data,
+                    return_btype,
+                    &param_btypes,
+                    false,
+                    program,
+                ) {
+                    return Err(CompileError::new(
+                        ErrorCode::BackendInternal,
+                        format!(
+                            "The definition of function '{}' does not match its prototype! ",
+                            func_id.content
+                        ),
+                        func_id.span,
+                    ));
+                }
+                // Koopa IR links the function data to the program when it is created.
+                let new_func = program.new_func(function_data);
+                let function_data = program.remove_func(new_func).unwrap();
+                *program.funcs_mut().get_mut(&func).unwrap() = function_data;
+                func
+            }
+            _ => {
+                my_ir_generator_info.check_redefinition(func_id, SymbolKind::Function)?;
+                program.new_func(function_data)
+            }
+        };
+        my_ir_generator_info.function_table.insert(
+            func_id.content.clone(),
+            FunctionEntry {
+                func,
+                return_type: return_btype,
+                param_types: param_btypes,
+                is_variadic: false,
+                is_prototype: false,
+            },
+        );
 
         /* Create a new BasicBlock and:
            - Allocate form params;
@@ -57,19 +86,14 @@ impl IRBuildable for FuncDef {
         func_data.layout_mut().bbs_mut().extend([new_block]);
         my_ir_generator_info.curr_block = Some(new_block);
         my_ir_generator_info.curr_func = Some(func);
+        my_ir_generator_info.label_blocks.clear();
 
         my_ir_generator_info.symbol_tables.add_new_table();
-        for idx in 0..program.func(func).params().len() {
-            let FuncFParam::Default(btype, ident, possible_shape_exps) = &params[idx];
+        for (idx, param) in params.iter().enumerate() {
+            let FuncFParam::Default(btype, _, ident, _, _) = param;
             let real_param = program.func(func).params()[idx];
             // Allocate form params.
-            let form_param_type = match possible_shape_exps {
-                Some(shape_exps) => {
-                    let shape = build_shape(shape_exps, program, my_ir_generator_info)?;
-                    Type::get_pointer(Type::get(get_array_type(btype, &shape)))
-                }
-                None => Type::get(btype.content.clone()),
-            };
+            let form_param_type = program.func(func).dfg().value(real_param).ty().clone();
             let form_param =
                 create_new_local_value(program, my_ir_generator_info).alloc(form_param_type);
             program
@@ -77,10 +101,11 @@ impl IRBuildable for FuncDef {
                 .dfg_mut()
                 .set_value_name(form_param, Some(format!("@{}", ident.content,)));
             // Insert form params into symbol table.
-            my_ir_generator_info.symbol_tables.insert(
-                ident.content.clone(),
-                SymbolTableEntry::Variable(btype.content.clone(), form_param),
-            );
+            my_ir_generator_info.declare_symbol(
+                ident,
+                SymbolTableEntry::Variable(btype.content, form_param),
+                SymbolKind::Parameter,
+            )?;
             // Assign real params to form params.
             let assign_inst =
                 create_new_local_value(program, my_ir_generator_info).store(real_param, form_param);
@@ -91,7 +116,22 @@ impl IRBuildable for FuncDef {
         match block.build(program, my_ir_generator_info)? {
             IRBuildResult::OK => {
                 // No return instruction. Add a return instruction.
-                let return_inst = create_new_local_value(program, my_ir_generator_info).ret(None);
+                // A function that returns a value returns 0, like main does in C.
+                let return_value = match return_btype {
+                    BTypeKind::Void => None,
+                    _ => {
+                        let Block::Default(_, block_span) = block;
+                        Some(get_typed_value(
+                            IRExpBuildResult::Const(0),
+                            return_btype,
+                            *block_span,
+                            program,
+                            my_ir_generator_info,
+                        )?)
+                    }
+                };
+                let return_inst =
+                    create_new_local_value(program, my_ir_generator_info).ret(return_value);
                 insert_local_instructions(program, my_ir_generator_info, [return_inst]);
             }
             IRBuildResult::EARLYSTOPPING => {}
@@ -103,35 +143,188 @@ impl IRBuildable for FuncDef {
     }
 }
 
-impl IRBuildable for Block {
+impl IRBuildable for FuncDecl {
     fn build(
         &self,
         program: &mut Program,
         my_ir_generator_info: &mut MyIRGeneratorInfo,
-    ) -> Result<IRBuildResult, String> {
-        let Block::Default(stmts) = self;
-        let mut block_result = IRBuildResult::OK;
-        for stmt in stmts {
-            let result = stmt.build(program, my_ir_generator_info)?;
-            // Ignore everything after the return statement.
-            if let IRBuildResult::EARLYSTOPPING = result {
-                block_result = result;
-                break;
+    ) -> Result<IRBuildResult, CompileError> {
+        let FuncDecl::Default(return_type, func_id, params, is_variadic, _) = self;
+        let (function_data, return_btype, param_btypes) = build_signature(
+            return_type,
+            func_id,
+            params,
+            false,
+            program,
+            my_ir_generator_info,
+        )?;
+
+        // Declaring a function again is OK, if the signatures are the same.
+        if let Some(entry) = my_ir_generator_info.function_table.get(&func_id.content) {
+            if !has_signature(
+                entry,
+                &function_data,
+                return_btype,
+                &param_btypes,
+                *is_variadic,
+                program,
+            ) {
+                return Err(CompileError::new(
+                    ErrorCode::BackendInternal,
+                    format!(
+                        "The declaration of function '{}' does not match its previous declaration! ",
+                        func_id.content
+                    ),
+                    func_id.span,
+                ));
             }
+            return Ok(IRBuildResult::OK);
+        }
+        my_ir_generator_info.check_redefinition(func_id, SymbolKind::Function)?;
+        let func = program.new_func(function_data);
+        my_ir_generator_info.function_table.insert(
+            func_id.content.clone(),
+            FunctionEntry {
+                func,
+                return_type: return_btype,
+                param_types: param_btypes,
+                is_variadic: false,
+                is_prototype: true,
+            },
+        );
+        Ok(IRBuildResult::OK)
+    }
+}
+
+/// Build the Koopa IR function (without body) and the SysY types of a function signature.
+fn build_signature(
+    return_type: &BType,
+    func_id: &IDENT,
+    params: &[FuncFParam],
+    is_definition: bool,
+    program: &mut Program,
+    my_ir_generator_info: &mut MyIRGeneratorInfo,
+) -> Result<(FunctionData, BTypeKind, Vec<BTypeKind>), CompileError> {
+    let return_btype = return_type.content;
+    if let BTypeKind::Struct(_) = return_btype {
+        return Err(CompileError::new(
+            ErrorCode::BackendInternal,
+            "A function can not return a struct! ".to_string(),
+            return_type.span,
+        ));
+    }
+    let return_type = Type::get(return_btype.to_type_kind());
+    let mut koopa_ir_params = Vec::<(Option<String>, Type)>::new();
+    let mut param_btypes = vec![];
+    for FuncFParam::Default(btype, ptr_depth, ident, possible_shape_exps, span) in params {
+        check_not_void(btype)?;
+        if let (BTypeKind::Struct(_), 0, None) = (btype.content, ptr_depth, possible_shape_exps) {
+            return Err(CompileError::new(
+                ErrorCode::BackendInternal,
+                "A struct can not be passed by value! Pass a pointer to it instead. ".to_string(),
+                *span,
+            ));
         }
+        let param_type = match possible_shape_exps {
+            Some(shape_exps) => {
+                let shape = build_shape(shape_exps, program, my_ir_generator_info)?;
+                Type::get_pointer(Type::get(get_array_type(
+                    btype.content,
+                    *ptr_depth,
+                    &shape,
+                    btype.span,
+                    my_ir_generator_info,
+                )?))
+            }
+            None => Type::get(get_array_type(
+                btype.content,
+                *ptr_depth,
+                &[],
+                btype.span,
+                my_ir_generator_info,
+            )?),
+        };
+        param_btypes.push(btype.content);
+        koopa_ir_params.push((Some(format!("%{}_param", &ident.content)), param_type));
+    }
+    let function_data = match is_definition {
+        true => FunctionData::with_param_names(
+            format!("@{}", func_id.content),
+            koopa_ir_params,
+            return_type,
+        ),
+        false => FunctionData::new_decl(
+            format!("@{}", func_id.content),
+            koopa_ir_params.into_iter().map(|(_, ty)| ty).collect(),
+            return_type,
+        ),
+    };
+    Ok((function_data, return_btype, param_btypes))
+}
+
+/// Whether a declared function has the given signature.
+fn has_signature(
+    entry: &FunctionEntry,
+    function_data: &FunctionData,
+    return_btype: BTypeKind,
+    param_btypes: &[BTypeKind],
+    is_variadic: bool,
+    program: &Program,
+) -> bool {
+    entry.is_variadic == is_variadic
+        && entry.return_type == return_btype
+        && entry.param_types == param_btypes
+        && program.func(entry.func).ty() == function_data.ty()
+}
+
+impl IRBuildable for Block {
+    fn build(
+        &self,
+        program: &mut Program,
+        my_ir_generator_info: &mut MyIRGeneratorInfo,
+    ) -> Result<IRBuildResult, CompileError> {
+        let Block::Default(stmts, _) = self;
+        let block_result = build_block_items(stmts, program, my_ir_generator_info)?;
         my_ir_generator_info.symbol_tables.delete_new_table();
         Ok(block_result)
     }
 }
 
+/// Builds the items of a block or a case in order.
+/// Everything after the return statement is ignored, unless a goto can jump to a label there.
+/// Then the items before the label are built into an unreachable block.
+pub fn build_block_items(
+    items: &[BlockItem],
+    program: &mut Program,
+    my_ir_generator_info: &mut MyIRGeneratorInfo,
+) -> Result<IRBuildResult, CompileError> {
+    let mut result = IRBuildResult::OK;
+    for (i, item) in items.iter().enumerate() {
+        if let IRBuildResult::EARLYSTOPPING = result {
+            if !items[i..].iter().any(|item| item.has_label()) {
+                break;
+            }
+            let block = create_new_block(program, my_ir_generator_info, "unreachable");
+            insert_basic_blocks(program, my_ir_generator_info, [block]);
+            my_ir_generator_info.curr_block = Some(block);
+        }
+        result = item.build(program, my_ir_generator_info)?;
+    }
+    Ok(result)
+}
+
 impl IRBuildable for BlockItem {
     fn build(
         &self,
         program: &mut Program,
         my_ir_generator_info: &mut MyIRGeneratorInfo,
-    ) -> Result<IRBuildResult, String> {
+    ) -> Result<IRBuildResult, CompileError> {
         match self {
             BlockItem::Decl(decl) => decl.build(program, my_ir_generator_info),
+            BlockItem::StaticDecl(var_decl) => {
+                build_static_var_decl(var_decl, program, my_ir_generator_info)
+            }
+            BlockItem::TypeDef(_) => Ok(IRBuildResult::OK),
             BlockItem::Stmt(stmt) => stmt.build(program, my_ir_generator_info),
         }
     }
@@ -139,17 +332,19 @@ impl IRBuildable for BlockItem {
 
 pub enum IRInitValBuildResult {
     Const(i32),
+    LongLongConst(i64),
     Var(Value),
     Aggregate(Value),
 }
 
 fn build_new_aggregate(
+    btype: BTypeKind,
     shape: &[usize],
     childs: &[Box<InitVal>],
     is_global: bool,
     program: &mut Program,
     my_ir_generator_info: &mut MyIRGeneratorInfo,
-) -> Result<(Value, usize), String> {
+) -> Result<(Value, usize), CompileError> {
     let mut elems = vec![];
     let mut curr_child_idx = 0;
     if shape.len() == 1 {
@@ -160,24 +355,60 @@ fn build_new_aggregate(
             } else {
                 None
             };
+            // An array of structs. The braces of a struct can be omitted, like those of arrays.
+            if let BTypeKind::Struct(_) = btype {
+                let (value, used_child_cnt) = match next_child {
+                    Some(InitVal::Aggregate(grand_childs, _)) => {
+                        let (value, _) = build_struct_aggregate(
+                            btype,
+                            grand_childs,
+                            is_global,
+                            program,
+                            my_ir_generator_info,
+                        )?;
+                        (value, 1)
+                    }
+                    _ => build_struct_aggregate(
+                        btype,
+                        &childs[curr_child_idx.min(childs.len())..],
+                        is_global,
+                        program,
+                        my_ir_generator_info,
+                    )?,
+                };
+                curr_child_idx += used_child_cnt;
+                elems.push(value);
+                continue;
+            }
             let result = match next_child {
                 Some(InitVal::Exp(exp)) => exp.build(program, my_ir_generator_info)?,
-                Some(InitVal::Aggregate(_)) => return Err(format!("Wrong aggregate")),
+                Some(InitVal::Aggregate(_, span)) => {
+                    return Err(CompileError::new(
+                        ErrorCode::InvalidInitializer,
+                        "Wrong aggregate: too many levels of braces! ".to_string(),
+                        *span,
+                    ))
+                }
                 None => IRExpBuildResult::Const(0),
             };
             curr_child_idx += 1;
-            let value = match result {
-                IRExpBuildResult::Const(int) => {
+            if let (BTypeKind::LongLong, Some(int)) = (btype, get_const_long_long(&result)) {
+                elems.push(new_long_long(int, is_global, program, my_ir_generator_info));
+                continue;
+            }
+            let value = match get_const_bits(&result, btype) {
+                Some(int) => {
                     if is_global {
                         program.new_value().integer(int)
                     } else {
                         create_new_local_value(program, my_ir_generator_info).integer(int)
                     }
                 }
-                IRExpBuildResult::Value(_) => {
-                    return Err(format!(
-                        "Non-constant expression in aggregate initval: {:?}",
-                        next_child
+                None => {
+                    return Err(CompileError::new(
+                        ErrorCode::NonConstantExpression,
+                        "Non-constant expression in aggregate initval! ".to_string(),
+                        next_child.map(InitVal::span).unwrap_or_default(),
                     ))
                 }
             };
@@ -192,12 +423,22 @@ fn build_new_aggregate(
             };
             match next_child {
                 Some(InitVal::Exp(_)) => {}
-                Some(InitVal::Aggregate(_)) => {
-                    let result =
-                        childs[curr_child_idx].build(&shape[1..], program, my_ir_generator_info)?;
+                Some(InitVal::Aggregate(_, _)) => {
+                    let result = childs[curr_child_idx].build(
+                        btype,
+                        &shape[1..],
+                        program,
+                        my_ir_generator_info,
+                    )?;
                     match result {
-                        IRInitValBuildResult::Const(_) | IRInitValBuildResult::Var(_) => {
-                            panic!("Aggregate can only build aggregate!")
+                        IRInitValBuildResult::Const(_)
+                        | IRInitValBuildResult::LongLongConst(_)
+                        | IRInitValBuildResult::Var(_) => {
+                            return Err(CompileError::new(
+                                ErrorCode::BackendInternal,
+                                "An aggregate initval is not built into an aggregate! ".to_string(),
+                                childs[curr_child_idx].span(),
+                            ))
                         }
                         IRInitValBuildResult::Aggregate(aggr) => elems.push(aggr),
                     }
@@ -207,6 +448,7 @@ fn build_new_aggregate(
                 None => {}
             };
             let (result, used_child_cnt) = build_new_aggregate(
+                btype,
                 &shape[1..],
                 if curr_child_idx < childs.len() {
                     &childs[curr_child_idx..]
@@ -231,12 +473,182 @@ fn build_new_aggregate(
     }
 }
 
+fn new_integer(
+    int: i32,
+    is_global: bool,
+    program: &mut Program,
+    my_ir_generator_info: &mut MyIRGeneratorInfo,
+) -> Value {
+    if is_global {
+        program.new_value().integer(int)
+    } else {
+        create_new_local_value(program, my_ir_generator_info).integer(int)
+    }
+}
+
+/// Appends the words of an aggregate to words. A word is an element that is not an aggregate.
+fn flatten_aggregate(
+    aggr: Value,
+    words: &mut Vec<Value>,
+    program: &mut Program,
+    my_ir_generator_info: &mut MyIRGeneratorInfo,
+) {
+    match get_valuedata(aggr, program, my_ir_generator_info).kind() {
+        koopa::ir::ValueKind::Aggregate(aggr) => {
+            for &elem in aggr.elems() {
+                flatten_aggregate(elem, words, program, my_ir_generator_info);
+            }
+        }
+        _ => words.push(aggr),
+    }
+}
+
+/// Builds the aggregate of a struct, which is an array of its words.
+/// Members are initialized in order, and the rest are 0.
+/// Returns the number of used childs too, because the braces of the struct may be omitted.
+fn build_struct_aggregate(
+    btype: BTypeKind,
+    childs: &[Box<InitVal>],
+    is_global: bool,
+    program: &mut Program,
+    my_ir_generator_info: &mut MyIRGeneratorInfo,
+) -> Result<(Value, usize), CompileError> {
+    let BTypeKind::Struct(name) = btype else {
+        unreachable!("Only structs are built by build_struct_aggregate")
+    };
+    let members = my_ir_generator_info.struct_table[name].members.clone();
+    let mut words = vec![];
+    let mut curr_child_idx = 0;
+    for member in members {
+        // Members are aligned like in the struct layout.
+        while words.len() < member.offset {
+            words.push(new_integer(0, is_global, program, my_ir_generator_info));
+        }
+        let next_child = childs.get(curr_child_idx).map(|child| &**child);
+        let is_scalar = member.ptr_depth == 0
+            && member.shape.is_empty()
+            && !matches!(member.btype, BTypeKind::Struct(_));
+        match (next_child, is_scalar) {
+            (None, _) => {
+                for _ in 0..member.ty.size() / Type::get_i32().size() {
+                    words.push(new_integer(0, is_global, program, my_ir_generator_info));
+                }
+                continue;
+            }
+            // A long long takes 2 words.
+            (Some(InitVal::Exp(exp)), true) if member.btype == BTypeKind::LongLong => {
+                let result = exp.build(program, my_ir_generator_info)?;
+                let Some(int) = get_const_long_long(&result) else {
+                    return Err(CompileError::new(
+                        ErrorCode::NonConstantExpression,
+                        "Non-constant expression in aggregate initval! ".to_string(),
+                        exp.span(),
+                    ));
+                };
+                let long_long = new_long_long(int, is_global, program, my_ir_generator_info);
+                flatten_aggregate(long_long, &mut words, program, my_ir_generator_info);
+                curr_child_idx += 1;
+                continue;
+            }
+            // An int, float or char.
+            (Some(InitVal::Exp(exp)), true) => {
+                let result = exp.build(program, my_ir_generator_info)?;
+                let Some(int) = get_const_bits(&result, member.btype) else {
+                    return Err(CompileError::new(
+                        ErrorCode::NonConstantExpression,
+                        "Non-constant expression in aggregate initval! ".to_string(),
+                        exp.span(),
+                    ));
+                };
+                words.push(new_integer(int, is_global, program, my_ir_generator_info));
+                curr_child_idx += 1;
+                continue;
+            }
+            (Some(InitVal::Aggregate(_, span)), true) => {
+                return Err(CompileError::new(
+                    ErrorCode::InvalidInitializer,
+                    "Wrong aggregate: too many levels of braces! ".to_string(),
+                    *span,
+                ))
+            }
+            // Like global pointers, a pointer member can only be initialized with 0.
+            (Some(InitVal::Exp(exp)), false) if member.ptr_depth > 0 && member.shape.is_empty() => {
+                if let IRExpBuildResult::Const(0) = exp.build(program, my_ir_generator_info)? {
+                    for _ in 0..member.ty.size() / Type::get_i32().size() {
+                        words.push(new_integer(0, is_global, program, my_ir_generator_info));
+                    }
+                    curr_child_idx += 1;
+                    continue;
+                }
+                return Err(CompileError::new(
+                    ErrorCode::NonConstantExpression,
+                    "A pointer member can only be initialized with 0! ".to_string(),
+                    exp.span(),
+                ));
+            }
+            (Some(InitVal::Aggregate(_, span)), false)
+                if member.ptr_depth > 0 && member.shape.is_empty() =>
+            {
+                return Err(CompileError::new(
+                    ErrorCode::InvalidInitializer,
+                    "A scalar can not be initialized with braces! ".to_string(),
+                    *span,
+                ))
+            }
+            (Some(child), false) if member.ptr_depth > 0 => {
+                return Err(CompileError::new(
+                    ErrorCode::InvalidInitializer,
+                    "An array of pointers can not be initialized! ".to_string(),
+                    child.span(),
+                ))
+            }
+            _ => {}
+        }
+        // An array or a struct. Its braces can be omitted.
+        let (member_childs, is_braced) = match next_child {
+            Some(InitVal::Aggregate(grand_childs, _)) => (&grand_childs[..], true),
+            _ => (&childs[curr_child_idx..], false),
+        };
+        let (aggr, used_child_cnt) = match member.shape.is_empty() {
+            true => build_struct_aggregate(
+                member.btype,
+                member_childs,
+                is_global,
+                program,
+                my_ir_generator_info,
+            )?,
+            false => build_new_aggregate(
+                member.btype,
+                &member.shape,
+                member_childs,
+                is_global,
+                program,
+                my_ir_generator_info,
+            )?,
+        };
+        curr_child_idx += match is_braced {
+            true => 1,
+            false => used_child_cnt,
+        };
+        flatten_aggregate(aggr, &mut words, program, my_ir_generator_info);
+    }
+    let size = my_ir_generator_info.struct_table[name].size;
+    while words.len() < size {
+        words.push(new_integer(0, is_global, program, my_ir_generator_info));
+    }
+    let aggr = match is_global {
+        true => program.new_value().aggregate(words),
+        false => create_new_local_value(program, my_ir_generator_info).aggregate(words),
+    };
+    Ok((aggr, curr_child_idx))
+}
+
 fn aggregate_to_store_insts(
     aggr: Value,
     aggr_ptr: Value,
     program: &mut Program,
     my_ir_generator_info: &mut MyIRGeneratorInfo,
-) -> Result<(), String> {
+) -> Result<(), CompileError> {
     assert!(
         !aggr_ptr.is_global(),
         "Global aggregate initialization cannot be converted into store instructions! "
@@ -263,7 +675,12 @@ fn aggregate_to_store_insts(
                 }
             }
         }
-        _ => panic!("This value must be an aggregate value! "),
+        _ => {
+            return Err(CompileError::without_span(
+                ErrorCode::BackendInternal,
+                "Only aggregates can be stored element by element! ".to_string(),
+            ))
+        }
     }
 
     Ok(())
@@ -272,40 +689,337 @@ fn aggregate_to_store_insts(
 impl InitVal {
     fn build(
         &self,
+        btype: BTypeKind,
         shape: &[usize],
         program: &mut Program,
         my_ir_generator_info: &mut MyIRGeneratorInfo,
-    ) -> Result<IRInitValBuildResult, String> {
+    ) -> Result<IRInitValBuildResult, CompileError> {
         let is_global = my_ir_generator_info.curr_func.is_none();
         match self {
+            // A string literal fills a char array with its chars and the ending '\0'.
+            // The type checker has checked that they fit in the array.
+            InitVal::Exp(exp)
+                if shape.len() == 1
+                    && btype == BTypeKind::Char
+                    && exp.string_literal().is_some() =>
+            {
+                let bytes = exp.string_literal().unwrap();
+                let elems = (0..shape[0])
+                    .map(|i| {
+                        let char = bytes.get(i).copied().unwrap_or(0) as i32;
+                        match is_global {
+                            true => program.new_value().integer(char),
+                            false => {
+                                create_new_local_value(program, my_ir_generator_info).integer(char)
+                            }
+                        }
+                    })
+                    .collect();
+                let value = match is_global {
+                    true => program.new_value().aggregate(elems),
+                    false => create_new_local_value(program, my_ir_generator_info).aggregate(elems),
+                };
+                Ok(IRInitValBuildResult::Aggregate(value))
+            }
+            InitVal::Exp(exp) if !shape.is_empty() => Err(CompileError::new(
+                ErrorCode::InvalidInitializer,
+                "An array must be initialized with braces! ".to_string(),
+                exp.span(),
+            )),
+            // A local struct variable copied from another struct is built by the VarDef.
+            InitVal::Exp(exp) if matches!(btype, BTypeKind::Struct(_)) && is_global => {
+                Err(CompileError::new(
+                    ErrorCode::NonConstantExpression,
+                    "Non-constant expression in global variable initval! ".to_string(),
+                    exp.span(),
+                ))
+            }
+            InitVal::Exp(exp) if matches!(btype, BTypeKind::Struct(_)) => Err(CompileError::new(
+                ErrorCode::InvalidInitializer,
+                "A constant struct must be initialized with braces! ".to_string(),
+                exp.span(),
+            )),
+            InitVal::Aggregate(childs, _)
+                if matches!(btype, BTypeKind::Struct(_)) && shape.is_empty() =>
+            {
+                let (value, _) = build_struct_aggregate(
+                    btype,
+                    childs,
+                    is_global,
+                    program,
+                    my_ir_generator_info,
+                )?;
+                Ok(IRInitValBuildResult::Aggregate(value))
+            }
+            InitVal::Aggregate(_, span) if shape.is_empty() => Err(CompileError::new(
+                ErrorCode::InvalidInitializer,
+                "A scalar can not be initialized with braces! ".to_string(),
+                *span,
+            )),
             InitVal::Exp(exp) => match exp.build(program, my_ir_generator_info)? {
-                IRExpBuildResult::Const(int) => Ok(IRInitValBuildResult::Const(int)),
-                IRExpBuildResult::Value(value) => {
+                result
+                    if btype == BTypeKind::LongLong && get_const_long_long(&result).is_some() =>
+                {
+                    Ok(IRInitValBuildResult::LongLongConst(
+                        get_const_long_long(&result).unwrap(),
+                    ))
+                }
+                // A constant is converted into the variable type (a float is stored as its bits).
+                result if get_const_bits(&result, btype).is_some() => Ok(
+                    IRInitValBuildResult::Const(get_const_bits(&result, btype).unwrap()),
+                ),
+                result => {
                     if is_global {
-                        Err(format!(
-                            "Non-constant expression '{:?}' in global variable initval: {:?}",
-                            exp, self
+                        Err(CompileError::new(
+                            ErrorCode::NonConstantExpression,
+                            "Non-constant expression in global variable initval! ".to_string(),
+                            exp.span(),
                         ))
                     } else {
+                        let value = get_typed_value(
+                            result,
+                            btype,
+                            exp.span(),
+                            program,
+                            my_ir_generator_info,
+                        )?;
                         Ok(IRInitValBuildResult::Var(value))
                     }
                 }
             },
-            InitVal::Aggregate(childs) => {
-                let (value, _) =
-                    build_new_aggregate(shape, childs, is_global, program, my_ir_generator_info)?;
+            InitVal::Aggregate(childs, _) => {
+                let (value, _) = build_new_aggregate(
+                    btype,
+                    shape,
+                    childs,
+                    is_global,
+                    program,
+                    my_ir_generator_info,
+                )?;
                 Ok(IRInitValBuildResult::Aggregate(value))
             }
         }
     }
 }
 
+/// Builds the initval of a pointer, which should be a pointer of the same type.
+/// Returns None for 0, the null pointer. It is the only initval of global pointers,
+/// because global initvals are constant.
+fn build_pointer_initval(
+    rhs: &InitVal,
+    pointer_type: &Type,
+    btype: BTypeKind,
+    shape: &[usize],
+    program: &mut Program,
+    my_ir_generator_info: &mut MyIRGeneratorInfo,
+) -> Result<Option<Value>, CompileError> {
+    let exp = match rhs {
+        _ if !shape.is_empty() => {
+            return Err(CompileError::new(
+                ErrorCode::InvalidInitializer,
+                "An array of pointers can not be initialized! ".to_string(),
+                rhs.span(),
+            ))
+        }
+        InitVal::Aggregate(_, span) => {
+            return Err(CompileError::new(
+                ErrorCode::InvalidInitializer,
+                "A scalar can not be initialized with braces! ".to_string(),
+                *span,
+            ))
+        }
+        InitVal::Exp(exp) => exp,
+    };
+    let result = exp.build(program, my_ir_generator_info)?;
+    if let IRExpBuildResult::Const(0) = result {
+        return Ok(None);
+    }
+    if my_ir_generator_info.curr_func.is_none() {
+        return Err(CompileError::new(
+            ErrorCode::NonConstantExpression,
+            "A global pointer can only be initialized with 0! ".to_string(),
+            exp.span(),
+        ));
+    }
+    let value = get_pointer_value(
+        result,
+        pointer_type,
+        btype,
+        exp.span(),
+        program,
+        my_ir_generator_info,
+    )?;
+    Ok(Some(value))
+}
+
+impl IRBuildable for StructDef {
+    fn build(
+        &self,
+        program: &mut Program,
+        my_ir_generator_info: &mut MyIRGeneratorInfo,
+    ) -> Result<IRBuildResult, CompileError> {
+        let StructDef::Default(ident, member_decls, _) = self;
+        if my_ir_generator_info
+            .struct_table
+            .contains_key(&ident.content)
+        {
+            return Err(CompileError::new(
+                ErrorCode::Redefinition,
+                format!("Redefinition of struct {}! ", ident.content),
+                ident.span,
+            ));
+        }
+
+        // Lay out the members. A pointer takes the same size whatever it points to,
+        // so that a struct can have pointers to itself.
+        // A member of 8 bytes (a long long or a RV64 pointer) is aligned to 2 words.
+        let mut members: Vec<(&String, &BType, usize, Vec<usize>, usize)> = vec![];
+        let mut size: usize = 0;
+        let mut align = 1;
+        let ptr_words = Type::get_pointer(Type::get_i32()).size() / Type::get_i32().size();
+        for VarDecl::Default(btype, var_defs, _) in member_decls {
+            check_not_void(btype)?;
+            for VarDef::Default(ptr_depth, member_ident, shape_exps, possible_rhs, _) in var_defs {
+                if let Some(rhs) = possible_rhs {
+                    return Err(CompileError::new(
+                        ErrorCode::InvalidInitializer,
+                        "A member of a struct can not be initialized! ".to_string(),
+                        rhs.span(),
+                    ));
+                }
+                if members
+                    .iter()
+                    .any(|(name, ..)| *name == &member_ident.content)
+                {
+                    return Err(CompileError::new(
+                        ErrorCode::Redefinition,
+                        format!(
+                            "Duplicate member {} in struct {}! ",
+                            member_ident.content, ident.content
+                        ),
+                        member_ident.span,
+                    ));
+                }
+                if matches!(btype.content, BTypeKind::Struct(name) if name == ident.content)
+                    && *ptr_depth == 0
+                {
+                    return Err(CompileError::new(
+                        ErrorCode::BackendInternal,
+                        "A struct can not contain itself! Use a pointer instead. ".to_string(),
+                        member_ident.span,
+                    ));
+                }
+                let shape = build_shape(shape_exps, program, my_ir_generator_info)?;
+                let (words, member_align) = match ptr_depth {
+                    0 => {
+                        let ty = Type::get(get_array_type(
+                            btype.content,
+                            0,
+                            &shape,
+                            btype.span,
+                            my_ir_generator_info,
+                        )?);
+                        let member_align = match btype.content {
+                            BTypeKind::LongLong => 2,
+                            BTypeKind::Struct(name) => {
+                                my_ir_generator_info.struct_table[name].align
+                            }
+                            _ => 1,
+                        };
+                        (ty.size() / Type::get_i32().size(), member_align)
+                    }
+                    _ => (ptr_words * shape.iter().product::<usize>(), ptr_words),
+                };
+                size = size.next_multiple_of(member_align);
+                align = align.max(member_align);
+                members.push((&member_ident.content, btype, *ptr_depth, shape, size));
+                size += words;
+            }
+        }
+        size = size.next_multiple_of(align);
+
+        // Insert the struct before the types of its members are known, because they may point to it.
+        my_ir_generator_info.struct_table.insert(
+            ident.content.clone(),
+            StructEntry {
+                members: vec![],
+                size,
+                align,
+            },
+        );
+        let mut struct_members = vec![];
+        for (name, btype, ptr_depth, shape, offset) in members {
+            let ty = Type::get(get_array_type(
+                btype.content,
+                ptr_depth,
+                &shape,
+                btype.span,
+                my_ir_generator_info,
+            )?);
+            // A member that is not an int is reached by casting the address of its first word.
+            if !ty.is_i32() {
+                declare_pointer_cast(
+                    &Type::get_pointer(ty.clone()),
+                    program,
+                    my_ir_generator_info,
+                );
+            }
+            struct_members.push(StructMember {
+                name: name.clone(),
+                btype: btype.content,
+                ptr_depth,
+                shape,
+                ty,
+                offset,
+            });
+        }
+        my_ir_generator_info
+            .struct_table
+            .get_mut(&ident.content)
+            .unwrap()
+            .members = struct_members;
+        Ok(IRBuildResult::OK)
+    }
+}
+
+/// Enumerators are int constants, entered in the symbol table like the ones of ConstDecl.
+impl IRBuildable for EnumDef {
+    fn build(
+        &self,
+        program: &mut Program,
+        my_ir_generator_info: &mut MyIRGeneratorInfo,
+    ) -> Result<IRBuildResult, CompileError> {
+        let EnumDef::Default(_, enumerators, _) = self;
+        let mut value: i32 = 0;
+        for Enumerator::Default(ident, possible_exp, _) in enumerators {
+            if let Some(exp) = possible_exp {
+                let result = exp.build(program, my_ir_generator_info)?;
+                value = get_const_bits(&result, BTypeKind::Int).ok_or(CompileError::new(
+                    ErrorCode::NonConstantExpression,
+                    format!(
+                        "The value of enumerator '{}' is not a constant! ",
+                        ident.content
+                    ),
+                    exp.span(),
+                ))?;
+            }
+            my_ir_generator_info.declare_symbol(
+                ident,
+                SymbolTableEntry::Constant(BTypeKind::Int, value as i64),
+                SymbolKind::Constant,
+            )?;
+            value = value.wrapping_add(1);
+        }
+        Ok(IRBuildResult::OK)
+    }
+}
+
 impl IRBuildable for Decl {
     fn build(
         &self,
         program: &mut Program,
         my_ir_generator_info: &mut MyIRGeneratorInfo,
-    ) -> Result<IRBuildResult, String> {
+    ) -> Result<IRBuildResult, CompileError> {
         match self {
             Decl::ConstDecl(const_decl) => const_decl.build(program, my_ir_generator_info),
             Decl::VarDecl(var_decl) => var_decl.build(program, my_ir_generator_info),
@@ -318,28 +1032,50 @@ impl IRBuildable for ConstDecl {
         &self,
         program: &mut Program,
         my_ir_generator_info: &mut MyIRGeneratorInfo,
-    ) -> Result<IRBuildResult, String> {
-        let ConstDecl::Default(btype, const_defs) = self;
-        let const_type = &btype.content;
+    ) -> Result<IRBuildResult, CompileError> {
+        let ConstDecl::Default(btype, const_defs, _) = self;
+        check_not_void(btype)?;
+        let const_type = btype.content;
         for const_def in const_defs {
-            let ConstDef::Default(ident, shape_exps, rhs) = const_def;
+            let ConstDef::Default(ident, shape_exps, rhs, _) = const_def;
             let shape = build_shape(shape_exps, program, my_ir_generator_info)?.clone();
-            let result = rhs.build(&shape, program, my_ir_generator_info)?;
+            // The initval of a constant is a constant expression, even in a function.
+            let is_const_exp = std::mem::replace(&mut my_ir_generator_info.is_const_exp, true);
+            let result = rhs.build(const_type, &shape, program, my_ir_generator_info);
+            my_ir_generator_info.is_const_exp = is_const_exp;
+            let result = result?;
             // Add an entry in the symbol table.
             match result {
                 IRInitValBuildResult::Const(int) => {
-                    my_ir_generator_info.symbol_tables.insert(
-                        ident.content.clone(),
-                        SymbolTableEntry::Constant(const_type.clone(), int),
-                    );
+                    my_ir_generator_info.declare_symbol(
+                        ident,
+                        SymbolTableEntry::Constant(const_type, int as i64),
+                        SymbolKind::Constant,
+                    )?;
+                }
+                IRInitValBuildResult::LongLongConst(int) => {
+                    my_ir_generator_info.declare_symbol(
+                        ident,
+                        SymbolTableEntry::Constant(const_type, int),
+                        SymbolKind::Constant,
+                    )?;
                 }
                 IRInitValBuildResult::Var(_) => {
-                    return Err(format!(
-                        "Non-constant expression in constant initval: {:?}",
-                        const_def
+                    return Err(CompileError::new(
+                        ErrorCode::NonConstantExpression,
+                        format!(
+                            "Non-constant expression in the initval of constant '{}'! ",
+                            ident.content
+                        ),
+                        rhs.span(),
                     ))
                 }
                 IRInitValBuildResult::Aggregate(aggr) => {
+                    // Read the elements before the aggregate is stored.
+                    let const_array = match const_type {
+                        BTypeKind::Struct(_) => None,
+                        _ => ConstArray::new(shape.clone(), aggr, program, my_ir_generator_info),
+                    };
                     let array_ptr = if my_ir_generator_info.curr_func.is_some() {
                         let aggr_valuedata = get_valuedata(aggr, program, my_ir_generator_info);
                         let addr = create_new_local_value(program, my_ir_generator_info)
@@ -349,13 +1085,19 @@ impl IRBuildable for ConstDecl {
                         addr
                     } else {
                         let addr = program.new_value().global_alloc(aggr);
-                        program.set_value_name(addr, Some(format!("@{}", ident.content,)));
+                        let name = format!("@{}", ident.content);
+                        program.set_value_name(addr, Some(name));
                         addr
                     };
-                    my_ir_generator_info.symbol_tables.insert(
-                        ident.content.clone(),
-                        SymbolTableEntry::Variable(const_type.clone(), array_ptr),
-                    );
+                    let entry = match const_array {
+                        Some(const_array) => SymbolTableEntry::ConstArray(
+                            const_type,
+                            array_ptr,
+                            Rc::new(const_array),
+                        ),
+                        None => SymbolTableEntry::Variable(const_type, array_ptr),
+                    };
+                    my_ir_generator_info.declare_symbol(ident, entry, SymbolKind::Constant)?;
                 }
             }
         }
@@ -368,16 +1110,20 @@ impl IRBuildable for VarDecl {
         &self,
         program: &mut Program,
         my_ir_generator_info: &mut MyIRGeneratorInfo,
-    ) -> Result<IRBuildResult, String> {
-        let VarDecl::Default(btype, var_defs) = self;
+    ) -> Result<IRBuildResult, CompileError> {
+        let VarDecl::Default(btype, var_defs, _) = self;
+        check_not_void(btype)?;
 
         for var_def in var_defs {
-            let VarDef::Default(ident, shape_exps, possible_rhs) = var_def;
+            let VarDef::Default(ptr_depth, ident, shape_exps, possible_rhs, _) = var_def;
             let shape = build_shape(shape_exps, program, my_ir_generator_info)?;
-            let var_type = match shape.is_empty() {
-                true => btype.content.clone(),
-                false => get_array_type(btype, &shape),
-            };
+            let var_type = get_array_type(
+                btype.content,
+                *ptr_depth,
+                &shape,
+                btype.span,
+                my_ir_generator_info,
+            )?;
 
             // Allocate the new variable and get its Koopa IR Value.
             let final_var_addr = match my_ir_generator_info.curr_func {
@@ -392,26 +1138,64 @@ impl IRBuildable for VarDecl {
                         .set_value_name(var_addr, Some(format!("@{}", ident.content,)));
                     insert_local_instructions(program, my_ir_generator_info, [var_addr]);
                     // Build RHS value (if exists).
-                    let rhs_result = if let Some(rhs) = possible_rhs {
-                        let result = rhs.build(&shape, program, my_ir_generator_info)?;
-                        match result {
-                            IRInitValBuildResult::Const(int) => Some(
-                                create_new_local_value(program, my_ir_generator_info).integer(int),
-                            ),
-                            IRInitValBuildResult::Var(value) => Some(value),
-                            IRInitValBuildResult::Aggregate(value) => {
-                                // Do not straightly store aggregate initval.
-                                aggregate_to_store_insts(
-                                    value,
-                                    var_addr,
-                                    program,
-                                    my_ir_generator_info,
-                                )?;
-                                None
+                    let rhs_result = match possible_rhs {
+                        Some(rhs) if *ptr_depth > 0 => {
+                            let pointer_type = Type::get(var_type.clone());
+                            match build_pointer_initval(
+                                rhs,
+                                &pointer_type,
+                                btype.content,
+                                &shape,
+                                program,
+                                my_ir_generator_info,
+                            )? {
+                                Some(value) => Some(value),
+                                None => Some(
+                                    create_new_local_value(program, my_ir_generator_info)
+                                        .zero_init(pointer_type),
+                                ),
                             }
                         }
-                    } else {
-                        None
+                        // A struct can be copied from another struct.
+                        Some(InitVal::Exp(exp))
+                            if matches!(btype.content, BTypeKind::Struct(_))
+                                && shape.is_empty() =>
+                        {
+                            let src = build_struct_addr(exp, program, my_ir_generator_info)?;
+                            copy_struct(var_addr, src, program, my_ir_generator_info)?;
+                            None
+                        }
+                        Some(rhs) => {
+                            match rhs.build(btype.content, &shape, program, my_ir_generator_info)? {
+                                IRInitValBuildResult::Const(int) => Some(
+                                    create_new_local_value(program, my_ir_generator_info)
+                                        .integer(int),
+                                ),
+                                IRInitValBuildResult::LongLongConst(int) => {
+                                    let value =
+                                        new_long_long(int, false, program, my_ir_generator_info);
+                                    aggregate_to_store_insts(
+                                        value,
+                                        var_addr,
+                                        program,
+                                        my_ir_generator_info,
+                                    )?;
+                                    None
+                                }
+                                IRInitValBuildResult::Var(value) => Some(value),
+                                IRInitValBuildResult::Aggregate(value) => {
+                                    // Do not straightly store aggregate initval.
+                                    aggregate_to_store_insts(
+                                        value,
+                                        var_addr,
+                                        program,
+                                        my_ir_generator_info,
+                                    )?;
+                                    None
+                                }
+                            }
+                        }
+                        None => None,
                     };
                     // Assign the RHS value into the new variable (if needed).
                     if let Some(rhs_value) = rhs_result {
@@ -424,42 +1208,303 @@ impl IRBuildable for VarDecl {
                 }
                 // Or if it's global:
                 None => {
-                    // Check whether there are two global symbols with the same name.
-                    if my_ir_generator_info.check_duplicate_global_symbol(&ident.content) {
-                        return Err(format!(
-                            "Invalid global variable name: {}. There was another global symbol with the same name! ",
-                            ident.content
-                        ));
+                    // A variable declared by extern before can be defined here.
+                    let extern_var = match my_ir_generator_info
+                        .symbol_tables
+                        .get_global(&ident.content)
+                    {
+                        Some(&(SymbolTableEntry::Variable(extern_btype, extern_addr), _))
+                            if is_extern_variable(extern_addr, program) =>
+                        {
+                            Some((extern_btype, extern_addr))
+                        }
+                        _ => None,
+                    };
+                    if extern_var.is_none() {
+                        my_ir_generator_info.check_redefinition(ident, SymbolKind::Variable)?;
                     }
                     // Allocate the new global variable.
-                    let var_addr = match possible_rhs {
-                        Some(rhs) => match rhs.build(&shape, program, my_ir_generator_info)? {
-                            IRInitValBuildResult::Const(int) => {
-                                let int_init = program.new_value().integer(int);
-                                program.new_value().global_alloc(int_init)
-                            }
-                            IRInitValBuildResult::Var(val) => program.new_value().global_alloc(val),
-                            IRInitValBuildResult::Aggregate(val) => {
-                                program.new_value().global_alloc(val)
-                            }
-                        },
-                        None => {
-                            let zero_init =
-                                program.new_value().zero_init(Type::get(var_type.clone()));
-                            program.new_value().global_alloc(zero_init)
+                    let var_addr = build_global_alloc(
+                        var_def,
+                        btype,
+                        &shape,
+                        &var_type,
+                        program,
+                        my_ir_generator_info,
+                    )?;
+                    // Make the extern declaration refer to this definition.
+                    if let Some((extern_btype, extern_addr)) = extern_var {
+                        if extern_btype != btype.content
+                            || program.borrow_value(extern_addr).ty()
+                                != program.borrow_value(var_addr).ty()
+                        {
+                            return Err(CompileError::new(
+                                ErrorCode::BackendInternal,
+                                format!(
+                                    "Conflicting types for global variable {}! ",
+                                    ident.content
+                                ),
+                                ident.span,
+                            ));
                         }
-                    };
-                    program.set_value_name(var_addr, Some(format!("@{}", ident.content,)));
+                        replace_global_uses(extern_addr, var_addr, program);
+                    }
+                    let name = format!("@{}", ident.content);
+                    program.set_value_name(var_addr, Some(name));
                     var_addr
                 }
             };
 
             // Add an entry in the symbol table.
-            my_ir_generator_info.symbol_tables.insert(
-                ident.content.clone(),
-                SymbolTableEntry::Variable(var_type.clone(), final_var_addr),
-            );
+            let entry = SymbolTableEntry::Variable(btype.content, final_var_addr);
+            match my_ir_generator_info.curr_func {
+                Some(_) => {
+                    my_ir_generator_info.declare_symbol(ident, entry, SymbolKind::Variable)?
+                }
+                // A global variable has been checked before it is allocated,
+                // and its definition replaces its extern declaration.
+                None => my_ir_generator_info.symbol_tables.insert(
+                    ident.content.clone(),
+                    entry,
+                    SymbolKind::Variable,
+                ),
+            }
         }
         Ok(IRBuildResult::OK)
     }
 }
+
+/// Allocates a global variable, whose initval is built when the program is loaded.
+/// Its initval should be constant, because no function is running then.
+fn build_global_alloc(
+    var_def: &VarDef,
+    btype: &BType,
+    shape: &[usize],
+    var_type: &TypeKind,
+    program: &mut Program,
+    my_ir_generator_info: &mut MyIRGeneratorInfo,
+) -> Result<Value, CompileError> {
+    let VarDef::Default(ptr_depth, _, _, possible_rhs, _) = var_def;
+    let var_addr = match possible_rhs {
+        // A global pointer can only be null.
+        Some(rhs) if *ptr_depth > 0 => {
+            build_pointer_initval(
+                rhs,
+                &Type::get(var_type.clone()),
+                btype.content,
+                shape,
+                program,
+                my_ir_generator_info,
+            )?;
+            let zero_init = program.new_value().zero_init(Type::get(var_type.clone()));
+            program.new_value().global_alloc(zero_init)
+        }
+        Some(rhs) => match rhs.build(btype.content, shape, program, my_ir_generator_info)? {
+            IRInitValBuildResult::Const(int) => {
+                let int_init = program.new_value().integer(int);
+                program.new_value().global_alloc(int_init)
+            }
+            IRInitValBuildResult::LongLongConst(int) => {
+                let long_long_init = new_long_long(int, true, program, my_ir_generator_info);
+                program.new_value().global_alloc(long_long_init)
+            }
+            IRInitValBuildResult::Var(val) => program.new_value().global_alloc(val),
+            IRInitValBuildResult::Aggregate(val) => program.new_value().global_alloc(val),
+        },
+        None => {
+            let zero_init = program.new_value().zero_init(Type::get(var_type.clone()));
+            program.new_value().global_alloc(zero_init)
+        }
+    };
+    Ok(var_addr)
+}
+
+/// Declares global variables or constants that other files can not see.
+pub fn build_static_decl(
+    decl: &Decl,
+    program: &mut Program,
+    my_ir_generator_info: &mut MyIRGeneratorInfo,
+) -> Result<IRBuildResult, CompileError> {
+    let idents: Vec<&IDENT> = match decl {
+        Decl::ConstDecl(ConstDecl::Default(_, const_defs, _)) => const_defs
+            .iter()
+            .map(|ConstDef::Default(ident, _, _, _)| ident)
+            .collect(),
+        Decl::VarDecl(VarDecl::Default(_, var_defs, _)) => var_defs
+            .iter()
+            .map(|VarDef::Default(_, ident, _, _, _)| ident)
+            .collect(),
+    };
+    for ident in idents {
+        my_ir_generator_info.declare_static(ident)?;
+    }
+    decl.build(program, my_ir_generator_info)
+}
+
+/// A static local variable is a global variable that only its scope can see.
+/// Its initval is built like a global one, so it is initialized only once.
+pub fn build_static_var_decl(
+    var_decl: &VarDecl,
+    program: &mut Program,
+    my_ir_generator_info: &mut MyIRGeneratorInfo,
+) -> Result<IRBuildResult, CompileError> {
+    let VarDecl::Default(btype, var_defs, _) = var_decl;
+    check_not_void(btype)?;
+
+    for var_def in var_defs {
+        let VarDef::Default(ptr_depth, ident, shape_exps, _, _) = var_def;
+        let shape = build_shape(shape_exps, program, my_ir_generator_info)?;
+        let var_type = get_array_type(
+            btype.content,
+            *ptr_depth,
+            &shape,
+            btype.span,
+            my_ir_generator_info,
+        )?;
+        // Build the initval out of the function, where only constants can be used.
+        let curr_func = my_ir_generator_info.curr_func.take();
+        let result = build_global_alloc(
+            var_def,
+            btype,
+            &shape,
+            &var_type,
+            program,
+            my_ir_generator_info,
+        );
+        my_ir_generator_info.curr_func = curr_func;
+        let var_addr = result?;
+        // Static variables in different scopes may have the same name.
+        let name = format!(
+            "@__static_{}_{}",
+            my_ir_generator_info.static_locals.len(),
+            ident.content
+        );
+        my_ir_generator_info.static_locals.insert(var_addr);
+        program.set_value_name(var_addr, Some(name));
+        my_ir_generator_info.declare_symbol(
+            ident,
+            SymbolTableEntry::Variable(btype.content, var_addr),
+            SymbolKind::Variable,
+        )?;
+    }
+    Ok(IRBuildResult::OK)
+}
+
+/// Declare a global variable defined later or in another file.
+pub fn build_extern_var_decl(
+    var_decl: &VarDecl,
+    program: &mut Program,
+    my_ir_generator_info: &mut MyIRGeneratorInfo,
+) -> Result<IRBuildResult, CompileError> {
+    let VarDecl::Default(btype, var_defs, _) = var_decl;
+    check_not_void(btype)?;
+
+    for var_def in var_defs {
+        let VarDef::Default(ptr_depth, ident, shape_exps, possible_rhs, span) = var_def;
+        if possible_rhs.is_some() {
+            return Err(CompileError::new(
+                ErrorCode::InvalidInitializer,
+                "An extern variable can not be initialized! ".to_string(),
+                *span,
+            ));
+        }
+        let shape = build_shape(shape_exps, program, my_ir_generator_info)?;
+        let var_type = Type::get(get_array_type(
+            btype.content,
+            *ptr_depth,
+            &shape,
+            btype.span,
+            my_ir_generator_info,
+        )?);
+
+        // Declaring a global variable again is OK, if the types are the same.
+        if let Some(&(SymbolTableEntry::Variable(old_btype, old_addr), SymbolKind::Variable)) =
+            my_ir_generator_info
+                .symbol_tables
+                .get_global(&ident.content)
+        {
+            if old_btype != btype.content
+                || program.borrow_value(old_addr).ty() != &Type::get_pointer(var_type.clone())
+            {
+                return Err(CompileError::new(
+                    ErrorCode::BackendInternal,
+                    format!("Conflicting types for global variable {}! ", ident.content),
+                    ident.span,
+                ));
+            }
+            continue;
+        }
+        my_ir_generator_info.check_redefinition(ident, SymbolKind::Variable)?;
+
+        // The address has no initial value until the variable is defined.
+        let undef = program.new_value().undef(var_type);
+        let var_addr = program.new_value().global_alloc(undef);
+        let name = format!("@{}", ident.content);
+        program.set_value_name(var_addr, Some(name));
+        my_ir_generator_info.symbol_tables.insert(
+            ident.content.clone(),
+            SymbolTableEntry::Variable(btype.content, var_addr),
+            SymbolKind::Variable,
+        );
+    }
+    Ok(IRBuildResult::OK)
+}
+
+/// Whether a global variable is only declared by extern, i.e. has no initial value yet.
+fn is_extern_variable(var_addr: Value, program: &Program) -> bool {
+    let var_data = program.borrow_value(var_addr);
+    match var_data.kind() {
+        ValueKind::GlobalAlloc(alloc) => {
+            matches!(
+                program.borrow_value(alloc.init()).kind(),
+                ValueKind::Undef(_)
+            )
+        }
+        _ => false,
+    }
+}
+
+/// Replace every use of an extern variable with its definition, then remove it.
+fn replace_global_uses(old_addr: Value, new_addr: Value, program: &mut Program) {
+    let users: Vec<Value> = program
+        .borrow_value(old_addr)
+        .used_by()
+        .iter()
+        .copied()
+        .collect();
+    for func_data in program.funcs_mut().values_mut() {
+        let dfg = func_data.dfg_mut();
+        for &user in &users {
+            if !dfg.values().contains_key(&user) {
+                continue;
+            }
+            let replace = |v: Value| if v == old_addr { new_addr } else { v };
+            match dfg.value(user).kind().clone() {
+                ValueKind::Load(load) => {
+                    dfg.replace_value_with(user).load(replace(load.src()));
+                }
+                ValueKind::Store(store) => {
+                    dfg.replace_value_with(user)
+                        .store(replace(store.value()), replace(store.dest()));
+                }
+                ValueKind::GetPtr(get_ptr) => {
+                    dfg.replace_value_with(user)
+                        .get_ptr(replace(get_ptr.src()), get_ptr.index());
+                }
+                ValueKind::GetElemPtr(get_elem_ptr) => {
+                    dfg.replace_value_with(user)
+                        .get_elem_ptr(replace(get_elem_ptr.src()), get_elem_ptr.index());
+                }
+                ValueKind::Call(call) => {
+                    let args = call.args().iter().map(|&arg| replace(arg)).collect();
+                    dfg.replace_value_with(user).call(call.callee(), args);
+                }
+                _ => unreachable!(),
+            }
+        }
+    }
+    let old_data = program.remove_value(old_addr);
+    if let ValueKind::GlobalAlloc(alloc) = old_data.kind() {
+        program.remove_value(alloc.init());
+    }
+}
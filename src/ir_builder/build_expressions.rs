@@ -1,25 +1,75 @@
 //! Build a single component into Koopa IR.
 
 use crate::ast_def::expressions::*;
+use crate::ast_def::symbols::{BTypeKind, Span, IDENT};
+use crate::diagnostics::{CompileError, ErrorCode};
+use crate::type_checker::SysYType;
 use koopa::ir::{builder_traits::*, Program, Type, TypeKind, Value};
 
 use super::{
-    create_new_block, create_new_local_value, get_valuedata,
-    insert_basic_blocks, insert_local_instructions, MyIRGeneratorInfo, SymbolTableEntry,
+    call_intrinsic, cast_pointer, create_new_block, create_new_local_value, get_cond_value,
+    get_const_long_long, get_float_value, get_int_value, get_long_long_value, get_pointer,
+    get_string_constant, get_struct_type, get_typed_value, get_value_for_addr, get_valuedata,
+    insert_basic_blocks, insert_local_instructions, new_long_long, MyIRGeneratorInfo,
+    SymbolTableEntry,
 };
 
 /// IR expression building result. If the expression is a constant expression, returns the i32 result.
 /// Otherwise, returns the Koopa IR Value.
+/// A float Value is stored as the bits of an i32, or it is a pointer to floats.
+/// A long long Value is an array of 2 i32s, or it is a pointer to long longs.
+/// Arrays are converted into pointers to their first elements.
 pub enum IRExpBuildResult {
     Const(i32),
     Value(Value),
+    FloatConst(f32),
+    FloatValue(Value),
+    LongLongConst(i64),
+    LongLongValue(Value),
+    // A string literal is the address of its first char. Its array holds 4 chars in an i32,
+    // so it can only be passed to library functions.
+    StringConst(Value),
+}
+
+impl IRExpBuildResult {
+    pub fn is_float(&self) -> bool {
+        matches!(
+            self,
+            IRExpBuildResult::FloatConst(_) | IRExpBuildResult::FloatValue(_)
+        )
+    }
+
+    pub fn is_long_long(&self) -> bool {
+        matches!(
+            self,
+            IRExpBuildResult::LongLongConst(_) | IRExpBuildResult::LongLongValue(_)
+        )
+    }
+
+    /// Makes a result of the given type from a Value.
+    pub fn from_value(value: Value, btype: BTypeKind) -> IRExpBuildResult {
+        match btype {
+            BTypeKind::Float => IRExpBuildResult::FloatValue(value),
+            BTypeKind::LongLong => IRExpBuildResult::LongLongValue(value),
+            _ => IRExpBuildResult::Value(value),
+        }
+    }
+
+    /// A float constant is true if it is not 0.0. A long long constant is true if it is not 0.
+    fn float_const_to_cond(self) -> IRExpBuildResult {
+        match self {
+            IRExpBuildResult::FloatConst(float) => IRExpBuildResult::Const((float != 0.0) as i32),
+            IRExpBuildResult::LongLongConst(int) => IRExpBuildResult::Const((int != 0) as i32),
+            result => result,
+        }
+    }
 }
 pub trait IRExpBuildable {
     fn build(
         &self,
         program: &mut Program,
         my_ir_generator_info: &mut MyIRGeneratorInfo,
-    ) -> Result<IRExpBuildResult, String>;
+    ) -> Result<IRExpBuildResult, CompileError>;
 }
 
 impl IRExpBuildable for Exp {
@@ -27,58 +77,479 @@ impl IRExpBuildable for Exp {
         &self,
         program: &mut Program,
         my_ir_generator_info: &mut MyIRGeneratorInfo,
-    ) -> Result<IRExpBuildResult, String> {
+    ) -> Result<IRExpBuildResult, CompileError> {
         match self {
-            Exp::LOrExp(lor_exp) => lor_exp.build(program, my_ir_generator_info),
+            Exp::CondExp(cond_exp, _) => cond_exp.build(program, my_ir_generator_info),
         }
     }
 }
 
-fn build_binary_from_build_results(
+impl IRExpBuildable for CondExp {
+    fn build(
+        &self,
+        program: &mut Program,
+        my_ir_generator_info: &mut MyIRGeneratorInfo,
+    ) -> Result<IRExpBuildResult, CompileError> {
+        match self {
+            CondExp::LOrExp(exp) => exp.build(program, my_ir_generator_info),
+            CondExp::TernaryExp(cond, exp1, exp2, span) => {
+                match cond
+                    .build(program, my_ir_generator_info)?
+                    .float_const_to_cond()
+                {
+                    // If cond is constant, only build the selected expression.
+                    IRExpBuildResult::Const(int) => match int != 0 {
+                        true => exp1.build(program, my_ir_generator_info),
+                        false => exp2.build(program, my_ir_generator_info),
+                    },
+
+                    // If cond is variable.
+                    cond_result => {
+                        let cond_value =
+                            get_cond_value(cond_result, *span, program, my_ir_generator_info)?;
+                        /*
+                           int result;
+                           if (cond) {
+                               result = exp1;
+                           } else {
+                               result = exp2;
+                           }
+                        */
+                        let block1 =
+                            create_new_block(program, my_ir_generator_info, "ternary_block_1");
+                        let block2 =
+                            create_new_block(program, my_ir_generator_info, "ternary_block_2");
+                        let block_end =
+                            create_new_block(program, my_ir_generator_info, "ternary_block_end");
+                        insert_basic_blocks(
+                            program,
+                            my_ir_generator_info,
+                            [block1, block2, block_end],
+                        );
+
+                        // The result is a float if any of them is. Otherwise, it is a long long
+                        // if any of them is. The type checker has found it.
+                        let result_type = match my_ir_generator_info.get_exp_type(*span)? {
+                            SysYType::Basic(btype) => *btype,
+                            ty => {
+                                return Err(CompileError::new(
+                                    ErrorCode::BackendInternal,
+                                    format!("The result of \"?:\" is {}, not a number! ", ty),
+                                    *span,
+                                ))
+                            }
+                        };
+                        let result_ptr = create_new_local_value(program, my_ir_generator_info)
+                            .alloc(Type::get(result_type.to_type_kind()));
+                        program
+                            .func_mut(my_ir_generator_info.curr_func.unwrap())
+                            .dfg_mut()
+                            .set_value_name(result_ptr, Some("@ternary_result".to_string()));
+                        let branch_inst = create_new_local_value(program, my_ir_generator_info)
+                            .branch(cond_value, block1, block2);
+                        insert_local_instructions(
+                            program,
+                            my_ir_generator_info,
+                            [result_ptr, branch_inst],
+                        );
+
+                        // Each block stores its expression into the result, and jumps to the end.
+                        for (block, exp) in [(block1, &**exp1), (block2, &**exp2)] {
+                            my_ir_generator_info.curr_block = Some(block);
+                            let result = exp.build(program, my_ir_generator_info)?;
+                            let value = get_typed_value(
+                                result,
+                                result_type,
+                                exp.span(),
+                                program,
+                                my_ir_generator_info,
+                            )?;
+                            let store_inst = create_new_local_value(program, my_ir_generator_info)
+                                .store(value, result_ptr);
+                            let jmp_inst = create_new_local_value(program, my_ir_generator_info)
+                                .jump(block_end);
+                            insert_local_instructions(
+                                program,
+                                my_ir_generator_info,
+                                [store_inst, jmp_inst],
+                            );
+                        }
+
+                        my_ir_generator_info.curr_block = Some(block_end);
+                        let loaded_result =
+                            create_new_local_value(program, my_ir_generator_info).load(result_ptr);
+                        insert_local_instructions(program, my_ir_generator_info, [loaded_result]);
+                        Ok(IRExpBuildResult::from_value(loaded_result, result_type))
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub fn build_binary_from_build_results(
     result1: IRExpBuildResult,
     result2: IRExpBuildResult,
     program: &mut Program,
     my_ir_generator_info: &mut MyIRGeneratorInfo,
     binary_op: koopa::ir::BinaryOp,
-) -> Result<IRExpBuildResult, String> {
+    span: Span,
+) -> Result<IRExpBuildResult, CompileError> {
+    // Pointers are not ints in Koopa IR. A float pointer is not a float either.
+    if get_pointer(&result1, program, my_ir_generator_info).is_some()
+        || get_pointer(&result2, program, my_ir_generator_info).is_some()
+    {
+        return build_pointer_binary_from_build_results(
+            result1,
+            result2,
+            program,
+            my_ir_generator_info,
+            binary_op,
+            span,
+        );
+    }
+    // If any of them is a float, the other is converted into a float.
+    if result1.is_float() || result2.is_float() {
+        return build_float_binary_from_build_results(
+            result1,
+            result2,
+            program,
+            my_ir_generator_info,
+            binary_op,
+            span,
+        );
+    }
+    // Otherwise, if any of them is a long long, the other is converted into a long long.
+    if result1.is_long_long() || result2.is_long_long() {
+        return build_long_long_binary_from_build_results(
+            result1,
+            result2,
+            program,
+            my_ir_generator_info,
+            binary_op,
+            span,
+        );
+    }
     // If both expressions are constant expressions, then the result should be a constant expression.
+    // Overflows wrap around, like in the backend, e.g. INT_MIN / -1 is INT_MIN.
+    // A division by 0 is reported by the type checker, and left to the runtime here.
     if let (IRExpBuildResult::Const(int1), IRExpBuildResult::Const(int2)) = (&result1, &result2) {
-        Ok(IRExpBuildResult::Const(match binary_op {
-            koopa::ir::BinaryOp::NotEq => (int1 != int2) as i32,
-            koopa::ir::BinaryOp::Eq => (int1 == int2) as i32,
-            koopa::ir::BinaryOp::Gt => (int1 > int2) as i32,
-            koopa::ir::BinaryOp::Lt => (int1 < int2) as i32,
-            koopa::ir::BinaryOp::Ge => (int1 >= int2) as i32,
-            koopa::ir::BinaryOp::Le => (int1 <= int2) as i32,
-            koopa::ir::BinaryOp::Add => int1 + int2,
-            koopa::ir::BinaryOp::Sub => int1 - int2,
-            koopa::ir::BinaryOp::Mul => int1 * int2,
-            koopa::ir::BinaryOp::Div => int1 / int2,
-            koopa::ir::BinaryOp::Mod => int1 % int2,
-            koopa::ir::BinaryOp::And => int1 & int2,
-            koopa::ir::BinaryOp::Or => int1 | int2,
-            koopa::ir::BinaryOp::Xor => int1 ^ int2,
-            koopa::ir::BinaryOp::Shl => todo!(),
-            koopa::ir::BinaryOp::Shr => todo!(),
-            koopa::ir::BinaryOp::Sar => todo!(),
-        }))
-    } else {
-        let value1 = match result1 {
-            IRExpBuildResult::Const(int) => {
-                create_new_local_value(program, my_ir_generator_info).integer(int)
-            }
-            IRExpBuildResult::Value(value) => value,
+        let (int1, int2) = (*int1, *int2);
+        let folded = match binary_op {
+            koopa::ir::BinaryOp::NotEq => Some((int1 != int2) as i32),
+            koopa::ir::BinaryOp::Eq => Some((int1 == int2) as i32),
+            koopa::ir::BinaryOp::Gt => Some((int1 > int2) as i32),
+            koopa::ir::BinaryOp::Lt => Some((int1 < int2) as i32),
+            koopa::ir::BinaryOp::Ge => Some((int1 >= int2) as i32),
+            koopa::ir::BinaryOp::Le => Some((int1 <= int2) as i32),
+            koopa::ir::BinaryOp::Add => Some(int1.wrapping_add(int2)),
+            koopa::ir::BinaryOp::Sub => Some(int1.wrapping_sub(int2)),
+            koopa::ir::BinaryOp::Mul => Some(int1.wrapping_mul(int2)),
+            koopa::ir::BinaryOp::Div if int2 != 0 => Some(int1.wrapping_div(int2)),
+            koopa::ir::BinaryOp::Mod if int2 != 0 => Some(int1.wrapping_rem(int2)),
+            koopa::ir::BinaryOp::Div | koopa::ir::BinaryOp::Mod => None,
+            koopa::ir::BinaryOp::And => Some(int1 & int2),
+            koopa::ir::BinaryOp::Or => Some(int1 | int2),
+            koopa::ir::BinaryOp::Xor => Some(int1 ^ int2),
+            // Like sll, srl and sra in RISC-V, only the low 5 bits of the shift amount are used.
+            koopa::ir::BinaryOp::Shl => Some(int1.wrapping_shl(int2 as u32)),
+            koopa::ir::BinaryOp::Shr => Some((int1 as u32).wrapping_shr(int2 as u32) as i32),
+            koopa::ir::BinaryOp::Sar => Some(int1.wrapping_shr(int2 as u32)),
         };
-        let value2 = match result2 {
-            IRExpBuildResult::Const(int) => {
-                create_new_local_value(program, my_ir_generator_info).integer(int)
+        if let Some(int) = folded {
+            return Ok(IRExpBuildResult::Const(int));
+        }
+    }
+    let value1 = get_int_value(result1, span, program, my_ir_generator_info)?;
+    let value2 = get_int_value(result2, span, program, my_ir_generator_info)?;
+    let new_value =
+        create_new_local_value(program, my_ir_generator_info).binary(binary_op, value1, value2);
+    insert_local_instructions(program, my_ir_generator_info, [new_value]);
+    Ok(IRExpBuildResult::Value(new_value))
+}
+
+fn build_float_binary_from_build_results(
+    result1: IRExpBuildResult,
+    result2: IRExpBuildResult,
+    program: &mut Program,
+    my_ir_generator_info: &mut MyIRGeneratorInfo,
+    binary_op: koopa::ir::BinaryOp,
+    span: Span,
+) -> Result<IRExpBuildResult, CompileError> {
+    let intrinsic = match binary_op {
+        koopa::ir::BinaryOp::Add => "__float_add",
+        koopa::ir::BinaryOp::Sub => "__float_sub",
+        koopa::ir::BinaryOp::Mul => "__float_mul",
+        koopa::ir::BinaryOp::Div => "__float_div",
+        koopa::ir::BinaryOp::Eq => "__float_eq",
+        koopa::ir::BinaryOp::NotEq => "__float_ne",
+        koopa::ir::BinaryOp::Lt => "__float_lt",
+        koopa::ir::BinaryOp::Gt => "__float_gt",
+        koopa::ir::BinaryOp::Le => "__float_le",
+        koopa::ir::BinaryOp::Ge => "__float_ge",
+        _ => {
+            return Err(CompileError::new(
+                ErrorCode::BackendInternal,
+                format!("Operator {} can not be used on floats! ", binary_op),
+                span,
+            ))
+        }
+    };
+    let to_float = |result: &IRExpBuildResult| match *result {
+        IRExpBuildResult::Const(int) => Some(int as f32),
+        IRExpBuildResult::FloatConst(float) => Some(float),
+        _ => None,
+    };
+    // If both expressions are constant expressions, then the result should be a constant expression.
+    if let (Some(float1), Some(float2)) = (to_float(&result1), to_float(&result2)) {
+        return Ok(match binary_op {
+            koopa::ir::BinaryOp::Add => IRExpBuildResult::FloatConst(float1 + float2),
+            koopa::ir::BinaryOp::Sub => IRExpBuildResult::FloatConst(float1 - float2),
+            koopa::ir::BinaryOp::Mul => IRExpBuildResult::FloatConst(float1 * float2),
+            koopa::ir::BinaryOp::Div => IRExpBuildResult::FloatConst(float1 / float2),
+            koopa::ir::BinaryOp::Eq => IRExpBuildResult::Const((float1 == float2) as i32),
+            koopa::ir::BinaryOp::NotEq => IRExpBuildResult::Const((float1 != float2) as i32),
+            koopa::ir::BinaryOp::Lt => IRExpBuildResult::Const((float1 < float2) as i32),
+            koopa::ir::BinaryOp::Gt => IRExpBuildResult::Const((float1 > float2) as i32),
+            koopa::ir::BinaryOp::Le => IRExpBuildResult::Const((float1 <= float2) as i32),
+            _ => IRExpBuildResult::Const((float1 >= float2) as i32),
+        });
+    }
+    let value1 = get_float_value(result1, span, program, my_ir_generator_info)?;
+    let value2 = get_float_value(result2, span, program, my_ir_generator_info)?;
+    let new_value = call_intrinsic(
+        intrinsic,
+        vec![value1, value2],
+        program,
+        my_ir_generator_info,
+    )?;
+    // Comparisons return ints.
+    match binary_op {
+        koopa::ir::BinaryOp::Add
+        | koopa::ir::BinaryOp::Sub
+        | koopa::ir::BinaryOp::Mul
+        | koopa::ir::BinaryOp::Div => Ok(IRExpBuildResult::FloatValue(new_value)),
+        _ => Ok(IRExpBuildResult::Value(new_value)),
+    }
+}
+
+fn build_long_long_binary_from_build_results(
+    result1: IRExpBuildResult,
+    result2: IRExpBuildResult,
+    program: &mut Program,
+    my_ir_generator_info: &mut MyIRGeneratorInfo,
+    binary_op: koopa::ir::BinaryOp,
+    span: Span,
+) -> Result<IRExpBuildResult, CompileError> {
+    let is_shift = matches!(
+        binary_op,
+        koopa::ir::BinaryOp::Shl | koopa::ir::BinaryOp::Shr | koopa::ir::BinaryOp::Sar
+    );
+    // The shift amount is an int, and the result has the type of the left operand.
+    let result2 = match (is_shift, result2) {
+        (true, IRExpBuildResult::LongLongConst(int)) => IRExpBuildResult::Const(int as i32),
+        (true, IRExpBuildResult::LongLongValue(value)) => IRExpBuildResult::Value(get_int_value(
+            IRExpBuildResult::LongLongValue(value),
+            span,
+            program,
+            my_ir_generator_info,
+        )?),
+        (_, result) => result,
+    };
+    if is_shift && !result1.is_long_long() {
+        return build_binary_from_build_results(
+            result1,
+            result2,
+            program,
+            my_ir_generator_info,
+            binary_op,
+            span,
+        );
+    }
+    let intrinsic = match binary_op {
+        koopa::ir::BinaryOp::Add => "__long_long_add",
+        koopa::ir::BinaryOp::Sub => "__long_long_sub",
+        koopa::ir::BinaryOp::Mul => "__long_long_mul",
+        koopa::ir::BinaryOp::Div => "__long_long_div",
+        koopa::ir::BinaryOp::Mod => "__long_long_mod",
+        koopa::ir::BinaryOp::And => "__long_long_and",
+        koopa::ir::BinaryOp::Or => "__long_long_or",
+        koopa::ir::BinaryOp::Xor => "__long_long_xor",
+        koopa::ir::BinaryOp::Shl => "__long_long_shl",
+        koopa::ir::BinaryOp::Shr => "__long_long_shr",
+        koopa::ir::BinaryOp::Sar => "__long_long_sar",
+        koopa::ir::BinaryOp::Eq => "__long_long_eq",
+        koopa::ir::BinaryOp::NotEq => "__long_long_ne",
+        koopa::ir::BinaryOp::Lt => "__long_long_lt",
+        koopa::ir::BinaryOp::Gt => "__long_long_gt",
+        koopa::ir::BinaryOp::Le => "__long_long_le",
+        koopa::ir::BinaryOp::Ge => "__long_long_ge",
+    };
+    // If both expressions are constant expressions, then the result should be a constant expression.
+    // Overflows wrap around, like in the backend. A division by 0 is left to the runtime.
+    if let (Some(int1), Some(int2)) = (get_const_long_long(&result1), get_const_long_long(&result2))
+    {
+        let folded = match binary_op {
+            koopa::ir::BinaryOp::Eq => return Ok(IRExpBuildResult::Const((int1 == int2) as i32)),
+            koopa::ir::BinaryOp::NotEq => {
+                return Ok(IRExpBuildResult::Const((int1 != int2) as i32))
             }
-            IRExpBuildResult::Value(value) => value,
+            koopa::ir::BinaryOp::Lt => return Ok(IRExpBuildResult::Const((int1 < int2) as i32)),
+            koopa::ir::BinaryOp::Gt => return Ok(IRExpBuildResult::Const((int1 > int2) as i32)),
+            koopa::ir::BinaryOp::Le => return Ok(IRExpBuildResult::Const((int1 <= int2) as i32)),
+            koopa::ir::BinaryOp::Ge => return Ok(IRExpBuildResult::Const((int1 >= int2) as i32)),
+            koopa::ir::BinaryOp::Add => Some(int1.wrapping_add(int2)),
+            koopa::ir::BinaryOp::Sub => Some(int1.wrapping_sub(int2)),
+            koopa::ir::BinaryOp::Mul => Some(int1.wrapping_mul(int2)),
+            koopa::ir::BinaryOp::Div if int2 != 0 => Some(int1.wrapping_div(int2)),
+            koopa::ir::BinaryOp::Mod if int2 != 0 => Some(int1.wrapping_rem(int2)),
+            koopa::ir::BinaryOp::Div | koopa::ir::BinaryOp::Mod => None,
+            koopa::ir::BinaryOp::And => Some(int1 & int2),
+            koopa::ir::BinaryOp::Or => Some(int1 | int2),
+            koopa::ir::BinaryOp::Xor => Some(int1 ^ int2),
+            // Like sll, srl and sra in RV64, only the low 6 bits of the shift amount are used.
+            koopa::ir::BinaryOp::Shl => Some(int1.wrapping_shl(int2 as u32)),
+            koopa::ir::BinaryOp::Shr => Some((int1 as u64).wrapping_shr(int2 as u32) as i64),
+            koopa::ir::BinaryOp::Sar => Some(int1.wrapping_shr(int2 as u32)),
         };
-        let new_value =
-            create_new_local_value(program, my_ir_generator_info).binary(binary_op, value1, value2);
-        insert_local_instructions(program, my_ir_generator_info, [new_value]);
-        Ok(IRExpBuildResult::Value(new_value))
+        if let Some(int) = folded {
+            return Ok(IRExpBuildResult::LongLongConst(int));
+        }
+    }
+    let value1 = get_long_long_value(result1, span, program, my_ir_generator_info)?;
+    let value2 = match is_shift {
+        true => get_int_value(result2, span, program, my_ir_generator_info)?,
+        false => get_long_long_value(result2, span, program, my_ir_generator_info)?,
+    };
+    let new_value = call_intrinsic(
+        intrinsic,
+        vec![value1, value2],
+        program,
+        my_ir_generator_info,
+    )?;
+    // Comparisons return ints.
+    match binary_op {
+        koopa::ir::BinaryOp::Eq
+        | koopa::ir::BinaryOp::NotEq
+        | koopa::ir::BinaryOp::Lt
+        | koopa::ir::BinaryOp::Gt
+        | koopa::ir::BinaryOp::Le
+        | koopa::ir::BinaryOp::Ge => Ok(IRExpBuildResult::Value(new_value)),
+        _ => Ok(IRExpBuildResult::LongLongValue(new_value)),
+    }
+}
+
+/// p + n and p - n move p by n elements, and p - q is the number of elements between them.
+/// Comparisons compare the addresses. A pointer can also be compared with 0, the null pointer.
+fn build_pointer_binary_from_build_results(
+    result1: IRExpBuildResult,
+    result2: IRExpBuildResult,
+    program: &mut Program,
+    my_ir_generator_info: &mut MyIRGeneratorInfo,
+    binary_op: koopa::ir::BinaryOp,
+    span: Span,
+) -> Result<IRExpBuildResult, CompileError> {
+    let pointer1 = get_pointer(&result1, program, my_ir_generator_info);
+    let pointer2 = get_pointer(&result2, program, my_ir_generator_info);
+    match (binary_op, pointer1, pointer2) {
+        (koopa::ir::BinaryOp::Add | koopa::ir::BinaryOp::Sub, Some(pointer), None)
+        | (koopa::ir::BinaryOp::Add, None, Some(pointer)) => {
+            let (pointer_result, int_result) = match pointer1 {
+                Some(_) => (result1, result2),
+                None => (result2, result1),
+            };
+            let mut index = get_int_value(int_result, span, program, my_ir_generator_info)?;
+            if binary_op == koopa::ir::BinaryOp::Sub {
+                let zero = create_new_local_value(program, my_ir_generator_info).integer(0);
+                index = create_new_local_value(program, my_ir_generator_info).binary(
+                    koopa::ir::BinaryOp::Sub,
+                    zero,
+                    index,
+                );
+                insert_local_instructions(program, my_ir_generator_info, [index]);
+            }
+            let new_pointer =
+                create_new_local_value(program, my_ir_generator_info).get_ptr(pointer, index);
+            insert_local_instructions(program, my_ir_generator_info, [new_pointer]);
+            match pointer_result {
+                IRExpBuildResult::FloatValue(_) => Ok(IRExpBuildResult::FloatValue(new_pointer)),
+                IRExpBuildResult::LongLongValue(_) => {
+                    Ok(IRExpBuildResult::LongLongValue(new_pointer))
+                }
+                _ => Ok(IRExpBuildResult::Value(new_pointer)),
+            }
+        }
+        (koopa::ir::BinaryOp::Sub, Some(pointer1), Some(pointer2)) => {
+            let TypeKind::Pointer(elem_type) =
+                get_valuedata(pointer1, program, my_ir_generator_info)
+                    .ty()
+                    .kind()
+                    .clone()
+            else {
+                unreachable!()
+            };
+            let address1 = call_intrinsic(
+                "__ptr_to_int",
+                vec![pointer1],
+                program,
+                my_ir_generator_info,
+            )?;
+            let address2 = call_intrinsic(
+                "__ptr_to_int",
+                vec![pointer2],
+                program,
+                my_ir_generator_info,
+            )?;
+            let distance = create_new_local_value(program, my_ir_generator_info).binary(
+                koopa::ir::BinaryOp::Sub,
+                address1,
+                address2,
+            );
+            let elem_size = create_new_local_value(program, my_ir_generator_info)
+                .integer(elem_type.size() as i32);
+            let elem_cnt = create_new_local_value(program, my_ir_generator_info).binary(
+                koopa::ir::BinaryOp::Div,
+                distance,
+                elem_size,
+            );
+            insert_local_instructions(program, my_ir_generator_info, [distance, elem_cnt]);
+            Ok(IRExpBuildResult::Value(elem_cnt))
+        }
+        (
+            koopa::ir::BinaryOp::Eq
+            | koopa::ir::BinaryOp::NotEq
+            | koopa::ir::BinaryOp::Lt
+            | koopa::ir::BinaryOp::Gt
+            | koopa::ir::BinaryOp::Le
+            | koopa::ir::BinaryOp::Ge,
+            _,
+            _,
+        ) => {
+            let mut addresses = vec![];
+            for (pointer, result) in [(pointer1, result1), (pointer2, result2)] {
+                addresses.push(match pointer {
+                    Some(pointer) => call_intrinsic(
+                        "__ptr_to_int",
+                        vec![pointer],
+                        program,
+                        my_ir_generator_info,
+                    )?,
+                    None => get_int_value(result, span, program, my_ir_generator_info)?,
+                });
+            }
+            let new_value = create_new_local_value(program, my_ir_generator_info).binary(
+                binary_op,
+                addresses[0],
+                addresses[1],
+            );
+            insert_local_instructions(program, my_ir_generator_info, [new_value]);
+            Ok(IRExpBuildResult::Value(new_value))
+        }
+        _ => Err(CompileError::new(
+            ErrorCode::BackendInternal,
+            format!("Operator {} can not be used on pointers! ", binary_op),
+            span,
+        )),
     }
 }
 
@@ -87,16 +558,40 @@ impl IRExpBuildable for LOrExp {
         &self,
         program: &mut Program,
         my_ir_generator_info: &mut MyIRGeneratorInfo,
-    ) -> Result<IRExpBuildResult, String> {
+    ) -> Result<IRExpBuildResult, CompileError> {
         match self {
             LOrExp::LAndExp(exp) => exp.build(program, my_ir_generator_info),
-            LOrExp::BinaryLOrExp(exp1, exp2) => {
+            LOrExp::BinaryLOrExp(exp1, exp2, span) => {
                 // Build exp1.
-                let exp1_build_result = exp1.build(program, my_ir_generator_info)?;
+                let exp1_build_result = exp1
+                    .build(program, my_ir_generator_info)?
+                    .float_const_to_cond();
 
                 match exp1_build_result {
+                    // If exp1 is constant.
+                    IRExpBuildResult::Const(i1) => {
+                        if i1 != 0 {
+                            Ok(IRExpBuildResult::Const(1))
+                        } else {
+                            build_binary_from_build_results(
+                                IRExpBuildResult::Const(0),
+                                exp2.build(program, my_ir_generator_info)?,
+                                program,
+                                my_ir_generator_info,
+                                koopa::ir::BinaryOp::NotEq,
+                                *span,
+                            )
+                        }
+                    }
+
                     // If exp1 is variable.
-                    IRExpBuildResult::Value(value1) => {
+                    _ => {
+                        let value1 = get_cond_value(
+                            exp1_build_result,
+                            *span,
+                            program,
+                            my_ir_generator_info,
+                        )?;
                         // Prepare for shortcut.
                         /*
                            int result = 1;
@@ -115,7 +610,7 @@ impl IRExpBuildable for LOrExp {
                         program
                             .func_mut(my_ir_generator_info.curr_func.unwrap())
                             .dfg_mut()
-                            .set_value_name(result_ptr, Some(format!("@LOr_result")));
+                            .set_value_name(result_ptr, Some("@LOr_result".to_string()));
                         let one = create_new_local_value(program, my_ir_generator_info).integer(1);
                         let zero = create_new_local_value(program, my_ir_generator_info).integer(0);
                         let store_inst = create_new_local_value(program, my_ir_generator_info)
@@ -141,13 +636,9 @@ impl IRExpBuildable for LOrExp {
                             program,
                             my_ir_generator_info,
                             koopa::ir::BinaryOp::NotEq,
+                            *span,
                         )?;
-                        let value2 = match result2 {
-                            IRExpBuildResult::Const(i2) => {
-                                create_new_local_value(program, my_ir_generator_info).integer(i2)
-                            }
-                            IRExpBuildResult::Value(v2) => v2,
-                        };
+                        let value2 = get_int_value(result2, *span, program, my_ir_generator_info)?;
                         let store_new_inst = create_new_local_value(program, my_ir_generator_info)
                             .store(value2, result_ptr);
                         let jmp_inst =
@@ -164,21 +655,6 @@ impl IRExpBuildable for LOrExp {
                         insert_local_instructions(program, my_ir_generator_info, [loaded_result]);
                         Ok(IRExpBuildResult::Value(loaded_result))
                     }
-
-                    // If exp1 is constant.
-                    IRExpBuildResult::Const(i1) => {
-                        if i1 != 0 {
-                            Ok(IRExpBuildResult::Const(1))
-                        } else {
-                            build_binary_from_build_results(
-                                IRExpBuildResult::Const(0),
-                                exp2.build(program, my_ir_generator_info)?,
-                                program,
-                                my_ir_generator_info,
-                                koopa::ir::BinaryOp::NotEq,
-                            )
-                        }
-                    }
                 }
             }
         }
@@ -190,16 +666,40 @@ impl IRExpBuildable for LAndExp {
         &self,
         program: &mut Program,
         my_ir_generator_info: &mut MyIRGeneratorInfo,
-    ) -> Result<IRExpBuildResult, String> {
+    ) -> Result<IRExpBuildResult, CompileError> {
         match self {
-            LAndExp::EqExp(exp) => exp.build(program, my_ir_generator_info),
-            LAndExp::BinaryLAndExp(exp1, exp2) => {
+            LAndExp::BitOrExp(exp) => exp.build(program, my_ir_generator_info),
+            LAndExp::BinaryLAndExp(exp1, exp2, span) => {
                 // Build exp1.
-                let exp1_build_result = exp1.build(program, my_ir_generator_info)?;
+                let exp1_build_result = exp1
+                    .build(program, my_ir_generator_info)?
+                    .float_const_to_cond();
 
                 match exp1_build_result {
+                    // If exp1 is constant.
+                    IRExpBuildResult::Const(i1) => {
+                        if i1 == 0 {
+                            Ok(IRExpBuildResult::Const(0))
+                        } else {
+                            build_binary_from_build_results(
+                                IRExpBuildResult::Const(0),
+                                exp2.build(program, my_ir_generator_info)?,
+                                program,
+                                my_ir_generator_info,
+                                koopa::ir::BinaryOp::NotEq,
+                                *span,
+                            )
+                        }
+                    }
+
                     // If exp1 is variable.
-                    IRExpBuildResult::Value(value1) => {
+                    _ => {
+                        let value1 = get_cond_value(
+                            exp1_build_result,
+                            *span,
+                            program,
+                            my_ir_generator_info,
+                        )?;
                         // Prepare for shortcut.
                         /*
                            int result = 0;
@@ -218,7 +718,7 @@ impl IRExpBuildable for LAndExp {
                         program
                             .func_mut(my_ir_generator_info.curr_func.unwrap())
                             .dfg_mut()
-                            .set_value_name(result_ptr, Some(format!("@LAnd_result")));
+                            .set_value_name(result_ptr, Some("@LAnd_result".to_string()));
                         let zero = create_new_local_value(program, my_ir_generator_info).integer(0);
                         let store_inst = create_new_local_value(program, my_ir_generator_info)
                             .store(zero, result_ptr);
@@ -243,13 +743,9 @@ impl IRExpBuildable for LAndExp {
                             program,
                             my_ir_generator_info,
                             koopa::ir::BinaryOp::NotEq,
+                            *span,
                         )?;
-                        let value2 = match result2 {
-                            IRExpBuildResult::Const(i2) => {
-                                create_new_local_value(program, my_ir_generator_info).integer(i2)
-                            }
-                            IRExpBuildResult::Value(v2) => v2,
-                        };
+                        let value2 = get_int_value(result2, *span, program, my_ir_generator_info)?;
                         let store_new_inst = create_new_local_value(program, my_ir_generator_info)
                             .store(value2, result_ptr);
                         let jmp_inst =
@@ -266,48 +762,95 @@ impl IRExpBuildable for LAndExp {
                         insert_local_instructions(program, my_ir_generator_info, [loaded_result]);
                         Ok(IRExpBuildResult::Value(loaded_result))
                     }
-
-                    // If exp1 is constant.
-                    IRExpBuildResult::Const(i1) => {
-                        if i1 == 0 {
-                            Ok(IRExpBuildResult::Const(0))
-                        } else {
-                            build_binary_from_build_results(
-                                IRExpBuildResult::Const(0),
-                                exp2.build(program, my_ir_generator_info)?,
-                                program,
-                                my_ir_generator_info,
-                                koopa::ir::BinaryOp::NotEq,
-                            )
-                        }
-                    }
                 }
             }
         }
     }
 }
 
+impl IRExpBuildable for BitOrExp {
+    fn build(
+        &self,
+        program: &mut Program,
+        my_ir_generator_info: &mut MyIRGeneratorInfo,
+    ) -> Result<IRExpBuildResult, CompileError> {
+        match self {
+            BitOrExp::BitXorExp(exp) => exp.build(program, my_ir_generator_info),
+            BitOrExp::BinaryBitOrExp(exp1, exp2, span) => build_binary_from_build_results(
+                exp1.build(program, my_ir_generator_info)?,
+                exp2.build(program, my_ir_generator_info)?,
+                program,
+                my_ir_generator_info,
+                koopa::ir::BinaryOp::Or,
+                *span,
+            ),
+        }
+    }
+}
+
+impl IRExpBuildable for BitXorExp {
+    fn build(
+        &self,
+        program: &mut Program,
+        my_ir_generator_info: &mut MyIRGeneratorInfo,
+    ) -> Result<IRExpBuildResult, CompileError> {
+        match self {
+            BitXorExp::BitAndExp(exp) => exp.build(program, my_ir_generator_info),
+            BitXorExp::BinaryBitXorExp(exp1, exp2, span) => build_binary_from_build_results(
+                exp1.build(program, my_ir_generator_info)?,
+                exp2.build(program, my_ir_generator_info)?,
+                program,
+                my_ir_generator_info,
+                koopa::ir::BinaryOp::Xor,
+                *span,
+            ),
+        }
+    }
+}
+
+impl IRExpBuildable for BitAndExp {
+    fn build(
+        &self,
+        program: &mut Program,
+        my_ir_generator_info: &mut MyIRGeneratorInfo,
+    ) -> Result<IRExpBuildResult, CompileError> {
+        match self {
+            BitAndExp::EqExp(exp) => exp.build(program, my_ir_generator_info),
+            BitAndExp::BinaryBitAndExp(exp1, exp2, span) => build_binary_from_build_results(
+                exp1.build(program, my_ir_generator_info)?,
+                exp2.build(program, my_ir_generator_info)?,
+                program,
+                my_ir_generator_info,
+                koopa::ir::BinaryOp::And,
+                *span,
+            ),
+        }
+    }
+}
+
 impl IRExpBuildable for EqExp {
     fn build(
         &self,
         program: &mut Program,
         my_ir_generator_info: &mut MyIRGeneratorInfo,
-    ) -> Result<IRExpBuildResult, String> {
+    ) -> Result<IRExpBuildResult, CompileError> {
         match self {
             EqExp::RelExp(exp) => exp.build(program, my_ir_generator_info),
-            EqExp::BinaryEqExp(exp1, exp2) => build_binary_from_build_results(
+            EqExp::BinaryEqExp(exp1, exp2, span) => build_binary_from_build_results(
                 exp1.build(program, my_ir_generator_info)?,
                 exp2.build(program, my_ir_generator_info)?,
                 program,
                 my_ir_generator_info,
                 koopa::ir::BinaryOp::Eq,
+                *span,
             ),
-            EqExp::BinaryUneqExp(exp1, exp2) => build_binary_from_build_results(
+            EqExp::BinaryUneqExp(exp1, exp2, span) => build_binary_from_build_results(
                 exp1.build(program, my_ir_generator_info)?,
                 exp2.build(program, my_ir_generator_info)?,
                 program,
                 my_ir_generator_info,
                 koopa::ir::BinaryOp::NotEq,
+                *span,
             ),
         }
     }
@@ -318,36 +861,68 @@ impl IRExpBuildable for RelExp {
         &self,
         program: &mut Program,
         my_ir_generator_info: &mut MyIRGeneratorInfo,
-    ) -> Result<IRExpBuildResult, String> {
+    ) -> Result<IRExpBuildResult, CompileError> {
         match self {
-            RelExp::AddExp(exp) => exp.build(program, my_ir_generator_info),
-            RelExp::BinaryLtExp(exp1, exp2) => build_binary_from_build_results(
+            RelExp::ShiftExp(exp) => exp.build(program, my_ir_generator_info),
+            RelExp::BinaryLtExp(exp1, exp2, span) => build_binary_from_build_results(
                 exp1.build(program, my_ir_generator_info)?,
                 exp2.build(program, my_ir_generator_info)?,
                 program,
                 my_ir_generator_info,
                 koopa::ir::BinaryOp::Lt,
+                *span,
             ),
-            RelExp::BinaryGtExp(exp1, exp2) => build_binary_from_build_results(
+            RelExp::BinaryGtExp(exp1, exp2, span) => build_binary_from_build_results(
                 exp1.build(program, my_ir_generator_info)?,
                 exp2.build(program, my_ir_generator_info)?,
                 program,
                 my_ir_generator_info,
                 koopa::ir::BinaryOp::Gt,
+                *span,
             ),
-            RelExp::BinaryLeExp(exp1, exp2) => build_binary_from_build_results(
+            RelExp::BinaryLeExp(exp1, exp2, span) => build_binary_from_build_results(
                 exp1.build(program, my_ir_generator_info)?,
                 exp2.build(program, my_ir_generator_info)?,
                 program,
                 my_ir_generator_info,
                 koopa::ir::BinaryOp::Le,
+                *span,
             ),
-            RelExp::BinaryGeExp(exp1, exp2) => build_binary_from_build_results(
+            RelExp::BinaryGeExp(exp1, exp2, span) => build_binary_from_build_results(
                 exp1.build(program, my_ir_generator_info)?,
                 exp2.build(program, my_ir_generator_info)?,
                 program,
                 my_ir_generator_info,
                 koopa::ir::BinaryOp::Ge,
+                *span,
+            ),
+        }
+    }
+}
+
+impl IRExpBuildable for ShiftExp {
+    fn build(
+        &self,
+        program: &mut Program,
+        my_ir_generator_info: &mut MyIRGeneratorInfo,
+    ) -> Result<IRExpBuildResult, CompileError> {
+        match self {
+            ShiftExp::AddExp(exp) => exp.build(program, my_ir_generator_info),
+            ShiftExp::BinaryShlExp(exp1, exp2, span) => build_binary_from_build_results(
+                exp1.build(program, my_ir_generator_info)?,
+                exp2.build(program, my_ir_generator_info)?,
+                program,
+                my_ir_generator_info,
+                koopa::ir::BinaryOp::Shl,
+                *span,
+            ),
+            ShiftExp::BinaryShrExp(exp1, exp2, span) => build_binary_from_build_results(
+                exp1.build(program, my_ir_generator_info)?,
+                exp2.build(program, my_ir_generator_info)?,
+                program,
+                my_ir_generator_info,
+                koopa::ir::BinaryOp::Sar,
+                *span,
             ),
         }
     }
@@ -358,22 +933,24 @@ impl IRExpBuildable for AddExp {
         &self,
         program: &mut Program,
         my_ir_generator_info: &mut MyIRGeneratorInfo,
-    ) -> Result<IRExpBuildResult, String> {
+    ) -> Result<IRExpBuildResult, CompileError> {
         match self {
             AddExp::MulExp(exp) => exp.build(program, my_ir_generator_info),
-            AddExp::BinaryAddExp(exp1, exp2) => build_binary_from_build_results(
+            AddExp::BinaryAddExp(exp1, exp2, span) => build_binary_from_build_results(
                 exp1.build(program, my_ir_generator_info)?,
                 exp2.build(program, my_ir_generator_info)?,
                 program,
                 my_ir_generator_info,
                 koopa::ir::BinaryOp::Add,
+                *span,
             ),
-            AddExp::BinarySubExp(exp1, exp2) => build_binary_from_build_results(
+            AddExp::BinarySubExp(exp1, exp2, span) => build_binary_from_build_results(
                 exp1.build(program, my_ir_generator_info)?,
                 exp2.build(program, my_ir_generator_info)?,
                 program,
                 my_ir_generator_info,
                 koopa::ir::BinaryOp::Sub,
+                *span,
             ),
         }
     }
@@ -384,29 +961,32 @@ impl IRExpBuildable for MulExp {
         &self,
         program: &mut Program,
         my_ir_generator_info: &mut MyIRGeneratorInfo,
-    ) -> Result<IRExpBuildResult, String> {
+    ) -> Result<IRExpBuildResult, CompileError> {
         match self {
             MulExp::UnaryExp(exp) => exp.build(program, my_ir_generator_info),
-            MulExp::BinaryMulExp(exp1, exp2) => build_binary_from_build_results(
+            MulExp::BinaryMulExp(exp1, exp2, span) => build_binary_from_build_results(
                 exp1.build(program, my_ir_generator_info)?,
                 exp2.build(program, my_ir_generator_info)?,
                 program,
                 my_ir_generator_info,
                 koopa::ir::BinaryOp::Mul,
+                *span,
             ),
-            MulExp::BinaryDivExp(exp1, exp2) => build_binary_from_build_results(
+            MulExp::BinaryDivExp(exp1, exp2, span) => build_binary_from_build_results(
                 exp1.build(program, my_ir_generator_info)?,
                 exp2.build(program, my_ir_generator_info)?,
                 program,
                 my_ir_generator_info,
                 koopa::ir::BinaryOp::Div,
+                *span,
             ),
-            MulExp::BinaryModExp(exp1, exp2) => build_binary_from_build_results(
+            MulExp::BinaryModExp(exp1, exp2, span) => build_binary_from_build_results(
                 exp1.build(program, my_ir_generator_info)?,
                 exp2.build(program, my_ir_generator_info)?,
                 program,
                 my_ir_generator_info,
                 koopa::ir::BinaryOp::Mod,
+                *span,
             ),
         }
     }
@@ -417,69 +997,252 @@ impl IRExpBuildable for UnaryExp {
         &self,
         program: &mut Program,
         my_ir_generator_info: &mut MyIRGeneratorInfo,
-    ) -> Result<IRExpBuildResult, String> {
+    ) -> Result<IRExpBuildResult, CompileError> {
         match self {
             UnaryExp::PrimaryExp(exp) => exp.build(program, my_ir_generator_info),
-            UnaryExp::PlusUnaryExp(exp) => exp.build(program, my_ir_generator_info),
-            UnaryExp::MinusUnaryExp(exp) => build_binary_from_build_results(
+            UnaryExp::PlusUnaryExp(exp, _) => exp.build(program, my_ir_generator_info),
+            UnaryExp::MinusUnaryExp(exp, span) => {
+                match exp.build(program, my_ir_generator_info)? {
+                    // -x is not 0.0 - x for floats, because -0.0 is not 0.0. Flip the sign bit instead.
+                    IRExpBuildResult::FloatConst(float) => Ok(IRExpBuildResult::FloatConst(-float)),
+                    IRExpBuildResult::FloatValue(value) => {
+                        let value = get_float_value(
+                            IRExpBuildResult::FloatValue(value),
+                            *span,
+                            program,
+                            my_ir_generator_info,
+                        )?;
+                        let sign_bit =
+                            create_new_local_value(program, my_ir_generator_info).integer(i32::MIN);
+                        let new_value = create_new_local_value(program, my_ir_generator_info)
+                            .binary(koopa::ir::BinaryOp::Xor, value, sign_bit);
+                        insert_local_instructions(program, my_ir_generator_info, [new_value]);
+                        Ok(IRExpBuildResult::FloatValue(new_value))
+                    }
+                    result => build_binary_from_build_results(
+                        IRExpBuildResult::Const(0),
+                        result,
+                        program,
+                        my_ir_generator_info,
+                        koopa::ir::BinaryOp::Sub,
+                        *span,
+                    ),
+                }
+            }
+            UnaryExp::NotUnaryExp(exp, span) => build_binary_from_build_results(
                 IRExpBuildResult::Const(0),
                 exp.build(program, my_ir_generator_info)?,
                 program,
                 my_ir_generator_info,
-                koopa::ir::BinaryOp::Sub,
+                koopa::ir::BinaryOp::Eq,
+                *span,
             ),
-            UnaryExp::NotUnaryExp(exp) => build_binary_from_build_results(
-                IRExpBuildResult::Const(0),
+            // ~a is a ^ -1.
+            UnaryExp::BitNotUnaryExp(exp, span) => build_binary_from_build_results(
                 exp.build(program, my_ir_generator_info)?,
+                IRExpBuildResult::Const(-1),
                 program,
                 my_ir_generator_info,
-                koopa::ir::BinaryOp::Eq,
+                koopa::ir::BinaryOp::Xor,
+                *span,
             ),
-            UnaryExp::FuncCall(func_id, param_exps) => {
-                let callee_func = match my_ir_generator_info
+            // The backend treats a variable as its value, so its address is made by getptr.
+            UnaryExp::AddrOfExp(_, span) if my_ir_generator_info.curr_func.is_none() => {
+                Err(CompileError::new(
+                    ErrorCode::NonConstantExpression,
+                    "An address can not be used in global or static initvals! ".to_string(),
+                    *span,
+                ))
+            }
+            UnaryExp::AddrOfExp(lval, span) => {
+                match lval.build_addr_of(program, my_ir_generator_info)? {
+                    (IRLValBuildResult::Addr(addr), btype)
+                    | (IRLValBuildResult::Struct(addr), btype) => {
+                        let zero = create_new_local_value(program, my_ir_generator_info).integer(0);
+                        let pointer = create_new_local_value(program, my_ir_generator_info)
+                            .get_ptr(addr, zero);
+                        insert_local_instructions(program, my_ir_generator_info, [pointer]);
+                        Ok(IRExpBuildResult::from_value(pointer, btype))
+                    }
+                    _ => Err(CompileError::new(
+                        ErrorCode::NotAssignable,
+                        "Only variables and elements of arrays have addresses! ".to_string(),
+                        *span,
+                    )),
+                }
+            }
+            UnaryExp::DerefExp(exp, span) => {
+                let (result, btype) = build_deref(exp, *span, program, my_ir_generator_info)?;
+                load_lval(result, btype, *span, program, my_ir_generator_info)
+            }
+            UnaryExp::FuncCall(_, _, span) if my_ir_generator_info.curr_func.is_none() => {
+                Err(CompileError::new(
+                    ErrorCode::NonConstantExpression,
+                    "A function can not be called in global or static initvals! ".to_string(),
+                    *span,
+                ))
+            }
+            UnaryExp::FuncCall(func_id, param_exps, span) => {
+                let callee = match my_ir_generator_info
                     .function_table
                     .get(&func_id.content)
                     .cloned()
                 {
-                    Some(f) => Ok(f),
-                    None => Err(format!("Undeclared FuncCall symbol: {}", &func_id.content)),
-                }?;
-                let TypeKind::Function(form_param_types, _) = 
-                    program.func(callee_func).ty().kind() 
-                    else {panic!("Should be a TypeKind::Function")};
+                    Some(f) => f,
+                    None => {
+                        return Err(CompileError::new(
+                            ErrorCode::UndeclaredSymbol,
+                            format!("Undeclared FuncCall symbol: {}", &func_id.content),
+                            func_id.span,
+                        ))
+                    }
+                };
+                let TypeKind::Function(form_param_types, _) = program.func(callee.func).ty().kind()
+                else {
+                    return Err(CompileError::new(
+                        ErrorCode::BackendInternal,
+                        format!("'{}' does not have a function type! ", func_id.content),
+                        func_id.span,
+                    ));
+                };
                 let form_param_types = form_param_types.clone();
-                if param_exps.len() != form_param_types.len() {
-                    return Err(format!(
-                        "The parameter number of function '{}' is incorrect! Expected {} parameters, but got {}.",
-                        &func_id.content, form_param_types.len(), param_exps.len()
+                let has_variadic_args = param_exps.len() > form_param_types.len();
+                if param_exps.len() != form_param_types.len()
+                    && !(callee.is_variadic && has_variadic_args)
+                {
+                    return Err(CompileError::new(
+                        ErrorCode::BackendInternal,
+                        format!(
+                            "The parameter number of function '{}' is incorrect! Expected {}{} parameters, but got {}.",
+                            &func_id.content,
+                            if callee.is_variadic { "at least " } else { "" },
+                            form_param_types.len(),
+                            param_exps.len()
+                        ),
+                        *span,
                     ));
                 }
                 let mut real_params = vec![];
                 for i in 0..param_exps.len() {
-                    let real_param = match param_exps[i].build(program, my_ir_generator_info)? {
+                    let result = param_exps[i].build(program, my_ir_generator_info)?;
+                    // A variadic float is passed as a double, a variadic long long is passed as
+                    // a long long, and other variadic args are ints.
+                    if i >= form_param_types.len() {
+                        let real_param = match result {
+                            IRExpBuildResult::FloatConst(_) | IRExpBuildResult::FloatValue(_) => {
+                                let value = get_float_value(
+                                    result,
+                                    param_exps[i].span(),
+                                    program,
+                                    my_ir_generator_info,
+                                )?;
+                                call_intrinsic(
+                                    "__float_to_double",
+                                    vec![value],
+                                    program,
+                                    my_ir_generator_info,
+                                )?
+                            }
+                            IRExpBuildResult::StringConst(string) => string,
+                            IRExpBuildResult::LongLongConst(_)
+                            | IRExpBuildResult::LongLongValue(_) => get_long_long_value(
+                                result,
+                                param_exps[i].span(),
+                                program,
+                                my_ir_generator_info,
+                            )?,
+                            _ => get_int_value(
+                                result,
+                                param_exps[i].span(),
+                                program,
+                                my_ir_generator_info,
+                            )?,
+                        };
+                        real_params.push(real_param);
+                        continue;
+                    }
+                    let param_type = callee.param_types[i];
+                    // A scalar param is converted into the param type.
+                    if form_param_types[i].is_i32()
+                        || form_param_types[i] == Type::get(BTypeKind::LongLong.to_type_kind())
+                    {
+                        real_params.push(get_typed_value(
+                            result,
+                            param_type,
+                            param_exps[i].span(),
+                            program,
+                            my_ir_generator_info,
+                        )?);
+                        continue;
+                    }
+                    // The type checker has matched an array param with its arg, e.g. a string
+                    // literal is only passed to a library function as a char array.
+                    let real_param = match result {
                         IRExpBuildResult::Const(int) => {
                             create_new_local_value(program, my_ir_generator_info).integer(int)
                         }
-                        IRExpBuildResult::Value(v) => v,
+                        IRExpBuildResult::FloatConst(float) => {
+                            create_new_local_value(program, my_ir_generator_info)
+                                .integer(float.to_bits() as i32)
+                        }
+                        IRExpBuildResult::LongLongConst(int) => {
+                            new_long_long(int, false, program, my_ir_generator_info)
+                        }
+                        IRExpBuildResult::Value(v)
+                        | IRExpBuildResult::FloatValue(v)
+                        | IRExpBuildResult::LongLongValue(v)
+                        | IRExpBuildResult::StringConst(v) => v,
                     };
                     // Here the real_param can only be local.
                     let real_param_type = get_valuedata(real_param, program, my_ir_generator_info)
                         .ty()
                         .clone();
                     if real_param_type != form_param_types[i] {
-                        return Err(format!(
-                            "The parameter type of function '{}' is incorrect! Wanted {}, but got {}.",
-                            &func_id.content,
-                            form_param_types[i], real_param_type
+                        return Err(CompileError::new(
+                            ErrorCode::BackendInternal,
+                            format!(
+                                "The parameter type of function '{}' is incorrect! Wanted {}, but got {}.",
+                                &func_id.content,
+                                form_param_types[i], real_param_type
+                            ),
+                            param_exps[i].span(),
                         ));
                     }
                     real_params.push(real_param);
                 }
                 let call_inst = create_new_local_value(program, my_ir_generator_info)
-                    .call(callee_func.clone(), real_params);
+                    .call(callee.func, real_params);
                 insert_local_instructions(program, my_ir_generator_info, [call_inst]);
-                Ok(IRExpBuildResult::Value(call_inst))
+                Ok(IRExpBuildResult::from_value(call_inst, callee.return_type))
             }
+            UnaryExp::PreIncExp(lval, _) => build_increment(
+                lval,
+                koopa::ir::BinaryOp::Add,
+                true,
+                program,
+                my_ir_generator_info,
+            ),
+            UnaryExp::PreDecExp(lval, _) => build_increment(
+                lval,
+                koopa::ir::BinaryOp::Sub,
+                true,
+                program,
+                my_ir_generator_info,
+            ),
+            UnaryExp::PostIncExp(lval, _) => build_increment(
+                lval,
+                koopa::ir::BinaryOp::Add,
+                false,
+                program,
+                my_ir_generator_info,
+            ),
+            UnaryExp::PostDecExp(lval, _) => build_increment(
+                lval,
+                koopa::ir::BinaryOp::Sub,
+                false,
+                program,
+                my_ir_generator_info,
+            ),
         }
     }
 }
@@ -489,39 +1252,30 @@ impl IRExpBuildable for PrimaryExp {
         &self,
         program: &mut Program,
         my_ir_generator_info: &mut MyIRGeneratorInfo,
-    ) -> Result<IRExpBuildResult, String> {
+    ) -> Result<IRExpBuildResult, CompileError> {
         match self {
             PrimaryExp::BracedExp(exp) => exp.build(program, my_ir_generator_info),
             PrimaryExp::LVal(lval) => {
-                let result = lval.build(program, my_ir_generator_info)?;
-                match result {
-                    IRLValBuildResult::Const(int) => Ok(IRExpBuildResult::Const(int)),
-                    IRLValBuildResult::TempVal(value) => Ok(IRExpBuildResult::Value(value)),
-                    IRLValBuildResult::Addr(addr) => {
-                        // Load the value from the address.
-                        match get_valuedata(addr, program, my_ir_generator_info)
-                            .ty()
-                            .kind()
-                        {
-                            TypeKind::Pointer(_base_type) => {
-                                let load_inst =
-                                    create_new_local_value(program, my_ir_generator_info)
-                                        .load(addr);
-                                insert_local_instructions(
-                                    program,
-                                    my_ir_generator_info,
-                                    [load_inst],
-                                );
-                                Ok(IRExpBuildResult::Value(load_inst))
-                            }
-                            _ => {
-                                panic!("LVal (as an address) must be a pointer to something!")
-                            }
-                        }
-                    }
-                }
+                let (result, btype) = lval.build(program, my_ir_generator_info)?;
+                load_lval(result, btype, lval.span(), program, my_ir_generator_info)
             }
             PrimaryExp::Number(number) => number.build(program, my_ir_generator_info),
+            PrimaryExp::StringConst(bytes, span) => {
+                if my_ir_generator_info.curr_func.is_none() {
+                    return Err(CompileError::new(
+                        ErrorCode::NonConstantExpression,
+                        "A string literal can not be used in global or static initvals! "
+                            .to_string(),
+                        *span,
+                    ));
+                }
+                let string = get_string_constant(bytes, program, my_ir_generator_info);
+                let zero = create_new_local_value(program, my_ir_generator_info).integer(0);
+                let ptr = create_new_local_value(program, my_ir_generator_info)
+                    .get_elem_ptr(string, zero);
+                insert_local_instructions(program, my_ir_generator_info, [ptr]);
+                Ok(IRExpBuildResult::StringConst(ptr))
+            }
         }
     }
 }
@@ -529,63 +1283,530 @@ impl IRExpBuildable for PrimaryExp {
 #[derive(Debug)]
 pub enum IRLValBuildResult {
     Const(i32),
+    FloatConst(f32),
+    LongLongConst(i64),
     TempVal(Value),
     Addr(Value),
+    Struct(Value), // The address of a struct. Only its members and its address can be used.
+}
+
+/// Converts a LVal into its value. A variable is loaded from its address.
+fn load_lval(
+    result: IRLValBuildResult,
+    btype: BTypeKind,
+    span: Span,
+    program: &mut Program,
+    my_ir_generator_info: &mut MyIRGeneratorInfo,
+) -> Result<IRExpBuildResult, CompileError> {
+    Ok(match result {
+        IRLValBuildResult::Const(int) => IRExpBuildResult::Const(int),
+        IRLValBuildResult::FloatConst(float) => IRExpBuildResult::FloatConst(float),
+        IRLValBuildResult::LongLongConst(int) => IRExpBuildResult::LongLongConst(int),
+        IRLValBuildResult::TempVal(value) => IRExpBuildResult::from_value(value, btype),
+        IRLValBuildResult::Struct(_) => {
+            return Err(CompileError::new(
+                ErrorCode::BackendInternal,
+                format!(
+                    "A {} can not be used as a value! Use its members instead. ",
+                    btype
+                ),
+                span,
+            ))
+        }
+        IRLValBuildResult::Addr(addr) => {
+            // Load the value from the address.
+            match get_valuedata(addr, program, my_ir_generator_info)
+                .ty()
+                .kind()
+            {
+                TypeKind::Pointer(_base_type) => {
+                    let load_inst =
+                        create_new_local_value(program, my_ir_generator_info).load(addr);
+                    insert_local_instructions(program, my_ir_generator_info, [load_inst]);
+                    IRExpBuildResult::from_value(load_inst, btype)
+                }
+                _ => {
+                    return Err(CompileError::new(
+                        ErrorCode::BackendInternal,
+                        "The address of a LVal is not a pointer! ".to_string(),
+                        span,
+                    ))
+                }
+            }
+        }
+    })
+}
+
+/// Builds *p. The result is the address p points to, or an array if p points to an array.
+/// Koopa IR can not tell char pointers from int pointers, so *p is an int, a float or a long long.
+fn build_deref(
+    exp: &UnaryExp,
+    span: Span,
+    program: &mut Program,
+    my_ir_generator_info: &mut MyIRGeneratorInfo,
+) -> Result<(IRLValBuildResult, BTypeKind), CompileError> {
+    let result = exp.build(program, my_ir_generator_info)?;
+    let ty = my_ir_generator_info.get_exp_type(span)?;
+    let Some(btype) = ty.base_type() else {
+        return Err(CompileError::new(
+            ErrorCode::BackendInternal,
+            format!("A {} is dereferenced! ", ty),
+            span,
+        ));
+    };
+    match get_pointer(&result, program, my_ir_generator_info) {
+        Some(pointer) => Ok((
+            get_element_in_ndarray(pointer, &[], btype, span, program, my_ir_generator_info)?,
+            btype,
+        )),
+        None => Err(CompileError::new(
+            ErrorCode::BackendInternal,
+            "Only pointers can be dereferenced! ".to_string(),
+            span,
+        )),
+    }
+}
+
+/// Builds the indexes of an array, which are ints.
+fn build_indexes(
+    index_exps: &[Exp],
+    program: &mut Program,
+    my_ir_generator_info: &mut MyIRGeneratorInfo,
+) -> Result<Vec<Value>, CompileError> {
+    let mut index_values = vec![];
+    for exp in index_exps {
+        let build = exp.build(program, my_ir_generator_info)?;
+        index_values.push(get_int_value(
+            build,
+            exp.span(),
+            program,
+            my_ir_generator_info,
+        )?);
+    }
+    Ok(index_values)
+}
+
+/// Builds s.a[i] or p->a[i], where addr is the address of the struct.
+/// The member is at the word of its offset. A member that is not an int
+/// is reached by casting the address of that word into a pointer to the member.
+fn build_member(
+    addr: Value,
+    btype: BTypeKind,
+    member_ident: &IDENT,
+    index_exps: &[Exp],
+    span: Span,
+    program: &mut Program,
+    my_ir_generator_info: &mut MyIRGeneratorInfo,
+) -> Result<(IRLValBuildResult, BTypeKind), CompileError> {
+    let BTypeKind::Struct(name) = btype else {
+        return Err(CompileError::new(
+            ErrorCode::BackendInternal,
+            format!("A {} has no members! ", btype),
+            span,
+        ));
+    };
+    let Some(member) = my_ir_generator_info.struct_table[name]
+        .members
+        .iter()
+        .find(|member| member.name == member_ident.content)
+        .cloned()
+    else {
+        return Err(CompileError::new(
+            ErrorCode::UndeclaredSymbol,
+            format!(
+                "struct {} has no member named {}! ",
+                name, member_ident.content
+            ),
+            member_ident.span,
+        ));
+    };
+    let offset =
+        create_new_local_value(program, my_ir_generator_info).integer(member.offset as i32);
+    let word_addr =
+        create_new_local_value(program, my_ir_generator_info).get_elem_ptr(addr, offset);
+    insert_local_instructions(program, my_ir_generator_info, [word_addr]);
+    let member_addr = match member.ty.is_i32() {
+        true => word_addr,
+        false => cast_pointer(
+            word_addr,
+            &Type::get_pointer(member.ty),
+            program,
+            my_ir_generator_info,
+        )?,
+    };
+    let index_values = build_indexes(index_exps, program, my_ir_generator_info)?;
+    let element = get_element_in_ndarray(
+        member_addr,
+        &index_values,
+        member.btype,
+        span,
+        program,
+        my_ir_generator_info,
+    )?;
+    Ok((element, member.btype))
 }
 
 impl LVal {
+    /// Builds the LVal, and returns its base type too.
+    /// An element of a constant array at constant indexes is folded into a constant.
     pub fn build(
         &self,
         program: &mut Program,
         my_ir_generator_info: &mut MyIRGeneratorInfo,
-    ) -> Result<IRLValBuildResult, String> {
-        let LVal::Default(ident, index_exps) = self;
-        match my_ir_generator_info.symbol_tables.get(&ident.content) {
-            Some(SymbolTableEntry::Variable(_, ptr)) => {
-                let ptr = ptr.clone();
-                // Build indexes.
-                let mut index_values = vec![];
-                for exp in index_exps {
-                    let build = exp.build(program, my_ir_generator_info)?;
-                    index_values.push(match build {
-                        IRExpBuildResult::Const(int) => {
-                            create_new_local_value(program, my_ir_generator_info)
-                                .integer(int)
-                        }
-                        IRExpBuildResult::Value(value) => value,
-                    })
+    ) -> Result<(IRLValBuildResult, BTypeKind), CompileError> {
+        self.build_lval(true, program, my_ir_generator_info)
+    }
+
+    /// Builds the LVal whose address is taken. Elements of constant arrays keep their addresses.
+    pub fn build_addr_of(
+        &self,
+        program: &mut Program,
+        my_ir_generator_info: &mut MyIRGeneratorInfo,
+    ) -> Result<(IRLValBuildResult, BTypeKind), CompileError> {
+        self.build_lval(false, program, my_ir_generator_info)
+    }
+
+    fn build_lval(
+        &self,
+        is_folded: bool,
+        program: &mut Program,
+        my_ir_generator_info: &mut MyIRGeneratorInfo,
+    ) -> Result<(IRLValBuildResult, BTypeKind), CompileError> {
+        let (ident, index_exps, span) = match self {
+            LVal::Default(ident, index_exps, span) => (ident, index_exps, span),
+            LVal::Member(lval, member_ident, index_exps, span) => {
+                return match lval.build(program, my_ir_generator_info)? {
+                    (IRLValBuildResult::Struct(addr), btype) => build_member(
+                        addr,
+                        btype,
+                        member_ident,
+                        index_exps,
+                        *span,
+                        program,
+                        my_ir_generator_info,
+                    ),
+                    _ => Err(CompileError::new(
+                        ErrorCode::BackendInternal,
+                        "Only structs have members! ".to_string(),
+                        lval.span(),
+                    )),
+                };
+            }
+            LVal::PtrMember(lval, member_ident, index_exps, span) => {
+                let (result, btype) = lval.build(program, my_ir_generator_info)?;
+                if let IRLValBuildResult::Struct(_) = result {
+                    return Err(CompileError::new(
+                        ErrorCode::BackendInternal,
+                        format!(
+                            "A {} is not a pointer! Use \".\" instead of \"->\". ",
+                            btype
+                        ),
+                        lval.span(),
+                    ));
                 }
-                // Get element.
-                let result = get_element_in_ndarray(ptr, &index_values, program, my_ir_generator_info);
-                Ok(result)
+                let result = load_lval(result, btype, lval.span(), program, my_ir_generator_info)?;
+                // The type checker has found that it points to a struct.
+                return match get_pointer(&result, program, my_ir_generator_info) {
+                    Some(pointer) => build_member(
+                        pointer,
+                        btype,
+                        member_ident,
+                        index_exps,
+                        *span,
+                        program,
+                        my_ir_generator_info,
+                    ),
+                    None => Err(CompileError::new(
+                        ErrorCode::BackendInternal,
+                        "Only pointers to structs have members! ".to_string(),
+                        lval.span(),
+                    )),
+                };
             }
-            Some(SymbolTableEntry::Constant(_lval_type, int)) => Ok(IRLValBuildResult::Const(*int)),
-            None => Err(format!("Undeclared LVal symbol: {}", ident.content)),
+            LVal::Deref(exp, span) => {
+                return build_deref(exp, *span, program, my_ir_generator_info);
+            }
+        };
+        let (btype, ptr, const_array) = match my_ir_generator_info.symbol_tables.get(&ident.content)
+        {
+            Some(&SymbolTableEntry::Variable(btype, ptr)) => (btype, ptr, None),
+            Some(SymbolTableEntry::ConstArray(btype, ptr, const_array)) => {
+                (*btype, *ptr, Some(const_array.clone()))
+            }
+            Some(SymbolTableEntry::Constant(BTypeKind::Float, bits)) => {
+                return Ok((
+                    IRLValBuildResult::FloatConst(f32::from_bits(*bits as u32)),
+                    BTypeKind::Float,
+                ))
+            }
+            Some(&SymbolTableEntry::Constant(BTypeKind::LongLong, int)) => {
+                return Ok((IRLValBuildResult::LongLongConst(int), BTypeKind::LongLong))
+            }
+            Some(&SymbolTableEntry::Constant(btype, int)) => {
+                return Ok((IRLValBuildResult::Const(int as i32), btype))
+            }
+            None => {
+                return Err(CompileError::new(
+                    ErrorCode::UndeclaredSymbol,
+                    format!("Undeclared LVal symbol: {}", ident.content),
+                    ident.span,
+                ))
+            }
+        };
+        let mut index_results = vec![];
+        for exp in index_exps {
+            index_results.push(exp.build(program, my_ir_generator_info)?);
+        }
+        // An element of a constant array at constant indexes is a constant.
+        let const_array = const_array.filter(|_| is_folded);
+        if let Some(element) = const_array
+            .as_ref()
+            .and_then(|array| array.get(&index_results, btype))
+        {
+            return Ok((element, btype));
+        }
+        let is_global = my_ir_generator_info.curr_func.is_none();
+        if is_global || my_ir_generator_info.is_const_exp {
+            // An index out of bounds is why the element is not a constant.
+            if let Some((i, index, length)) = const_array
+                .as_ref()
+                .and_then(|array| array.find_out_of_bounds(&index_results))
+            {
+                return Err(CompileError::new(
+                    ErrorCode::IndexOutOfBounds,
+                    format!(
+                        "Index {} is out of bounds of '{}'! The length is {}. ",
+                        index, ident.content, length
+                    ),
+                    index_exps[i].span(),
+                ));
+            }
+        }
+        if is_global {
+            return Err(CompileError::new(
+                ErrorCode::NonConstantExpression,
+                format!(
+                    "Variable '{}' can not be used in global or static initvals! ",
+                    ident.content
+                ),
+                *span,
+            ));
+        }
+        let mut index_values = vec![];
+        for (result, exp) in index_results.into_iter().zip(index_exps) {
+            index_values.push(get_int_value(
+                result,
+                exp.span(),
+                program,
+                my_ir_generator_info,
+            )?);
+        }
+        // Get element.
+        let element = get_element_in_ndarray(
+            ptr,
+            &index_values,
+            btype,
+            *span,
+            program,
+            my_ir_generator_info,
+        )?;
+        Ok((element, btype))
+    }
+
+    /// Builds the address of a LVal that is going to be assigned, and returns its base type too.
+    /// Constants and arrays can not be assigned. Pointers (and array parameters) can.
+    pub fn build_assignable_addr(
+        &self,
+        program: &mut Program,
+        my_ir_generator_info: &mut MyIRGeneratorInfo,
+    ) -> Result<(Value, BTypeKind), CompileError> {
+        let result = self.build(program, my_ir_generator_info)?;
+        self.get_assignable_addr(result)
+    }
+
+    /// Gets the address of a built LVal that is going to be assigned.
+    /// A whole struct can only be copied by "=", which handles it before.
+    pub fn get_assignable_addr(
+        &self,
+        result: (IRLValBuildResult, BTypeKind),
+    ) -> Result<(Value, BTypeKind), CompileError> {
+        match result {
+            (IRLValBuildResult::Addr(addr), btype) => Ok((addr, btype)),
+            (IRLValBuildResult::Struct(_), btype) => Err(CompileError::new(
+                ErrorCode::NotAssignable,
+                format!("A {} can not be assigned as a whole! ", btype),
+                self.span(),
+            )),
+            _ => Err(CompileError::new(
+                ErrorCode::NotAssignable,
+                match self {
+                    LVal::Default(ident, _, _) => format!(
+                        "Constant expression or temp value ({}) should not be a left value! ",
+                        ident.content
+                    ),
+                    LVal::Member(_, member_ident, _, _)
+                    | LVal::PtrMember(_, member_ident, _, _) => {
+                        format!(
+                            "Array member ({}) should not be a left value! ",
+                            member_ident.content
+                        )
+                    }
+                    LVal::Deref(_, _) => "Array should not be a left value! ".to_string(),
+                },
+                self.span(),
+            )),
         }
     }
 }
 
+/// Builds the address of a whole struct that is copied, like "s" in "t = s" or "*p" in "t = *p".
+pub fn build_struct_addr(
+    exp: &Exp,
+    program: &mut Program,
+    my_ir_generator_info: &mut MyIRGeneratorInfo,
+) -> Result<Value, CompileError> {
+    if let Ok(lval) = exp.clone().into_lval() {
+        if let (IRLValBuildResult::Struct(addr), _) = lval.build(program, my_ir_generator_info)? {
+            return Ok(addr);
+        }
+    }
+    Err(CompileError::new(
+        ErrorCode::MismatchedTypes,
+        "A struct can only be copied from a struct! ".to_string(),
+        exp.span(),
+    ))
+}
+
+/// Copies a whole struct word by word, because a struct is an array of i32 words.
+pub fn copy_struct(
+    dest: Value,
+    src: Value,
+    program: &mut Program,
+    my_ir_generator_info: &mut MyIRGeneratorInfo,
+) -> Result<(), CompileError> {
+    let size = match get_valuedata(dest, program, my_ir_generator_info)
+        .ty()
+        .kind()
+    {
+        TypeKind::Pointer(base_type) => match base_type.kind() {
+            TypeKind::Array(_, size) => *size,
+            _ => 0,
+        },
+        _ => 0,
+    };
+    for i in 0..size {
+        let index = create_new_local_value(program, my_ir_generator_info).integer(i as i32);
+        let src_ptr =
+            create_new_local_value(program, my_ir_generator_info).get_elem_ptr(src, index);
+        let load_inst = create_new_local_value(program, my_ir_generator_info).load(src_ptr);
+        let dest_ptr =
+            create_new_local_value(program, my_ir_generator_info).get_elem_ptr(dest, index);
+        let store_inst =
+            create_new_local_value(program, my_ir_generator_info).store(load_inst, dest_ptr);
+        insert_local_instructions(
+            program,
+            my_ir_generator_info,
+            [src_ptr, load_inst, dest_ptr, store_inst],
+        );
+    }
+    Ok(())
+}
+
+/// Builds ++a, --a, a++ or a--. The address of a is only built once.
+/// Returns the new value of a if it is a prefix operator, or the old value if postfix.
+fn build_increment(
+    lval: &LVal,
+    binary_op: koopa::ir::BinaryOp,
+    is_prefix: bool,
+    program: &mut Program,
+    my_ir_generator_info: &mut MyIRGeneratorInfo,
+) -> Result<IRExpBuildResult, CompileError> {
+    let span = lval.span();
+    let (lval_ptr, btype) = lval.build_assignable_addr(program, my_ir_generator_info)?;
+    let old_value = create_new_local_value(program, my_ir_generator_info).load(lval_ptr);
+    insert_local_instructions(program, my_ir_generator_info, [old_value]);
+    let new_result = build_binary_from_build_results(
+        IRExpBuildResult::from_value(old_value, btype),
+        IRExpBuildResult::Const(1),
+        program,
+        my_ir_generator_info,
+        binary_op,
+        span,
+    )?;
+    let new_value = get_value_for_addr(
+        new_result,
+        lval_ptr,
+        btype,
+        span,
+        program,
+        my_ir_generator_info,
+    )?;
+    let store_inst =
+        create_new_local_value(program, my_ir_generator_info).store(new_value, lval_ptr);
+    insert_local_instructions(program, my_ir_generator_info, [store_inst]);
+    match is_prefix {
+        true => Ok(IRExpBuildResult::from_value(new_value, btype)),
+        false => Ok(IRExpBuildResult::from_value(old_value, btype)),
+    }
+}
+
 impl IRExpBuildable for Number {
     fn build(
         &self,
         _program: &mut Program,
-        _my_ir_generator_info: &mut MyIRGeneratorInfo,
-    ) -> Result<IRExpBuildResult, String> {
+        my_ir_generator_info: &mut MyIRGeneratorInfo,
+    ) -> Result<IRExpBuildResult, CompileError> {
         match self {
-            Number::INTCONST(int) => Ok(IRExpBuildResult::Const(*int)),
+            // The type checker tells whether a literal is a long long, or an int that wraps around.
+            Number::INTCONST(int, span) => match my_ir_generator_info.get_exp_type(*span)? {
+                SysYType::Basic(BTypeKind::LongLong) => Ok(IRExpBuildResult::LongLongConst(*int)),
+                _ => Ok(IRExpBuildResult::Const(*int as i32)),
+            },
+            Number::LONGLONGCONST(int, _) => Ok(IRExpBuildResult::LongLongConst(*int)),
+            Number::FLOATCONST(float, _) => Ok(IRExpBuildResult::FloatConst(*float)),
+            Number::CHARCONST(int, _) => Ok(IRExpBuildResult::Const(*int)),
         }
     }
 }
 
 /// This is a LVal Value. It should always be a local Value.
+/// The btype tells whether an array of words is a struct.
+/// The span is the LVal's span, used when there are too many indexes.
 fn get_element_in_ndarray(
     array_or_pointer: Value,
     indexes: &[Value],
+    btype: BTypeKind,
+    span: Span,
     program: &mut Program,
     my_ir_generator_info: &mut MyIRGeneratorInfo,
-) -> IRLValBuildResult {
+) -> Result<IRLValBuildResult, CompileError> {
+    let value_data = get_valuedata(array_or_pointer, program, my_ir_generator_info);
+    let is_struct = get_struct_type(btype, my_ir_generator_info)
+        .is_some_and(|struct_type| *value_data.ty() == Type::get_pointer(struct_type));
+    if is_struct && indexes.is_empty() {
+        return Ok(IRLValBuildResult::Struct(array_or_pointer));
+    }
+    // A long long is an array of 2 words, but it is not an array in SysY.
+    let is_long_long = btype == BTypeKind::LongLong
+        && *value_data.ty() == Type::get_pointer(Type::get(btype.to_type_kind()));
+    if is_long_long && indexes.is_empty() {
+        return Ok(IRLValBuildResult::Addr(array_or_pointer));
+    }
+    if is_long_long {
+        return Err(CompileError::new(
+            ErrorCode::BackendInternal,
+            "Too many indexes: this is not an array! ".to_string(),
+            span,
+        ));
+    }
+    if is_struct {
+        return Err(CompileError::new(
+            ErrorCode::BackendInternal,
+            format!("Too many indexes: this is a {}, not an array! ", btype),
+            span,
+        ));
+    }
     if indexes.is_empty() {
-        let value_data = get_valuedata(array_or_pointer, program, my_ir_generator_info);
         // Finds the element. If it is an array, convert arr to &arr[0].
         match value_data.ty().kind() {
             TypeKind::Pointer(elem_type) => {
@@ -596,18 +1817,21 @@ fn get_element_in_ndarray(
                         let index0_addr = create_new_local_value(program, my_ir_generator_info)
                             .get_elem_ptr(array_or_pointer, zero);
                         insert_local_instructions(program, my_ir_generator_info, [index0_addr]);
-                        IRLValBuildResult::TempVal(index0_addr)
+                        Ok(IRLValBuildResult::TempVal(index0_addr))
                     }
                     _ => {
                         // Not an array.
-                        IRLValBuildResult::Addr(array_or_pointer)
+                        Ok(IRLValBuildResult::Addr(array_or_pointer))
                     }
                 }
             }
-            _ => panic!("Not a LVal: {:?}!", value_data),
+            _ => Err(CompileError::new(
+                ErrorCode::BackendInternal,
+                "The address of a LVal is not a pointer! ".to_string(),
+                span,
+            )),
         }
     } else {
-        let value_data = get_valuedata(array_or_pointer, program, my_ir_generator_info);
         let element = match value_data.ty().kind() {
             TypeKind::Pointer(base_type) => match base_type.kind() {
                 TypeKind::Array(_, _) => create_new_local_value(program, my_ir_generator_info)
@@ -620,15 +1844,30 @@ fn get_element_in_ndarray(
                         .get_ptr(loaded_ptr, indexes[0])
                 }
                 _ => {
-                    panic!("Not an array: {:?}!", value_data.name());
+                    return Err(CompileError::new(
+                        ErrorCode::BackendInternal,
+                        "Too many indexes: this is not an array! ".to_string(),
+                        span,
+                    ));
                 }
             },
             _ => {
-                panic!("Not a LVal: {:?}!", value_data.name());
+                return Err(CompileError::new(
+                    ErrorCode::BackendInternal,
+                    "The address of a LVal is not a pointer! ".to_string(),
+                    span,
+                ));
             }
         };
         insert_local_instructions(program, my_ir_generator_info, [element]);
-        get_element_in_ndarray(element, &indexes[1..], program, my_ir_generator_info)
+        get_element_in_ndarray(
+            element,
+            &indexes[1..],
+            btype,
+            span,
+            program,
+            my_ir_generator_info,
+        )
     }
 }
 
@@ -1,12 +1,19 @@
 //! Build a single component into Koopa IR.
 
-use crate::ast_def::statements::*;
-use koopa::ir::{builder_traits::*, Program};
+use crate::ast_def::{expressions::Exp, statements::*};
+use crate::diagnostics::{CompileError, ErrorCode};
+use koopa::ir::{builder_traits::*, BasicBlock, Program, Type};
 
 use super::{
-    build_expressions::{IRExpBuildResult, IRExpBuildable, IRLValBuildResult},
-    create_new_block, create_new_local_value, insert_basic_blocks, insert_local_instructions,
-    IRBuildResult, IRBuildable, MyIRGeneratorInfo,
+    build_const_exp,
+    build_declarations::build_block_items,
+    build_expressions::{
+        build_binary_from_build_results, build_struct_addr, copy_struct, IRExpBuildResult,
+        IRExpBuildable, IRLValBuildResult,
+    },
+    create_new_block, create_new_local_value, get_cond_value, get_curr_return_type, get_int_value,
+    get_label_block, get_typed_value, get_value_for_addr, insert_basic_blocks,
+    insert_local_instructions, IRBuildResult, IRBuildable, MyIRGeneratorInfo,
 };
 
 impl IRBuildable for Stmt {
@@ -14,7 +21,7 @@ impl IRBuildable for Stmt {
         &self,
         program: &mut Program,
         my_ir_generator_info: &mut MyIRGeneratorInfo,
-    ) -> Result<IRBuildResult, String> {
+    ) -> Result<IRBuildResult, CompileError> {
         match &self {
             Stmt::UnmatchedStmt(stmt) => stmt.build(program, my_ir_generator_info),
             Stmt::MatchedStmt(stmt) => stmt.build(program, my_ir_generator_info),
@@ -27,7 +34,7 @@ impl IRBuildable for UnmatchedStmt {
         &self,
         program: &mut Program,
         my_ir_generator_info: &mut MyIRGeneratorInfo,
-    ) -> Result<IRBuildResult, String> {
+    ) -> Result<IRBuildResult, CompileError> {
         self.default.build(program, my_ir_generator_info)
     }
 }
@@ -37,7 +44,7 @@ impl IRBuildable for MatchedStmt {
         &self,
         program: &mut Program,
         my_ir_generator_info: &mut MyIRGeneratorInfo,
-    ) -> Result<IRBuildResult, String> {
+    ) -> Result<IRBuildResult, CompileError> {
         self.default.build(program, my_ir_generator_info)
     }
 }
@@ -47,28 +54,29 @@ impl IRBuildable for BasicStmt {
         &self,
         program: &mut Program,
         my_ir_generator_info: &mut MyIRGeneratorInfo,
-    ) -> Result<IRBuildResult, String> {
+    ) -> Result<IRBuildResult, CompileError> {
         match &self {
-            BasicStmt::AssignStmt(lval, rhs_exp) => {
+            BasicStmt::AssignStmt(lval, rhs_exp, _) => {
                 // Build LVal value.
-                let result1 = lval.build(program, my_ir_generator_info)?;
-                let lval_ptr = match result1 {
-                    IRLValBuildResult::Const(_) | IRLValBuildResult::TempVal(_) => {
-                        return Err(format!(
-                            "Constant expression or temp value ({:?}) should not be a left value! ",
-                            lval
-                        ))
+                let (lval_ptr, btype) = match lval.build(program, my_ir_generator_info)? {
+                    // A whole struct is copied from another struct.
+                    (IRLValBuildResult::Struct(dest), _) => {
+                        let src = build_struct_addr(rhs_exp, program, my_ir_generator_info)?;
+                        copy_struct(dest, src, program, my_ir_generator_info)?;
+                        return Ok(IRBuildResult::OK);
                     }
-                    IRLValBuildResult::Addr(addr) => addr,
+                    result => lval.get_assignable_addr(result)?,
                 };
-                // Build RHS value.
+                // Build RHS value, and convert it into the type of LVal.
                 let result2 = rhs_exp.build(program, my_ir_generator_info)?;
-                let rhs_value = match result2 {
-                    IRExpBuildResult::Const(int) => {
-                        create_new_local_value(program, my_ir_generator_info).integer(int)
-                    }
-                    IRExpBuildResult::Value(value) => value,
-                };
+                let rhs_value = get_value_for_addr(
+                    result2,
+                    lval_ptr,
+                    btype,
+                    rhs_exp.span(),
+                    program,
+                    my_ir_generator_info,
+                )?;
                 // Assign the RHS value into the new variable.
                 let store_inst = create_new_local_value(program, my_ir_generator_info)
                     .store(rhs_value, lval_ptr);
@@ -76,7 +84,37 @@ impl IRBuildable for BasicStmt {
                 insert_local_instructions(program, my_ir_generator_info, [store_inst]);
                 Ok(IRBuildResult::OK)
             }
-            BasicStmt::Exp(e) => {
+            BasicStmt::CompoundAssignStmt(lval, binary_op, rhs_exp, span) => {
+                // Build the address of LVal only once, e.g. a[f()] += 1 calls f once.
+                let (lval_ptr, btype) =
+                    lval.build_assignable_addr(program, my_ir_generator_info)?;
+                let result2 = rhs_exp.build(program, my_ir_generator_info)?;
+                // lval = lval op rhs
+                let old_value =
+                    create_new_local_value(program, my_ir_generator_info).load(lval_ptr);
+                insert_local_instructions(program, my_ir_generator_info, [old_value]);
+                let new_result = build_binary_from_build_results(
+                    IRExpBuildResult::from_value(old_value, btype),
+                    result2,
+                    program,
+                    my_ir_generator_info,
+                    *binary_op,
+                    *span,
+                )?;
+                let new_value = get_value_for_addr(
+                    new_result,
+                    lval_ptr,
+                    btype,
+                    *span,
+                    program,
+                    my_ir_generator_info,
+                )?;
+                let store_inst = create_new_local_value(program, my_ir_generator_info)
+                    .store(new_value, lval_ptr);
+                insert_local_instructions(program, my_ir_generator_info, [store_inst]);
+                Ok(IRBuildResult::OK)
+            }
+            BasicStmt::Exp(e, _) => {
                 if let Some(exp) = e {
                     exp.build(program, my_ir_generator_info)?;
                     Ok(IRBuildResult::OK)
@@ -88,13 +126,10 @@ impl IRBuildable for BasicStmt {
                 my_ir_generator_info.symbol_tables.add_new_table();
                 block.build(program, my_ir_generator_info)
             }
-            BasicStmt::IfStmt(cond, stmt1, possible_stmt2) => {
-                let cond_value = match cond.build(program, my_ir_generator_info)? {
-                    IRExpBuildResult::Const(int) => {
-                        create_new_local_value(program, my_ir_generator_info).integer(int)
-                    }
-                    IRExpBuildResult::Value(value) => value,
-                };
+            BasicStmt::IfStmt(cond, stmt1, possible_stmt2, _) => {
+                let cond_result = cond.build(program, my_ir_generator_info)?;
+                let cond_value =
+                    get_cond_value(cond_result, cond.span(), program, my_ir_generator_info)?;
                 let block_end = create_new_block(program, my_ir_generator_info, "if_block_end");
                 let block_start = my_ir_generator_info
                     .curr_block
@@ -149,7 +184,7 @@ impl IRBuildable for BasicStmt {
 
                 Ok(IRBuildResult::OK)
             }
-            BasicStmt::WhileStmt(cond, stmt) => {
+            BasicStmt::WhileStmt(cond, stmt, _) => {
                 // Creat blocks for the while statement.
 
                 let block_start = create_new_block(program, my_ir_generator_info, "while_start");
@@ -168,12 +203,9 @@ impl IRBuildable for BasicStmt {
 
                 // Build while start.
                 my_ir_generator_info.curr_block = Some(block_start);
-                let cond_value = match cond.build(program, my_ir_generator_info)? {
-                    IRExpBuildResult::Const(int) => {
-                        create_new_local_value(program, my_ir_generator_info).integer(int)
-                    }
-                    IRExpBuildResult::Value(value) => value,
-                };
+                let cond_result = cond.build(program, my_ir_generator_info)?;
+                let cond_value =
+                    get_cond_value(cond_result, cond.span(), program, my_ir_generator_info)?;
                 let branch_inst = create_new_local_value(program, my_ir_generator_info)
                     .branch(cond_value, block_body, block_end);
                 insert_local_instructions(program, my_ir_generator_info, [branch_inst]);
@@ -197,36 +229,170 @@ impl IRBuildable for BasicStmt {
                 my_ir_generator_info.continue_tgt_blocks.pop();
                 Ok(IRBuildResult::OK)
             }
-            BasicStmt::BreakStmt => {
+            BasicStmt::SwitchStmt(exp, cases, _) => {
+                build_switch_stmt(exp, cases, program, my_ir_generator_info)
+            }
+            BasicStmt::DoWhileStmt(stmt, cond, _) => {
+                // Creat blocks for the do-while statement. The body comes first.
+                let block_body = create_new_block(program, my_ir_generator_info, "do_body");
+                let block_cond = create_new_block(program, my_ir_generator_info, "do_cond");
+                let block_end = create_new_block(program, my_ir_generator_info, "do_end");
+                insert_basic_blocks(
+                    program,
+                    my_ir_generator_info,
+                    [block_body, block_cond, block_end],
+                );
+
+                // Jump to do body.
+                let start_jmp_inst =
+                    create_new_local_value(program, my_ir_generator_info).jump(block_body);
+                insert_local_instructions(program, my_ir_generator_info, [start_jmp_inst]);
+
+                // Build do body. Continue goes to the cond.
+                my_ir_generator_info.curr_block = Some(block_body);
+                my_ir_generator_info.break_tgt_blocks.push(block_end);
+                my_ir_generator_info.continue_tgt_blocks.push(block_cond);
+                // If there is surely break, continue or return in the body, no need to jump to do cond.
+                // Then do cond can only be reached by continue.
+                match stmt.build(program, my_ir_generator_info)? {
+                    IRBuildResult::OK => {
+                        let jmp_inst =
+                            create_new_local_value(program, my_ir_generator_info).jump(block_cond);
+                        insert_local_instructions(program, my_ir_generator_info, [jmp_inst]);
+                    }
+                    IRBuildResult::EARLYSTOPPING => {}
+                }
+                my_ir_generator_info.break_tgt_blocks.pop();
+                my_ir_generator_info.continue_tgt_blocks.pop();
+
+                // Build do cond.
+                my_ir_generator_info.curr_block = Some(block_cond);
+                let cond_result = cond.build(program, my_ir_generator_info)?;
+                let cond_value =
+                    get_cond_value(cond_result, cond.span(), program, my_ir_generator_info)?;
+                let branch_inst = create_new_local_value(program, my_ir_generator_info)
+                    .branch(cond_value, block_body, block_end);
+                insert_local_instructions(program, my_ir_generator_info, [branch_inst]);
+
+                my_ir_generator_info.curr_block = Some(block_end);
+                Ok(IRBuildResult::OK)
+            }
+            BasicStmt::ForStmt(init, possible_cond, possible_step, stmt, _) => {
+                // The variables declared in init are only visible in the for statement.
+                my_ir_generator_info.symbol_tables.add_new_table();
+
+                // Build init in the current block.
+                match &**init {
+                    ForInit::Decl(decl) => decl.build(program, my_ir_generator_info)?,
+                    ForInit::Stmt(init_stmt) => init_stmt.build(program, my_ir_generator_info)?,
+                };
+
+                // Creat blocks for the for statement.
+                let block_cond = create_new_block(program, my_ir_generator_info, "for_cond");
+                let block_body = create_new_block(program, my_ir_generator_info, "for_body");
+                let block_step = create_new_block(program, my_ir_generator_info, "for_step");
+                let block_end = create_new_block(program, my_ir_generator_info, "for_end");
+                insert_basic_blocks(
+                    program,
+                    my_ir_generator_info,
+                    [block_cond, block_body, block_step, block_end],
+                );
+
+                // Jump to for cond.
+                let start_jmp_inst =
+                    create_new_local_value(program, my_ir_generator_info).jump(block_cond);
+                insert_local_instructions(program, my_ir_generator_info, [start_jmp_inst]);
+
+                // Build for cond. Without cond, it is an endless loop.
+                my_ir_generator_info.curr_block = Some(block_cond);
+                let cond_inst = match possible_cond {
+                    Some(cond) => {
+                        let cond_result = cond.build(program, my_ir_generator_info)?;
+                        let cond_value = get_cond_value(
+                            cond_result,
+                            cond.span(),
+                            program,
+                            my_ir_generator_info,
+                        )?;
+                        create_new_local_value(program, my_ir_generator_info)
+                            .branch(cond_value, block_body, block_end)
+                    }
+                    None => create_new_local_value(program, my_ir_generator_info).jump(block_body),
+                };
+                insert_local_instructions(program, my_ir_generator_info, [cond_inst]);
+
+                // Build for body. Continue goes to the step, not the cond.
+                my_ir_generator_info.curr_block = Some(block_body);
+                my_ir_generator_info.break_tgt_blocks.push(block_end);
+                my_ir_generator_info.continue_tgt_blocks.push(block_step);
+                match stmt.build(program, my_ir_generator_info)? {
+                    IRBuildResult::OK => {
+                        let jmp_inst =
+                            create_new_local_value(program, my_ir_generator_info).jump(block_step);
+                        insert_local_instructions(program, my_ir_generator_info, [jmp_inst]);
+                    }
+                    IRBuildResult::EARLYSTOPPING => {}
+                }
+                my_ir_generator_info.break_tgt_blocks.pop();
+                my_ir_generator_info.continue_tgt_blocks.pop();
+
+                // Build for step, then go back to for cond.
+                my_ir_generator_info.curr_block = Some(block_step);
+                if let Some(step) = &**possible_step {
+                    step.build(program, my_ir_generator_info)?;
+                }
+                let jmp_inst =
+                    create_new_local_value(program, my_ir_generator_info).jump(block_cond);
+                insert_local_instructions(program, my_ir_generator_info, [jmp_inst]);
+
+                my_ir_generator_info.curr_block = Some(block_end);
+                my_ir_generator_info.symbol_tables.delete_new_table();
+                Ok(IRBuildResult::OK)
+            }
+            BasicStmt::BreakStmt(span) => {
                 let tgt_block = match my_ir_generator_info.break_tgt_blocks.last() {
-                    Some(block) => Ok(block.clone()),
-                    None => Err("Incorrect break statement! "),
-                }?;
+                    Some(&block) => block,
+                    None => {
+                        return Err(CompileError::new(
+                            ErrorCode::MisplacedBreakOrContinue,
+                            "Incorrect break statement! ".to_string(),
+                            *span,
+                        ))
+                    }
+                };
                 let jmp_inst =
                     create_new_local_value(program, my_ir_generator_info).jump(tgt_block);
                 insert_local_instructions(program, my_ir_generator_info, [jmp_inst]);
                 Ok(IRBuildResult::EARLYSTOPPING)
             }
-            BasicStmt::ContinueStmt => {
+            BasicStmt::ContinueStmt(span) => {
                 let tgt_block = match my_ir_generator_info.continue_tgt_blocks.last() {
-                    Some(block) => Ok(block.clone()),
-                    None => Err("Incorrect continue statement! "),
-                }?;
+                    Some(&block) => block,
+                    None => {
+                        return Err(CompileError::new(
+                            ErrorCode::MisplacedBreakOrContinue,
+                            "Incorrect continue statement! ".to_string(),
+                            *span,
+                        ))
+                    }
+                };
                 let jmp_inst =
                     create_new_local_value(program, my_ir_generator_info).jump(tgt_block);
                 insert_local_instructions(program, my_ir_generator_info, [jmp_inst]);
                 Ok(IRBuildResult::EARLYSTOPPING)
             }
-            BasicStmt::ReturnStmt(returned_exp) => {
+            BasicStmt::ReturnStmt(returned_exp, _) => {
                 let return_value = match returned_exp {
                     Some(exp) => Some({
                         let result = exp.build(program, my_ir_generator_info)?; // Build the returned Exp into curr_value.
-                        match result {
-                            IRExpBuildResult::Const(int) => {
-                                create_new_local_value(program, my_ir_generator_info).integer(int)
-                            }
-                            IRExpBuildResult::Value(value) => value,
-                        }
+                        let return_type = get_curr_return_type(my_ir_generator_info);
+                        get_typed_value(
+                            result,
+                            return_type,
+                            exp.span(),
+                            program,
+                            my_ir_generator_info,
+                        )?
                     }),
                     None => None,
                 };
@@ -235,6 +401,197 @@ impl IRBuildable for BasicStmt {
                 insert_local_instructions(program, my_ir_generator_info, [return_stmt]);
                 Ok(IRBuildResult::EARLYSTOPPING)
             }
+            BasicStmt::GotoStmt(label, _) => {
+                let tgt_block = get_label_block(program, my_ir_generator_info, &label.content);
+                let jmp_inst =
+                    create_new_local_value(program, my_ir_generator_info).jump(tgt_block);
+                insert_local_instructions(program, my_ir_generator_info, [jmp_inst]);
+                Ok(IRBuildResult::EARLYSTOPPING)
+            }
+            BasicStmt::LabeledStmt(label, stmt, _) => {
+                // Fall through into the label block, then build the statement there.
+                let label_block = get_label_block(program, my_ir_generator_info, &label.content);
+                let jmp_inst =
+                    create_new_local_value(program, my_ir_generator_info).jump(label_block);
+                insert_local_instructions(program, my_ir_generator_info, [jmp_inst]);
+                insert_basic_blocks(program, my_ir_generator_info, [label_block]);
+                my_ir_generator_info.curr_block = Some(label_block);
+                stmt.build(program, my_ir_generator_info)
+            }
+            // Syntax errors are reported before building IR.
+            BasicStmt::Error(_) => Ok(IRBuildResult::OK),
         }
     }
 }
+
+/// A dense switch has at least 4 cases, and at least half of the values in its range are cases.
+/// The backend looks up the cases of a dense switch in a jump table.
+fn is_dense_switch(labels: &[i32]) -> bool {
+    match (labels.iter().min(), labels.iter().max()) {
+        (Some(&min), Some(&max)) => {
+            labels.len() >= 4 && (max as i64 - min as i64 + 1) <= 2 * labels.len() as i64
+        }
+        _ => false,
+    }
+}
+
+/// The switch statement is lowered into a chain of tests.
+/// Every test block loads the switch value, compares it with a case label, and branches.
+/// The case blocks are in the source order, so that a case without break falls through.
+fn build_switch_stmt(
+    exp: &Exp,
+    cases: &[SwitchCase],
+    program: &mut Program,
+    my_ir_generator_info: &mut MyIRGeneratorInfo,
+) -> Result<IRBuildResult, CompileError> {
+    // Build the switch value. Store it, because temp values can not be used across blocks.
+    let result = exp.build(program, my_ir_generator_info)?;
+    let value = get_int_value(result, exp.span(), program, my_ir_generator_info)?;
+    let switch_value = create_new_local_value(program, my_ir_generator_info).alloc(Type::get_i32());
+    program
+        .func_mut(my_ir_generator_info.curr_func.unwrap())
+        .dfg_mut()
+        .set_value_name(switch_value, Some("@switch_value".to_string()));
+    let store_inst =
+        create_new_local_value(program, my_ir_generator_info).store(value, switch_value);
+    insert_local_instructions(program, my_ir_generator_info, [switch_value, store_inst]);
+
+    // Case labels should be distinct constants, and there is at most one default.
+    let mut labels = vec![];
+    let mut has_default = false;
+    for case in cases {
+        match case {
+            SwitchCase::Case(label_exp, _, _) => {
+                let label = match build_const_exp(label_exp, program, my_ir_generator_info)? {
+                    IRExpBuildResult::Const(int) => int,
+                    // The switch value is an int, so is the label.
+                    IRExpBuildResult::LongLongConst(int) => int as i32,
+                    IRExpBuildResult::Value(_) | IRExpBuildResult::LongLongValue(_) => {
+                        return Err(CompileError::new(
+                            ErrorCode::NonConstantExpression,
+                            "The case label must be constant! ".to_string(),
+                            label_exp.span(),
+                        ))
+                    }
+                    IRExpBuildResult::FloatConst(_)
+                    | IRExpBuildResult::FloatValue(_)
+                    | IRExpBuildResult::StringConst(_) => {
+                        return Err(CompileError::new(
+                            ErrorCode::BackendInternal,
+                            "The case label must be an integer! ".to_string(),
+                            label_exp.span(),
+                        ))
+                    }
+                };
+                if labels.contains(&label) {
+                    return Err(CompileError::new(
+                        ErrorCode::DuplicateCaseLabel,
+                        format!("Duplicate case label {} in the switch! ", label),
+                        label_exp.span(),
+                    ));
+                }
+                labels.push(label);
+            }
+            SwitchCase::DefaultCase(_, span) => {
+                if has_default {
+                    return Err(CompileError::new(
+                        ErrorCode::DuplicateCaseLabel,
+                        "Multiple default labels in the switch! ".to_string(),
+                        *span,
+                    ));
+                }
+                has_default = true;
+            }
+        }
+    }
+
+    // Creat blocks for the switch statement.
+    // The tests of a dense switch are recorded, so that the backend makes a jump table of them.
+    let mut test_blocks = vec![];
+    for i in 0..labels.len() {
+        let name = match (is_dense_switch(&labels), i) {
+            (true, 0) => "switch_jump_table",
+            (true, _) => "switch_jump_table_test",
+            (false, _) => "switch_test",
+        };
+        test_blocks.push(create_new_block(program, my_ir_generator_info, name));
+    }
+    let mut case_blocks = vec![];
+    let mut block_default = None;
+    for case in cases {
+        let case_block = match case {
+            SwitchCase::Case(_, _, _) => {
+                create_new_block(program, my_ir_generator_info, "switch_case")
+            }
+            SwitchCase::DefaultCase(_, _) => {
+                let block = create_new_block(program, my_ir_generator_info, "switch_default");
+                block_default = Some(block);
+                block
+            }
+        };
+        case_blocks.push(case_block);
+    }
+    let block_end = create_new_block(program, my_ir_generator_info, "switch_end");
+    insert_basic_blocks(program, my_ir_generator_info, test_blocks.clone());
+    if is_dense_switch(&labels) {
+        my_ir_generator_info
+            .dense_switches
+            .insert(test_blocks[0], test_blocks[1..].to_vec());
+    }
+    insert_basic_blocks(program, my_ir_generator_info, case_blocks.clone());
+    insert_basic_blocks(program, my_ir_generator_info, [block_end]);
+    // If no case matches, go to default. Without default, the switch does nothing.
+    let block_default = block_default.unwrap_or(block_end);
+
+    // Jump to the first test.
+    let first_block = test_blocks.first().copied().unwrap_or(block_default);
+    let jmp_inst = create_new_local_value(program, my_ir_generator_info).jump(first_block);
+    insert_local_instructions(program, my_ir_generator_info, [jmp_inst]);
+
+    // Build the tests.
+    let case_label_blocks: Vec<BasicBlock> = cases
+        .iter()
+        .zip(&case_blocks)
+        .filter(|(case, _)| matches!(case, SwitchCase::Case(_, _, _)))
+        .map(|(_, &block)| block)
+        .collect();
+    for (i, (&label, &case_block)) in labels.iter().zip(&case_label_blocks).enumerate() {
+        my_ir_generator_info.curr_block = Some(test_blocks[i]);
+        let next_block = test_blocks.get(i + 1).copied().unwrap_or(block_default);
+        let loaded_value = create_new_local_value(program, my_ir_generator_info).load(switch_value);
+        let label_value = create_new_local_value(program, my_ir_generator_info).integer(label);
+        let cond = create_new_local_value(program, my_ir_generator_info).binary(
+            koopa::ir::BinaryOp::Eq,
+            loaded_value,
+            label_value,
+        );
+        let branch_inst = create_new_local_value(program, my_ir_generator_info)
+            .branch(cond, case_block, next_block);
+        insert_local_instructions(
+            program,
+            my_ir_generator_info,
+            [loaded_value, cond, branch_inst],
+        );
+    }
+
+    // Build the cases. The whole switch body is one scope. Break goes to the end.
+    my_ir_generator_info.symbol_tables.add_new_table();
+    my_ir_generator_info.break_tgt_blocks.push(block_end);
+    for (i, case) in cases.iter().enumerate() {
+        my_ir_generator_info.curr_block = Some(case_blocks[i]);
+        let (SwitchCase::Case(_, items, _) | SwitchCase::DefaultCase(items, _)) = case;
+        // Ignore everything after break, continue or return in this case.
+        let case_result = build_block_items(items, program, my_ir_generator_info)?;
+        // Fall through to the next case.
+        if let IRBuildResult::OK = case_result {
+            let next_block = case_blocks.get(i + 1).copied().unwrap_or(block_end);
+            let jmp_inst = create_new_local_value(program, my_ir_generator_info).jump(next_block);
+            insert_local_instructions(program, my_ir_generator_info, [jmp_inst]);
+        }
+    }
+    my_ir_generator_info.break_tgt_blocks.pop();
+    my_ir_generator_info.symbol_tables.delete_new_table();
+
+    my_ir_generator_info.curr_block = Some(block_end);
+    Ok(IRBuildResult::OK)
+}
@@ -1,81 +1,655 @@
 //! This module is the frontend of my compiler.
 //! It converts the C code into Koopa IR.
+//! The types have been checked by the type checker, so a type error here is an internal error.
 
 mod build_declarations;
 mod build_expressions;
 mod build_statements;
 use crate::ast_def::expressions::Exp;
-use crate::ast_def::symbols::BType;
+use crate::ast_def::symbols::{BType, BTypeKind, Span, IDENT};
 use crate::ast_def::*;
-use koopa::ir::builder_traits::{BasicBlockBuilder};
+use crate::diagnostics::{CompileError, ErrorCode, Warning, WarningFlag};
+use crate::type_checker::{ExpTypes, SysYType};
+use koopa::ir::builder_traits::{
+    BasicBlockBuilder, GlobalInstBuilder, LocalInstBuilder, ValueBuilder,
+};
 use koopa::ir::entities::{BasicBlock, Function, Value, ValueData}; // Koopa IR builder
-use koopa::ir::{Program, Type, TypeKind}; // All the symbol defined in the AST
-use std::collections::HashMap;
+use koopa::ir::{Program, Type, TypeKind, ValueKind}; // All the symbol defined in the AST
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
 
-use self::build_expressions::{IRExpBuildResult, IRExpBuildable};
+use self::build_declarations::{build_extern_var_decl, build_static_decl};
+use self::build_expressions::{IRExpBuildResult, IRExpBuildable, IRLValBuildResult};
 
-pub fn generate_ir(comp_unit: &CompUnit) -> Result<Program, String> {
+/// The warnings found are added to `warnings`, even if an error stops the IR builder.
+pub fn generate_ir(
+    comp_unit: &CompUnit,
+    exp_types: ExpTypes,
+    warnings: &mut Vec<Warning>,
+) -> Result<(Program, ProgramInfo), CompileError> {
     let mut program = Program::new();
     let mut my_ir_generator_info = MyIRGeneratorInfo {
         curr_block: None,
         break_tgt_blocks: vec![],
         continue_tgt_blocks: vec![],
+        label_blocks: HashMap::new(),
+        static_symbols: HashSet::new(),
+        static_locals: HashSet::new(),
         curr_func: None,
+        is_const_exp: false,
         symbol_tables: SymbolTableStack {
             symbol_tables: vec![HashMap::new()],
         },
         bb_cnt: 0,
         function_table: HashMap::new(),
+        intrinsics: HashMap::new(),
+        string_constants: HashMap::new(),
+        struct_table: HashMap::new(),
+        pointer_casts: HashMap::new(),
+        dense_switches: HashMap::new(),
+        exp_types,
+        warnings: vec![],
     };
-    comp_unit.build(&mut program, &mut my_ir_generator_info)?;
-    Ok(program)
+    let result = comp_unit.build(&mut program, &mut my_ir_generator_info);
+    warnings.append(&mut my_ir_generator_info.warnings);
+    result?;
+    order_functions(&mut program, &mut my_ir_generator_info);
+    let program_info = my_ir_generator_info.program_info(&program);
+    Ok((program, program_info))
 }
 
+/// Koopa IR text needs a function to be defined before it is called, but the definition of a
+/// prototyped function takes the place of its prototype. So the defined functions are moved after
+/// the functions they call. Mutually recursive functions still call one defined after them,
+/// because Koopa IR can not declare a function that the same program defines.
+fn order_functions(program: &mut Program, my_ir_generator_info: &mut MyIRGeneratorInfo) {
+    fn visit(
+        func: Function,
+        program: &Program,
+        visited: &mut HashSet<Function>,
+        order: &mut Vec<Function>,
+    ) {
+        let func_data = program.func(func);
+        if func_data.layout().entry_bb().is_none() || !visited.insert(func) {
+            return;
+        }
+        for (_, node) in func_data.layout().bbs() {
+            for &inst in node.insts().keys() {
+                if let ValueKind::Call(call) = func_data.dfg().value(inst).kind() {
+                    visit(call.callee(), program, visited, order);
+                }
+            }
+        }
+        order.push(func);
+    }
+    let layout = program.func_layout().to_vec();
+    let mut visited = HashSet::new();
+    let mut order = vec![];
+    for &func in &layout {
+        visit(func, program, &mut visited, &mut order);
+    }
+    if layout
+        .iter()
+        .filter(|func| visited.contains(func))
+        .eq(order.iter())
+    {
+        return;
+    }
+    // Koopa IR can not move a function, so it is added again with a new handle.
+    let mut new_funcs = HashMap::new();
+    for func in order {
+        let function_data = program.remove_func(func).unwrap();
+        new_funcs.insert(func, program.new_func(function_data));
+    }
+    for &func in new_funcs.values() {
+        let dfg = program.func_mut(func).dfg_mut();
+        let calls: Vec<(Value, Function, Vec<Value>)> = dfg
+            .values()
+            .iter()
+            .filter_map(|(&value, value_data)| match value_data.kind() {
+                ValueKind::Call(call) => Some((value, call.callee(), call.args().to_vec())),
+                _ => None,
+            })
+            .collect();
+        for (value, callee, args) in calls {
+            if let Some(&new_callee) = new_funcs.get(&callee) {
+                dfg.replace_value_with(value).call(new_callee, args);
+            }
+        }
+    }
+    for entry in my_ir_generator_info.function_table.values_mut() {
+        if let Some(&func) = new_funcs.get(&entry.func) {
+            entry.func = func;
+        }
+    }
+}
+
+/// What the backend needs to know about the program, which Koopa IR can not tell.
+#[derive(Debug, Default)]
+pub struct ProgramInfo {
+    /// The intrinsics called in the program, and their names. Every pointer cast is "__ptr_cast".
+    /// A user function may have the same name as an intrinsic, so they are told by the handles.
+    pub intrinsics: HashMap<Function, &'static str>,
+    /// The functions and intrinsics with float params or a float return value: (whether every
+    /// param is a float, whether it returns a float). The calling convention passes floats in
+    /// fa0-fa7, and the backend keeps them in float registers.
+    pub float_signatures: HashMap<Function, (Vec<bool>, bool)>,
+    /// The global arrays of string literals, which are read-only.
+    pub string_constants: HashSet<Value>,
+    /// Static globals and static local variables, which other files can not see.
+    pub static_values: HashSet<Value>,
+    /// Static local variables. Ones in different scopes may have the same name,
+    /// so they are named by the IR builder.
+    pub static_locals: HashSet<Value>,
+    pub static_functions: HashSet<Function>,
+    /// The first test block of every dense switch, and its other test blocks in order.
+    /// A label may have any name, so the tests are told by the handles, not by the names.
+    pub dense_switches: HashMap<BasicBlock, Vec<BasicBlock>>,
+}
+
+/// (name, [(param type, is array)], return type)
+pub type LibFunction = (&'static str, &'static [(BTypeKind, bool)], BTypeKind);
+
+/// The SysY library functions. The type checker declares them too.
+/*
+   decl @getint(): i32
+   decl @getch(): i32
+   decl @getarray(*i32): i32
+   decl @putint(i32)
+   decl @putch(i32)
+   decl @putarray(i32, *i32)
+   decl @starttime()
+   decl @stoptime()
+   decl @_sysy_starttime(i32)
+   decl @_sysy_stoptime(i32)
+   decl @getfloat(): i32
+   decl @getfarray(*i32): i32
+   decl @putfloat(i32)
+   decl @putfarray(i32, *i32)
+   decl @putf(*i32, ...) (Koopa IR has no variadic functions, so "..." is not printed.)
+*/
+pub const LIB_FUNCTIONS: [LibFunction; 15] = [
+    ("getint", &[], BTypeKind::Int),
+    ("getch", &[], BTypeKind::Int),
+    ("getarray", &[(BTypeKind::Int, true)], BTypeKind::Int),
+    ("putint", &[(BTypeKind::Int, false)], BTypeKind::Void),
+    ("putch", &[(BTypeKind::Int, false)], BTypeKind::Void),
+    (
+        "putarray",
+        &[(BTypeKind::Int, false), (BTypeKind::Int, true)],
+        BTypeKind::Void,
+    ),
+    ("starttime", &[], BTypeKind::Void),
+    ("stoptime", &[], BTypeKind::Void),
+    // The macros starttime() and stoptime() in sylib.h call these with __LINE__.
+    (
+        "_sysy_starttime",
+        &[(BTypeKind::Int, false)],
+        BTypeKind::Void,
+    ),
+    (
+        "_sysy_stoptime",
+        &[(BTypeKind::Int, false)],
+        BTypeKind::Void,
+    ),
+    ("getfloat", &[], BTypeKind::Float),
+    ("getfarray", &[(BTypeKind::Float, true)], BTypeKind::Int),
+    ("putfloat", &[(BTypeKind::Float, false)], BTypeKind::Void),
+    (
+        "putfarray",
+        &[(BTypeKind::Int, false), (BTypeKind::Float, true)],
+        BTypeKind::Void,
+    ),
+    ("putf", &[(BTypeKind::Char, true)], BTypeKind::Void),
+];
+
+/// Koopa IR has no float type. Floats are stored as the bits of i32 values,
+/// and float operations are calls to these functions, whose params and return values are i32.
+/// The backend turns every call of them into RV32F instructions.
+/// __float_to_double keeps the bits, but tells the backend to pass a variadic arg as a double.
+/// __ptr_to_int keeps the address, so that pointers can be compared and subtracted.
+/// It takes a pointer of any type, so it declares no params (Koopa IR allows more args).
+/// Koopa IR has no i64 either, so long long operations are intrinsics too.
+/// Only the RV64 backend supports them.
+const INTRINSICS: [(&str, &[BTypeKind], BTypeKind); 35] = [
+    (
+        "__float_add",
+        &[BTypeKind::Float, BTypeKind::Float],
+        BTypeKind::Float,
+    ),
+    (
+        "__float_sub",
+        &[BTypeKind::Float, BTypeKind::Float],
+        BTypeKind::Float,
+    ),
+    (
+        "__float_mul",
+        &[BTypeKind::Float, BTypeKind::Float],
+        BTypeKind::Float,
+    ),
+    (
+        "__float_div",
+        &[BTypeKind::Float, BTypeKind::Float],
+        BTypeKind::Float,
+    ),
+    (
+        "__float_eq",
+        &[BTypeKind::Float, BTypeKind::Float],
+        BTypeKind::Int,
+    ),
+    (
+        "__float_ne",
+        &[BTypeKind::Float, BTypeKind::Float],
+        BTypeKind::Int,
+    ),
+    (
+        "__float_lt",
+        &[BTypeKind::Float, BTypeKind::Float],
+        BTypeKind::Int,
+    ),
+    (
+        "__float_gt",
+        &[BTypeKind::Float, BTypeKind::Float],
+        BTypeKind::Int,
+    ),
+    (
+        "__float_le",
+        &[BTypeKind::Float, BTypeKind::Float],
+        BTypeKind::Int,
+    ),
+    (
+        "__float_ge",
+        &[BTypeKind::Float, BTypeKind::Float],
+        BTypeKind::Int,
+    ),
+    ("__int_to_float", &[BTypeKind::Int], BTypeKind::Float),
+    ("__float_to_int", &[BTypeKind::Float], BTypeKind::Int),
+    ("__float_to_double", &[BTypeKind::Float], BTypeKind::Float),
+    ("__ptr_to_int", &[], BTypeKind::Int),
+    (
+        "__long_long_add",
+        &[BTypeKind::LongLong, BTypeKind::LongLong],
+        BTypeKind::LongLong,
+    ),
+    (
+        "__long_long_sub",
+        &[BTypeKind::LongLong, BTypeKind::LongLong],
+        BTypeKind::LongLong,
+    ),
+    (
+        "__long_long_mul",
+        &[BTypeKind::LongLong, BTypeKind::LongLong],
+        BTypeKind::LongLong,
+    ),
+    (
+        "__long_long_div",
+        &[BTypeKind::LongLong, BTypeKind::LongLong],
+        BTypeKind::LongLong,
+    ),
+    (
+        "__long_long_mod",
+        &[BTypeKind::LongLong, BTypeKind::LongLong],
+        BTypeKind::LongLong,
+    ),
+    (
+        "__long_long_and",
+        &[BTypeKind::LongLong, BTypeKind::LongLong],
+        BTypeKind::LongLong,
+    ),
+    (
+        "__long_long_or",
+        &[BTypeKind::LongLong, BTypeKind::LongLong],
+        BTypeKind::LongLong,
+    ),
+    (
+        "__long_long_xor",
+        &[BTypeKind::LongLong, BTypeKind::LongLong],
+        BTypeKind::LongLong,
+    ),
+    // The shift amount is an int, like other shifts.
+    (
+        "__long_long_shl",
+        &[BTypeKind::LongLong, BTypeKind::Int],
+        BTypeKind::LongLong,
+    ),
+    (
+        "__long_long_shr",
+        &[BTypeKind::LongLong, BTypeKind::Int],
+        BTypeKind::LongLong,
+    ),
+    (
+        "__long_long_sar",
+        &[BTypeKind::LongLong, BTypeKind::Int],
+        BTypeKind::LongLong,
+    ),
+    (
+        "__long_long_eq",
+        &[BTypeKind::LongLong, BTypeKind::LongLong],
+        BTypeKind::Int,
+    ),
+    (
+        "__long_long_ne",
+        &[BTypeKind::LongLong, BTypeKind::LongLong],
+        BTypeKind::Int,
+    ),
+    (
+        "__long_long_lt",
+        &[BTypeKind::LongLong, BTypeKind::LongLong],
+        BTypeKind::Int,
+    ),
+    (
+        "__long_long_gt",
+        &[BTypeKind::LongLong, BTypeKind::LongLong],
+        BTypeKind::Int,
+    ),
+    (
+        "__long_long_le",
+        &[BTypeKind::LongLong, BTypeKind::LongLong],
+        BTypeKind::Int,
+    ),
+    (
+        "__long_long_ge",
+        &[BTypeKind::LongLong, BTypeKind::LongLong],
+        BTypeKind::Int,
+    ),
+    ("__int_to_long_long", &[BTypeKind::Int], BTypeKind::LongLong),
+    ("__long_long_to_int", &[BTypeKind::LongLong], BTypeKind::Int),
+    (
+        "__float_to_long_long",
+        &[BTypeKind::Float],
+        BTypeKind::LongLong,
+    ),
+    (
+        "__long_long_to_float",
+        &[BTypeKind::LongLong],
+        BTypeKind::Float,
+    ),
+];
+
 #[derive(Debug)]
 pub struct MyIRGeneratorInfo {
-    curr_block: Option<BasicBlock>,            // Current block
-    break_tgt_blocks: Vec<BasicBlock>,         // Target blocks of break statements
-    continue_tgt_blocks: Vec<BasicBlock>,      // Target blocks of continue statements
-    curr_func: Option<Function>,               // Current function
-    symbol_tables: SymbolTableStack,           // Symbol table: ident-(type, Value)
-    bb_cnt: usize,                             // Number of BasicBlocks
-    function_table: HashMap<String, Function>, // Function table
+    curr_block: Option<BasicBlock>,                       // Current block
+    break_tgt_blocks: Vec<BasicBlock>,                    // Target blocks of break statements
+    continue_tgt_blocks: Vec<BasicBlock>,                 // Target blocks of continue statements
+    label_blocks: HashMap<String, BasicBlock>,            // Label-block of the current function
+    static_symbols: HashSet<String>,                      // Globals that other files can not see
+    static_locals: HashSet<Value>,                        // Static local variables
+    curr_func: Option<Function>,                          // Current function
+    is_const_exp: bool,                                   // Whether a constant is expected here
+    symbol_tables: SymbolTableStack,                      // Symbol table: ident-(type, Value)
+    bb_cnt: usize,                                        // Number of BasicBlocks
+    function_table: HashMap<String, FunctionEntry>,       // Function table
+    intrinsics: HashMap<&'static str, (Function, bool)>,  // Intrinsic-(function, is used)
+    string_constants: HashMap<Vec<u8>, Value>,            // String literal-global array
+    struct_table: HashMap<String, StructEntry>,           // Struct table: tag-layout
+    pointer_casts: HashMap<Type, Function>,               // Pointer type-cast intrinsic
+    dense_switches: HashMap<BasicBlock, Vec<BasicBlock>>, // First test-other tests
+    exp_types: ExpTypes,                                  // Expression types from the type checker
+    warnings: Vec<Warning>,                               // All warnings found so far
+}
+
+/// A function and its types in SysY, because Koopa IR can not tell floats from ints.
+#[derive(Debug, Clone)]
+pub struct FunctionEntry {
+    func: Function,
+    return_type: BTypeKind,
+    param_types: Vec<BTypeKind>, // Base types of params. Array params are pointers to them.
+    is_variadic: bool,           // Whether it takes more args after the params, like putf.
+    is_prototype: bool,          // Whether it is declared by a prototype but not defined yet.
+}
+
+/// The layout of a struct. Koopa IR has no structs, so a struct is an array of i32 words.
+/// Every member takes whole words, because chars are stored in i32 and pointers are 4 bytes.
+#[derive(Debug)]
+pub struct StructEntry {
+    members: Vec<StructMember>,
+    size: usize,  // Number of words.
+    align: usize, // Alignment in words.
+}
+
+#[derive(Debug, Clone)]
+pub struct StructMember {
+    name: String,
+    btype: BTypeKind,
+    ptr_depth: usize,
+    shape: Vec<usize>,
+    ty: Type,      // The Koopa IR type of the member, like a variable of it.
+    offset: usize, // The index of its first word.
 }
 
 impl MyIRGeneratorInfo {
-    fn check_duplicate_global_symbol(&self, name: &String) -> bool {
-        let result_var = match self.symbol_tables.symbol_tables[0].get(name) {
-            Some(s) => match s {
-                SymbolTableEntry::Variable(_, _) => true,
-                SymbolTableEntry::Constant(_, _) => false,
-            },
-            None => false,
+    fn program_info(&self, program: &Program) -> ProgramInfo {
+        let intrinsics = self
+            .intrinsics
+            .iter()
+            .filter(|(_, &(_, is_used))| is_used)
+            .map(|(&name, &(func, _))| (func, name))
+            .chain(
+                self.pointer_casts
+                    .values()
+                    .map(|&func| (func, "__ptr_cast")),
+            )
+            .collect();
+        // Pointers to floats are not floats, so the Koopa IR types are checked too.
+        let is_float = |btype: BTypeKind, ty: &Type| {
+            btype == BTypeKind::Float && matches!(ty.kind(), TypeKind::Int32)
         };
-        let result_func = self.function_table.get(name).is_some();
-        result_var || result_func
+        let mut float_signatures = HashMap::new();
+        for entry in self.function_table.values() {
+            let TypeKind::Function(param_tys, return_ty) = program.func(entry.func).ty().kind()
+            else {
+                continue;
+            };
+            let float_params: Vec<bool> = entry
+                .param_types
+                .iter()
+                .zip(param_tys)
+                .map(|(&btype, ty)| is_float(btype, ty))
+                .collect();
+            let returns_float = is_float(entry.return_type, return_ty);
+            if returns_float || float_params.contains(&true) {
+                float_signatures.insert(entry.func, (float_params, returns_float));
+            }
+        }
+        for (name, param_types, return_type) in INTRINSICS {
+            let (func, _) = self.intrinsics[name];
+            let float_params: Vec<bool> = param_types
+                .iter()
+                .map(|&param_type| param_type == BTypeKind::Float)
+                .collect();
+            let returns_float = return_type == BTypeKind::Float;
+            if returns_float || float_params.contains(&true) {
+                float_signatures.insert(func, (float_params, returns_float));
+            }
+        }
+        let mut static_values = self.static_locals.clone();
+        let mut static_functions = HashSet::new();
+        for name in &self.static_symbols {
+            if let Some(entry) = self.function_table.get(name) {
+                static_functions.insert(entry.func);
+            }
+            if let Some((
+                SymbolTableEntry::Variable(_, value) | SymbolTableEntry::ConstArray(_, value, _),
+                _,
+            )) = self.symbol_tables.get_global(name)
+            {
+                static_values.insert(*value);
+            }
+        }
+        ProgramInfo {
+            intrinsics,
+            float_signatures,
+            string_constants: self.string_constants.values().copied().collect(),
+            static_values,
+            static_locals: self.static_locals.clone(),
+            static_functions,
+            dense_switches: self.dense_switches.clone(),
+        }
+    }
+
+    /// Returns the type of an expression, which the type checker has found.
+    /// Koopa IR can not tell chars from ints, or floats from ints, so the IR builder asks for it.
+    fn get_exp_type(&self, span: Span) -> Result<&SysYType, CompileError> {
+        self.exp_types.get(&span).ok_or_else(|| {
+            CompileError::new(
+                ErrorCode::BackendInternal,
+                "The expression has not been annotated by the type checker! ".to_string(),
+                span,
+            )
+        })
+    }
+
+    /// Makes a global static. It can not be declared without static before,
+    /// because it may have been used by other files then.
+    /// main can not be static, because the startup code calls it.
+    fn declare_static(&mut self, ident: &IDENT) -> Result<(), CompileError> {
+        if ident.content == "main" {
+            return Err(CompileError::new(
+                ErrorCode::StaticMain,
+                "'main' can not be static! ".to_string(),
+                ident.span,
+            ));
+        }
+        if self.get_declared_kind(&ident.content).is_some()
+            && !self.static_symbols.contains(&ident.content)
+        {
+            return Err(CompileError::new(
+                ErrorCode::Redefinition,
+                format!(
+                    "Static declaration of '{}' follows a non-static declaration! ",
+                    ident.content
+                ),
+                ident.span,
+            ));
+        }
+        self.static_symbols.insert(ident.content.clone());
+        Ok(())
+    }
+
+    /// What the name has been declared as in the current scope.
+    /// Functions are in the global scope, together with global variables and constants.
+    fn get_declared_kind(&self, name: &String) -> Option<SymbolKind> {
+        match self.symbol_tables.get_in_curr_scope(name) {
+            Some(&(_, kind)) => Some(kind),
+            None if self.symbol_tables.symbol_tables.len() == 1
+                && self.function_table.contains_key(name) =>
+            {
+                Some(SymbolKind::Function)
+            }
+            None => None,
+        }
+    }
+
+    /// A name can only be declared once in a scope.
+    fn check_redefinition(&self, ident: &IDENT, kind: SymbolKind) -> Result<(), CompileError> {
+        let message = match self.get_declared_kind(&ident.content) {
+            None => return Ok(()),
+            Some(old_kind) if old_kind == kind => {
+                format!("Redefinition of {} '{}'! ", kind, ident.content)
+            }
+            Some(old_kind) => format!(
+                "Redefinition of '{}' as a {}! It was a {} before. ",
+                ident.content, kind, old_kind
+            ),
+        };
+        Err(CompileError::new(
+            ErrorCode::Redefinition,
+            message,
+            ident.span,
+        ))
+    }
+
+    /// Declares a variable, a constant or a parameter in the current scope.
+    /// If it hides a symbol of an outer scope, a -Wshadow warning is given.
+    fn declare_symbol(
+        &mut self,
+        ident: &IDENT,
+        entry: SymbolTableEntry,
+        kind: SymbolKind,
+    ) -> Result<(), CompileError> {
+        self.check_redefinition(ident, kind)?;
+        let shadowed = match self.symbol_tables.get_in_outer_scopes(&ident.content) {
+            Some((0, old_kind)) => Some(format!("a global {}", old_kind)),
+            Some((_, old_kind)) => Some(format!("a {} in an outer scope", old_kind)),
+            None if self.function_table.contains_key(&ident.content) => {
+                Some("a function".to_string())
+            }
+            None => None,
+        };
+        if let Some(shadowed) = shadowed {
+            self.warnings.push(Warning::new(
+                WarningFlag::Shadow,
+                format!(
+                    "The declaration of '{}' shadows {}! ",
+                    ident.content, shadowed
+                ),
+                ident.span,
+            ));
+        }
+        self.symbol_tables
+            .insert(ident.content.clone(), entry, kind);
+        Ok(())
+    }
+}
+
+/// What a name is declared as, for diagnostics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Function,
+    Variable,
+    Constant, // Including constant arrays, which are stored like variables.
+    Parameter,
+}
+
+impl std::fmt::Display for SymbolKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SymbolKind::Function => write!(f, "function"),
+            SymbolKind::Variable => write!(f, "variable"),
+            SymbolKind::Constant => write!(f, "constant"),
+            SymbolKind::Parameter => write!(f, "parameter"),
+        }
     }
 }
 
 #[derive(Debug)]
 pub struct SymbolTableStack {
-    symbol_tables: Vec<HashMap<String, SymbolTableEntry>>, // Symbol table: ident-(type, Value)
+    symbol_tables: Vec<HashMap<String, (SymbolTableEntry, SymbolKind)>>, // Symbol table: ident-(type, Value)
 }
 
 impl SymbolTableStack {
     fn get(&self, name: &String) -> Option<&SymbolTableEntry> {
         for table in self.symbol_tables.iter().rev() {
-            if let Some(symbol) = table.get(name) {
+            if let Some((symbol, _)) = table.get(name) {
                 return Some(symbol);
             }
         }
         None
     }
+    fn get_global(&self, name: &String) -> Option<&(SymbolTableEntry, SymbolKind)> {
+        self.symbol_tables[0].get(name)
+    }
+    fn get_in_curr_scope(&self, name: &String) -> Option<&(SymbolTableEntry, SymbolKind)> {
+        self.symbol_tables.last().unwrap().get(name)
+    }
+    /// Returns the depth of the nearest outer scope declaring the name, and what it is there.
+    /// The global scope has depth 0.
+    fn get_in_outer_scopes(&self, name: &String) -> Option<(usize, SymbolKind)> {
+        let outer_tables = &self.symbol_tables[..self.symbol_tables.len() - 1];
+        outer_tables
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(depth, table)| table.get(name).map(|&(_, kind)| (depth, kind)))
+    }
     // fn get_nearest(&self, name: &String) -> Option<&SymbolTableEntry> {
 
     // }
-    fn insert(&mut self, name: String, entry: SymbolTableEntry) {
+    /// Inserts a symbol without checks. Use declare_symbol of MyIRGeneratorInfo instead,
+    /// unless the symbol replaces its own declaration, like a definition of an extern variable.
+    fn insert(&mut self, name: String, entry: SymbolTableEntry, kind: SymbolKind) {
         let table = self.symbol_tables.last_mut().unwrap();
-        table.insert(name, entry);
+        table.insert(name, (entry, kind));
     }
     fn add_new_table(&mut self) {
         self.symbol_tables.push(HashMap::new());
@@ -88,9 +662,91 @@ impl SymbolTableStack {
     // }
 }
 
+/// The BTypeKind is the base type. A float constant is stored as its bits.
+/// The type of a variable's Value tells whether it is a pointer, e.g. "int *p" is a "**i32" alloc.
+/// A constant array is stored like a variable, and its elements are kept to fold constant indexes.
 pub enum SymbolTableEntry {
-    Variable(TypeKind, Value),
-    Constant(TypeKind, i32),
+    Variable(BTypeKind, Value),
+    Constant(BTypeKind, i64), // i64 for long longs. Other constants fit in an i32.
+    ConstArray(BTypeKind, Value, Rc<ConstArray>),
+}
+
+/// The elements of a constant array, as the words of its initval.
+/// A long long takes 2 words, and a float is stored as its bits.
+pub struct ConstArray {
+    shape: Vec<usize>,
+    words: Vec<i32>,
+}
+
+impl ConstArray {
+    /// Reads the words of a constant aggregate. Returns None if it has a non-constant word.
+    fn new(
+        shape: Vec<usize>,
+        aggr: Value,
+        program: &mut Program,
+        my_ir_generator_info: &mut MyIRGeneratorInfo,
+    ) -> Option<ConstArray> {
+        let mut words = vec![];
+        let mut values = vec![aggr];
+        // Visit the elements in order, like a stack.
+        while let Some(value) = values.pop() {
+            let value_data = get_valuedata(value, program, my_ir_generator_info);
+            match value_data.kind() {
+                ValueKind::Integer(int) => words.push(int.value()),
+                ValueKind::ZeroInit(_) => words.extend(vec![0; value_data.ty().size() / 4]),
+                ValueKind::Aggregate(aggr) => values.extend(aggr.elems().iter().rev()),
+                _ => return None,
+            }
+        }
+        Some(ConstArray { shape, words })
+    }
+
+    /// The value of a constant index.
+    fn get_index(result: &IRExpBuildResult) -> Option<i64> {
+        match *result {
+            IRExpBuildResult::Const(int) => Some(int as i64),
+            IRExpBuildResult::LongLongConst(int) => Some(int),
+            _ => None,
+        }
+    }
+
+    /// Returns the element at constant indexes. Returns None if the indexes are not constants,
+    /// are out of bounds, or do not reach an element.
+    fn get(&self, indexes: &[IRExpBuildResult], btype: BTypeKind) -> Option<IRLValBuildResult> {
+        if indexes.len() != self.shape.len() {
+            return None;
+        }
+        let mut offset = 0;
+        for (result, &length) in indexes.iter().zip(&self.shape) {
+            let index = ConstArray::get_index(result)?;
+            if !(0..length as i64).contains(&index) {
+                return None;
+            }
+            offset = offset * length + index as usize;
+        }
+        Some(match btype {
+            BTypeKind::LongLong => {
+                let (low, high) = (self.words[2 * offset], self.words[2 * offset + 1]);
+                IRLValBuildResult::LongLongConst((high as i64) << 32 | low as u32 as i64)
+            }
+            BTypeKind::Float => {
+                IRLValBuildResult::FloatConst(f32::from_bits(self.words[offset] as u32))
+            }
+            _ => IRLValBuildResult::Const(self.words[offset]),
+        })
+    }
+
+    /// Returns the first constant index that is out of bounds, with its position and the length.
+    fn find_out_of_bounds(&self, indexes: &[IRExpBuildResult]) -> Option<(usize, i64, usize)> {
+        indexes
+            .iter()
+            .zip(&self.shape)
+            .enumerate()
+            .find_map(|(i, (result, &length))| {
+                let index = ConstArray::get_index(result)?;
+                (!(0..length as i64).contains(&index)).then_some((i, index, length))
+            })
+    }
 }
 
 impl std::fmt::Debug for SymbolTableEntry {
@@ -98,10 +754,12 @@ impl std::fmt::Debug for SymbolTableEntry {
         match self {
             SymbolTableEntry::Variable(tk, v) => write!(f, "Variable({}): {:?}", tk, v),
             SymbolTableEntry::Constant(tk, v) => write!(f, "Constant({}): {:?}", tk, v),
+            SymbolTableEntry::ConstArray(tk, v, _) => write!(f, "ConstArray({}): {:?}", tk, v),
         }
     }
 }
 
+#[allow(clippy::upper_case_acronyms)]
 pub enum IRBuildResult {
     OK,
     EARLYSTOPPING,
@@ -112,7 +770,7 @@ pub trait IRBuildable {
         &self,
         program: &mut Program,
         my_ir_generator_info: &mut MyIRGeneratorInfo,
-    ) -> Result<IRBuildResult, String>;
+    ) -> Result<IRBuildResult, CompileError>;
 }
 
 impl IRBuildable for CompUnit {
@@ -120,43 +778,46 @@ impl IRBuildable for CompUnit {
         &self,
         program: &mut Program,
         my_ir_generator_info: &mut MyIRGeneratorInfo,
-    ) -> Result<IRBuildResult, String> {
+    ) -> Result<IRBuildResult, CompileError> {
         // Declare all SysY library functions.
-        /*
-           decl @getint(): i32
-           decl @getch(): i32
-           decl @getarray(*i32): i32
-           decl @putint(i32)
-           decl @putch(i32)
-           decl @putarray(i32, *i32)
-           decl @starttime()
-           decl @stoptime()
-        */
-        let lib_functions = vec![
-            ("getint", vec![], Type::get_i32()),
-            ("getch", vec![], Type::get_i32()),
-            (
-                "getarray",
-                vec![Type::get_pointer(Type::get_i32())],
-                Type::get_i32(),
-            ),
-            ("putint", vec![Type::get_i32()], Type::get_unit()),
-            ("putch", vec![Type::get_i32()], Type::get_unit()),
-            (
-                "putarray",
-                vec![Type::get_i32(), Type::get_pointer(Type::get_i32())],
-                Type::get_unit(),
-            ),
-            ("starttime", vec![], Type::get_unit()),
-            ("stoptime", vec![], Type::get_unit()),
-        ];
-        for (name, params_ty, ret_ty) in lib_functions {
-            let function_data =
-                koopa::ir::FunctionData::new_decl(format!("@{}", name), params_ty, ret_ty);
+        for (name, params, return_type) in LIB_FUNCTIONS {
+            let params_ty = params
+                .iter()
+                .map(|&(param_type, is_array)| match is_array {
+                    true => Type::get_pointer(Type::get(param_type.to_type_kind())),
+                    false => Type::get(param_type.to_type_kind()),
+                })
+                .collect();
+            let function_data = koopa::ir::FunctionData::new_decl(
+                format!("@{}", name),
+                params_ty,
+                Type::get(return_type.to_type_kind()),
+            );
             let func = program.new_func(function_data);
-            my_ir_generator_info
-                .function_table
-                .insert(name.to_string(), func);
+            my_ir_generator_info.function_table.insert(
+                name.to_string(),
+                FunctionEntry {
+                    func,
+                    return_type,
+                    param_types: params.iter().map(|&(param_type, _)| param_type).collect(),
+                    // putf is the only variadic library function.
+                    is_variadic: name == "putf",
+                    is_prototype: false,
+                },
+            );
+        }
+        // Declare intrinsics. They must be declared before used.
+        for (name, param_types, return_type) in INTRINSICS {
+            let function_data = koopa::ir::FunctionData::new_decl(
+                format!("@{}", name),
+                param_types
+                    .iter()
+                    .map(|param_type| Type::get(param_type.to_type_kind()))
+                    .collect(),
+                Type::get(return_type.to_type_kind()),
+            );
+            let func = program.new_func(function_data);
+            my_ir_generator_info.intrinsics.insert(name, (func, false));
         }
 
         // Build every unit.
@@ -164,6 +825,13 @@ impl IRBuildable for CompUnit {
         for unit in units {
             unit.build(program, my_ir_generator_info)?;
         }
+
+        // Remove the intrinsics that are not used.
+        for &(func, is_used) in my_ir_generator_info.intrinsics.values() {
+            if !is_used {
+                program.remove_func(func);
+            }
+        }
         Ok(IRBuildResult::OK)
     }
 }
@@ -173,10 +841,37 @@ impl IRBuildable for Unit {
         &self,
         program: &mut Program,
         my_ir_generator_info: &mut MyIRGeneratorInfo,
-    ) -> Result<IRBuildResult, String> {
+    ) -> Result<IRBuildResult, CompileError> {
         match self {
             Unit::Decl(d) => d.build(program, my_ir_generator_info),
             Unit::FuncDef(f) => f.build(program, my_ir_generator_info),
+            Unit::FuncDecl(fs) => {
+                for f in fs {
+                    f.build(program, my_ir_generator_info)?;
+                }
+                Ok(IRBuildResult::OK)
+            }
+            Unit::ExternVarDecl(v) => build_extern_var_decl(v, program, my_ir_generator_info),
+            Unit::StaticDecl(d) => build_static_decl(d, program, my_ir_generator_info),
+            Unit::StaticFuncDef(f) => {
+                let declarations::FuncDef::Default(_, func_id, _, _, _) = f;
+                my_ir_generator_info.declare_static(func_id)?;
+                f.build(program, my_ir_generator_info)
+            }
+            Unit::StaticFuncDecl(fs) => {
+                for f in fs {
+                    let declarations::FuncDecl::Default(_, func_id, _, _, _) = f;
+                    my_ir_generator_info.declare_static(func_id)?;
+                    f.build(program, my_ir_generator_info)?;
+                }
+                Ok(IRBuildResult::OK)
+            }
+            Unit::StructDef(s) => s.build(program, my_ir_generator_info),
+            Unit::EnumDef(e) => e.build(program, my_ir_generator_info),
+            // Typedefs are expanded by the parser.
+            Unit::TypeDef(_) => Ok(IRBuildResult::OK),
+            // Syntax errors are reported before building IR.
+            Unit::Error(_) => Ok(IRBuildResult::OK),
         }
     }
 }
@@ -227,6 +922,22 @@ fn create_new_block<'a>(
     block
 }
 
+/// Helper function to get the block of a label, which is created by its first goto or the label.
+fn get_label_block(
+    program: &mut Program,
+    my_ir_generator_info: &mut MyIRGeneratorInfo,
+    label: &str,
+) -> BasicBlock {
+    if let Some(&block) = my_ir_generator_info.label_blocks.get(label) {
+        return block;
+    }
+    let block = create_new_block(program, my_ir_generator_info, &format!("label_{}", label));
+    my_ir_generator_info
+        .label_blocks
+        .insert(label.to_string(), block);
+    block
+}
+
 /// Helper function to insert instructions into the current function's data flow graph.
 fn insert_local_instructions<T>(
     program: &mut Program,
@@ -262,24 +973,574 @@ fn build_shape(
     shape_exps: &Vec<Exp>,
     program: &mut Program,
     my_ir_generator_info: &mut MyIRGeneratorInfo,
-) -> Result<Vec<usize>, String> {
+) -> Result<Vec<usize>, CompileError> {
     let mut result = vec![];
     for exp in shape_exps {
-        match exp.build(program, my_ir_generator_info)? {
-            IRExpBuildResult::Const(int) => result.push(int as usize),
-            IRExpBuildResult::Value(_) => {
-                return Err(format!("The shape of array must be constant! "))
+        let length = match build_const_exp(exp, program, my_ir_generator_info)? {
+            IRExpBuildResult::Const(int) => int as i64,
+            IRExpBuildResult::LongLongConst(int) => int,
+            IRExpBuildResult::FloatConst(_) | IRExpBuildResult::StringConst(_) => {
+                return Err(CompileError::new(
+                    ErrorCode::BackendInternal,
+                    "The length of array must be an int! ".to_string(),
+                    exp.span(),
+                ))
+            }
+            IRExpBuildResult::Value(_)
+            | IRExpBuildResult::FloatValue(_)
+            | IRExpBuildResult::LongLongValue(_) => {
+                return Err(CompileError::new(
+                    ErrorCode::NonConstantExpression,
+                    "The shape of array must be constant! ".to_string(),
+                    exp.span(),
+                ))
             }
+        };
+        if length <= 0 {
+            return Err(CompileError::new(
+                ErrorCode::InvalidArraySize,
+                format!("The length of array must be positive, but got {}! ", length),
+                exp.span(),
+            ));
         }
+        result.push(length as usize);
     }
     Ok(result.clone())
 }
 
-fn get_array_type(btype: &BType, shape: &[usize]) -> TypeKind {
+/// Builds an expression that must be a constant, like the length of an array or a case label.
+/// An element of a constant array out of bounds is reported then, because it is not a constant.
+/// Out of constant expressions, it is read at run time like in C.
+fn build_const_exp(
+    exp: &Exp,
+    program: &mut Program,
+    my_ir_generator_info: &mut MyIRGeneratorInfo,
+) -> Result<IRExpBuildResult, CompileError> {
+    let is_const_exp = std::mem::replace(&mut my_ir_generator_info.is_const_exp, true);
+    let result = exp.build(program, my_ir_generator_info);
+    my_ir_generator_info.is_const_exp = is_const_exp;
+    result
+}
+
+/// Declares the intrinsic that casts a pointer into the given pointer type.
+/// Koopa IR has no casts, so every pointer type has its own "@__ptr_cast_n", which keeps the address.
+/// It takes a pointer of any type, so it declares no params (Koopa IR allows more args).
+fn declare_pointer_cast(
+    ty: &Type,
+    program: &mut Program,
+    my_ir_generator_info: &mut MyIRGeneratorInfo,
+) {
+    if my_ir_generator_info.pointer_casts.contains_key(ty) {
+        return;
+    }
+    let function_data = koopa::ir::FunctionData::new_decl(
+        format!("@__ptr_cast_{}", my_ir_generator_info.pointer_casts.len()),
+        vec![],
+        ty.clone(),
+    );
+    let func = program.new_func(function_data);
+    my_ir_generator_info.pointer_casts.insert(ty.clone(), func);
+}
+
+/// Casts a pointer into the given pointer type. The cast must have been declared.
+fn cast_pointer(
+    pointer: Value,
+    ty: &Type,
+    program: &mut Program,
+    my_ir_generator_info: &mut MyIRGeneratorInfo,
+) -> Result<Value, CompileError> {
+    let Some(&func) = my_ir_generator_info.pointer_casts.get(ty) else {
+        return Err(CompileError::without_span(
+            ErrorCode::BackendInternal,
+            format!("The pointer cast to {} is not declared! ", ty),
+        ));
+    };
+    let call_inst = create_new_local_value(program, my_ir_generator_info).call(func, vec![pointer]);
+    insert_local_instructions(program, my_ir_generator_info, [call_inst]);
+    Ok(call_inst)
+}
+
+/// Calls an intrinsic, and returns the result.
+fn call_intrinsic(
+    name: &'static str,
+    args: Vec<Value>,
+    program: &mut Program,
+    my_ir_generator_info: &mut MyIRGeneratorInfo,
+) -> Result<Value, CompileError> {
+    let Some((func, is_used)) = my_ir_generator_info.intrinsics.get_mut(name) else {
+        return Err(CompileError::without_span(
+            ErrorCode::BackendInternal,
+            format!("Unknown intrinsic '{}'! ", name),
+        ));
+    };
+    *is_used = true;
+    let func = *func;
+    let call_inst = create_new_local_value(program, my_ir_generator_info).call(func, args);
+    insert_local_instructions(program, my_ir_generator_info, [call_inst]);
+    Ok(call_inst)
+}
+
+/// Returns the global array of a string literal, ending with '\0'. Equal strings share an array.
+/// Library functions read strings as bytes, so every i32 of the array holds 4 bytes
+/// in little-endian order, and the array is as large as the bytes in memory.
+fn get_string_constant(
+    bytes: &[u8],
+    program: &mut Program,
+    my_ir_generator_info: &mut MyIRGeneratorInfo,
+) -> Value {
+    if let Some(&string) = my_ir_generator_info.string_constants.get(bytes) {
+        return string;
+    }
+    let mut bytes_with_nul = bytes.to_vec();
+    bytes_with_nul.push(0);
+    let elems = bytes_with_nul
+        .chunks(4)
+        .map(|word| {
+            let mut word_bytes = [0; 4];
+            word_bytes[..word.len()].copy_from_slice(word);
+            program.new_value().integer(i32::from_le_bytes(word_bytes))
+        })
+        .collect();
+    let aggr = program.new_value().aggregate(elems);
+    let string = program.new_value().global_alloc(aggr);
+    let name = format!("@__str_{}", my_ir_generator_info.string_constants.len());
+    program.set_value_name(string, Some(name));
+    my_ir_generator_info
+        .string_constants
+        .insert(bytes.to_vec(), string);
+    string
+}
+
+/// Converts a constant into the given type. A float is returned as its bits.
+/// A long long takes 2 words, so it is converted by get_const_long_long instead.
+fn get_const_bits(result: &IRExpBuildResult, btype: BTypeKind) -> Option<i32> {
+    match (result, btype) {
+        (_, BTypeKind::LongLong) => None,
+        (IRExpBuildResult::LongLongConst(int), BTypeKind::Float) => {
+            Some((*int as f32).to_bits() as i32)
+        }
+        (IRExpBuildResult::LongLongConst(int), BTypeKind::Char) => Some(*int as u8 as i32),
+        (IRExpBuildResult::LongLongConst(int), _) => Some(*int as i32),
+        (IRExpBuildResult::Const(int), BTypeKind::Float) => Some((*int as f32).to_bits() as i32),
+        (IRExpBuildResult::Const(int), BTypeKind::Char) => Some(*int as u8 as i32),
+        (IRExpBuildResult::Const(int), _) => Some(*int),
+        (IRExpBuildResult::FloatConst(float), BTypeKind::Float) => Some(float.to_bits() as i32),
+        (IRExpBuildResult::FloatConst(float), BTypeKind::Char) => Some(*float as i32 as u8 as i32),
+        (IRExpBuildResult::FloatConst(float), _) => Some(*float as i32),
+        _ => None,
+    }
+}
+
+/// Converts a constant into a long long.
+fn get_const_long_long(result: &IRExpBuildResult) -> Option<i64> {
+    match *result {
+        IRExpBuildResult::Const(int) => Some(int as i64),
+        IRExpBuildResult::LongLongConst(int) => Some(int),
+        IRExpBuildResult::FloatConst(float) => Some(float as i64),
+        _ => None,
+    }
+}
+
+/// Makes a long long constant, which is an aggregate of its low word and its high word.
+fn new_long_long(
+    int: i64,
+    is_global: bool,
+    program: &mut Program,
+    my_ir_generator_info: &mut MyIRGeneratorInfo,
+) -> Value {
+    let words = [int as i32, (int >> 32) as i32];
+    match is_global {
+        true => {
+            let elems = words.map(|word| program.new_value().integer(word));
+            program.new_value().aggregate(elems.to_vec())
+        }
+        false => {
+            let elems = words
+                .map(|word| create_new_local_value(program, my_ir_generator_info).integer(word));
+            create_new_local_value(program, my_ir_generator_info).aggregate(elems.to_vec())
+        }
+    }
+}
+
+/// Checks that the Value is not an array or the result of a void function.
+fn check_scalar_value(
+    value: Value,
+    btype: BTypeKind,
+    span: Span,
+    program: &mut Program,
+    my_ir_generator_info: &mut MyIRGeneratorInfo,
+) -> Result<Value, CompileError> {
+    let value_type = get_valuedata(value, program, my_ir_generator_info)
+        .ty()
+        .clone();
+    let scalar_type = match btype {
+        BTypeKind::LongLong => Type::get(btype.to_type_kind()),
+        _ => Type::get_i32(),
+    };
+    if value_type == scalar_type {
+        Ok(value)
+    } else {
+        Err(CompileError::new(
+            ErrorCode::BackendInternal,
+            format!("Expected {} here, but got {}! ", btype, value_type),
+            span,
+        ))
+    }
+}
+
+/// Converts an expression building result into an i32 Value.
+/// Floats and long longs are converted into ints.
+/// Arrays and results of void functions are not ints, so they are rejected here.
+fn get_int_value(
+    result: IRExpBuildResult,
+    span: Span,
+    program: &mut Program,
+    my_ir_generator_info: &mut MyIRGeneratorInfo,
+) -> Result<Value, CompileError> {
+    match result {
+        IRExpBuildResult::Const(_)
+        | IRExpBuildResult::FloatConst(_)
+        | IRExpBuildResult::LongLongConst(_) => {
+            let int = get_const_bits(&result, BTypeKind::Int).unwrap();
+            Ok(create_new_local_value(program, my_ir_generator_info).integer(int))
+        }
+        IRExpBuildResult::Value(value) | IRExpBuildResult::StringConst(value) => {
+            check_scalar_value(value, BTypeKind::Int, span, program, my_ir_generator_info)
+        }
+        IRExpBuildResult::FloatValue(value) => {
+            let value =
+                check_scalar_value(value, BTypeKind::Float, span, program, my_ir_generator_info)?;
+            call_intrinsic("__float_to_int", vec![value], program, my_ir_generator_info)
+        }
+        IRExpBuildResult::LongLongValue(value) => {
+            let value = check_scalar_value(
+                value,
+                BTypeKind::LongLong,
+                span,
+                program,
+                my_ir_generator_info,
+            )?;
+            call_intrinsic(
+                "__long_long_to_int",
+                vec![value],
+                program,
+                my_ir_generator_info,
+            )
+        }
+    }
+}
+
+/// Converts an expression building result into the bits of a float.
+/// Ints and long longs are converted into floats.
+fn get_float_value(
+    result: IRExpBuildResult,
+    span: Span,
+    program: &mut Program,
+    my_ir_generator_info: &mut MyIRGeneratorInfo,
+) -> Result<Value, CompileError> {
+    match result {
+        IRExpBuildResult::Const(_)
+        | IRExpBuildResult::FloatConst(_)
+        | IRExpBuildResult::LongLongConst(_) => {
+            let bits = get_const_bits(&result, BTypeKind::Float).unwrap();
+            Ok(create_new_local_value(program, my_ir_generator_info).integer(bits))
+        }
+        IRExpBuildResult::Value(value) => {
+            let value =
+                check_scalar_value(value, BTypeKind::Int, span, program, my_ir_generator_info)?;
+            call_intrinsic("__int_to_float", vec![value], program, my_ir_generator_info)
+        }
+        IRExpBuildResult::FloatValue(value) | IRExpBuildResult::StringConst(value) => {
+            check_scalar_value(value, BTypeKind::Float, span, program, my_ir_generator_info)
+        }
+        IRExpBuildResult::LongLongValue(value) => {
+            let value = check_scalar_value(
+                value,
+                BTypeKind::LongLong,
+                span,
+                program,
+                my_ir_generator_info,
+            )?;
+            call_intrinsic(
+                "__long_long_to_float",
+                vec![value],
+                program,
+                my_ir_generator_info,
+            )
+        }
+    }
+}
+
+/// Converts an expression building result into a long long Value.
+/// Ints and floats are converted into long longs.
+fn get_long_long_value(
+    result: IRExpBuildResult,
+    span: Span,
+    program: &mut Program,
+    my_ir_generator_info: &mut MyIRGeneratorInfo,
+) -> Result<Value, CompileError> {
+    if let Some(int) = get_const_long_long(&result) {
+        return Ok(new_long_long(int, false, program, my_ir_generator_info));
+    }
+    match result {
+        IRExpBuildResult::LongLongValue(value) => check_scalar_value(
+            value,
+            BTypeKind::LongLong,
+            span,
+            program,
+            my_ir_generator_info,
+        ),
+        IRExpBuildResult::FloatValue(value) => {
+            let value =
+                check_scalar_value(value, BTypeKind::Float, span, program, my_ir_generator_info)?;
+            call_intrinsic(
+                "__float_to_long_long",
+                vec![value],
+                program,
+                my_ir_generator_info,
+            )
+        }
+        result => {
+            let value = get_int_value(result, span, program, my_ir_generator_info)?;
+            call_intrinsic(
+                "__int_to_long_long",
+                vec![value],
+                program,
+                my_ir_generator_info,
+            )
+        }
+    }
+}
+
+/// Converts an expression building result into a Value of the given type.
+fn get_typed_value(
+    result: IRExpBuildResult,
+    btype: BTypeKind,
+    span: Span,
+    program: &mut Program,
+    my_ir_generator_info: &mut MyIRGeneratorInfo,
+) -> Result<Value, CompileError> {
+    match btype {
+        BTypeKind::Float => get_float_value(result, span, program, my_ir_generator_info),
+        BTypeKind::LongLong => get_long_long_value(result, span, program, my_ir_generator_info),
+        // A char keeps the lowest byte. Chars are unsigned in RISC-V.
+        BTypeKind::Char => match get_const_bits(&result, btype) {
+            Some(int) => Ok(create_new_local_value(program, my_ir_generator_info).integer(int)),
+            None => {
+                let value = get_int_value(result, span, program, my_ir_generator_info)?;
+                let mask = create_new_local_value(program, my_ir_generator_info).integer(0xff);
+                let char_value = create_new_local_value(program, my_ir_generator_info).binary(
+                    koopa::ir::BinaryOp::And,
+                    value,
+                    mask,
+                );
+                insert_local_instructions(program, my_ir_generator_info, [char_value]);
+                Ok(char_value)
+            }
+        },
+        _ => get_int_value(result, span, program, my_ir_generator_info),
+    }
+}
+
+/// Returns the Value of a pointer. Arrays have been converted into pointers to their elements.
+/// A string literal is not a pointer here, because its chars are bytes.
+fn get_pointer(
+    result: &IRExpBuildResult,
+    program: &mut Program,
+    my_ir_generator_info: &mut MyIRGeneratorInfo,
+) -> Option<Value> {
+    match *result {
+        IRExpBuildResult::Value(value)
+        | IRExpBuildResult::FloatValue(value)
+        | IRExpBuildResult::LongLongValue(value) => {
+            match get_valuedata(value, program, my_ir_generator_info)
+                .ty()
+                .kind()
+            {
+                TypeKind::Pointer(_) => Some(value),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Converts an expression building result into a pointer Value of the given type.
+/// The constant 0 is the null pointer.
+/// Koopa IR can not tell float pointers from int pointers, so the base type is checked too.
+fn get_pointer_value(
+    result: IRExpBuildResult,
+    pointer_type: &Type,
+    btype: BTypeKind,
+    span: Span,
+    program: &mut Program,
+    my_ir_generator_info: &mut MyIRGeneratorInfo,
+) -> Result<Value, CompileError> {
+    if let IRExpBuildResult::Const(0) = result {
+        return Ok(
+            create_new_local_value(program, my_ir_generator_info).zero_init(pointer_type.clone())
+        );
+    }
+    let is_float = result.is_float();
+    if let Some(value) = get_pointer(&result, program, my_ir_generator_info) {
+        let value_type = get_valuedata(value, program, my_ir_generator_info)
+            .ty()
+            .clone();
+        if value_type == *pointer_type && is_float == (btype == BTypeKind::Float) {
+            return Ok(value);
+        }
+    }
+    Err(CompileError::new(
+        ErrorCode::BackendInternal,
+        format!("Expected a pointer to {} ({}) here! ", btype, pointer_type),
+        span,
+    ))
+}
+
+/// Converts an expression building result into the type of the variable at the address.
+fn get_value_for_addr(
+    result: IRExpBuildResult,
+    addr: Value,
+    btype: BTypeKind,
+    span: Span,
+    program: &mut Program,
+    my_ir_generator_info: &mut MyIRGeneratorInfo,
+) -> Result<Value, CompileError> {
+    match get_valuedata(addr, program, my_ir_generator_info)
+        .ty()
+        .kind()
+    {
+        TypeKind::Pointer(base_type) if matches!(base_type.kind(), TypeKind::Pointer(_)) => {
+            get_pointer_value(
+                result,
+                &base_type.clone(),
+                btype,
+                span,
+                program,
+                my_ir_generator_info,
+            )
+        }
+        _ => get_typed_value(result, btype, span, program, my_ir_generator_info),
+    }
+}
+
+/// Converts a condition into an i32 Value, which is not 0 if the condition is true.
+/// A float condition is true if it is not 0.0, so it is compared with 0.0 instead of converted.
+/// A long long condition is compared with 0 too, because its high word counts.
+/// A pointer condition is true if it is not null.
+fn get_cond_value(
+    result: IRExpBuildResult,
+    span: Span,
+    program: &mut Program,
+    my_ir_generator_info: &mut MyIRGeneratorInfo,
+) -> Result<Value, CompileError> {
+    if let Some(pointer) = get_pointer(&result, program, my_ir_generator_info) {
+        return call_intrinsic("__ptr_to_int", vec![pointer], program, my_ir_generator_info);
+    }
+    match result {
+        IRExpBuildResult::FloatConst(float) => Ok(create_new_local_value(
+            program,
+            my_ir_generator_info,
+        )
+        .integer((float != 0.0) as i32)),
+        IRExpBuildResult::FloatValue(value) => {
+            let value =
+                check_scalar_value(value, BTypeKind::Float, span, program, my_ir_generator_info)?;
+            let zero = create_new_local_value(program, my_ir_generator_info).integer(0);
+            call_intrinsic(
+                "__float_ne",
+                vec![value, zero],
+                program,
+                my_ir_generator_info,
+            )
+        }
+        IRExpBuildResult::LongLongConst(int) => {
+            Ok(create_new_local_value(program, my_ir_generator_info).integer((int != 0) as i32))
+        }
+        IRExpBuildResult::LongLongValue(value) => {
+            let value = check_scalar_value(
+                value,
+                BTypeKind::LongLong,
+                span,
+                program,
+                my_ir_generator_info,
+            )?;
+            let zero = new_long_long(0, false, program, my_ir_generator_info);
+            call_intrinsic(
+                "__long_long_ne",
+                vec![value, zero],
+                program,
+                my_ir_generator_info,
+            )
+        }
+        _ => get_int_value(result, span, program, my_ir_generator_info),
+    }
+}
+
+/// Returns the return type of the current function.
+/// It is found by the function, because the name of a static function is changed in Koopa IR.
+fn get_curr_return_type(my_ir_generator_info: &MyIRGeneratorInfo) -> BTypeKind {
+    my_ir_generator_info
+        .function_table
+        .values()
+        .find(|entry| Some(entry.func) == my_ir_generator_info.curr_func)
+        .expect("The current function is not in the function table! ")
+        .return_type
+}
+
+/// Variables and parameters can not be void.
+fn check_not_void(btype: &BType) -> Result<(), CompileError> {
+    match btype.content {
+        BTypeKind::Void => Err(CompileError::new(
+            ErrorCode::VoidVariable,
+            "Variables and parameters can not be void! ".to_string(),
+            btype.span,
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Returns the Koopa IR type of a struct, which is an array of its words.
+/// Returns None if the type is not a struct, or the struct is not defined.
+fn get_struct_type(btype: BTypeKind, my_ir_generator_info: &MyIRGeneratorInfo) -> Option<Type> {
+    match btype {
+        BTypeKind::Struct(name) => my_ir_generator_info
+            .struct_table
+            .get(name)
+            .map(|entry| Type::get_array(Type::get_i32(), entry.size)),
+        _ => None,
+    }
+}
+
+/// Each "*" makes a pointer to the base type, and the shape makes an array of the pointers.
+/// The span is the base type's span, used when the struct is not defined.
+fn get_array_type(
+    btype: BTypeKind,
+    ptr_depth: usize,
+    shape: &[usize],
+    span: Span,
+    my_ir_generator_info: &MyIRGeneratorInfo,
+) -> Result<TypeKind, CompileError> {
     if shape.is_empty() {
-        return btype.content.clone();
+        let mut typekind = match btype {
+            BTypeKind::Struct(name) => match get_struct_type(btype, my_ir_generator_info) {
+                Some(struct_type) => struct_type.kind().clone(),
+                None => {
+                    return Err(CompileError::new(
+                        ErrorCode::UndeclaredSymbol,
+                        format!("Undefined struct: {}", name),
+                        span,
+                    ))
+                }
+            },
+            _ => btype.to_type_kind(),
+        };
+        for _ in 0..ptr_depth {
+            typekind = TypeKind::Pointer(Type::get(typekind));
+        }
+        return Ok(typekind);
     }
-    let inner_typekind = get_array_type(btype, &shape[1..]);
-    TypeKind::Array(Type::get(inner_typekind), shape[0])
+    let inner_typekind = get_array_type(btype, ptr_depth, &shape[1..], span, my_ir_generator_info)?;
+    Ok(TypeKind::Array(Type::get(inner_typekind), shape[0]))
 }
 
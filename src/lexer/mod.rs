@@ -0,0 +1,185 @@
+//! The lexer of my compiler. It splits the preprocessed source into tokens for the parser.
+//!
+//! Tokens are matched like in the lexer lalrpop generates: the longest match wins,
+//! and a keyword or punctuation wins over a literal or identifier of the same length.
+//!
+//! It also does the lexer hack of C: an identifier declared by typedef is a type name,
+//! so that the parser can tell "T * p;" (a declaration) from "a * b;" (an expression).
+//! The parser declares the name before it asks for the token after the typedef.
+
+use std::sync::OnceLock;
+
+use lalrpop_util::lexer::{Matcher, MatcherBuilder, Token};
+use lalrpop_util::ParseError;
+
+use crate::ast_def::declarations::TypeAliases;
+use crate::ast_def::symbols::Span;
+use crate::diagnostics::{CompileError, ErrorCode};
+
+/// A token. The literals keep their text, which the parser converts to values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tok<'input> {
+    Keyword(&'input str),
+    Punct(&'input str),
+    Ident(&'input str),
+    TypeName(&'input str), // An identifier declared by typedef.
+    Int(&'input str),
+    LongLong(&'input str), // An integer with the suffix "ll" or "LL".
+    Float(&'input str),
+    Char(&'input str),
+    String(&'input str),
+}
+
+impl std::fmt::Display for Tok<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Tok::Keyword(text)
+            | Tok::Punct(text)
+            | Tok::Ident(text)
+            | Tok::TypeName(text)
+            | Tok::Int(text)
+            | Tok::LongLong(text)
+            | Tok::Float(text)
+            | Tok::Char(text)
+            | Tok::String(text) => write!(f, "{}", text),
+        }
+    }
+}
+
+/// What a pattern of the lexer matches.
+#[derive(Clone, Copy)]
+enum Pattern {
+    Skip, // Spaces and comments.
+    Ident,
+    Int,
+    LongLong,
+    Float,
+    Char,
+    String,
+    Fixed, // A keyword or punctuation.
+}
+
+// Keep them the same as the terminals in sysy.lalrpop.
+const KEYWORDS: [&str; 23] = [
+    "break", "case", "char", "const", "continue", "default", "do", "else", "enum", "extern",
+    "float", "for", "goto", "if", "int", "long", "return", "static", "struct", "switch", "typedef",
+    "void", "while",
+];
+
+const PUNCTS: [&str; 46] = [
+    "!", "!=", "%", "%=", "&", "&&", "&=", "(", ")", "*", "*=", "+", "++", "+=", ",", "-", "--",
+    "-=", "->", ".", "...", "/", "/=", ":", ";", "<", "<<", "<<=", "<=", "=", "==", ">", ">=",
+    ">>", ">>=", "?", "[", "]", "^", "^=", "{", "|", "|=", "||", "}", "~",
+];
+
+const INT: &str = r"[1-9][0-9]*|0[0-7]*|0[xX][0-9a-fA-F]+";
+
+/// The patterns in the order of priority, the last one first.
+fn patterns() -> Vec<(String, Pattern)> {
+    let mut patterns = vec![
+        (r"\s*".to_string(), Pattern::Skip),
+        (r"//[^\n\r]*[\n\r]*".to_string(), Pattern::Skip),
+        (r"/\*([^*]|(\*+([^*/])))*\*+/".to_string(), Pattern::Skip),
+        (r"[_a-zA-Z][_a-zA-Z0-9]*".to_string(), Pattern::Ident),
+        (INT.to_string(), Pattern::Int),
+        (format!("({})(ll|LL)", INT), Pattern::LongLong),
+        (
+            r"([0-9]*\.[0-9]+|[0-9]+\.)([eE][+-]?[0-9]+)?|[0-9]+[eE][+-]?[0-9]+".to_string(),
+            Pattern::Float,
+        ),
+        (
+            r"0[xX]([0-9a-fA-F]*\.[0-9a-fA-F]+|[0-9a-fA-F]+\.?)[pP][+-]?[0-9]+".to_string(),
+            Pattern::Float,
+        ),
+        (
+            r#"'([^'\\\n]|\\([ntrabfv\\'"?]|x[0-9a-fA-F]+|[0-7]{1,3}))'"#.to_string(),
+            Pattern::Char,
+        ),
+        (
+            r#""([^"\\\n]|\\([ntrabfv\\'"?]|x[0-9a-fA-F]+|[0-7]{1,3}))*""#.to_string(),
+            Pattern::String,
+        ),
+    ];
+    for fixed in KEYWORDS.iter().chain(PUNCTS.iter()) {
+        let escaped: String = fixed
+            .chars()
+            .map(|c| match r"\.+*?()|[]{}^$#&-~".contains(c) {
+                true => format!(r"\{}", c),
+                false => c.to_string(),
+            })
+            .collect();
+        patterns.push((escaped, Pattern::Fixed));
+    }
+    patterns
+}
+
+/// The patterns and the matcher built from them, which are built once.
+fn matcher_builder() -> &'static (Vec<Pattern>, MatcherBuilder) {
+    static BUILDER: OnceLock<(Vec<Pattern>, MatcherBuilder)> = OnceLock::new();
+    BUILDER.get_or_init(|| {
+        let (regexes, kinds): (Vec<String>, Vec<Pattern>) = patterns().into_iter().unzip();
+        let builder = MatcherBuilder::new(
+            regexes
+                .iter()
+                .zip(kinds.iter())
+                .map(|(regex, kind)| (regex, matches!(kind, Pattern::Skip))),
+        )
+        .unwrap();
+        (kinds, builder)
+    })
+}
+
+pub struct Lexer<'input, 'aliases> {
+    matcher: Matcher<'input, 'static, CompileError>,
+    type_aliases: &'aliases TypeAliases,
+}
+
+impl<'input, 'aliases> Lexer<'input, 'aliases> {
+    /// Identifiers in `type_aliases` are type names.
+    /// The parser adds to it while the lexer runs, and the lexer opens and closes its scopes.
+    pub fn new(text: &'input str, type_aliases: &'aliases TypeAliases) -> Self {
+        Lexer {
+            matcher: matcher_builder().1.matcher(text),
+            type_aliases,
+        }
+    }
+}
+
+impl<'input> Iterator for Lexer<'input, '_> {
+    type Item = Result<(usize, Tok<'input>, usize), CompileError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (l, Token(index, text), r) = match self.matcher.next()? {
+            Ok(triple) => triple,
+            Err(ParseError::InvalidToken { location }) => {
+                return Some(Err(CompileError::new(
+                    ErrorCode::InvalidToken,
+                    "Invalid token! ".to_string(),
+                    Span::new(location, location + 1),
+                )))
+            }
+            Err(_) => unreachable!("The matcher only reports invalid tokens"),
+        };
+        let tok = match matcher_builder().0[index] {
+            Pattern::Ident if self.type_aliases.is_type_name(text) => Tok::TypeName(text),
+            Pattern::Ident => Tok::Ident(text),
+            Pattern::Int => Tok::Int(text),
+            Pattern::LongLong => Tok::LongLong(text),
+            Pattern::Float => Tok::Float(text),
+            Pattern::Char => Tok::Char(text),
+            Pattern::String => Tok::String(text),
+            Pattern::Fixed if text.starts_with(|c: char| c.is_ascii_alphabetic()) => {
+                Tok::Keyword(text)
+            }
+            Pattern::Fixed => Tok::Punct(text),
+            Pattern::Skip => unreachable!("The matcher skips spaces and comments"),
+        };
+        match tok {
+            Tok::Punct("{") => self.type_aliases.open_scope(),
+            Tok::Punct("}") => self.type_aliases.close_scope(),
+            Tok::Punct(";") => self.type_aliases.forget_params(),
+            _ => {}
+        }
+        Some(Ok((l, tok, r)))
+    }
+}
@@ -1,40 +1,167 @@
 mod assembly_builder;
 mod ast_def;
+mod diagnostics;
 mod ir_builder;
+mod lexer;
+mod preprocessor;
+mod type_checker;
+
+use assembly_builder::Target;
+use diagnostics::{CompileError, ErrorKind, SourceFile, Warning, WarningFlag};
+
+use std::io::Write;
+use std::path::PathBuf;
 
 use koopa::back::KoopaGenerator;
 use lalrpop_util::lalrpop_mod;
 
 // 引用 lalrpop 生成的解析器
 // 因为我们刚刚创建了 sysy.lalrpop, 所以模块名是 sysy
-lalrpop_mod!(sysy);
+lalrpop_mod!(
+    #[allow(clippy::all)]
+    sysy
+);
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Compiler configuration:
-    // Set the pointer size in our compiled code to 4. (32 bits)
-    koopa::ir::Type::set_ptr_size(4);
+const USAGE: &str = "Usage: compiler-lab (-koopa | -riscv | -perf) <input> -o <output> \
+    [-march=rv32 | -march=rv64] [-I<dir>]... [-D<name>[=<value>]]... [-W[no-]<warning> | -Wall]...";
+
+/// Prints the errors and exits. The exit status tells the kind of the first error.
+fn report_and_exit(source_file: &SourceFile, errors: &[CompileError]) -> ! {
+    for error in errors {
+        eprintln!("{}\n", source_file.render(error));
+    }
+    if errors.len() > 1 {
+        eprintln!("error: aborting due to {} previous errors", errors.len());
+    }
+    std::process::exit(match errors[0].kind() {
+        ErrorKind::Parse => 2,
+        ErrorKind::Semantic => 3,
+        ErrorKind::Type => 4,
+        ErrorKind::UnsupportedIR => 5,
+        ErrorKind::BackendInternal => 6,
+    })
+}
+
+/// Prints the warnings whose flags are enabled.
+fn report_warnings(
+    source_file: &SourceFile,
+    warnings: &[Warning],
+    enabled_warnings: &[WarningFlag],
+) {
+    for warning in warnings {
+        if enabled_warnings.contains(&warning.flag) {
+            eprintln!("{}\n", source_file.render_warning(warning));
+        }
+    }
+}
 
+fn main() -> Result<(), Box<dyn std::error::Error>> {
     // 解析命令行参数
-    let mut args = std::env::args();
-    args.next();
-    let mode = args.next().unwrap();
-    let input = args.next().unwrap();
+    // -march, -I, -D and -W may be anywhere. The target is RV32 by default.
+    let (options, args): (Vec<String>, Vec<String>) = std::env::args().skip(1).partition(|arg| {
+        ["-march=", "-I", "-D", "-W"]
+            .iter()
+            .any(|prefix| arg.starts_with(prefix))
+    });
+    let mut target = Target::RV32;
+    let mut preprocessor_options = preprocessor::Options {
+        include_dirs: vec![],
+        defines: vec![],
+    };
+    let mut enabled_warnings = vec![];
+    for option in options {
+        if let Some(march) = option.strip_prefix("-march=") {
+            target =
+                Target::from_march(march).ok_or(format!("Unknown target {}! {}", option, USAGE))?;
+        } else if let Some(dir) = option.strip_prefix("-I") {
+            preprocessor_options.include_dirs.push(PathBuf::from(dir));
+        } else if let Some(define) = option.strip_prefix("-D") {
+            preprocessor_options.defines.push(define.to_string());
+        } else if let Some(name) = option.strip_prefix("-W") {
+            // -W<warning> enables a warning, and -Wno-<warning> disables it.
+            // -Wall stands for all warnings in it.
+            let (is_enabled, name) = match name.strip_prefix("no-") {
+                Some(name) => (false, name),
+                None => (true, name),
+            };
+            let flags = match name {
+                "all" => WarningFlag::ALL
+                    .into_iter()
+                    .filter(|flag| flag.is_in_wall())
+                    .collect(),
+                _ => vec![WarningFlag::from_name(name)
+                    .ok_or(format!("Unknown warning {}! {}", option, USAGE))?],
+            };
+            enabled_warnings.retain(|enabled_flag| !flags.contains(enabled_flag));
+            if is_enabled {
+                enabled_warnings.extend(flags);
+            }
+        }
+    }
+    let mut args = args.into_iter();
+    let mode = args.next().ok_or(USAGE)?;
+    let input_path = args.next().ok_or(USAGE)?;
     args.next();
-    let output = args.next().unwrap();
+    let output = args.next().ok_or(USAGE)?;
 
     // 读取输入文件
-    let input = std::fs::read_to_string(input)?;
+    let input = std::fs::read_to_string(&input_path)?;
+
+    // Preprocess the input: #include, #define and conditional compilation.
+    // Errors in the parser and later are reported at the file and line the code comes from.
+    let (source_file, preprocess_errors) =
+        preprocessor::preprocess(input_path, input, &preprocessor_options);
+    if !preprocess_errors.is_empty() {
+        report_and_exit(&source_file, &preprocess_errors);
+    }
 
     // 调用 lalrpop 生成的 parser 解析输入文件
-    let ast = sysy::CompUnitParser::new()
-        .parse(&input)
-        .expect("Parse error");
+    // The parser recovers from syntax errors, so that all of them are reported at once.
+    // It shares the typedefs with the lexer, which tells type names from identifiers by them.
+    let mut recovered_errors = vec![];
+    let type_aliases = ast_def::declarations::TypeAliases::default();
+    let lexer = lexer::Lexer::new(source_file.text(), &type_aliases);
+    let result = sysy::CompUnitParser::new().parse(&mut recovered_errors, &type_aliases, lexer);
+    let mut syntax_errors: Vec<CompileError> = recovered_errors
+        .into_iter()
+        .map(|recovery| diagnostics::from_parse_error(recovery.error))
+        .collect();
+    let ast = match result {
+        Ok(ast) if syntax_errors.is_empty() => ast,
+        Ok(_) => report_and_exit(&source_file, &syntax_errors),
+        Err(error) => {
+            syntax_errors.push(diagnostics::from_parse_error(error));
+            report_and_exit(&source_file, &syntax_errors)
+        }
+    };
 
     // 输出解析得到的 AST
     // dbg!("AST:\n{:#?}", &ast);
 
+    // Compiler configuration:
+    // Set the pointer size in our compiled code to the target's. (32 or 64 bits)
+    koopa::ir::Type::set_ptr_size(target.ptr_size());
+
+    // Check the types of all expressions before generating IR, and report all type errors at once.
+    // Warnings are shown before the errors, if any.
+    let mut warnings = vec![];
+    let type_result = type_checker::check_types(&ast, target, &mut warnings);
+    report_warnings(&source_file, &warnings, &enabled_warnings);
+    let exp_types = match type_result {
+        Ok(exp_types) => exp_types,
+        Err(type_errors) => report_and_exit(&source_file, &type_errors),
+    };
+
     // Generate in-memory Koopa IR (struct Program) using my IR builder.
-    let ir: koopa::ir::Program = ir_builder::generate_ir(&ast)?;
+    // Semantic errors are reported with their location in the source file.
+    // Warnings are shown before the error that stops the IR builder, if any.
+    let mut warnings = vec![];
+    let ir_result = ir_builder::generate_ir(&ast, exp_types, &mut warnings);
+    report_warnings(&source_file, &warnings, &enabled_warnings);
+    let (ir, program_info) = match ir_result {
+        Ok(result) => result,
+        Err(error) => report_and_exit(&source_file, &[error]),
+    };
 
     match mode.as_str() {
         // Convert in-memory Koopa IR to text, and write it to output file (hello.koopa).
@@ -45,14 +172,196 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             Ok(())
         }
         "-riscv" | "-perf" => {
+            let assembly_codes =
+                match assembly_builder::generate_assembly(&ir, &program_info, target) {
+                    Ok(codes) => codes,
+                    Err(error) => report_and_exit(&source_file, &[error]),
+                };
             let mut output_file = std::fs::File::create(output)?;
-            assembly_builder::generate_assembly(&ir, &mut output_file)?;
-            // for assembly_code in assembly_codes {
-            //   writeln!(output_file, "{}", assembly_code)?;
-            // }
+            for assembly_code in assembly_codes {
+                writeln!(output_file, "{}", assembly_code)?;
+            }
             Ok(())
         }
-        mode => Err(mode),
+        mode => Err(format!("Unknown mode {}! {}", mode, USAGE)),
     }?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diagnostics::ErrorCode;
+
+    /// Compiles the code like main does, and returns the Koopa IR and the assembly,
+    /// or the errors of the first step that fails.
+    fn compile(code: &str, target: Target) -> Result<(String, String), Vec<CompileError>> {
+        let options = preprocessor::Options {
+            include_dirs: vec![],
+            defines: vec![],
+        };
+        let (source_file, errors) =
+            preprocessor::preprocess("test.c".to_string(), code.to_string(), &options);
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        let mut recovered_errors = vec![];
+        let type_aliases = ast_def::declarations::TypeAliases::default();
+        let lexer = lexer::Lexer::new(source_file.text(), &type_aliases);
+        let result = sysy::CompUnitParser::new().parse(&mut recovered_errors, &type_aliases, lexer);
+        let mut syntax_errors: Vec<CompileError> = recovered_errors
+            .into_iter()
+            .map(|recovery| diagnostics::from_parse_error(recovery.error))
+            .collect();
+        let ast = match result {
+            Ok(ast) if syntax_errors.is_empty() => ast,
+            Ok(_) => return Err(syntax_errors),
+            Err(error) => {
+                syntax_errors.push(diagnostics::from_parse_error(error));
+                return Err(syntax_errors);
+            }
+        };
+        koopa::ir::Type::set_ptr_size(target.ptr_size());
+        let exp_types = type_checker::check_types(&ast, target, &mut vec![])?;
+        let (ir, program_info) =
+            ir_builder::generate_ir(&ast, exp_types, &mut vec![]).map_err(|error| vec![error])?;
+        let mut text_generator = KoopaGenerator::new(Vec::new());
+        text_generator.generate_on(&ir).unwrap();
+        let koopa = String::from_utf8(text_generator.writer()).unwrap();
+        let assembly = assembly_builder::generate_assembly(&ir, &program_info, target)
+            .map_err(|error| vec![error])?;
+        Ok((koopa, assembly.join("\n")))
+    }
+
+    fn koopa_of(code: &str) -> String {
+        match compile(code, Target::RV32) {
+            Ok((koopa, _)) => koopa,
+            Err(errors) => panic!("{:?}", errors),
+        }
+    }
+
+    fn error_codes(code: &str) -> Vec<ErrorCode> {
+        match compile(code, Target::RV32) {
+            Ok(_) => vec![],
+            Err(errors) => errors.into_iter().map(|error| error.code).collect(),
+        }
+    }
+
+    #[test]
+    fn string_literals_initialize_char_arrays() {
+        let koopa =
+            koopa_of("char s[4] = \"abc\";\nchar t[6] = \"a\\n\";\nint main() { return 0; }");
+        assert!(koopa.contains("global @s = alloc [i32, 4], {97, 98, 99, 0}"));
+        assert!(koopa.contains("global @t = alloc [i32, 6], {97, 10, 0, 0, 0, 0}"));
+        let koopa = koopa_of("int main() { char s[4] = \"abc\"; return s[2]; }");
+        assert!(koopa.contains("alloc [i32, 4]"));
+        assert_eq!(
+            error_codes("int main() { char s[3] = \"abc\"; return 0; }"),
+            [ErrorCode::InvalidInitializer]
+        );
+        assert_eq!(
+            error_codes("int main() { int s[4] = \"abc\"; return 0; }"),
+            [ErrorCode::MismatchedTypes]
+        );
+    }
+
+    #[test]
+    fn structs_are_copied_from_struct_lvals() {
+        let code = "struct P { int x; struct P *next; };\nstruct P g;\n\
+            int main() { struct P *p = &g; struct P a = *p; struct P b[2]; b[1] = a; \
+            a.next = &b[0]; *a.next = g; return 0; }";
+        assert!(error_codes(code).is_empty());
+        let prefix = "struct P { int x; int y; };\nstruct Q { int x; int y; };\nstruct P g;\n";
+        assert_eq!(
+            error_codes(&format!(
+                "{}int main() {{ struct Q q; struct P a = q; return 0; }}",
+                prefix
+            )),
+            [ErrorCode::MismatchedTypes]
+        );
+        assert_eq!(
+            error_codes(&format!(
+                "{}int main() {{ struct P a; a = 1; return 0; }}",
+                prefix
+            )),
+            [ErrorCode::MismatchedTypes]
+        );
+        assert_eq!(
+            error_codes(&format!(
+                "{}struct P h = g;\nint main() {{ return 0; }}",
+                prefix
+            )),
+            [ErrorCode::NonConstantExpression]
+        );
+    }
+
+    #[test]
+    fn functions_are_defined_before_their_calls() {
+        let koopa = koopa_of(
+            "int f(int x);\nint g(int x) { return x * 2; }\n\
+            int main() { return f(3); }\nint f(int x) { return g(x) + 1; }",
+        );
+        let position = |name: &str| koopa.find(&format!("fun @{}(", name)).unwrap();
+        assert!(position("g") < position("f") && position("f") < position("main"));
+        assert!(koopa::front::Driver::from(koopa).generate_program().is_ok());
+    }
+
+    #[test]
+    fn long_long_comparisons_need_rv64() {
+        let code = "int main() { int y = getint(); return y < 10000000000; }";
+        assert_eq!(error_codes(code), [ErrorCode::UnsupportedTarget]);
+        assert!(compile(code, Target::RV64).is_ok());
+        assert!(error_codes("int main() { return 1 < 10000000000; }").is_empty());
+    }
+
+    #[test]
+    fn literals_in_32_bits_are_ints_on_rv32() {
+        for exp in ["y == -2147483648", "y & 0xFFFFFFFF", "y + 3000000000"] {
+            let code = format!("int main() {{ int y = getint(); return {}; }}", exp);
+            assert!(error_codes(&code).is_empty(), "{}", exp);
+        }
+        let koopa =
+            koopa_of("int main() { int y = getint(); return (y & 0xFFFFFFFF) + 3000000000; }");
+        assert!(koopa.contains(" = and %2, -1"));
+        assert!(koopa.contains(", -1294967296"));
+        assert!(koopa_of("int main() { return -2147483648; }").contains("ret -2147483648"));
+    }
+
+    #[test]
+    fn constant_indexes_out_of_bounds_in_functions() {
+        let prefix = "const int a[3] = {1, 2, 3};\n";
+        for body in [
+            "int b[a[5]]; return 0;",
+            "switch (getint()) { case a[3]: return 1; } return 0;",
+            "const int c = a[-1]; return c;",
+        ] {
+            let code = format!("{}int main() {{ {} }}", prefix, body);
+            assert_eq!(
+                error_codes(&code),
+                [ErrorCode::IndexOutOfBounds],
+                "{}",
+                body
+            );
+        }
+        let code = format!("{}int main() {{ int b[getint()]; return 0; }}", prefix);
+        assert_eq!(error_codes(&code), [ErrorCode::NonConstantExpression]);
+        let code = format!("{}int main() {{ return a[getint()] + a[2]; }}", prefix);
+        assert!(error_codes(&code).is_empty());
+    }
+
+    #[test]
+    fn parser_goes_on_after_a_non_lval() {
+        let code =
+            "int main() { int a = 1; (a + 1)++; (a * 2) = 3; (a - 1).x = 0; a = ; return a; }";
+        assert_eq!(
+            error_codes(code),
+            [
+                ErrorCode::NotAssignable,
+                ErrorCode::NotAssignable,
+                ErrorCode::NotAssignable,
+                ErrorCode::UnexpectedToken
+            ]
+        );
+        assert!(error_codes("int main() { int a = 1; (*&a)++; return (a); }").is_empty());
+    }
+}
@@ -0,0 +1,185 @@
+//! Evaluates the condition of #if and #elif.
+//! Macros are expanded before, so only numbers, chars, identifiers and operators are left.
+//! Identifiers that are not macros are 0, like in C.
+
+use super::macros::{Token, TokenKind};
+
+/// Binary operators and their precedences. A larger number binds tighter.
+const BINARY_OPS: [(&str, u32); 18] = [
+    ("||", 1),
+    ("&&", 2),
+    ("|", 3),
+    ("^", 4),
+    ("&", 5),
+    ("==", 6),
+    ("!=", 6),
+    ("<", 7),
+    (">", 7),
+    ("<=", 7),
+    (">=", 7),
+    ("<<", 8),
+    (">>", 8),
+    ("+", 9),
+    ("-", 9),
+    ("*", 10),
+    ("/", 10),
+    ("%", 10),
+];
+
+pub fn evaluate(tokens: &[Token]) -> Result<i64, String> {
+    let tokens: Vec<&Token> = tokens
+        .iter()
+        .filter(|token| token.kind != TokenKind::Space)
+        .collect();
+    if tokens.is_empty() {
+        return Err("Expected a condition! ".to_string());
+    }
+    let mut parser = ConditionParser { tokens, pos: 0 };
+    let value = parser.conditional()?;
+    match parser.tokens.get(parser.pos) {
+        Some(token) => Err(format!("Unexpected '{}' in the condition! ", token.text)),
+        None => Ok(value),
+    }
+}
+
+struct ConditionParser<'a> {
+    tokens: Vec<&'a Token>,
+    pos: usize,
+}
+
+impl ConditionParser<'_> {
+    fn eat(&mut self, punct: &str) -> bool {
+        let found = self.tokens.get(self.pos).is_some_and(|t| t.is(punct));
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, punct: &str) -> Result<(), String> {
+        match self.eat(punct) {
+            true => Ok(()),
+            false => Err(format!("Expected '{}' in the condition! ", punct)),
+        }
+    }
+
+    /// cond ? a : b
+    fn conditional(&mut self) -> Result<i64, String> {
+        let cond = self.binary(1)?;
+        if !self.eat("?") {
+            return Ok(cond);
+        }
+        let then_value = self.conditional()?;
+        self.expect(":")?;
+        let else_value = self.conditional()?;
+        Ok(if cond != 0 { then_value } else { else_value })
+    }
+
+    /// Parses the binary operators with at least the given precedence.
+    fn binary(&mut self, min_precedence: u32) -> Result<i64, String> {
+        let mut lhs = self.unary()?;
+        while let Some(&(op, precedence)) = BINARY_OPS.iter().find(|(op, precedence)| {
+            *precedence >= min_precedence && self.tokens.get(self.pos).is_some_and(|t| t.is(op))
+        }) {
+            self.pos += 1;
+            let rhs = self.binary(precedence + 1)?;
+            lhs = match op {
+                "||" => ((lhs != 0) || (rhs != 0)) as i64,
+                "&&" => ((lhs != 0) && (rhs != 0)) as i64,
+                "|" => lhs | rhs,
+                "^" => lhs ^ rhs,
+                "&" => lhs & rhs,
+                "==" => (lhs == rhs) as i64,
+                "!=" => (lhs != rhs) as i64,
+                "<" => (lhs < rhs) as i64,
+                ">" => (lhs > rhs) as i64,
+                "<=" => (lhs <= rhs) as i64,
+                ">=" => (lhs >= rhs) as i64,
+                "<<" => lhs.wrapping_shl(rhs as u32),
+                ">>" => lhs.wrapping_shr(rhs as u32),
+                "+" => lhs.wrapping_add(rhs),
+                "-" => lhs.wrapping_sub(rhs),
+                "*" => lhs.wrapping_mul(rhs),
+                _ if rhs == 0 => return Err("Division by zero in the condition! ".to_string()),
+                "/" => lhs.wrapping_div(rhs),
+                _ => lhs.wrapping_rem(rhs),
+            };
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<i64, String> {
+        if self.eat("!") {
+            Ok((self.unary()? == 0) as i64)
+        } else if self.eat("~") {
+            Ok(!self.unary()?)
+        } else if self.eat("-") {
+            Ok(self.unary()?.wrapping_neg())
+        } else if self.eat("+") {
+            self.unary()
+        } else if self.eat("(") {
+            let value = self.conditional()?;
+            self.expect(")")?;
+            Ok(value)
+        } else {
+            self.primary()
+        }
+    }
+
+    fn primary(&mut self) -> Result<i64, String> {
+        let Some(token) = self.tokens.get(self.pos) else {
+            return Err("Unexpected end of the condition! ".to_string());
+        };
+        self.pos += 1;
+        let value = match token.kind {
+            TokenKind::Ident => Some(0),
+            TokenKind::Number => parse_integer(&token.text),
+            TokenKind::Literal if token.text.starts_with('\'') => parse_char(&token.text),
+            _ => None,
+        };
+        value.ok_or(format!("Unexpected '{}' in the condition! ", token.text))
+    }
+}
+
+/// Parses a decimal, octal or hexadecimal integer with an optional "u" or "l" suffix.
+fn parse_integer(text: &str) -> Option<i64> {
+    let digits = text.trim_end_matches(['u', 'U', 'l', 'L']);
+    let (digits, radix) = if let Some(hex) = digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        (hex, 16)
+    } else if digits.len() > 1 && digits.starts_with('0') {
+        (&digits[1..], 8)
+    } else {
+        (digits, 10)
+    };
+    u64::from_str_radix(digits, radix)
+        .ok()
+        .map(|value| value as i64)
+}
+
+/// Parses a char literal, e.g. 'a' or '\n'.
+fn parse_char(text: &str) -> Option<i64> {
+    let content = text.strip_prefix('\'')?.strip_suffix('\'')?;
+    let mut chars = content.chars();
+    let value = match (chars.next()?, chars.next()) {
+        ('\\', Some(escaped)) => match escaped {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            'a' => '\x07',
+            'b' => '\x08',
+            'f' => '\x0c',
+            'v' => '\x0b',
+            escaped => escaped,
+        },
+        (c, None) => c,
+        _ => return None,
+    };
+    match chars.next() {
+        None => Some(value as i64),
+        Some(_) => None,
+    }
+}
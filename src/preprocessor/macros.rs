@@ -0,0 +1,428 @@
+//! Preprocessing tokens, macro definitions and macro expansion.
+//!
+//! Expansion follows Prosser's algorithm: every token remembers the macros that produced it
+//! (its hide set), and a macro is not expanded again inside its own expansion.
+
+use std::collections::{HashMap, VecDeque};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Ident,
+    Number,
+    Literal, // A string or char literal
+    Punct,
+    Space,
+    Paste,       // "##" in the body of a macro
+    Placemarker, // An empty argument next to "##"
+}
+
+#[derive(Debug, Clone)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub text: String,
+    pub hideset: Vec<String>,
+    pub offset: Option<usize>, // Byte offset in the line, if the token is from the source
+}
+
+impl Token {
+    fn new(kind: TokenKind, text: String) -> Token {
+        Token {
+            kind,
+            text,
+            hideset: vec![],
+            offset: None,
+        }
+    }
+
+    pub fn is(&self, punct: &str) -> bool {
+        self.kind == TokenKind::Punct && self.text == punct
+    }
+}
+
+const PUNCTS: [&str; 12] = [
+    "...", "##", "&&", "||", "==", "!=", "<=", ">=", "<<", ">>", "->", "#",
+];
+
+/// Splits a line into preprocessing tokens. Concatenating their texts gives the line back.
+pub fn tokenize(line: &str) -> Vec<Token> {
+    let chars: Vec<(usize, char)> = line.char_indices().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let (start, c) = chars[i];
+        let next = chars.get(i + 1).map(|&(_, c)| c);
+        let kind = if c.is_whitespace() {
+            while i < chars.len() && chars[i].1.is_whitespace() {
+                i += 1;
+            }
+            TokenKind::Space
+        } else if c == '_' || c.is_ascii_alphabetic() {
+            while i < chars.len() && (chars[i].1 == '_' || chars[i].1.is_ascii_alphanumeric()) {
+                i += 1;
+            }
+            TokenKind::Ident
+        } else if c.is_ascii_digit() || (c == '.' && next.is_some_and(|c| c.is_ascii_digit())) {
+            // A pp-number also takes suffixes and exponents, e.g. "0x1.8p+1f".
+            i += 1;
+            while i < chars.len() {
+                let c = chars[i].1;
+                let is_exponent_sign =
+                    matches!(c, '+' | '-') && matches!(chars[i - 1].1, 'e' | 'E' | 'p' | 'P');
+                if !is_exponent_sign && c != '.' && c != '_' && !c.is_ascii_alphanumeric() {
+                    break;
+                }
+                i += 1;
+            }
+            TokenKind::Number
+        } else if c == '"' || c == '\'' {
+            i += 1;
+            while i < chars.len() && chars[i].1 != c {
+                i += if chars[i].1 == '\\' { 2 } else { 1 };
+            }
+            i = std::cmp::min(i + 1, chars.len());
+            TokenKind::Literal
+        } else {
+            let punct = PUNCTS
+                .iter()
+                .find(|punct| line[start..].starts_with(*punct));
+            i += punct.map_or(1, |punct| punct.len());
+            TokenKind::Punct
+        };
+        let end = chars.get(i).map_or(line.len(), |&(offset, _)| offset);
+        tokens.push(Token {
+            offset: Some(start),
+            ..Token::new(kind, line[start..end].to_string())
+        });
+    }
+    tokens
+}
+
+/// Returns the index of the first token after `start` that is not a space.
+pub fn skip_spaces<'a>(tokens: impl IntoIterator<Item = &'a Token>, start: usize) -> usize {
+    start
+        + tokens
+            .into_iter()
+            .skip(start)
+            .take_while(|token| token.kind == TokenKind::Space)
+            .count()
+}
+
+pub fn to_text(tokens: &[Token]) -> String {
+    tokens.iter().map(|token| token.text.as_str()).collect()
+}
+
+#[derive(Debug)]
+pub struct Macro {
+    params: Option<Vec<String>>, // None for an object-like macro
+    is_variadic: bool,           // If so, the last param is __VA_ARGS__
+    body: Vec<Token>,
+}
+
+impl Macro {
+    /// Parses the rest of a #define directive, e.g. "max(a, b) ((a) > (b) ? (a) : (b))".
+    pub fn parse(tokens: &[Token]) -> Result<(String, Macro), String> {
+        let mut i = skip_spaces(tokens, 0);
+        let name = match tokens.get(i) {
+            Some(token) if token.kind == TokenKind::Ident => token.text.clone(),
+            _ => return Err("Expected the name of the macro! ".to_string()),
+        };
+        if name == "defined" {
+            return Err("\"defined\" cannot be a macro! ".to_string());
+        }
+        i += 1;
+
+        // A function-like macro has a "(" right after its name.
+        let mut params = None;
+        let mut is_variadic = false;
+        if tokens.get(i).is_some_and(|token| token.is("(")) {
+            let mut names = vec![];
+            i = skip_spaces(tokens, i + 1);
+            if tokens.get(i).is_some_and(|token| token.is(")")) {
+                i += 1;
+            } else {
+                loop {
+                    match tokens.get(i) {
+                        Some(token) if token.kind == TokenKind::Ident => {
+                            names.push(token.text.clone())
+                        }
+                        Some(token) if token.is("...") => {
+                            names.push("__VA_ARGS__".to_string());
+                            is_variadic = true;
+                        }
+                        _ => return Err("Expected the name of a macro parameter! ".to_string()),
+                    }
+                    i = skip_spaces(tokens, i + 1);
+                    match tokens.get(i) {
+                        Some(token) if token.is(")") => break,
+                        Some(token) if token.is(",") && !is_variadic => {
+                            i = skip_spaces(tokens, i + 1)
+                        }
+                        _ => return Err("Expected ',' or ')' in macro parameters! ".to_string()),
+                    }
+                }
+                i += 1;
+            }
+            params = Some(names);
+        }
+
+        // Spaces in the body are merged, and the spaces around it are removed.
+        let mut body: Vec<Token> = vec![];
+        for token in &tokens[skip_spaces(tokens, i)..] {
+            let token = match token.kind {
+                TokenKind::Space if body.last().is_some_and(|t| t.kind == TokenKind::Space) => {
+                    continue
+                }
+                TokenKind::Space => Token::new(TokenKind::Space, " ".to_string()),
+                _ if token.is("##") => Token::new(TokenKind::Paste, token.text.clone()),
+                _ => Token::new(token.kind, token.text.clone()),
+            };
+            body.push(token);
+        }
+        if body.last().is_some_and(|t| t.kind == TokenKind::Space) {
+            body.pop();
+        }
+        let first_and_last = [body.first(), body.last()];
+        if first_and_last
+            .iter()
+            .flatten()
+            .any(|t| t.kind == TokenKind::Paste)
+        {
+            return Err("'##' cannot be at either end of a macro! ".to_string());
+        }
+        if let Some(params) = &params {
+            for (idx, token) in body.iter().enumerate() {
+                let next = body.get(skip_spaces(&body, idx + 1));
+                if token.is("#") && !next.is_some_and(|next| params.contains(&next.text)) {
+                    return Err("'#' is not followed by a macro parameter! ".to_string());
+                }
+            }
+        }
+        Ok((
+            name,
+            Macro {
+                params,
+                is_variadic,
+                body,
+            },
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub enum ExpandError {
+    // The arguments of a function-like macro go past the end of the line.
+    // The line may be joined with the next line and expanded again.
+    Unterminated(Token),
+    Invalid(String, Option<usize>),
+}
+
+/// The macros defined so far, and where the expansion happens (for __FILE__ and __LINE__).
+pub struct Expander<'a> {
+    pub macros: &'a HashMap<String, Macro>,
+    pub file: &'a str,
+    pub line: usize,
+}
+
+impl Expander<'_> {
+    /// Expands all macros in the tokens.
+    pub fn expand(&self, tokens: Vec<Token>) -> Result<Vec<Token>, ExpandError> {
+        let mut input: VecDeque<Token> = tokens.into();
+        let mut output = vec![];
+        while let Some(token) = input.pop_front() {
+            if token.kind != TokenKind::Ident || token.hideset.contains(&token.text) {
+                output.push(token);
+                continue;
+            }
+            let builtin = match token.text.as_str() {
+                "__LINE__" => Some(Token::new(TokenKind::Number, self.line.to_string())),
+                "__FILE__" => Some(Token::new(TokenKind::Literal, quote(self.file))),
+                _ => None,
+            };
+            if let Some(builtin) = builtin {
+                output.push(builtin);
+                continue;
+            }
+            let Some(macro_def) = self.macros.get(&token.text) else {
+                output.push(token);
+                continue;
+            };
+
+            let mut hideset = token.hideset.clone();
+            hideset.push(token.text.clone());
+            let args = match &macro_def.params {
+                None => vec![],
+                Some(params) => {
+                    // The name of a function-like macro is kept if no "(" follows.
+                    let lparen = skip_spaces(&input, 0);
+                    if !input.get(lparen).is_some_and(|t| t.is("(")) {
+                        output.push(token);
+                        continue;
+                    }
+                    input.drain(..=lparen);
+                    let args = collect_args(&mut input, &token)?;
+                    check_args(&token, params, macro_def.is_variadic, args)?
+                }
+            };
+
+            // Rescan the replacement together with the rest of the line.
+            // It is surrounded by spaces, so that it is not joined with the tokens around it.
+            let replacement = self.substitute(macro_def, &args, &hideset)?;
+            input.push_front(Token::new(TokenKind::Space, " ".to_string()));
+            for token in replacement.into_iter().rev() {
+                input.push_front(token);
+            }
+            output.push(Token::new(TokenKind::Space, " ".to_string()));
+        }
+        Ok(output)
+    }
+
+    /// Replaces the params in the body of a macro with the args.
+    fn substitute(
+        &self,
+        macro_def: &Macro,
+        args: &[Vec<Token>],
+        hideset: &[String],
+    ) -> Result<Vec<Token>, ExpandError> {
+        let params = macro_def.params.as_deref().unwrap_or_default();
+        let body = &macro_def.body;
+        let mut result: Vec<Token> = vec![];
+        let mut i = 0;
+        while i < body.len() {
+            let token = &body[i];
+            let next = skip_spaces(body, i + 1);
+            // "#param" turns the arg into a string.
+            if token.is("#") && macro_def.params.is_some() {
+                let idx = params.iter().position(|p| *p == body[next].text).unwrap();
+                let text = to_text(&args[idx]);
+                let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+                result.push(Token::new(TokenKind::Literal, quote(&text)));
+                i = next + 1;
+                continue;
+            }
+            match params.iter().position(|param| *param == token.text) {
+                Some(idx) if token.kind == TokenKind::Ident => {
+                    // An arg next to "##" is pasted as it is, and other args are expanded first.
+                    let prev = result.iter().rev().find(|t| t.kind != TokenKind::Space);
+                    let is_pasted = prev.is_some_and(|t| t.kind == TokenKind::Paste)
+                        || body.get(next).is_some_and(|t| t.kind == TokenKind::Paste);
+                    let arg = args[idx].clone();
+                    if !is_pasted {
+                        result.extend(self.expand(arg)?);
+                    } else if arg.iter().all(|t| t.kind == TokenKind::Space) {
+                        result.push(Token::new(TokenKind::Placemarker, String::new()));
+                    } else {
+                        let start = skip_spaces(&arg, 0);
+                        let end = arg.len() - skip_spaces(arg.iter().rev(), 0);
+                        result.extend_from_slice(&arg[start..end]);
+                    }
+                }
+                _ => result.push(token.clone()),
+            }
+            i += 1;
+        }
+
+        // Paste the tokens around each "##".
+        let mut pasted: Vec<Token> = vec![];
+        let mut tokens = result.into_iter();
+        while let Some(token) = tokens.next() {
+            if token.kind != TokenKind::Paste {
+                pasted.push(token);
+                continue;
+            }
+            while pasted.last().is_some_and(|t| t.kind == TokenKind::Space) {
+                pasted.pop();
+            }
+            let left = pasted.pop().unwrap();
+            let right = tokens.find(|t| t.kind != TokenKind::Space).unwrap();
+            let text = left.text + &right.text;
+            let mut new_tokens = tokenize(&text);
+            if new_tokens.len() > 1 {
+                return Err(ExpandError::Invalid(
+                    format!("Pasting makes \"{}\", which is not a valid token! ", text),
+                    None,
+                ));
+            }
+            pasted.extend(new_tokens.pop().map(|token| Token {
+                offset: None,
+                ..token
+            }));
+        }
+
+        Ok(pasted
+            .into_iter()
+            .filter(|token| token.kind != TokenKind::Placemarker)
+            .map(|token| {
+                let mut token_hideset = token.hideset;
+                token_hideset.extend(hideset.iter().cloned());
+                Token {
+                    hideset: token_hideset,
+                    offset: None,
+                    ..token
+                }
+            })
+            .collect())
+    }
+}
+
+/// Takes the args of a macro call from the input, until the ")" closing the call.
+fn collect_args(input: &mut VecDeque<Token>, name: &Token) -> Result<Vec<Vec<Token>>, ExpandError> {
+    let mut args = vec![vec![]];
+    let mut depth = 0;
+    loop {
+        let Some(token) = input.pop_front() else {
+            return Err(ExpandError::Unterminated(name.clone()));
+        };
+        if token.is("(") {
+            depth += 1;
+        } else if token.is(")") && depth == 0 {
+            return Ok(args);
+        } else if token.is(")") {
+            depth -= 1;
+        } else if token.is(",") && depth == 0 {
+            args.push(vec![]);
+            continue;
+        }
+        args.last_mut().unwrap().push(token);
+    }
+}
+
+/// Checks the number of args. The extra args of a variadic macro become one arg.
+fn check_args(
+    name: &Token,
+    params: &[String],
+    is_variadic: bool,
+    mut args: Vec<Vec<Token>>,
+) -> Result<Vec<Vec<Token>>, ExpandError> {
+    // "f()" has an empty arg, which is no arg if f has no params.
+    if params.is_empty() && args.len() == 1 && args[0].iter().all(|t| t.kind == TokenKind::Space) {
+        args.clear();
+    }
+    if is_variadic && args.len() + 1 == params.len() {
+        args.push(vec![]);
+    }
+    if is_variadic && args.len() > params.len() {
+        let rest = args.split_off(params.len());
+        let last = args.last_mut().unwrap();
+        for arg in rest {
+            last.push(Token::new(TokenKind::Punct, ",".to_string()));
+            last.extend(arg);
+        }
+    }
+    if args.len() != params.len() {
+        return Err(ExpandError::Invalid(
+            format!(
+                "The macro {} takes {} arguments, but got {}! ",
+                name.text,
+                params.len(),
+                args.len()
+            ),
+            name.offset,
+        ));
+    }
+    Ok(args)
+}
+
+/// Makes a string literal of the text.
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
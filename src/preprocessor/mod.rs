@@ -0,0 +1,759 @@
+//! The preprocessor runs before the parser, like the one of C.
+//! It handles #include, #define, #undef, #if, #ifdef, #ifndef, #elif, #else, #endif,
+//! #error and #pragma once, and expands macros including __LINE__ and __FILE__.
+//!
+//! Comments are replaced by spaces, and directives and skipped lines by empty lines,
+//! so that a line without macros keeps its line number and columns.
+//! Every output line remembers where it comes from (see LineOrigin),
+//! so that diagnostics point at the original file.
+
+mod condition;
+mod macros;
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use macros::{skip_spaces, to_text, tokenize, ExpandError, Expander, Macro, Token, TokenKind};
+
+use crate::ast_def::symbols::Span;
+use crate::diagnostics::{CompileError, ErrorCode, LineOrigin, SourceFile};
+
+const MAX_INCLUDE_DEPTH: usize = 200;
+
+/// Options from the command line.
+pub struct Options {
+    pub include_dirs: Vec<PathBuf>, // Searched by #include, after the directory of the file for "file"
+    pub defines: Vec<String>,       // "NAME" or "NAME=value", like -D of gcc
+}
+
+/// Preprocesses the input file.
+/// All errors are returned together with the text that is preprocessed so far,
+/// so that they can be rendered.
+pub fn preprocess(
+    name: String,
+    text: String,
+    options: &Options,
+) -> (SourceFile, Vec<CompileError>) {
+    let mut preprocessor = Preprocessor {
+        options,
+        macros: HashMap::new(),
+        files: vec![(name.clone(), text)],
+        lines: vec![],
+        errors: vec![],
+        once_files: HashSet::new(),
+    };
+    // Macros from the command line are defined in a file of their own.
+    if !options.defines.is_empty() {
+        let defines: Vec<String> = options
+            .defines
+            .iter()
+            .map(|define| match define.split_once('=') {
+                Some((name, value)) => format!("#define {} {}", name, value),
+                None => format!("#define {} 1", define),
+            })
+            .collect();
+        preprocessor
+            .files
+            .push(("<command line>".to_string(), defines.join("\n")));
+        preprocessor.process_file(1, None, 0);
+    }
+    preprocessor.process_file(0, Some(Path::new(&name)), 0);
+    preprocessor.finish()
+}
+
+/// A line that may be made of several physical lines joined by "\" at their ends.
+struct LogicalLine {
+    text: String,
+    line: usize,  // 1-based number of its first physical line
+    count: usize, // Number of physical lines
+}
+
+impl LogicalLine {
+    fn is_directive(&self) -> bool {
+        self.text.trim_start().starts_with('#')
+    }
+}
+
+/// An #if, #ifdef or #ifndef, and the #elif and #else after it so far.
+struct Conditional {
+    is_active: bool, // Whether lines are kept in the current branch
+    is_taken: bool,  // Whether a branch has been kept, or the whole conditional is skipped
+    has_else: bool,
+    output_line: usize, // Where the #if is, for the error if it has no #endif
+    text: String,
+}
+
+struct Preprocessor<'a> {
+    options: &'a Options,
+    macros: HashMap<String, Macro>,
+    files: Vec<(String, String)>,
+    lines: Vec<(String, LineOrigin)>,
+    // Errors, and the output lines they are on. Their spans are relative to the line.
+    errors: Vec<(usize, CompileError)>,
+    once_files: HashSet<PathBuf>, // Files with #pragma once
+}
+
+impl Preprocessor<'_> {
+    fn finish(self) -> (SourceFile, Vec<CompileError>) {
+        let mut text = String::new();
+        let mut line_starts = vec![];
+        let mut line_origins = vec![];
+        for (line, origin) in self.lines {
+            line_starts.push(text.len());
+            text += &line;
+            text.push('\n');
+            line_origins.push(origin);
+        }
+        let errors = self
+            .errors
+            .into_iter()
+            .map(|(line_idx, mut error)| {
+                if let Some(span) = error.span.as_mut() {
+                    *span = Span::new(
+                        span.start + line_starts[line_idx],
+                        span.end + line_starts[line_idx],
+                    );
+                }
+                error
+            })
+            .collect();
+        (SourceFile::new(self.files, text, line_origins), errors)
+    }
+
+    /// Outputs the text for the first physical line of a logical line,
+    /// and empty lines for the rest. Returns the index of the output line.
+    fn push_line(&mut self, file: usize, line: &LogicalLine, text: String) -> usize {
+        let verbatim = line.count == 1 && text == line.text;
+        self.lines.push((
+            text,
+            LineOrigin {
+                file,
+                line: line.line,
+                verbatim,
+            },
+        ));
+        for offset in 1..line.count {
+            self.lines.push((
+                String::new(),
+                LineOrigin {
+                    file,
+                    line: line.line + offset,
+                    verbatim: true,
+                },
+            ));
+        }
+        self.lines.len() - line.count
+    }
+
+    /// Reports an error in an output line. The line shows the source text,
+    /// and the error points at the given range of it, or the whole line if there is none.
+    fn error_at(
+        &mut self,
+        output_line: usize,
+        text: &str,
+        range: Option<(usize, usize)>,
+        code: ErrorCode,
+        message: String,
+    ) {
+        let (start, end) =
+            range.unwrap_or((text.len() - text.trim_start().len(), text.trim_end().len()));
+        let (line, origin) = &mut self.lines[output_line];
+        origin.verbatim = origin.verbatim && *line == text;
+        *line = text.to_string();
+        self.errors.push((
+            output_line,
+            CompileError::new(code, message, Span::new(start, end)),
+        ));
+    }
+
+    /// Preprocesses a file, whose path is None if it is not a real file.
+    fn process_file(&mut self, file: usize, path: Option<&Path>, depth: usize) {
+        let name = self.files[file].0.clone();
+        let lines = logical_lines(&strip_comments(&self.files[file].1));
+        let mut conditionals: Vec<Conditional> = vec![];
+        let mut idx = 0;
+        while idx < lines.len() {
+            let line = &lines[idx];
+            let is_active = conditionals.last().is_none_or(|c| c.is_active);
+            if line.is_directive() {
+                self.directive(file, path, depth, line, &mut conditionals);
+                idx += 1;
+                continue;
+            }
+            if !is_active {
+                self.push_line(file, line, String::new());
+                idx += 1;
+                continue;
+            }
+
+            // The args of a macro call may go on in the next lines.
+            let mut text = line.text.clone();
+            let mut end = idx + 1;
+            let expander = Expander {
+                macros: &self.macros,
+                file: &name,
+                line: line.line,
+            };
+            let result = loop {
+                match expander.expand(tokenize(&text)) {
+                    Err(ExpandError::Unterminated(_))
+                        if end < lines.len() && !lines[end].is_directive() =>
+                    {
+                        text.push(' ');
+                        text += &lines[end].text;
+                        end += 1;
+                    }
+                    result => break result,
+                }
+            };
+            let (expanded, error) = match result {
+                Ok(tokens) => (to_text(&tokens), None),
+                Err(ExpandError::Unterminated(token)) => (
+                    text.clone(),
+                    Some((
+                        token.offset,
+                        format!("Unterminated call of the macro {}! ", token.text),
+                    )),
+                ),
+                Err(ExpandError::Invalid(message, offset)) => {
+                    (text.clone(), Some((offset, message)))
+                }
+            };
+            let joined = LogicalLine {
+                text: text.clone(),
+                line: line.line,
+                count: lines[idx..end].iter().map(|line| line.count).sum(),
+            };
+            let output_line = self.push_line(file, &joined, expanded);
+            if let Some((offset, message)) = error {
+                // Point at the name of the macro if it is in the source.
+                let range = offset.map(|offset| {
+                    let len = tokenize(&text[offset..])[0].text.len();
+                    (offset, offset + len)
+                });
+                self.error_at(
+                    output_line,
+                    &text,
+                    range,
+                    ErrorCode::InvalidMacroCall,
+                    message,
+                );
+            }
+            idx = end;
+        }
+
+        for conditional in conditionals {
+            self.error_at(
+                conditional.output_line,
+                &conditional.text,
+                None,
+                ErrorCode::UnterminatedConditional,
+                "Missing #endif for this conditional directive! ".to_string(),
+            );
+        }
+    }
+
+    fn directive(
+        &mut self,
+        file: usize,
+        path: Option<&Path>,
+        depth: usize,
+        line: &LogicalLine,
+        conditionals: &mut Vec<Conditional>,
+    ) {
+        let output_line = self.push_line(file, line, String::new());
+        let tokens = tokenize(&line.text);
+        let name_idx = skip_spaces(&tokens, skip_spaces(&tokens, 0) + 1);
+        let rest = tokens.get(name_idx + 1..).unwrap_or_default();
+        let name = match tokens.get(name_idx) {
+            Some(token) => token.text.as_str(),
+            // A "#" alone is a null directive.
+            None => return,
+        };
+
+        let is_active = conditionals.last().is_none_or(|c| c.is_active);
+        let result = match name {
+            "if" | "ifdef" | "ifndef" => {
+                let value = match is_active {
+                    true => self.evaluate(name, rest, &self.files[file].0, line.line),
+                    false => Ok(false),
+                };
+                conditionals.push(Conditional {
+                    is_active: *value.as_ref().unwrap_or(&false),
+                    is_taken: *value.as_ref().unwrap_or(&true) || !is_active,
+                    has_else: false,
+                    output_line,
+                    text: line.text.clone(),
+                });
+                value.map(|_| ())
+            }
+            "elif" | "else" | "endif" if conditionals.is_empty() => Err((
+                ErrorCode::InvalidDirective,
+                format!("#{} without #if! ", name),
+            )),
+            "elif" | "else" if conditionals.last().unwrap().has_else => Err((
+                ErrorCode::InvalidDirective,
+                format!("#{} after #else! ", name),
+            )),
+            "elif" => {
+                let conditional = conditionals.last().unwrap();
+                let value = match conditional.is_taken {
+                    true => Ok(false),
+                    false => self.evaluate(name, rest, &self.files[file].0, line.line),
+                };
+                let conditional = conditionals.last_mut().unwrap();
+                conditional.is_active = *value.as_ref().unwrap_or(&false);
+                conditional.is_taken |= *value.as_ref().unwrap_or(&true);
+                value.map(|_| ())
+            }
+            "else" => {
+                let conditional = conditionals.last_mut().unwrap();
+                conditional.is_active = !conditional.is_taken;
+                conditional.is_taken = true;
+                conditional.has_else = true;
+                Ok(())
+            }
+            "endif" => {
+                conditionals.pop();
+                Ok(())
+            }
+            // Other directives in skipped lines are ignored.
+            _ if !is_active => Ok(()),
+            "define" => Macro::parse(rest)
+                .map(|(name, macro_def)| {
+                    self.macros.insert(name, macro_def);
+                })
+                .map_err(|message| (ErrorCode::InvalidDirective, message)),
+            "undef" => match rest.get(skip_spaces(rest, 0)) {
+                Some(token) if token.kind == TokenKind::Ident => {
+                    self.macros.remove(&token.text);
+                    Ok(())
+                }
+                _ => Err((
+                    ErrorCode::InvalidDirective,
+                    "Expected the name of the macro! ".to_string(),
+                )),
+            },
+            "include" => self.include(file, path, depth, line, rest),
+            "error" => Err((
+                ErrorCode::ErrorDirective,
+                format!("#error {}", to_text(rest).trim()),
+            )),
+            "pragma" => {
+                if to_text(rest).trim() == "once" {
+                    if let Some(path) = path.and_then(|path| path.canonicalize().ok()) {
+                        self.once_files.insert(path);
+                    }
+                }
+                // Other pragmas are ignored.
+                Ok(())
+            }
+            _ => Err((
+                ErrorCode::InvalidDirective,
+                format!("Unknown preprocessing directive #{}! ", name),
+            )),
+        };
+        if let Err((code, message)) = result {
+            self.error_at(output_line, &line.text, None, code, message);
+        }
+    }
+
+    /// Evaluates the condition of #if, #ifdef, #ifndef or #elif.
+    fn evaluate(
+        &self,
+        directive: &str,
+        rest: &[Token],
+        file_name: &str,
+        line: usize,
+    ) -> Result<bool, (ErrorCode, String)> {
+        let invalid = |message: String| (ErrorCode::InvalidDirective, message);
+        if directive == "ifdef" || directive == "ifndef" {
+            return match rest.get(skip_spaces(rest, 0)) {
+                Some(token) if token.kind == TokenKind::Ident => {
+                    Ok(self.macros.contains_key(&token.text) == (directive == "ifdef"))
+                }
+                _ => Err(invalid("Expected the name of the macro! ".to_string())),
+            };
+        }
+
+        // "defined NAME" and "defined(NAME)" are replaced before expanding macros.
+        let mut tokens = vec![];
+        let mut i = 0;
+        while i < rest.len() {
+            if rest[i].kind != TokenKind::Ident || rest[i].text != "defined" {
+                tokens.push(rest[i].clone());
+                i += 1;
+                continue;
+            }
+            i = skip_spaces(rest, i + 1);
+            let has_paren = rest.get(i).is_some_and(|token| token.is("("));
+            if has_paren {
+                i = skip_spaces(rest, i + 1);
+            }
+            let is_defined = match rest.get(i) {
+                Some(token) if token.kind == TokenKind::Ident => {
+                    self.macros.contains_key(&token.text)
+                }
+                _ => {
+                    return Err(invalid(
+                        "Expected the name of a macro after defined! ".to_string(),
+                    ))
+                }
+            };
+            i += 1;
+            if has_paren {
+                i = skip_spaces(rest, i);
+                if !rest.get(i).is_some_and(|token| token.is(")")) {
+                    return Err(invalid("Expected ')' after defined(NAME! ".to_string()));
+                }
+                i += 1;
+            }
+            tokens.extend(tokenize(if is_defined { "1" } else { "0" }));
+        }
+
+        let expander = Expander {
+            macros: &self.macros,
+            file: file_name,
+            line,
+        };
+        let tokens = match expander.expand(tokens) {
+            Ok(tokens) => tokens,
+            Err(ExpandError::Unterminated(token)) => {
+                return Err((
+                    ErrorCode::InvalidMacroCall,
+                    format!("Unterminated call of the macro {}! ", token.text),
+                ))
+            }
+            Err(ExpandError::Invalid(message, _)) => {
+                return Err((ErrorCode::InvalidMacroCall, message))
+            }
+        };
+        condition::evaluate(&tokens)
+            .map(|value| value != 0)
+            .map_err(invalid)
+    }
+
+    /// Finds the file of an #include, and preprocesses it in place.
+    fn include(
+        &mut self,
+        file: usize,
+        path: Option<&Path>,
+        depth: usize,
+        line: &LogicalLine,
+        rest: &[Token],
+    ) -> Result<(), (ErrorCode, String)> {
+        let mut text = to_text(rest).trim().to_string();
+        // "#include MACRO" is allowed if the macro expands to "file" or <file>.
+        if !text.starts_with(['"', '<']) {
+            let expander = Expander {
+                macros: &self.macros,
+                file: &self.files[file].0,
+                line: line.line,
+            };
+            if let Ok(tokens) = expander.expand(rest.to_vec()) {
+                text = to_text(&tokens).trim().to_string();
+            }
+        }
+        let (name, is_quoted) = if let Some(name) = text
+            .strip_prefix('"')
+            .and_then(|text| text.strip_suffix('"'))
+        {
+            (name, true)
+        } else if let Some(name) = text
+            .strip_prefix('<')
+            .and_then(|text| text.strip_suffix('>'))
+        {
+            (name.trim(), false)
+        } else {
+            return Err((
+                ErrorCode::InvalidDirective,
+                "Expected \"FILE\" or <FILE> after #include! ".to_string(),
+            ));
+        };
+        if depth >= MAX_INCLUDE_DEPTH {
+            return Err((
+                ErrorCode::InvalidDirective,
+                format!("#include is nested more than {} times! ", MAX_INCLUDE_DEPTH),
+            ));
+        }
+
+        // "file" is searched in the directory of the current file first.
+        let current_dir = path.map(|path| path.parent().unwrap_or(Path::new("")));
+        let found = current_dir
+            .filter(|_| is_quoted)
+            .into_iter()
+            .chain(self.options.include_dirs.iter().map(PathBuf::as_path))
+            .map(|dir| dir.join(name))
+            .find(|path| path.is_file());
+        let Some(found) = found else {
+            return Err((
+                ErrorCode::IncludeNotFound,
+                format!("Cannot find the file {}! ", name),
+            ));
+        };
+        if let Ok(canonical) = found.canonicalize() {
+            if self.once_files.contains(&canonical) {
+                return Ok(());
+            }
+        }
+        let text = std::fs::read_to_string(&found).map_err(|error| {
+            (
+                ErrorCode::IncludeNotFound,
+                format!("Cannot read the file {}: {}! ", found.display(), error),
+            )
+        })?;
+        self.files.push((found.display().to_string(), text));
+        self.process_file(self.files.len() - 1, Some(&found), depth + 1);
+        Ok(())
+    }
+}
+
+/// Replaces every comment with spaces, but keeps line breaks,
+/// so that lines keep their numbers and columns.
+/// An unterminated block comment is kept, and the parser reports it.
+fn strip_comments(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut quote = None;
+    let mut chars = text.char_indices().peekable();
+    while let Some((offset, c)) = chars.next() {
+        match quote {
+            Some(_) if c == '\\' => {
+                result.push(c);
+                result.extend(chars.next().map(|(_, c)| c));
+                continue;
+            }
+            Some(q) if c == q || c == '\n' => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if text[offset..].starts_with("//") => {
+                while chars.next_if(|&(_, c)| c != '\n').is_some() {}
+                continue;
+            }
+            None if text[offset..].starts_with("/*") => {
+                if let Some(len) = text[offset + 2..].find("*/") {
+                    let comment = &text[offset..offset + 2 + len + 2];
+                    result.extend(comment.chars().map(|c| if c == '\n' { c } else { ' ' }));
+                    while chars
+                        .next_if(|&(next, _)| next < offset + comment.len())
+                        .is_some()
+                    {}
+                    continue;
+                }
+            }
+            None => {}
+        }
+        result.push(c);
+    }
+    result
+}
+
+/// Splits the text into lines, and joins the lines ending with "\" with the next ones.
+fn logical_lines(text: &str) -> Vec<LogicalLine> {
+    let mut lines = vec![];
+    let mut current: Option<LogicalLine> = None;
+    for (idx, line) in text.lines().enumerate() {
+        let (content, is_spliced) = match line.strip_suffix('\\') {
+            Some(content) => (content, true),
+            None => (line, false),
+        };
+        match current.as_mut() {
+            Some(current) => {
+                current.text += content;
+                current.count += 1;
+            }
+            None => {
+                current = Some(LogicalLine {
+                    text: content.to_string(),
+                    line: idx + 1,
+                    count: 1,
+                })
+            }
+        }
+        if !is_spliced {
+            lines.extend(current.take());
+        }
+    }
+    lines.extend(current);
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(text: &str, options: &Options) -> (SourceFile, Vec<CompileError>) {
+        preprocess("test.c".to_string(), text.to_string(), options)
+    }
+
+    fn no_options() -> Options {
+        Options {
+            include_dirs: vec![],
+            defines: vec![],
+        }
+    }
+
+    /// The lines of the preprocessed text, which must have no errors.
+    fn lines(text: &str) -> Vec<String> {
+        let (source_file, errors) = run(text, &no_options());
+        assert!(errors.is_empty(), "{:?}", errors);
+        source_file.text().lines().map(str::to_string).collect()
+    }
+
+    fn error_codes(text: &str) -> Vec<ErrorCode> {
+        let (_, errors) = run(text, &no_options());
+        errors.into_iter().map(|error| error.code).collect()
+    }
+
+    /// A directory of its own for every test, because tests run at the same time.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("sysy_{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn text_without_directives_is_kept() {
+        let text = "int main() {\n  return 0;\n}\n";
+        assert_eq!(lines(text), ["int main() {", "  return 0;", "}"]);
+    }
+
+    #[test]
+    fn comments_become_spaces() {
+        let text = "int a; // one\n/* two\nthree */ int b;\nputf(\"// not a comment\");\n";
+        assert_eq!(
+            lines(text),
+            [
+                "int a; ",
+                "      ",
+                "         int b;",
+                "putf(\"// not a comment\");"
+            ]
+        );
+    }
+
+    // Expansions are padded with spaces, so that they never glue to the tokens next to them.
+    #[test]
+    fn object_like_macros() {
+        let text = "#define N 10\n#define M (N + 1)\nint a[M];\n#undef N\nint N;\n";
+        assert_eq!(lines(text), ["", "", "int a[ ( 10  + 1) ];", "", "int N;"]);
+    }
+
+    #[test]
+    fn function_like_macros() {
+        let text = "#define MAX(a, b) ((a) > (b) ? (a) : (b))\nx = MAX(y, f(1, 2));\n";
+        assert_eq!(
+            lines(text)[1],
+            "x =  ((y) > ( f(1, 2)) ? (y) : ( f(1, 2))) ;"
+        );
+    }
+
+    #[test]
+    fn a_macro_is_not_expanded_in_itself() {
+        let text = "#define A A + 1\nint x = A;\n";
+        assert_eq!(lines(text)[1], "int x =  A + 1 ;");
+    }
+
+    #[test]
+    fn macro_args_may_go_on_in_the_next_lines() {
+        let text = "#define ADD(a, b) a + b\nx = ADD(1,\n  2);\ny;\n";
+        assert_eq!(lines(text), ["", "x =  1 +    2 ;", "", "y;"]);
+    }
+
+    #[test]
+    fn line_and_file() {
+        let text = "\nputint(__LINE__);\nputf(__FILE__);\n";
+        assert_eq!(lines(text), ["", "putint(2);", "putf(\"test.c\");"]);
+    }
+
+    #[test]
+    fn sylib_timing_macros() {
+        let text = "#define starttime() _sysy_starttime(__LINE__)\n\nstarttime();\n";
+        assert_eq!(lines(text)[2], " _sysy_starttime(3) ;");
+    }
+
+    #[test]
+    fn conditionals() {
+        let text = "#define A\n#ifdef A\na;\n#else\nnot_a;\n#endif\n\
+                    #if defined(B) || 1 + 1 == 3\nb;\n#elif !defined B\nnot_b;\n#endif\n";
+        let lines = lines(text);
+        assert_eq!(lines.len(), 11);
+        assert_eq!(lines[2], "a;");
+        assert_eq!(lines[4], "");
+        assert_eq!(lines[7], "");
+        assert_eq!(lines[9], "not_b;");
+    }
+
+    #[test]
+    fn defines_from_the_command_line() {
+        let options = Options {
+            include_dirs: vec![],
+            defines: vec!["DEBUG".to_string(), "N=3".to_string()],
+        };
+        // The defines are the lines of a "<command line>" file before the source.
+        let text = "#if DEBUG\nint a[N];\n#endif\n";
+        let (source_file, errors) = run(text, &options);
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(source_file.text(), "\n\n\nint a[ 3 ];\n\n");
+    }
+
+    #[test]
+    fn directive_errors() {
+        assert_eq!(
+            error_codes("#ifdef A\nint a;\n"),
+            [ErrorCode::UnterminatedConditional]
+        );
+        assert_eq!(error_codes("#endif\n"), [ErrorCode::InvalidDirective]);
+        assert_eq!(error_codes("#error stop\n"), [ErrorCode::ErrorDirective]);
+        assert_eq!(
+            error_codes("#include \"none.h\"\n"),
+            [ErrorCode::IncludeNotFound]
+        );
+        assert_eq!(
+            error_codes("#define F(a) a\nF(1, 2);\n"),
+            [ErrorCode::InvalidMacroCall]
+        );
+    }
+
+    #[test]
+    fn skipped_lines_are_not_checked() {
+        assert!(error_codes("#if 0\n#error stop\n#include \"none.h\"\n#endif\n").is_empty());
+    }
+
+    #[test]
+    fn includes_and_line_origins() {
+        let dir = temp_dir("includes");
+        std::fs::write(
+            dir.join("lib.h"),
+            "#pragma once\n#define TWO 2\nint two() { return TWO; }\n",
+        )
+        .unwrap();
+        let options = Options {
+            include_dirs: vec![dir.clone()],
+            defines: vec![],
+        };
+        let text = "#include <lib.h>\n#include \"lib.h\"\nint x = TWO;\n";
+        let (source_file, errors) = run(text, &options);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(
+            source_file.text(),
+            "\n\n\nint two() { return  2 ; }\n\nint x =  2 ;\n"
+        );
+
+        // Errors point at the file and the line that the text comes from.
+        let offset = source_file.text().find("int x").unwrap();
+        let error = CompileError::new(
+            ErrorCode::UndeclaredSymbol,
+            "Test! ".to_string(),
+            Span::new(offset, offset + 3),
+        );
+        assert!(source_file.render(&error).contains("test.c:3:1"));
+        let offset = source_file.text().find("int two").unwrap();
+        let error = CompileError::new(
+            ErrorCode::UndeclaredSymbol,
+            "Test! ".to_string(),
+            Span::new(offset, offset + 3),
+        );
+        assert!(source_file.render(&error).contains("lib.h:3:1"));
+    }
+}
@@ -0,0 +1,463 @@
+//! Check the types of declarations, and declare their symbols.
+
+use crate::ast_def::declarations::*;
+use crate::ast_def::expressions::Exp;
+use crate::ast_def::statements::BasicStmt;
+use crate::ast_def::symbols::{BType, BTypeKind, Span, IDENT};
+use crate::diagnostics::{ErrorCode, WarningFlag};
+use crate::ir_builder::SymbolKind;
+use std::collections::HashSet;
+
+use super::check_expressions::{
+    check_conversion, check_struct_copy, convert_const, get_value, ExpTypeCheckable,
+};
+use super::{FunctionSignature, SysYType, TypeCheckable, TypeCheckerInfo};
+
+/// Checks the base type of a variable or a parameter.
+/// It can not be void, and a struct must have been defined.
+fn check_btype(btype: &BType, type_checker_info: &mut TypeCheckerInfo) -> Option<BTypeKind> {
+    match btype.content {
+        BTypeKind::Void => type_checker_info.error(
+            ErrorCode::VoidVariable,
+            "Variables and parameters can not be void! ".to_string(),
+            btype.span,
+        ),
+        BTypeKind::Struct(name) if !type_checker_info.struct_table.contains_key(name) => {
+            type_checker_info.error(
+                ErrorCode::UndeclaredSymbol,
+                format!("Undefined struct: {}", name),
+                btype.span,
+            )
+        }
+        btype => Some(btype),
+    }
+}
+
+/// Checks the base type of something stored at run time, which is not a constant.
+/// Long long needs RV64.
+fn check_stored_btype(btype: &BType, type_checker_info: &mut TypeCheckerInfo) -> Option<BTypeKind> {
+    if btype.content == BTypeKind::LongLong {
+        type_checker_info.check_long_long(btype.span, true);
+    }
+    check_btype(btype, type_checker_info)
+}
+
+/// Checks the lengths of an array, which are integers.
+/// A length is None if it is not a positive constant, which is reported by the IR builder.
+fn check_shape(shape_exps: &[Exp], type_checker_info: &mut TypeCheckerInfo) -> Vec<Option<usize>> {
+    let mut shape = vec![];
+    for exp in shape_exps {
+        let length = match get_value(exp.check(type_checker_info), exp.span(), type_checker_info) {
+            Some(result) if !result.ty.is_integer() => type_checker_info.error(
+                ErrorCode::MismatchedTypes,
+                "The length of array must be an int! ".to_string(),
+                exp.span(),
+            ),
+            Some(result) => result.value.filter(|&int| int > 0).map(|int| int as usize),
+            None => None,
+        };
+        shape.push(length);
+    }
+    shape
+}
+
+/// Each "*" makes a pointer to the base type, and the shape makes an array of the pointers.
+fn get_declared_type(
+    btype: Option<BTypeKind>,
+    ptr_depth: usize,
+    shape: &[Option<usize>],
+) -> Option<SysYType> {
+    let mut ty = SysYType::Basic(btype?);
+    for _ in 0..ptr_depth {
+        ty = SysYType::Pointer(Box::new(ty));
+    }
+    for &length in shape.iter().rev() {
+        ty = SysYType::Array(Box::new(ty), length);
+    }
+    Some(ty)
+}
+
+fn is_char_array(ty: Option<&SysYType>) -> bool {
+    match ty {
+        Some(SysYType::Array(elem_type, _)) => {
+            matches!(**elem_type, SysYType::Basic(BTypeKind::Char))
+        }
+        _ => false,
+    }
+}
+
+/// Checks that the values in an initval can be converted into the type of the variable.
+/// Returns the value of a scalar initialized by an integer constant.
+/// The braces are checked by the IR builder, and so are the members of structs.
+fn check_initval(
+    initval: &InitVal,
+    ty: Option<&SysYType>,
+    type_checker_info: &mut TypeCheckerInfo,
+) -> Option<i64> {
+    match initval {
+        // A string literal fills a char array with its chars and the ending '\0'.
+        InitVal::Exp(exp) if exp.string_literal().is_some() && is_char_array(ty) => {
+            let length = exp.string_literal().unwrap().len() + 1;
+            if let Some(SysYType::Array(_, Some(array_length))) = ty {
+                if length > *array_length {
+                    type_checker_info.error::<()>(
+                        ErrorCode::InvalidInitializer,
+                        format!(
+                            "The string and its '\\0' need {} chars, but the array has {}! ",
+                            length, array_length
+                        ),
+                        exp.span(),
+                    );
+                }
+            }
+            None
+        }
+        InitVal::Exp(exp) if matches!(ty, Some(SysYType::Basic(BTypeKind::Struct(_)))) => {
+            check_struct_copy(ty.unwrap(), exp, type_checker_info);
+            None
+        }
+        InitVal::Exp(exp) => {
+            let result = get_value(exp.check(type_checker_info), exp.span(), type_checker_info)?;
+            match ty {
+                Some(ty @ SysYType::Pointer(_)) => {
+                    check_conversion(ty, &result, exp.span(), type_checker_info);
+                    None
+                }
+                Some(ty @ SysYType::Basic(btype)) if ty.is_arithmetic() => {
+                    check_conversion(ty, &result, exp.span(), type_checker_info);
+                    convert_const(result.value?, *btype)
+                }
+                _ => None,
+            }
+        }
+        InitVal::Aggregate(childs, _) => {
+            // Every value in an array of numbers is a number.
+            let mut elem_type = ty;
+            while let Some(SysYType::Array(ty, _)) = elem_type {
+                elem_type = Some(ty);
+            }
+            let elem_type = elem_type.filter(|ty| ty.is_arithmetic());
+            for child in childs {
+                check_initval(child, elem_type, type_checker_info);
+            }
+            None
+        }
+    }
+}
+
+impl TypeCheckable for Decl {
+    fn check(&self, type_checker_info: &mut TypeCheckerInfo) {
+        match self {
+            Decl::ConstDecl(const_decl) => const_decl.check(type_checker_info),
+            Decl::VarDecl(var_decl) => var_decl.check(type_checker_info),
+        }
+    }
+}
+
+impl TypeCheckable for ConstDecl {
+    fn check(&self, type_checker_info: &mut TypeCheckerInfo) {
+        let ConstDecl::Default(btype, const_defs, _) = self;
+        let btype = check_btype(btype, type_checker_info);
+        for ConstDef::Default(ident, shape_exps, rhs, _) in const_defs {
+            let shape = check_shape(shape_exps, type_checker_info);
+            let ty = get_declared_type(btype, 0, &shape);
+            let value = check_initval(rhs, ty.as_ref(), type_checker_info);
+            type_checker_info.insert_symbol(ident, SymbolKind::Constant, ty, value);
+        }
+    }
+}
+
+/// An extern declaration has no initvals, so it is checked like a variable declaration.
+impl TypeCheckable for VarDecl {
+    fn check(&self, type_checker_info: &mut TypeCheckerInfo) {
+        check_var_decl(self, false, type_checker_info);
+    }
+}
+
+/// A local number or pointer without an initval is uninitialized, unless it is static.
+/// A static variable is 0 before it is initialized, like a global one.
+fn check_var_decl(var_decl: &VarDecl, is_static: bool, type_checker_info: &mut TypeCheckerInfo) {
+    let VarDecl::Default(btype, var_defs, _) = var_decl;
+    let btype = check_stored_btype(btype, type_checker_info);
+    for VarDef::Default(ptr_depth, ident, shape_exps, possible_rhs, _) in var_defs {
+        let shape = check_shape(shape_exps, type_checker_info);
+        let ty = get_declared_type(btype, *ptr_depth, &shape);
+        // The variable is declared after its initval, like in the IR builder.
+        if let Some(rhs) = possible_rhs {
+            check_initval(rhs, ty.as_ref(), type_checker_info);
+        }
+        // A global variable can be declared again, e.g. by extern, with the same type.
+        // A redefinition is reported by the IR builder.
+        let old_symbol = match type_checker_info.symbol_tables.len() {
+            1 => type_checker_info.symbol_tables[0].get(&ident.content),
+            _ => None,
+        };
+        let old_type = old_symbol
+            .filter(|symbol| symbol.kind == SymbolKind::Variable)
+            .and_then(|symbol| symbol.ty.clone());
+        if let (Some(old_type), Some(ty)) = (old_type, &ty) {
+            if !old_type.is_same(ty) {
+                type_checker_info.error::<()>(
+                    ErrorCode::MismatchedTypes,
+                    format!("Conflicting types for global variable {}! ", ident.content),
+                    ident.span,
+                );
+            }
+        }
+        let is_scalar = ty
+            .as_ref()
+            .is_some_and(|ty| ty.is_arithmetic() || ty.is_pointer());
+        let id = type_checker_info.insert_symbol(ident, SymbolKind::Variable, ty, None);
+        if possible_rhs.is_none() && is_scalar && !is_static {
+            type_checker_info.declare_uninitialized(id);
+        }
+    }
+}
+
+impl TypeCheckable for StructDef {
+    fn check(&self, type_checker_info: &mut TypeCheckerInfo) {
+        let StructDef::Default(ident, member_decls, _) = self;
+        // A redefinition is reported by the IR builder.
+        if type_checker_info.struct_table.contains_key(&ident.content) {
+            return;
+        }
+        // Insert the struct first, because its members may point to it.
+        type_checker_info
+            .struct_table
+            .insert(ident.content.clone(), vec![]);
+        let mut members = vec![];
+        for VarDecl::Default(btype, var_defs, _) in member_decls {
+            let btype = check_stored_btype(btype, type_checker_info);
+            for VarDef::Default(ptr_depth, member_ident, shape_exps, _, _) in var_defs {
+                if matches!(btype, Some(BTypeKind::Struct(name)) if name == ident.content)
+                    && *ptr_depth == 0
+                {
+                    type_checker_info.error::<()>(
+                        ErrorCode::MismatchedTypes,
+                        "A struct can not contain itself! Use a pointer instead. ".to_string(),
+                        member_ident.span,
+                    );
+                }
+                let shape = check_shape(shape_exps, type_checker_info);
+                members.push((
+                    member_ident.content.clone(),
+                    get_declared_type(btype, *ptr_depth, &shape),
+                ));
+            }
+        }
+        type_checker_info
+            .struct_table
+            .insert(ident.content.clone(), members);
+    }
+}
+
+/// An enumerator without a value is 1 more than the one before.
+/// If the value before is unknown because of an error, so are the ones after it.
+impl TypeCheckable for EnumDef {
+    fn check(&self, type_checker_info: &mut TypeCheckerInfo) {
+        let EnumDef::Default(_, enumerators, _) = self;
+        let mut value = Some(0);
+        for Enumerator::Default(ident, possible_exp, _) in enumerators {
+            if let Some(exp) = possible_exp {
+                value = match get_value(exp.check(type_checker_info), exp.span(), type_checker_info)
+                {
+                    Some(result) if !result.ty.is_integer() => type_checker_info.error(
+                        ErrorCode::MismatchedTypes,
+                        "The value of an enumerator must be an int! ".to_string(),
+                        exp.span(),
+                    ),
+                    Some(result) => result
+                        .value
+                        .and_then(|int| convert_const(int, BTypeKind::Int)),
+                    None => None,
+                };
+            }
+            let ty = Some(SysYType::Basic(BTypeKind::Int));
+            type_checker_info.insert_symbol(ident, SymbolKind::Constant, ty, value);
+            value = value.map(|int| (int as i32).wrapping_add(1) as i64);
+        }
+    }
+}
+
+/// Checks the signature of a function, and declares the function.
+/// A function can be declared again, with the same signature.
+/// Returns the params with their types. An array param is a pointer to its first element.
+fn check_signature<'a>(
+    return_type: &BType,
+    func_id: &IDENT,
+    params: &'a [FuncFParam],
+    is_variadic: bool,
+    is_definition: bool,
+    type_checker_info: &mut TypeCheckerInfo,
+) -> Vec<(&'a IDENT, Option<SysYType>)> {
+    if let BTypeKind::Struct(_) = return_type.content {
+        type_checker_info.error::<()>(
+            ErrorCode::MismatchedTypes,
+            "A function can not return a struct! ".to_string(),
+            return_type.span,
+        );
+    }
+    if return_type.content == BTypeKind::LongLong {
+        type_checker_info.check_long_long(return_type.span, true);
+    }
+    let mut checked_params = vec![];
+    for FuncFParam::Default(btype, ptr_depth, ident, possible_shape_exps, span) in params {
+        let ty = match (btype.content, ptr_depth, possible_shape_exps) {
+            (BTypeKind::Struct(_), 0, None) => type_checker_info.error(
+                ErrorCode::MismatchedTypes,
+                "A struct can not be passed by value! Pass a pointer to it instead. ".to_string(),
+                *span,
+            ),
+            (_, _, Some(shape_exps)) => {
+                let btype = check_stored_btype(btype, type_checker_info);
+                let shape = check_shape(shape_exps, type_checker_info);
+                get_declared_type(btype, *ptr_depth, &shape)
+                    .map(|ty| SysYType::Pointer(Box::new(ty)))
+            }
+            (_, _, None) => {
+                let btype = check_stored_btype(btype, type_checker_info);
+                get_declared_type(btype, *ptr_depth, &[])
+            }
+        };
+        checked_params.push((ident, ty));
+    }
+    let signature = FunctionSignature {
+        return_type: return_type.content,
+        param_types: checked_params.iter().map(|(_, ty)| ty.clone()).collect(),
+        is_variadic,
+        is_library: false,
+        is_defined: is_definition,
+    };
+    // A redefinition is reported by the IR builder. The first signature is kept.
+    match type_checker_info.function_table.get_mut(&func_id.content) {
+        Some(old_signature) if old_signature.is_defined && is_definition => {}
+        Some(old_signature) if !old_signature.is_same(&signature) => {
+            let message = match is_definition {
+                true => format!(
+                    "The definition of function '{}' does not match its prototype! ",
+                    func_id.content
+                ),
+                false => format!(
+                    "The declaration of function '{}' does not match its previous declaration! ",
+                    func_id.content
+                ),
+            };
+            type_checker_info.error::<()>(ErrorCode::MismatchedTypes, message, func_id.span);
+        }
+        Some(old_signature) => old_signature.is_defined |= is_definition,
+        None => {
+            type_checker_info
+                .function_table
+                .insert(func_id.content.clone(), signature);
+        }
+    }
+    checked_params
+}
+
+impl TypeCheckable for FuncDecl {
+    fn check(&self, type_checker_info: &mut TypeCheckerInfo) {
+        let FuncDecl::Default(return_type, func_id, params, is_variadic, _) = self;
+        let is_library_variadic = type_checker_info
+            .function_table
+            .get(&func_id.content)
+            .is_some_and(|signature| signature.is_library && signature.is_variadic);
+        if *is_variadic && !is_library_variadic {
+            type_checker_info.error::<()>(
+                ErrorCode::UnsupportedVariadic,
+                format!(
+                    "Only library functions like putf can take more args, but got '{}'! ",
+                    func_id.content
+                ),
+                func_id.span,
+            );
+        }
+        check_signature(
+            return_type,
+            func_id,
+            params,
+            *is_variadic,
+            false,
+            type_checker_info,
+        );
+    }
+}
+
+impl TypeCheckable for FuncDef {
+    fn check(&self, type_checker_info: &mut TypeCheckerInfo) {
+        let FuncDef::Default(return_type, func_id, params, block, _) = self;
+        let params = check_signature(return_type, func_id, params, false, true, type_checker_info);
+        // The params are in the same scope as the top level of the body.
+        type_checker_info.add_new_table();
+        for (ident, ty) in params {
+            type_checker_info.insert_symbol(ident, SymbolKind::Parameter, ty, None);
+        }
+        type_checker_info.curr_func = Some((func_id.content.clone(), return_type.content));
+        type_checker_info.uninitialized = Some(HashSet::new());
+        block.check(type_checker_info);
+        // main returns 0 at its end, like in C.
+        let Block::Default(_, block_span) = block;
+        if type_checker_info.uninitialized.is_some()
+            && return_type.content != BTypeKind::Void
+            && func_id.content != "main"
+        {
+            type_checker_info.warn(
+                WarningFlag::ReturnType,
+                format!(
+                    "Function '{}' can reach its end without returning a value! ",
+                    func_id.content
+                ),
+                Span::new(block_span.end - 1, block_span.end),
+            );
+        }
+        type_checker_info.check_gotos();
+        type_checker_info.uninitialized = None;
+        type_checker_info.curr_func = None;
+        type_checker_info.delete_new_table();
+    }
+}
+
+/// The caller creates the scope of the block.
+impl TypeCheckable for Block {
+    fn check(&self, type_checker_info: &mut TypeCheckerInfo) {
+        let Block::Default(items, _) = self;
+        check_block_items(items, type_checker_info);
+    }
+}
+
+/// Checks the items of a block or a case in order.
+/// The first item that can never run is reported, unless the whole list can never run.
+/// Empty statements and breaks are not worth reporting, e.g. a break after a return in a case.
+/// An item with a label can run after a goto.
+pub fn check_block_items(items: &[BlockItem], type_checker_info: &mut TypeCheckerInfo) {
+    let mut is_reported = type_checker_info.uninitialized.is_none();
+    for item in items {
+        let is_trivial = matches!(
+            item,
+            BlockItem::Stmt(stmt) if matches!(stmt.basic_stmt(), BasicStmt::Exp(None, _) | BasicStmt::BreakStmt(_))
+        );
+        if !is_reported
+            && !is_trivial
+            && !item.has_label()
+            && type_checker_info.uninitialized.is_none()
+        {
+            is_reported = true;
+            type_checker_info.warn(
+                WarningFlag::UnreachableCode,
+                "This code is unreachable! ".to_string(),
+                item.span(),
+            );
+        }
+        item.check(type_checker_info);
+    }
+}
+
+impl TypeCheckable for BlockItem {
+    fn check(&self, type_checker_info: &mut TypeCheckerInfo) {
+        match self {
+            BlockItem::Decl(decl) => decl.check(type_checker_info),
+            BlockItem::StaticDecl(var_decl) => check_var_decl(var_decl, true, type_checker_info),
+            BlockItem::TypeDef(_) => {}
+            BlockItem::Stmt(stmt) => stmt.check(type_checker_info),
+        }
+    }
+}
@@ -0,0 +1,1090 @@
+//! Check the type of a single expression.
+
+use crate::assembly_builder::Target;
+use crate::ast_def::expressions::*;
+use crate::ast_def::symbols::{BTypeKind, Span, IDENT};
+use crate::diagnostics::ErrorCode;
+use crate::ir_builder::SymbolKind;
+use koopa::ir::BinaryOp;
+
+use super::{SysYType, TypeCheckerInfo};
+
+/// The type of a checked expression, and its value if it is an integer constant.
+/// Arrays have been converted into pointers to their first elements.
+#[derive(Debug, Clone)]
+pub struct CheckedExp {
+    pub ty: SysYType,
+    pub value: Option<i64>,
+}
+
+impl CheckedExp {
+    pub fn of_type(ty: SysYType) -> CheckedExp {
+        CheckedExp { ty, value: None }
+    }
+
+    /// The constant 0 is the null pointer.
+    pub fn is_null_pointer(&self) -> bool {
+        self.ty == SysYType::Basic(BTypeKind::Int) && self.value == Some(0)
+    }
+}
+
+/// A checked LVal. Only scalar constants have no address, because they are folded.
+#[derive(Debug)]
+pub struct CheckedLVal {
+    pub ty: SysYType,
+    pub is_const: bool,
+    pub has_address: bool,
+    pub value: Option<i64>,
+}
+
+pub trait ExpTypeCheckable {
+    fn check(&self, type_checker_info: &mut TypeCheckerInfo) -> Option<CheckedExp>;
+}
+
+/// Checks that the result is a value, which is a number or a pointer.
+/// The result of a void function and a string literal are not values.
+pub fn get_value(
+    result: Option<CheckedExp>,
+    span: Span,
+    type_checker_info: &mut TypeCheckerInfo,
+) -> Option<CheckedExp> {
+    let result = result?;
+    match result.ty {
+        SysYType::Void => type_checker_info.error(
+            ErrorCode::MismatchedTypes,
+            "The result of a void function can not be used as a value! ".to_string(),
+            span,
+        ),
+        SysYType::String => type_checker_info.error(
+            ErrorCode::MismatchedTypes,
+            "A string literal can only be passed to library functions as a char array! "
+                .to_string(),
+            span,
+        ),
+        _ => Some(result),
+    }
+}
+
+/// Whether a value can be converted into the type, e.g. assigned to a variable of it.
+/// Numbers are converted into each other. A pointer must have the same type, or be 0.
+fn is_convertible(ty: &SysYType, result: &CheckedExp) -> bool {
+    match ty {
+        SysYType::Pointer(_) => result.ty.is_same(ty) || result.is_null_pointer(),
+        _ => ty.is_arithmetic() && result.ty.is_arithmetic(),
+    }
+}
+
+/// Checks that a value can be converted into the type, and reports an error if not.
+pub fn check_conversion(
+    ty: &SysYType,
+    result: &CheckedExp,
+    span: Span,
+    type_checker_info: &mut TypeCheckerInfo,
+) {
+    if !is_convertible(ty, result) {
+        type_checker_info.error::<()>(
+            ErrorCode::MismatchedTypes,
+            format!("Expected {} here, but got {}! ", ty, result.ty),
+            span,
+        );
+    }
+}
+
+/// Checks the source of a whole struct copy, like "t = s" or "struct S t = *p".
+/// A struct is not a value, so it can only be copied from a struct LVal of the same type.
+pub fn check_struct_copy(ty: &SysYType, exp: &Exp, type_checker_info: &mut TypeCheckerInfo) {
+    let source_type = match exp.clone().into_lval() {
+        Ok(lval) => lval.check(type_checker_info).map(|lval| lval.ty),
+        Err(_) => get_value(exp.check(type_checker_info), exp.span(), type_checker_info)
+            .map(|result| result.ty),
+    };
+    if let Some(source_type) = source_type.filter(|source_type| !source_type.is_same(ty)) {
+        type_checker_info.error::<()>(
+            ErrorCode::MismatchedTypes,
+            format!("Expected {} here, but got {}! ", ty, source_type),
+            exp.span(),
+        );
+    }
+}
+
+/// Converts the value of an integer constant into the given type.
+/// A char keeps the lowest byte. Chars are unsigned in RISC-V.
+pub fn convert_const(value: i64, btype: BTypeKind) -> Option<i64> {
+    match btype {
+        BTypeKind::Int => Some(value as i32 as i64),
+        BTypeKind::Char => Some(value as u8 as i64),
+        BTypeKind::LongLong => Some(value),
+        _ => None,
+    }
+}
+
+/// If any of them is a float, the result is a float.
+/// Otherwise, if any of them is a long long, the result is a long long.
+fn get_arithmetic_type(ty1: &SysYType, ty2: &SysYType) -> SysYType {
+    let float = SysYType::Basic(BTypeKind::Float);
+    let long_long = SysYType::Basic(BTypeKind::LongLong);
+    if *ty1 == float || *ty2 == float {
+        float
+    } else if *ty1 == long_long || *ty2 == long_long {
+        long_long
+    } else {
+        SysYType::Basic(BTypeKind::Int)
+    }
+}
+
+/// The operator in C, for error messages.
+fn operator_symbol(binary_op: BinaryOp) -> &'static str {
+    match binary_op {
+        BinaryOp::NotEq => "!=",
+        BinaryOp::Eq => "==",
+        BinaryOp::Gt => ">",
+        BinaryOp::Lt => "<",
+        BinaryOp::Ge => ">=",
+        BinaryOp::Le => "<=",
+        BinaryOp::Add => "+",
+        BinaryOp::Sub => "-",
+        BinaryOp::Mul => "*",
+        BinaryOp::Div => "/",
+        BinaryOp::Mod => "%",
+        BinaryOp::And => "&",
+        BinaryOp::Or => "|",
+        BinaryOp::Xor => "^",
+        BinaryOp::Shl => "<<",
+        BinaryOp::Shr | BinaryOp::Sar => ">>",
+    }
+}
+
+/// Folds a binary operation on integer constants, like the IR builder.
+/// Overflows wrap around. A division by 0 is not folded.
+fn fold_integers(binary_op: BinaryOp, int1: i64, int2: i64, is_long_long: bool) -> Option<i64> {
+    if !is_long_long {
+        let (int1, int2) = (int1 as i32, int2 as i32);
+        return Some(match binary_op {
+            BinaryOp::NotEq => (int1 != int2) as i32,
+            BinaryOp::Eq => (int1 == int2) as i32,
+            BinaryOp::Gt => (int1 > int2) as i32,
+            BinaryOp::Lt => (int1 < int2) as i32,
+            BinaryOp::Ge => (int1 >= int2) as i32,
+            BinaryOp::Le => (int1 <= int2) as i32,
+            BinaryOp::Add => int1.wrapping_add(int2),
+            BinaryOp::Sub => int1.wrapping_sub(int2),
+            BinaryOp::Mul => int1.wrapping_mul(int2),
+            BinaryOp::Div | BinaryOp::Mod if int2 == 0 => return None,
+            BinaryOp::Div => int1.wrapping_div(int2),
+            BinaryOp::Mod => int1.wrapping_rem(int2),
+            BinaryOp::And => int1 & int2,
+            BinaryOp::Or => int1 | int2,
+            BinaryOp::Xor => int1 ^ int2,
+            BinaryOp::Shl => int1.wrapping_shl(int2 as u32),
+            BinaryOp::Shr => (int1 as u32).wrapping_shr(int2 as u32) as i32,
+            BinaryOp::Sar => int1.wrapping_shr(int2 as u32),
+        } as i64);
+    }
+    Some(match binary_op {
+        BinaryOp::NotEq => (int1 != int2) as i64,
+        BinaryOp::Eq => (int1 == int2) as i64,
+        BinaryOp::Gt => (int1 > int2) as i64,
+        BinaryOp::Lt => (int1 < int2) as i64,
+        BinaryOp::Ge => (int1 >= int2) as i64,
+        BinaryOp::Le => (int1 <= int2) as i64,
+        BinaryOp::Add => int1.wrapping_add(int2),
+        BinaryOp::Sub => int1.wrapping_sub(int2),
+        BinaryOp::Mul => int1.wrapping_mul(int2),
+        BinaryOp::Div | BinaryOp::Mod if int2 == 0 => return None,
+        BinaryOp::Div => int1.wrapping_div(int2),
+        BinaryOp::Mod => int1.wrapping_rem(int2),
+        BinaryOp::And => int1 & int2,
+        BinaryOp::Or => int1 | int2,
+        BinaryOp::Xor => int1 ^ int2,
+        BinaryOp::Shl => int1.wrapping_shl(int2 as u32),
+        BinaryOp::Shr => (int1 as u64).wrapping_shr(int2 as u32) as i64,
+        BinaryOp::Sar => int1.wrapping_shr(int2 as u32),
+    })
+}
+
+/// Checks a binary operation on two values, and returns the type of the result.
+pub fn check_binary_from_check_results(
+    result1: CheckedExp,
+    result2: CheckedExp,
+    binary_op: BinaryOp,
+    span: Span,
+    type_checker_info: &mut TypeCheckerInfo,
+) -> Option<CheckedExp> {
+    if result1.ty.is_pointer() || result2.ty.is_pointer() {
+        return check_pointer_binary_from_check_results(
+            result1,
+            result2,
+            binary_op,
+            span,
+            type_checker_info,
+        );
+    }
+    let is_comparison = matches!(
+        binary_op,
+        BinaryOp::Eq | BinaryOp::NotEq | BinaryOp::Lt | BinaryOp::Gt | BinaryOp::Le | BinaryOp::Ge
+    );
+    let operand_type = get_arithmetic_type(&result1.ty, &result2.ty);
+    if operand_type == SysYType::Basic(BTypeKind::Float) {
+        if !is_comparison
+            && !matches!(
+                binary_op,
+                BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div
+            )
+        {
+            return type_checker_info.error(
+                ErrorCode::MismatchedTypes,
+                format!(
+                    "Operator {} can not be used on floats! ",
+                    operator_symbol(binary_op)
+                ),
+                span,
+            );
+        }
+        return Some(CheckedExp::of_type(match is_comparison {
+            true => SysYType::Basic(BTypeKind::Int),
+            false => operand_type,
+        }));
+    }
+    // The shift amount is an int, and the result has the type of the left operand.
+    let operand_type = match binary_op {
+        BinaryOp::Shl | BinaryOp::Shr | BinaryOp::Sar => result1.ty.promote(),
+        _ => operand_type,
+    };
+    let is_long_long = operand_type == SysYType::Basic(BTypeKind::LongLong);
+    let value = match (result1.value, result2.value) {
+        // A constant expression has no value if it divides by 0, unless it is never evaluated.
+        (Some(_), Some(0))
+            if matches!(binary_op, BinaryOp::Div | BinaryOp::Mod)
+                && type_checker_info.skipped_depth == 0 =>
+        {
+            return type_checker_info.error(
+                ErrorCode::DivisionByZero,
+                "Division by zero in a constant expression! ".to_string(),
+                span,
+            );
+        }
+        (Some(int1), Some(int2)) => fold_integers(binary_op, int1, int2, is_long_long),
+        _ => None,
+    };
+    // A comparison of long longs needs RV64 too, although its result is an int.
+    if is_long_long && value.is_none() {
+        type_checker_info.check_long_long(span, false);
+    }
+    let ty = match is_comparison {
+        true => SysYType::Basic(BTypeKind::Int),
+        false => operand_type,
+    };
+    Some(CheckedExp { ty, value })
+}
+
+/// p + n and p - n move p by n elements, and p - q is the number of elements between them.
+/// Comparisons compare the addresses. A pointer can also be compared with 0, the null pointer.
+fn check_pointer_binary_from_check_results(
+    result1: CheckedExp,
+    result2: CheckedExp,
+    binary_op: BinaryOp,
+    span: Span,
+    type_checker_info: &mut TypeCheckerInfo,
+) -> Option<CheckedExp> {
+    let int = SysYType::Basic(BTypeKind::Int);
+    let is_same_type = result1.ty.is_pointer() && result1.ty.is_same(&result2.ty);
+    match (binary_op, result1.ty.is_pointer(), result2.ty.is_pointer()) {
+        (BinaryOp::Add | BinaryOp::Sub, true, false) | (BinaryOp::Add, false, true) => {
+            let (pointer, index) = match result1.ty.is_pointer() {
+                true => (result1, result2),
+                false => (result2, result1),
+            };
+            match index.ty.is_integer() {
+                true => Some(CheckedExp::of_type(pointer.ty)),
+                false => type_checker_info.error(
+                    ErrorCode::MismatchedTypes,
+                    "A pointer can only be moved by an int! ".to_string(),
+                    span,
+                ),
+            }
+        }
+        (BinaryOp::Sub, true, true) => match is_same_type {
+            true => Some(CheckedExp::of_type(int)),
+            false => type_checker_info.error(
+                ErrorCode::MismatchedTypes,
+                format!(
+                    "Only pointers of the same type can be subtracted, but got {} and {}! ",
+                    result1.ty, result2.ty
+                ),
+                span,
+            ),
+        },
+        (
+            BinaryOp::Eq
+            | BinaryOp::NotEq
+            | BinaryOp::Lt
+            | BinaryOp::Gt
+            | BinaryOp::Le
+            | BinaryOp::Ge,
+            is_pointer1,
+            is_pointer2,
+        ) => {
+            let is_comparable = match (is_pointer1, is_pointer2) {
+                (true, true) => is_same_type,
+                (true, false) => result2.is_null_pointer(),
+                _ => result1.is_null_pointer(),
+            };
+            match is_comparable {
+                true => Some(CheckedExp::of_type(int)),
+                false => type_checker_info.error(
+                    ErrorCode::MismatchedTypes,
+                    format!(
+                        "A pointer can only be compared with a pointer of the same type or 0, but got {} and {}! ",
+                        result1.ty, result2.ty
+                    ),
+                    span,
+                ),
+            }
+        }
+        _ => type_checker_info.error(
+            ErrorCode::MismatchedTypes,
+            format!(
+                "Operator {} can not be used on pointers! ",
+                operator_symbol(binary_op)
+            ),
+            span,
+        ),
+    }
+}
+
+/// Checks both operands of a binary expression, and annotates the expression.
+fn check_binary_exp(
+    exp1: &impl ExpTypeCheckable,
+    exp2: &impl ExpTypeCheckable,
+    binary_op: BinaryOp,
+    span: Span,
+    type_checker_info: &mut TypeCheckerInfo,
+) -> Option<CheckedExp> {
+    let result1 = get_value(exp1.check(type_checker_info), span, type_checker_info);
+    let result2 = get_value(exp2.check(type_checker_info), span, type_checker_info);
+    let result =
+        check_binary_from_check_results(result1?, result2?, binary_op, span, type_checker_info)?;
+    type_checker_info.annotate(span, result)
+}
+
+impl ExpTypeCheckable for Exp {
+    fn check(&self, type_checker_info: &mut TypeCheckerInfo) -> Option<CheckedExp> {
+        match self {
+            Exp::CondExp(cond_exp, span) => {
+                let result = cond_exp.check(type_checker_info)?;
+                type_checker_info.annotate(*span, result)
+            }
+        }
+    }
+}
+
+/// Checks an operand, which is never evaluated if it is skipped by a constant,
+/// e.g. the other branch of "1 ? a : b", or b in "0 && b".
+fn check_operand(
+    exp: &dyn ExpTypeCheckable,
+    is_skipped: bool,
+    type_checker_info: &mut TypeCheckerInfo,
+) -> Option<CheckedExp> {
+    type_checker_info.skipped_depth += is_skipped as usize;
+    let result = exp.check(type_checker_info);
+    type_checker_info.skipped_depth -= is_skipped as usize;
+    result
+}
+
+impl ExpTypeCheckable for CondExp {
+    fn check(&self, type_checker_info: &mut TypeCheckerInfo) -> Option<CheckedExp> {
+        match self {
+            CondExp::LOrExp(exp) => exp.check(type_checker_info),
+            CondExp::TernaryExp(cond, exp1, exp2, span) => {
+                let cond_result =
+                    get_value(cond.check(type_checker_info), *span, type_checker_info);
+                let cond_value = cond_result.as_ref().and_then(|result| result.value);
+                let result1 = get_value(
+                    check_operand(&**exp1, cond_value == Some(0), type_checker_info),
+                    exp1.span(),
+                    type_checker_info,
+                );
+                let result2 = get_value(
+                    check_operand(
+                        &**exp2,
+                        cond_value.is_some_and(|int| int != 0),
+                        type_checker_info,
+                    ),
+                    exp2.span(),
+                    type_checker_info,
+                );
+                let (cond_result, result1, result2) = (cond_result?, result1?, result2?);
+                // The result is stored in a variable of a number type.
+                if !result1.ty.is_arithmetic() || !result2.ty.is_arithmetic() {
+                    return type_checker_info.error(
+                        ErrorCode::MismatchedTypes,
+                        format!(
+                            "The operands of \"?:\" must be numbers, but got {} and {}! ",
+                            result1.ty, result2.ty
+                        ),
+                        *span,
+                    );
+                }
+                let ty = get_arithmetic_type(&result1.ty, &result2.ty);
+                let value = match (cond_result.value, &ty) {
+                    (_, SysYType::Basic(BTypeKind::Float)) | (None, _) => None,
+                    (Some(0), _) => result2.value,
+                    (Some(_), _) => result1.value,
+                };
+                type_checker_info.annotate(*span, CheckedExp { ty, value })
+            }
+        }
+    }
+}
+
+impl ExpTypeCheckable for LOrExp {
+    fn check(&self, type_checker_info: &mut TypeCheckerInfo) -> Option<CheckedExp> {
+        match self {
+            LOrExp::LAndExp(exp) => exp.check(type_checker_info),
+            LOrExp::BinaryLOrExp(exp1, exp2, span) => {
+                let result1 = get_value(exp1.check(type_checker_info), *span, type_checker_info);
+                let is_skipped = result1
+                    .as_ref()
+                    .is_some_and(|result| result.value.is_some_and(|int| int != 0));
+                let result2 = get_value(
+                    check_operand(exp2, is_skipped, type_checker_info),
+                    *span,
+                    type_checker_info,
+                );
+                let (result1, result2) = (result1?, result2?);
+                let value = match (result1.value, result2.value) {
+                    (Some(int1), _) if int1 != 0 => Some(1),
+                    (Some(_), Some(int2)) => Some((int2 != 0) as i64),
+                    _ => None,
+                };
+                let ty = SysYType::Basic(BTypeKind::Int);
+                type_checker_info.annotate(*span, CheckedExp { ty, value })
+            }
+        }
+    }
+}
+
+impl ExpTypeCheckable for LAndExp {
+    fn check(&self, type_checker_info: &mut TypeCheckerInfo) -> Option<CheckedExp> {
+        match self {
+            LAndExp::BitOrExp(exp) => exp.check(type_checker_info),
+            LAndExp::BinaryLAndExp(exp1, exp2, span) => {
+                let result1 = get_value(exp1.check(type_checker_info), *span, type_checker_info);
+                let is_skipped = result1
+                    .as_ref()
+                    .is_some_and(|result| result.value == Some(0));
+                let result2 = get_value(
+                    check_operand(exp2, is_skipped, type_checker_info),
+                    *span,
+                    type_checker_info,
+                );
+                let (result1, result2) = (result1?, result2?);
+                let value = match (result1.value, result2.value) {
+                    (Some(0), _) => Some(0),
+                    (Some(_), Some(int2)) => Some((int2 != 0) as i64),
+                    _ => None,
+                };
+                let ty = SysYType::Basic(BTypeKind::Int);
+                type_checker_info.annotate(*span, CheckedExp { ty, value })
+            }
+        }
+    }
+}
+
+impl ExpTypeCheckable for BitOrExp {
+    fn check(&self, type_checker_info: &mut TypeCheckerInfo) -> Option<CheckedExp> {
+        match self {
+            BitOrExp::BitXorExp(exp) => exp.check(type_checker_info),
+            BitOrExp::BinaryBitOrExp(exp1, exp2, span) => {
+                check_binary_exp(&**exp1, exp2, BinaryOp::Or, *span, type_checker_info)
+            }
+        }
+    }
+}
+
+impl ExpTypeCheckable for BitXorExp {
+    fn check(&self, type_checker_info: &mut TypeCheckerInfo) -> Option<CheckedExp> {
+        match self {
+            BitXorExp::BitAndExp(exp) => exp.check(type_checker_info),
+            BitXorExp::BinaryBitXorExp(exp1, exp2, span) => {
+                check_binary_exp(&**exp1, exp2, BinaryOp::Xor, *span, type_checker_info)
+            }
+        }
+    }
+}
+
+impl ExpTypeCheckable for BitAndExp {
+    fn check(&self, type_checker_info: &mut TypeCheckerInfo) -> Option<CheckedExp> {
+        match self {
+            BitAndExp::EqExp(exp) => exp.check(type_checker_info),
+            BitAndExp::BinaryBitAndExp(exp1, exp2, span) => {
+                check_binary_exp(&**exp1, exp2, BinaryOp::And, *span, type_checker_info)
+            }
+        }
+    }
+}
+
+impl ExpTypeCheckable for EqExp {
+    fn check(&self, type_checker_info: &mut TypeCheckerInfo) -> Option<CheckedExp> {
+        match self {
+            EqExp::RelExp(exp) => exp.check(type_checker_info),
+            EqExp::BinaryEqExp(exp1, exp2, span) => {
+                check_binary_exp(&**exp1, exp2, BinaryOp::Eq, *span, type_checker_info)
+            }
+            EqExp::BinaryUneqExp(exp1, exp2, span) => {
+                check_binary_exp(&**exp1, exp2, BinaryOp::NotEq, *span, type_checker_info)
+            }
+        }
+    }
+}
+
+impl ExpTypeCheckable for RelExp {
+    fn check(&self, type_checker_info: &mut TypeCheckerInfo) -> Option<CheckedExp> {
+        match self {
+            RelExp::ShiftExp(exp) => exp.check(type_checker_info),
+            RelExp::BinaryLtExp(exp1, exp2, span) => {
+                check_binary_exp(&**exp1, exp2, BinaryOp::Lt, *span, type_checker_info)
+            }
+            RelExp::BinaryGtExp(exp1, exp2, span) => {
+                check_binary_exp(&**exp1, exp2, BinaryOp::Gt, *span, type_checker_info)
+            }
+            RelExp::BinaryLeExp(exp1, exp2, span) => {
+                check_binary_exp(&**exp1, exp2, BinaryOp::Le, *span, type_checker_info)
+            }
+            RelExp::BinaryGeExp(exp1, exp2, span) => {
+                check_binary_exp(&**exp1, exp2, BinaryOp::Ge, *span, type_checker_info)
+            }
+        }
+    }
+}
+
+impl ExpTypeCheckable for ShiftExp {
+    fn check(&self, type_checker_info: &mut TypeCheckerInfo) -> Option<CheckedExp> {
+        match self {
+            ShiftExp::AddExp(exp) => exp.check(type_checker_info),
+            ShiftExp::BinaryShlExp(exp1, exp2, span) => {
+                check_binary_exp(&**exp1, exp2, BinaryOp::Shl, *span, type_checker_info)
+            }
+            ShiftExp::BinaryShrExp(exp1, exp2, span) => {
+                check_binary_exp(&**exp1, exp2, BinaryOp::Sar, *span, type_checker_info)
+            }
+        }
+    }
+}
+
+impl ExpTypeCheckable for AddExp {
+    fn check(&self, type_checker_info: &mut TypeCheckerInfo) -> Option<CheckedExp> {
+        match self {
+            AddExp::MulExp(exp) => exp.check(type_checker_info),
+            AddExp::BinaryAddExp(exp1, exp2, span) => {
+                check_binary_exp(&**exp1, exp2, BinaryOp::Add, *span, type_checker_info)
+            }
+            AddExp::BinarySubExp(exp1, exp2, span) => {
+                check_binary_exp(&**exp1, exp2, BinaryOp::Sub, *span, type_checker_info)
+            }
+        }
+    }
+}
+
+impl ExpTypeCheckable for MulExp {
+    fn check(&self, type_checker_info: &mut TypeCheckerInfo) -> Option<CheckedExp> {
+        match self {
+            MulExp::UnaryExp(exp) => exp.check(type_checker_info),
+            MulExp::BinaryMulExp(exp1, exp2, span) => {
+                check_binary_exp(&**exp1, exp2, BinaryOp::Mul, *span, type_checker_info)
+            }
+            MulExp::BinaryDivExp(exp1, exp2, span) => {
+                check_binary_exp(&**exp1, exp2, BinaryOp::Div, *span, type_checker_info)
+            }
+            MulExp::BinaryModExp(exp1, exp2, span) => {
+                check_binary_exp(&**exp1, exp2, BinaryOp::Mod, *span, type_checker_info)
+            }
+        }
+    }
+}
+
+impl ExpTypeCheckable for UnaryExp {
+    fn check(&self, type_checker_info: &mut TypeCheckerInfo) -> Option<CheckedExp> {
+        let (result, span) = match self {
+            UnaryExp::PrimaryExp(exp) => return exp.check(type_checker_info),
+            UnaryExp::PlusUnaryExp(exp, span) => {
+                let result = get_value(exp.check(type_checker_info), *span, type_checker_info)?;
+                let ty = result.ty.promote();
+                (CheckedExp { ty, ..result }, span)
+            }
+            UnaryExp::MinusUnaryExp(exp, span) => {
+                let result = get_value(exp.check(type_checker_info), *span, type_checker_info)?;
+                if result.ty.is_pointer() {
+                    return type_checker_info.error(
+                        ErrorCode::MismatchedTypes,
+                        "Operator - can not be used on pointers! ".to_string(),
+                        *span,
+                    );
+                }
+                let ty = result.ty.promote();
+                let is_long_long = ty == SysYType::Basic(BTypeKind::LongLong);
+                let value = result
+                    .value
+                    .and_then(|int| fold_integers(BinaryOp::Sub, 0, int, is_long_long));
+                (CheckedExp { ty, value }, span)
+            }
+            UnaryExp::NotUnaryExp(exp, span) => {
+                let result = get_value(exp.check(type_checker_info), *span, type_checker_info)?;
+                let ty = SysYType::Basic(BTypeKind::Int);
+                let value = result.value.map(|int| (int == 0) as i64);
+                (CheckedExp { ty, value }, span)
+            }
+            UnaryExp::BitNotUnaryExp(exp, span) => {
+                let result = get_value(exp.check(type_checker_info), *span, type_checker_info)?;
+                if !result.ty.is_integer() {
+                    return type_checker_info.error(
+                        ErrorCode::MismatchedTypes,
+                        format!("Operator ~ can not be used on {}! ", result.ty),
+                        *span,
+                    );
+                }
+                let ty = result.ty.promote();
+                let is_long_long = ty == SysYType::Basic(BTypeKind::LongLong);
+                let value = result
+                    .value
+                    .and_then(|int| fold_integers(BinaryOp::Xor, int, -1, is_long_long));
+                (CheckedExp { ty, value }, span)
+            }
+            UnaryExp::AddrOfExp(lval, span) => {
+                let checked_lval = lval.check(type_checker_info)?;
+                // The variable may be initialized through the pointer.
+                type_checker_info.write_variable(lval);
+                if !checked_lval.has_address || matches!(checked_lval.ty, SysYType::Array(_, _)) {
+                    return type_checker_info.error(
+                        ErrorCode::NotAssignable,
+                        "Only variables and elements of arrays have addresses! ".to_string(),
+                        *span,
+                    );
+                }
+                let ty = SysYType::Pointer(Box::new(checked_lval.ty));
+                (CheckedExp::of_type(ty), span)
+            }
+            UnaryExp::DerefExp(exp, span) => {
+                let lval = check_deref(exp, *span, type_checker_info)?;
+                (load_lval(lval, *span, type_checker_info)?, span)
+            }
+            UnaryExp::FuncCall(func_id, param_exps, span) => (
+                check_func_call(func_id, param_exps, *span, type_checker_info)?,
+                span,
+            ),
+            UnaryExp::PreIncExp(lval, span)
+            | UnaryExp::PreDecExp(lval, span)
+            | UnaryExp::PostIncExp(lval, span)
+            | UnaryExp::PostDecExp(lval, span) => {
+                // a++ is a += 1.
+                let ty = lval.check_assignable(false, type_checker_info)?;
+                type_checker_info.read_variable(lval);
+                let one = CheckedExp {
+                    ty: SysYType::Basic(BTypeKind::Int),
+                    value: Some(1),
+                };
+                check_binary_from_check_results(
+                    CheckedExp::of_type(ty.clone()),
+                    one,
+                    BinaryOp::Add,
+                    *span,
+                    type_checker_info,
+                )?;
+                (CheckedExp::of_type(ty), span)
+            }
+        };
+        type_checker_info.annotate(*span, result)
+    }
+}
+
+/// Checks the args of a function call, and returns the type of its result.
+fn check_func_call(
+    func_id: &IDENT,
+    param_exps: &[Exp],
+    span: Span,
+    type_checker_info: &mut TypeCheckerInfo,
+) -> Option<CheckedExp> {
+    // Check the args first, so that their errors are reported even if the call is wrong.
+    let args: Vec<Option<CheckedExp>> = param_exps
+        .iter()
+        .map(|exp| exp.check(type_checker_info))
+        .collect();
+    let Some(callee) = type_checker_info
+        .function_table
+        .get(&func_id.content)
+        .cloned()
+    else {
+        return type_checker_info.error(
+            ErrorCode::UndeclaredSymbol,
+            format!("Undeclared function: {}! ", func_id.content),
+            func_id.span,
+        );
+    };
+    let result = CheckedExp::of_type(match callee.return_type {
+        BTypeKind::Void => SysYType::Void,
+        return_type => SysYType::Basic(return_type),
+    });
+    let param_cnt = callee.param_types.len();
+    if param_exps.len() != param_cnt && !(callee.is_variadic && param_exps.len() > param_cnt) {
+        type_checker_info.error::<()>(
+            ErrorCode::WrongArgumentCount,
+            format!(
+                "The parameter number of function '{}' is incorrect! Expected {}{} parameters, but got {}.",
+                func_id.content,
+                if callee.is_variadic { "at least " } else { "" },
+                param_cnt,
+                param_exps.len()
+            ),
+            span,
+        );
+        return Some(result);
+    }
+    for (i, (arg, exp)) in args.into_iter().zip(param_exps).enumerate() {
+        let Some(arg) = arg else {
+            continue;
+        };
+        if arg.ty == SysYType::Void {
+            get_value(Some(arg), exp.span(), type_checker_info);
+            continue;
+        }
+        let Some(param_type) = callee.param_types.get(i) else {
+            // A variadic arg is passed as a number, or a string literal for putf.
+            if !arg.ty.is_arithmetic() && arg.ty != SysYType::String {
+                type_checker_info.error::<()>(
+                    ErrorCode::MismatchedTypes,
+                    format!(
+                        "The variadic parameters of function '{}' must be numbers or string literals, but got {}! ",
+                        func_id.content, arg.ty
+                    ),
+                    exp.span(),
+                );
+            }
+            continue;
+        };
+        let Some(param_type) = param_type else {
+            continue;
+        };
+        // Strings are stored as bytes, but char arrays are stored as i32s.
+        // So only library functions take strings, and they only take strings.
+        let is_string_param = callee.is_library
+            && *param_type == SysYType::Pointer(Box::new(SysYType::Basic(BTypeKind::Char)));
+        let message = match (arg.ty == SysYType::String, is_string_param) {
+            (true, true) => continue,
+            (true, false) => {
+                "A string literal can only be passed to library functions as a char array! "
+                    .to_string()
+            }
+            (false, true) => format!(
+                "Library function '{}' only accepts string literals as char arrays! ",
+                func_id.content
+            ),
+            // A scalar param is converted into the param type, but an array param
+            // must be an array of the same type. The null pointer is not an array.
+            (false, false)
+                if (param_type.is_arithmetic() && arg.ty.is_arithmetic())
+                    || arg.ty.is_same(param_type) =>
+            {
+                continue
+            }
+            (false, false) => format!(
+                "The parameter type of function '{}' is incorrect! Wanted {}, but got {}.",
+                func_id.content, param_type, arg.ty
+            ),
+        };
+        type_checker_info.error::<()>(ErrorCode::MismatchedTypes, message, exp.span());
+    }
+    Some(result)
+}
+
+impl ExpTypeCheckable for PrimaryExp {
+    fn check(&self, type_checker_info: &mut TypeCheckerInfo) -> Option<CheckedExp> {
+        match self {
+            PrimaryExp::BracedExp(exp) => exp.check(type_checker_info),
+            PrimaryExp::LVal(lval) => {
+                let checked_lval = lval.check(type_checker_info)?;
+                type_checker_info.read_variable(lval);
+                let result = load_lval(checked_lval, lval.span(), type_checker_info)?;
+                type_checker_info.annotate(lval.span(), result)
+            }
+            PrimaryExp::Number(number) => number.check(type_checker_info),
+            PrimaryExp::StringConst(_, span) => {
+                type_checker_info.annotate(*span, CheckedExp::of_type(SysYType::String))
+            }
+        }
+    }
+}
+
+impl ExpTypeCheckable for Number {
+    fn check(&self, type_checker_info: &mut TypeCheckerInfo) -> Option<CheckedExp> {
+        let (ty, value, span) = match *self {
+            // A literal that does not fit in an int is a long long.
+            // RV32 has no long long, so one that fits in 32 bits wraps around like an unsigned.
+            Number::INTCONST(int, span) => match i32::try_from(int) {
+                Ok(_) => (BTypeKind::Int, Some(int), span),
+                Err(_)
+                    if type_checker_info.target == Target::RV32 && u32::try_from(int).is_ok() =>
+                {
+                    (BTypeKind::Int, Some(int as i32 as i64), span)
+                }
+                Err(_) => (BTypeKind::LongLong, Some(int), span),
+            },
+            Number::LONGLONGCONST(int, span) => (BTypeKind::LongLong, Some(int), span),
+            Number::FLOATCONST(_, span) => (BTypeKind::Float, None, span),
+            Number::CHARCONST(int, span) => (BTypeKind::Int, Some(int as i64), span),
+        };
+        let ty = SysYType::Basic(ty);
+        type_checker_info.annotate(span, CheckedExp { ty, value })
+    }
+}
+
+/// Converts a LVal into its value. An array is converted into a pointer to its first element.
+fn load_lval(
+    lval: CheckedLVal,
+    span: Span,
+    type_checker_info: &mut TypeCheckerInfo,
+) -> Option<CheckedExp> {
+    match lval.ty {
+        SysYType::Basic(BTypeKind::Struct(_)) => type_checker_info.error(
+            ErrorCode::MismatchedTypes,
+            format!(
+                "A {} can not be used as a value! Use its members instead. ",
+                lval.ty
+            ),
+            span,
+        ),
+        SysYType::Array(elem_type, _) => Some(CheckedExp::of_type(SysYType::Pointer(elem_type))),
+        ty => Some(CheckedExp {
+            ty,
+            value: lval.value,
+        }),
+    }
+}
+
+/// Checks *p. The result is what p points to.
+fn check_deref(
+    exp: &UnaryExp,
+    span: Span,
+    type_checker_info: &mut TypeCheckerInfo,
+) -> Option<CheckedLVal> {
+    let result = get_value(exp.check(type_checker_info), span, type_checker_info)?;
+    match result.ty {
+        SysYType::Pointer(ty) => {
+            // The IR builder finds the type that the pointer points to here.
+            type_checker_info.exp_types.insert(span, (*ty).clone());
+            Some(CheckedLVal {
+                ty: *ty,
+                is_const: false,
+                has_address: true,
+                value: None,
+            })
+        }
+        ty => type_checker_info.error(
+            ErrorCode::MismatchedTypes,
+            format!("Only pointers can be dereferenced, but got {}! ", ty),
+            span,
+        ),
+    }
+}
+
+/// Checks the indexes of an array or a pointer, which are integers.
+/// Returns the type of the element. The type is None if it has been reported.
+fn check_indexes(
+    ty: Option<SysYType>,
+    index_exps: &[Exp],
+    span: Span,
+    type_checker_info: &mut TypeCheckerInfo,
+) -> Option<SysYType> {
+    let mut is_valid = true;
+    for exp in index_exps {
+        match get_value(exp.check(type_checker_info), exp.span(), type_checker_info) {
+            Some(index) if index.ty.is_integer() => {}
+            Some(index) => {
+                is_valid = false;
+                type_checker_info.error::<()>(
+                    ErrorCode::MismatchedTypes,
+                    format!("An index must be an integer, but got {}! ", index.ty),
+                    exp.span(),
+                );
+            }
+            None => is_valid = false,
+        }
+    }
+    let mut ty = ty.filter(|_| is_valid)?;
+    for _ in index_exps {
+        ty = match ty {
+            SysYType::Array(elem_type, _) | SysYType::Pointer(elem_type) => *elem_type,
+            SysYType::Basic(BTypeKind::Struct(_)) => {
+                return type_checker_info.error(
+                    ErrorCode::NotAnArray,
+                    format!("Too many indexes: this is a {}, not an array! ", ty),
+                    span,
+                )
+            }
+            _ => {
+                return type_checker_info.error(
+                    ErrorCode::NotAnArray,
+                    "Too many indexes: this is not an array! ".to_string(),
+                    span,
+                )
+            }
+        };
+    }
+    Some(ty)
+}
+
+/// Returns the type of a member of a struct.
+fn get_member_type(
+    name: &str,
+    member_ident: &IDENT,
+    type_checker_info: &mut TypeCheckerInfo,
+) -> Option<SysYType> {
+    let member = type_checker_info
+        .struct_table
+        .get(name)?
+        .iter()
+        .find(|(member_name, _)| *member_name == member_ident.content);
+    match member {
+        Some((_, ty)) => ty.clone(),
+        None => type_checker_info.error(
+            ErrorCode::UndeclaredSymbol,
+            format!(
+                "struct {} has no member named {}! ",
+                name, member_ident.content
+            ),
+            member_ident.span,
+        ),
+    }
+}
+
+impl LVal {
+    /// Checks the LVal, and returns its type.
+    pub fn check(&self, type_checker_info: &mut TypeCheckerInfo) -> Option<CheckedLVal> {
+        match self {
+            LVal::Default(ident, index_exps, span) => {
+                let Some(symbol) = type_checker_info.get_symbol(&ident.content).cloned() else {
+                    // Check the indexes anyway.
+                    check_indexes(None, index_exps, *span, type_checker_info);
+                    return type_checker_info.error(
+                        ErrorCode::UndeclaredSymbol,
+                        format!("Undeclared symbol: {}! ", ident.content),
+                        ident.span,
+                    );
+                };
+                let is_const = symbol.kind == SymbolKind::Constant;
+                let has_address =
+                    !(is_const && symbol.ty.as_ref().is_some_and(SysYType::is_arithmetic));
+                let ty = check_indexes(symbol.ty, index_exps, *span, type_checker_info)?;
+                Some(CheckedLVal {
+                    ty,
+                    is_const,
+                    has_address,
+                    value: symbol.value.filter(|_| index_exps.is_empty()),
+                })
+            }
+            LVal::Member(lval, member_ident, index_exps, span) => {
+                let struct_lval = lval.check(type_checker_info);
+                let member_type = match struct_lval.as_ref().map(|lval| &lval.ty) {
+                    Some(SysYType::Basic(BTypeKind::Struct(name))) => {
+                        get_member_type(name, member_ident, type_checker_info)
+                    }
+                    Some(SysYType::Pointer(ty))
+                        if matches!(**ty, SysYType::Basic(BTypeKind::Struct(_))) =>
+                    {
+                        type_checker_info.error(
+                            ErrorCode::NotAStruct,
+                            format!(
+                                "A {} is not a struct! Use \"->\" instead of \".\". ",
+                                struct_lval.as_ref().unwrap().ty
+                            ),
+                            lval.span(),
+                        )
+                    }
+                    Some(ty) => type_checker_info.error(
+                        ErrorCode::NotAStruct,
+                        format!("Only structs have members, but got {}! ", ty),
+                        lval.span(),
+                    ),
+                    None => None,
+                };
+                let ty = check_indexes(member_type, index_exps, *span, type_checker_info)?;
+                Some(CheckedLVal {
+                    ty,
+                    is_const: struct_lval?.is_const,
+                    has_address: true,
+                    value: None,
+                })
+            }
+            LVal::PtrMember(lval, member_ident, index_exps, span) => {
+                let member_type = match lval.check(type_checker_info) {
+                    Some(
+                        pointer_lval @ CheckedLVal {
+                            ty: SysYType::Basic(BTypeKind::Struct(_)),
+                            ..
+                        },
+                    ) => type_checker_info.error(
+                        ErrorCode::NotAStruct,
+                        format!(
+                            "A {} is not a pointer! Use \".\" instead of \"->\". ",
+                            pointer_lval.ty
+                        ),
+                        lval.span(),
+                    ),
+                    Some(pointer_lval) => {
+                        type_checker_info.read_variable(lval);
+                        match load_lval(pointer_lval, lval.span(), type_checker_info).map(|p| p.ty)
+                        {
+                            Some(SysYType::Pointer(ty)) => match *ty {
+                                SysYType::Basic(BTypeKind::Struct(name)) => {
+                                    get_member_type(name, member_ident, type_checker_info)
+                                }
+                                ty => type_checker_info.error(
+                                    ErrorCode::NotAStruct,
+                                    format!(
+                                        "Only pointers to structs have members, but got {}! ",
+                                        SysYType::Pointer(Box::new(ty))
+                                    ),
+                                    lval.span(),
+                                ),
+                            },
+                            Some(ty) => type_checker_info.error(
+                                ErrorCode::NotAStruct,
+                                format!("Only pointers to structs have members, but got {}! ", ty),
+                                lval.span(),
+                            ),
+                            None => None,
+                        }
+                    }
+                    None => None,
+                };
+                let ty = check_indexes(member_type, index_exps, *span, type_checker_info)?;
+                Some(CheckedLVal {
+                    ty,
+                    is_const: false,
+                    has_address: true,
+                    value: None,
+                })
+            }
+            LVal::Deref(exp, span) => check_deref(exp, *span, type_checker_info),
+        }
+    }
+
+    /// Checks a LVal that is going to be assigned, and returns its type.
+    /// Constants and arrays can not be assigned. Pointers (and array parameters) can.
+    /// A whole struct can only be copied by "=", which is what allows_struct tells.
+    pub fn check_assignable(
+        &self,
+        allows_struct: bool,
+        type_checker_info: &mut TypeCheckerInfo,
+    ) -> Option<SysYType> {
+        let lval = self.check(type_checker_info)?;
+        let message = match lval.ty {
+            SysYType::Array(_, _) => "An array can not be assigned! ".to_string(),
+            SysYType::Basic(BTypeKind::Struct(_)) if !allows_struct => {
+                format!("A {} can not be assigned as a whole! ", lval.ty)
+            }
+            _ if lval.is_const => "A constant can not be assigned! ".to_string(),
+            ty => return Some(ty),
+        };
+        type_checker_info.error(ErrorCode::NotAssignable, message, self.span())
+    }
+}
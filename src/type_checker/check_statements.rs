@@ -0,0 +1,296 @@
+//! Check the types of a single statement.
+
+use crate::ast_def::expressions::Exp;
+use crate::ast_def::statements::*;
+use crate::ast_def::symbols::{BTypeKind, Span};
+use crate::diagnostics::ErrorCode;
+
+use super::check_declarations::check_block_items;
+use super::check_expressions::{
+    check_binary_from_check_results, check_conversion, check_struct_copy, get_value, CheckedExp,
+    ExpTypeCheckable,
+};
+use super::{merge, FlowState, JumpTarget, SysYType, TypeCheckable, TypeCheckerInfo};
+
+/// Checks a condition, which is a number or a pointer. Returns its value if it is a constant.
+fn check_cond(exp: &Exp, type_checker_info: &mut TypeCheckerInfo) -> Option<i64> {
+    get_value(exp.check(type_checker_info), exp.span(), type_checker_info)?.value
+}
+
+/// Checks the body of a loop. The states at its end and at the continues go back to the condition,
+/// so they are merged into the current state. Returns the merged states at the breaks.
+fn check_loop_body(body: &BasicStmt, type_checker_info: &mut TypeCheckerInfo) -> FlowState {
+    type_checker_info.jump_targets.push(JumpTarget::new(true));
+    body.check(type_checker_info);
+    let target = type_checker_info.jump_targets.pop().unwrap();
+    type_checker_info.uninitialized =
+        merge(type_checker_info.uninitialized.take(), target.continues);
+    target.breaks
+}
+
+/// The state where a loop ends because its condition is false.
+/// A loop whose condition is always true only ends at a break.
+fn get_exit_state(is_infinite: bool, type_checker_info: &TypeCheckerInfo) -> FlowState {
+    match is_infinite {
+        true => None,
+        false => type_checker_info.uninitialized.clone(),
+    }
+}
+
+impl TypeCheckable for Stmt {
+    fn check(&self, type_checker_info: &mut TypeCheckerInfo) {
+        match self {
+            Stmt::UnmatchedStmt(stmt) => stmt.default.check(type_checker_info),
+            Stmt::MatchedStmt(stmt) => stmt.default.check(type_checker_info),
+        }
+    }
+}
+
+impl TypeCheckable for BasicStmt {
+    fn check(&self, type_checker_info: &mut TypeCheckerInfo) {
+        match self {
+            BasicStmt::AssignStmt(lval, rhs_exp, _) => {
+                let lval_type = lval.check_assignable(true, type_checker_info);
+                if let Some(ty @ SysYType::Basic(BTypeKind::Struct(_))) = &lval_type {
+                    check_struct_copy(ty, rhs_exp, type_checker_info);
+                    return;
+                }
+                let result = get_value(
+                    rhs_exp.check(type_checker_info),
+                    rhs_exp.span(),
+                    type_checker_info,
+                );
+                // The variable is initialized after its new value is computed.
+                type_checker_info.write_variable(lval);
+                if let (Some(lval_type), Some(result)) = (lval_type, result) {
+                    check_conversion(&lval_type, &result, rhs_exp.span(), type_checker_info);
+                }
+            }
+            // a += b is a = a + b.
+            BasicStmt::CompoundAssignStmt(lval, binary_op, rhs_exp, span) => {
+                let lval_type = lval.check_assignable(false, type_checker_info);
+                type_checker_info.read_variable(lval);
+                let result = get_value(
+                    rhs_exp.check(type_checker_info),
+                    rhs_exp.span(),
+                    type_checker_info,
+                );
+                if let (Some(lval_type), Some(result)) = (lval_type, result) {
+                    let lhs = CheckedExp::of_type(lval_type.clone());
+                    if let Some(result) = check_binary_from_check_results(
+                        lhs,
+                        result,
+                        *binary_op,
+                        *span,
+                        type_checker_info,
+                    ) {
+                        check_conversion(&lval_type, &result, *span, type_checker_info);
+                    }
+                }
+            }
+            // The result of an expression statement is discarded, so it can be void.
+            BasicStmt::Exp(possible_exp, _) => {
+                if let Some(exp) = possible_exp {
+                    exp.check(type_checker_info);
+                }
+            }
+            BasicStmt::Block(block) => {
+                type_checker_info.add_new_table();
+                block.check(type_checker_info);
+                type_checker_info.delete_new_table();
+            }
+            // Both branches start from the state after the condition, and join after the if.
+            BasicStmt::IfStmt(cond, then_stmt, possible_else_stmt, _) => {
+                check_cond(cond, type_checker_info);
+                let cond_state = type_checker_info.uninitialized.clone();
+                then_stmt.check(type_checker_info);
+                let then_state =
+                    std::mem::replace(&mut type_checker_info.uninitialized, cond_state);
+                if let Some(else_stmt) = &**possible_else_stmt {
+                    else_stmt.check(type_checker_info);
+                }
+                type_checker_info.uninitialized =
+                    merge(then_state, type_checker_info.uninitialized.take());
+            }
+            BasicStmt::WhileStmt(cond, body, _) => {
+                let is_infinite = check_cond(cond, type_checker_info).is_some_and(|int| int != 0);
+                let exit_state = get_exit_state(is_infinite, type_checker_info);
+                let break_state = check_loop_body(body, type_checker_info);
+                type_checker_info.uninitialized = merge(exit_state, break_state);
+            }
+            BasicStmt::DoWhileStmt(body, cond, _) => {
+                let break_state = check_loop_body(body, type_checker_info);
+                let is_infinite = check_cond(cond, type_checker_info).is_some_and(|int| int != 0);
+                let exit_state = get_exit_state(is_infinite, type_checker_info);
+                type_checker_info.uninitialized = merge(exit_state, break_state);
+            }
+            // The variables declared in the init are only visible in the for statement.
+            // The step runs after the body. A for without a condition is infinite.
+            BasicStmt::ForStmt(init, possible_cond, possible_step, body, _) => {
+                type_checker_info.add_new_table();
+                match &**init {
+                    ForInit::Decl(decl) => decl.check(type_checker_info),
+                    ForInit::Stmt(stmt) => stmt.check(type_checker_info),
+                }
+                let is_infinite = match possible_cond {
+                    Some(cond) => check_cond(cond, type_checker_info).is_some_and(|int| int != 0),
+                    None => true,
+                };
+                let exit_state = get_exit_state(is_infinite, type_checker_info);
+                let break_state = check_loop_body(body, type_checker_info);
+                if let Some(step) = &**possible_step {
+                    step.check(type_checker_info);
+                }
+                type_checker_info.uninitialized = merge(exit_state, break_state);
+                type_checker_info.delete_new_table();
+            }
+            BasicStmt::SwitchStmt(exp, cases, _) => {
+                check_switch_stmt(exp, cases, type_checker_info)
+            }
+            BasicStmt::BreakStmt(_) => type_checker_info.jump(true),
+            BasicStmt::ContinueStmt(_) => type_checker_info.jump(false),
+            // Nothing after a return can run.
+            BasicStmt::ReturnStmt(possible_exp, span) => {
+                check_return_stmt(possible_exp.as_ref(), *span, type_checker_info);
+                type_checker_info.uninitialized = None;
+            }
+            // The label is checked at the end of the function.
+            BasicStmt::GotoStmt(label, span) => {
+                let visible_locals = type_checker_info.get_visible_locals();
+                let goto_table = &mut type_checker_info.goto_table;
+                goto_table
+                    .gotos
+                    .push((label.content.clone(), *span, visible_locals));
+                let states = goto_table.states.entry(label.content.clone()).or_default();
+                *states = merge(states.take(), type_checker_info.uninitialized.take());
+            }
+            // A label is reached by the gotos before it. The gotos after it are not followed,
+            // like the end of a loop body. Without them, the label is still reachable.
+            BasicStmt::LabeledStmt(label, stmt, _) => {
+                let visible_locals = type_checker_info.get_visible_locals();
+                if type_checker_info
+                    .goto_table
+                    .labels
+                    .insert(label.content.clone(), visible_locals)
+                    .is_some()
+                {
+                    type_checker_info.error::<()>(
+                        ErrorCode::Redefinition,
+                        format!("Redefinition of label '{}'! ", label.content),
+                        label.span,
+                    );
+                }
+                let goto_state = type_checker_info.goto_table.states.remove(&label.content);
+                let state = merge(type_checker_info.uninitialized.take(), goto_state.flatten());
+                type_checker_info.uninitialized = Some(state.unwrap_or_default());
+                stmt.check(type_checker_info);
+            }
+            // Syntax errors are reported before checking types.
+            BasicStmt::Error(_) => {}
+        }
+    }
+}
+
+/// The switch value and the case labels are integers.
+/// All cases are in the same scope, like the body of the switch in C.
+/// A case is reached from the switch, or by falling through the previous case.
+fn check_switch_stmt(exp: &Exp, cases: &[SwitchCase], type_checker_info: &mut TypeCheckerInfo) {
+    if let Some(result) = get_value(exp.check(type_checker_info), exp.span(), type_checker_info) {
+        if !result.ty.is_integer() {
+            type_checker_info.error::<()>(
+                ErrorCode::MismatchedTypes,
+                format!(
+                    "The switch value must be an integer, but got {}! ",
+                    result.ty
+                ),
+                exp.span(),
+            );
+        }
+    }
+    type_checker_info.add_new_table();
+    type_checker_info.jump_targets.push(JumpTarget::new(false));
+    let switch_state = type_checker_info.uninitialized.clone();
+    let mut has_default = false;
+    for case in cases {
+        let items = match case {
+            SwitchCase::Case(label_exp, items, _) => {
+                let label = get_value(
+                    label_exp.check(type_checker_info),
+                    label_exp.span(),
+                    type_checker_info,
+                );
+                if label.is_some_and(|label| !label.ty.is_integer()) {
+                    type_checker_info.error::<()>(
+                        ErrorCode::MismatchedTypes,
+                        "The case label must be an integer! ".to_string(),
+                        label_exp.span(),
+                    );
+                }
+                items
+            }
+            SwitchCase::DefaultCase(items, _) => {
+                has_default = true;
+                items
+            }
+        };
+        type_checker_info.uninitialized =
+            merge(switch_state.clone(), type_checker_info.uninitialized.take());
+        check_block_items(items, type_checker_info);
+    }
+    // Without a default, the switch may run no case at all.
+    let target = type_checker_info.jump_targets.pop().unwrap();
+    let end_state = merge(type_checker_info.uninitialized.take(), target.breaks);
+    type_checker_info.uninitialized = match has_default {
+        true => end_state,
+        false => merge(end_state, switch_state),
+    };
+    type_checker_info.delete_new_table();
+}
+
+/// A void function returns nothing, and other functions return a value of their return types.
+fn check_return_stmt(
+    possible_exp: Option<&Exp>,
+    span: Span,
+    type_checker_info: &mut TypeCheckerInfo,
+) {
+    let (func_name, return_type) = type_checker_info
+        .curr_func
+        .clone()
+        .expect("A return statement is always in a function! ");
+    match (possible_exp, return_type) {
+        (None, BTypeKind::Void) => {}
+        (None, _) => {
+            type_checker_info.error::<()>(
+                ErrorCode::MismatchedTypes,
+                format!(
+                    "Function '{}' returns {}, so the return statement needs a value! ",
+                    func_name, return_type
+                ),
+                span,
+            );
+        }
+        (Some(exp), BTypeKind::Void) => {
+            // Check the expression anyway, so that its errors are reported.
+            exp.check(type_checker_info);
+            type_checker_info.error::<()>(
+                ErrorCode::MismatchedTypes,
+                format!(
+                    "Function '{}' returns void, so it can not return a value! ",
+                    func_name
+                ),
+                exp.span(),
+            );
+        }
+        (Some(exp), return_type) => {
+            let result = get_value(exp.check(type_checker_info), exp.span(), type_checker_info);
+            if let Some(result) = result {
+                check_conversion(
+                    &SysYType::Basic(return_type),
+                    &result,
+                    exp.span(),
+                    type_checker_info,
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,493 @@
+//! This module checks the types of the AST before the IR builder runs.
+//! Every expression is annotated with its type, and all type errors are reported at once.
+//! Redefinitions, constness and the lengths of arrays are still checked by the IR builder.
+//! It also follows the control flow of every function, and gives the warnings of -Wall.
+
+mod check_declarations;
+mod check_expressions;
+mod check_statements;
+use crate::assembly_builder::Target;
+use crate::ast_def::expressions::LVal;
+use crate::ast_def::symbols::{BTypeKind, Span, IDENT};
+use crate::ast_def::*;
+use crate::diagnostics::{CompileError, ErrorCode, Warning, WarningFlag};
+use crate::ir_builder::{SymbolKind, LIB_FUNCTIONS};
+use std::collections::{HashMap, HashSet};
+
+use self::check_expressions::CheckedExp;
+
+/// The type of every checked expression, by its span.
+pub type ExpTypes = HashMap<Span, SysYType>;
+
+/// The warnings are appended to `warnings` in the order of the source, even if there are errors.
+/// Long long is only supported by the RV64 target.
+pub fn check_types(
+    comp_unit: &CompUnit,
+    target: Target,
+    warnings: &mut Vec<Warning>,
+) -> Result<ExpTypes, Vec<CompileError>> {
+    let mut type_checker_info = TypeCheckerInfo {
+        target,
+        long_long_rejected: false,
+        symbol_tables: vec![HashMap::new()],
+        function_table: HashMap::new(),
+        struct_table: HashMap::new(),
+        curr_func: None,
+        exp_types: HashMap::new(),
+        errors: vec![],
+        symbol_cnt: 0,
+        skipped_depth: 0,
+        goto_table: GotoTable::default(),
+        uninitialized: None,
+        jump_targets: vec![],
+        warnings: vec![],
+    };
+    // Declare all SysY library functions.
+    for (name, params, return_type) in LIB_FUNCTIONS {
+        let param_types = params
+            .iter()
+            .map(|&(param_type, is_array)| {
+                Some(match is_array {
+                    true => SysYType::Pointer(Box::new(SysYType::Basic(param_type))),
+                    false => SysYType::Basic(param_type),
+                })
+            })
+            .collect();
+        type_checker_info.function_table.insert(
+            name.to_string(),
+            FunctionSignature {
+                return_type,
+                param_types,
+                // putf is the only variadic library function.
+                is_variadic: name == "putf",
+                is_library: true,
+                is_defined: true,
+            },
+        );
+    }
+    comp_unit.check(&mut type_checker_info);
+    type_checker_info
+        .warnings
+        .sort_by_key(|warning| warning.span.start);
+    warnings.append(&mut type_checker_info.warnings);
+    match type_checker_info.errors.is_empty() {
+        true => Ok(type_checker_info.exp_types),
+        false => Err(type_checker_info.errors),
+    }
+}
+
+/// The type of an expression or a variable, like in C.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SysYType {
+    Void, // The result of a void function
+    Basic(BTypeKind),
+    Pointer(Box<SysYType>),
+    Array(Box<SysYType>, Option<usize>), // The length is None if it is not a valid constant.
+    String,                              // A string literal, whose chars are bytes
+}
+
+impl SysYType {
+    pub fn is_arithmetic(&self) -> bool {
+        matches!(
+            self,
+            SysYType::Basic(
+                BTypeKind::Int | BTypeKind::LongLong | BTypeKind::Float | BTypeKind::Char
+            )
+        )
+    }
+
+    pub fn is_integer(&self) -> bool {
+        matches!(
+            self,
+            SysYType::Basic(BTypeKind::Int | BTypeKind::LongLong | BTypeKind::Char)
+        )
+    }
+
+    pub fn is_pointer(&self) -> bool {
+        matches!(self, SysYType::Pointer(_))
+    }
+
+    /// The type of the elements, after all pointers and arrays are taken away.
+    pub fn base_type(&self) -> Option<BTypeKind> {
+        match self {
+            SysYType::Basic(btype) => Some(*btype),
+            SysYType::Pointer(ty) | SysYType::Array(ty, _) => ty.base_type(),
+            SysYType::Void | SysYType::String => None,
+        }
+    }
+
+    /// A char is converted into an int in arithmetic, like in C.
+    pub fn promote(self) -> SysYType {
+        match self {
+            SysYType::Basic(BTypeKind::Char) => SysYType::Basic(BTypeKind::Int),
+            ty => ty,
+        }
+    }
+
+    /// Whether two types are the same. An unknown length is the same as any length,
+    /// because its error has been reported.
+    pub fn is_same(&self, other: &SysYType) -> bool {
+        match (self, other) {
+            (SysYType::Pointer(ty1), SysYType::Pointer(ty2)) => ty1.is_same(ty2),
+            (SysYType::Array(ty1, len1), SysYType::Array(ty2, len2)) => {
+                ty1.is_same(ty2) && (len1.is_none() || len2.is_none() || len1 == len2)
+            }
+            _ => self == other,
+        }
+    }
+
+    /// Writes the type around a declarator, like C, e.g. "int (*)[3]" for a pointer to an array.
+    fn declare(&self, declarator: String) -> String {
+        let join = |base: String| match declarator.is_empty() {
+            true => base,
+            false => format!("{} {}", base, declarator),
+        };
+        match self {
+            SysYType::Void => join("void".to_string()),
+            SysYType::Basic(btype) => join(btype.to_string()),
+            SysYType::String => join("string literal".to_string()),
+            SysYType::Pointer(ty) => match **ty {
+                SysYType::Array(_, _) => ty.declare(format!("(*{})", declarator)),
+                _ => ty.declare(format!("*{}", declarator)),
+            },
+            SysYType::Array(ty, len) => ty.declare(match len {
+                Some(len) => format!("{}[{}]", declarator, len),
+                None => format!("{}[]", declarator),
+            }),
+        }
+    }
+}
+
+impl std::fmt::Display for SysYType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.declare(String::new()))
+    }
+}
+
+#[derive(Debug)]
+pub struct TypeCheckerInfo {
+    target: Target,           // The target, which decides if long long works
+    long_long_rejected: bool, // Whether long long has been reported on RV32
+    symbol_tables: Vec<HashMap<String, Symbol>>, // Symbol table: ident-type
+    function_table: HashMap<String, FunctionSignature>, // Function table
+    struct_table: HashMap<String, Vec<(String, Option<SysYType>)>>, // Struct table: tag-members
+    curr_func: Option<(String, BTypeKind)>, // Name and return type of the current function
+    exp_types: ExpTypes,      // Types of the checked expressions
+    errors: Vec<CompileError>, // All errors found so far
+    symbol_cnt: usize,        // Number of symbols declared so far
+    skipped_depth: usize,     // Number of operands around that are never run
+    uninitialized: FlowState, // The state at the current point of the function
+    jump_targets: Vec<JumpTarget>, // The loops and switches around the current point
+    goto_table: GotoTable,    // The gotos and labels of the current function
+    warnings: Vec<Warning>,   // All warnings found so far
+}
+
+/// A variable, a constant or a parameter. The value of an integer constant is known.
+/// The type is None if its declaration has an error, so that its uses are not reported again.
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    ty: Option<SysYType>,
+    kind: SymbolKind,
+    value: Option<i64>,
+    id: usize,     // Unique in the program, unlike the name
+    span: Span,    // Where it is declared
+    is_used: bool, // Whether any expression refers to it
+}
+
+/// The ids of the local variables that may be uninitialized at a point of a function,
+/// i.e. they are not assigned on some path to the point. None if the point is unreachable.
+pub type FlowState = Option<HashSet<usize>>;
+
+/// Merges the states of the paths that join at a point. An unreachable path adds nothing.
+fn merge(state1: FlowState, state2: FlowState) -> FlowState {
+    match (state1, state2) {
+        (Some(mut ids1), Some(ids2)) => {
+            ids1.extend(ids2);
+            Some(ids1)
+        }
+        (state1, None) => state1,
+        (None, state2) => state2,
+    }
+}
+
+/// A loop or a switch, where "break" jumps out of. "continue" only jumps to loops.
+#[derive(Debug)]
+pub struct JumpTarget {
+    is_loop: bool,
+    breaks: FlowState,    // The merged states at the breaks
+    continues: FlowState, // The merged states at the continues
+}
+
+/// The gotos and labels of a function. The gotos are checked at the end of the function,
+/// because a label can be after the gotos to it.
+#[derive(Debug, Default)]
+pub struct GotoTable {
+    labels: HashMap<String, HashMap<usize, String>>, // Name - the locals visible at the label
+    gotos: Vec<(String, Span, HashMap<usize, String>)>, // Label - the locals at the goto
+    states: HashMap<String, FlowState>,              // The merged states at the gotos, by label
+}
+
+impl JumpTarget {
+    fn new(is_loop: bool) -> JumpTarget {
+        JumpTarget {
+            is_loop,
+            breaks: None,
+            continues: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FunctionSignature {
+    return_type: BTypeKind,
+    param_types: Vec<Option<SysYType>>, // Array params are pointers.
+    is_variadic: bool,                  // Whether it takes more args after the params, like putf.
+    is_library: bool,                   // Library functions take string literals as char arrays.
+    is_defined: bool,                   // Whether it has a body. Library functions have one.
+}
+
+impl FunctionSignature {
+    /// Whether two signatures of a function are the same.
+    /// An unknown param type is the same as any type, because its error has been reported.
+    fn is_same(&self, other: &FunctionSignature) -> bool {
+        self.return_type == other.return_type
+            && self.is_variadic == other.is_variadic
+            && self.param_types.len() == other.param_types.len()
+            && self
+                .param_types
+                .iter()
+                .zip(&other.param_types)
+                .all(|(ty1, ty2)| match (ty1, ty2) {
+                    (Some(ty1), Some(ty2)) => ty1.is_same(ty2),
+                    _ => true,
+                })
+    }
+}
+
+impl TypeCheckerInfo {
+    /// Looks up a symbol, which is used from now on.
+    fn get_symbol(&mut self, name: &String) -> Option<&Symbol> {
+        let symbol = self
+            .symbol_tables
+            .iter_mut()
+            .rev()
+            .find_map(|table| table.get_mut(name))?;
+        symbol.is_used = true;
+        Some(symbol)
+    }
+    /// A redefinition in the same scope is reported by the IR builder,
+    /// so the first definition is kept, and the uses are checked against it.
+    /// Returns the id of the new symbol.
+    fn insert_symbol(
+        &mut self,
+        ident: &IDENT,
+        kind: SymbolKind,
+        ty: Option<SysYType>,
+        value: Option<i64>,
+    ) -> usize {
+        self.symbol_cnt += 1;
+        let symbol = Symbol {
+            ty,
+            kind,
+            value,
+            id: self.symbol_cnt,
+            span: ident.span,
+            is_used: false,
+        };
+        let table = self.symbol_tables.last_mut().unwrap();
+        table.entry(ident.content.clone()).or_insert(symbol);
+        self.symbol_cnt
+    }
+    fn add_new_table(&mut self) {
+        self.symbol_tables.push(HashMap::new());
+    }
+    /// Leaves a local scope. The symbols that are never used in it are reported.
+    fn delete_new_table(&mut self) {
+        let table = self.symbol_tables.pop().unwrap();
+        for (name, symbol) in table {
+            if symbol.is_used {
+                continue;
+            }
+            let flag = match symbol.kind {
+                SymbolKind::Parameter => WarningFlag::UnusedParameter,
+                _ => WarningFlag::UnusedVariable,
+            };
+            self.warn(
+                flag,
+                format!("Unused {} '{}'! ", symbol.kind, name),
+                symbol.span,
+            );
+        }
+    }
+
+    /// A local variable declared without an initval is uninitialized.
+    fn declare_uninitialized(&mut self, id: usize) {
+        if let Some(ids) = &mut self.uninitialized {
+            ids.insert(id);
+        }
+    }
+
+    /// Returns the name and the id of a LVal that is a variable itself, not a part of it.
+    fn get_variable(&self, lval: &LVal) -> Option<(String, usize)> {
+        match lval {
+            LVal::Default(ident, index_exps, _) if index_exps.is_empty() => self
+                .symbol_tables
+                .iter()
+                .rev()
+                .find_map(|table| table.get(&ident.content))
+                .map(|symbol| (ident.content.clone(), symbol.id)),
+            _ => None,
+        }
+    }
+
+    /// Reads the value of a checked LVal. A read of an uninitialized variable is reported once.
+    fn read_variable(&mut self, lval: &LVal) {
+        let Some((name, id)) = self.get_variable(lval) else {
+            return;
+        };
+        if self
+            .uninitialized
+            .as_mut()
+            .is_some_and(|ids| ids.remove(&id))
+        {
+            self.warn(
+                WarningFlag::Uninitialized,
+                format!("'{}' may be used uninitialized! ", name),
+                lval.span(),
+            );
+        }
+    }
+
+    /// Assigns a checked LVal, or takes its address, so that it is initialized from now on.
+    fn write_variable(&mut self, lval: &LVal) {
+        if let (Some((_, id)), Some(ids)) = (self.get_variable(lval), &mut self.uninitialized) {
+            ids.remove(&id);
+        }
+    }
+
+    /// Returns the ids and the names of the local symbols visible at the current point.
+    fn get_visible_locals(&self) -> HashMap<usize, String> {
+        self.symbol_tables[1..]
+            .iter()
+            .flat_map(|table| table.iter())
+            .map(|(name, symbol)| (symbol.id, name.clone()))
+            .collect()
+    }
+
+    /// Every goto jumps to a label in the function,
+    /// and it can not skip the declaration of a symbol that is visible at the label.
+    fn check_gotos(&mut self) {
+        let goto_table = std::mem::take(&mut self.goto_table);
+        for (label, span, visible_locals) in goto_table.gotos {
+            let Some(label_locals) = goto_table.labels.get(&label) else {
+                self.error::<()>(
+                    ErrorCode::UndeclaredSymbol,
+                    format!("Undefined label: {}! ", label),
+                    span,
+                );
+                continue;
+            };
+            let skipped_local = label_locals
+                .iter()
+                .filter(|(id, _)| !visible_locals.contains_key(id))
+                .min_by_key(|(id, _)| **id);
+            if let Some((_, name)) = skipped_local {
+                self.error::<()>(
+                    ErrorCode::JumpIntoScope,
+                    format!(
+                        "The goto jumps into the scope of '{}', past its declaration! ",
+                        name
+                    ),
+                    span,
+                );
+            }
+        }
+    }
+
+    /// Jumps to the innermost loop or switch for "break", or the innermost loop for "continue".
+    /// The current point becomes unreachable.
+    fn jump(&mut self, is_break: bool) {
+        let state = self.uninitialized.take();
+        let target = self
+            .jump_targets
+            .iter_mut()
+            .rev()
+            .find(|target| is_break || target.is_loop);
+        // A jump out of nothing is reported by the IR builder.
+        if let Some(target) = target {
+            let states = match is_break {
+                true => &mut target.breaks,
+                false => &mut target.continues,
+            };
+            *states = merge(states.take(), state);
+        }
+    }
+
+    /// Records the type of an expression.
+    /// A long long computed at run time needs RV64. Constants are folded, so they are allowed.
+    fn annotate(&mut self, span: Span, result: CheckedExp) -> Option<CheckedExp> {
+        if result.ty == SysYType::Basic(BTypeKind::LongLong) && result.value.is_none() {
+            self.check_long_long(span, false);
+        }
+        self.exp_types.insert(span, result.ty.clone());
+        Some(result)
+    }
+
+    /// Reports long long on RV32. Every type specifier is reported,
+    /// but an expression is only reported if nothing has been, so that the uses of
+    /// a long long variable are not reported again.
+    fn check_long_long(&mut self, span: Span, is_type_specifier: bool) {
+        if self.target == Target::RV64 || (self.long_long_rejected && !is_type_specifier) {
+            return;
+        }
+        self.long_long_rejected = true;
+        self.error::<()>(
+            ErrorCode::UnsupportedTarget,
+            "long long is only supported by RV64! Use -march=rv64. ".to_string(),
+            span,
+        );
+    }
+
+    /// Reports an error. Returns None, so that the expression is not checked again by its parents.
+    fn error<T>(&mut self, code: ErrorCode, message: String, span: Span) -> Option<T> {
+        self.errors.push(CompileError::new(code, message, span));
+        None
+    }
+
+    fn warn(&mut self, flag: WarningFlag, message: String, span: Span) {
+        self.warnings.push(Warning::new(flag, message, span));
+    }
+}
+
+pub trait TypeCheckable {
+    fn check(&self, type_checker_info: &mut TypeCheckerInfo);
+}
+
+impl TypeCheckable for CompUnit {
+    fn check(&self, type_checker_info: &mut TypeCheckerInfo) {
+        let CompUnit::Default(units) = self;
+        for unit in units {
+            unit.check(type_checker_info);
+        }
+    }
+}
+
+impl TypeCheckable for Unit {
+    fn check(&self, type_checker_info: &mut TypeCheckerInfo) {
+        match self {
+            Unit::Decl(d) => d.check(type_checker_info),
+            Unit::FuncDef(f) => f.check(type_checker_info),
+            Unit::FuncDecl(fs) => fs.iter().for_each(|f| f.check(type_checker_info)),
+            Unit::ExternVarDecl(v) => v.check(type_checker_info),
+            Unit::StaticDecl(d) => d.check(type_checker_info),
+            Unit::StaticFuncDef(f) => f.check(type_checker_info),
+            Unit::StaticFuncDecl(fs) => fs.iter().for_each(|f| f.check(type_checker_info)),
+            Unit::StructDef(s) => s.check(type_checker_info),
+            Unit::EnumDef(e) => e.check(type_checker_info),
+            // Typedefs are expanded by the parser.
+            Unit::TypeDef(_) => {}
+            // Syntax errors are reported before checking types.
+            Unit::Error(_) => {}
+        }
+    }
+}